@@ -4,52 +4,29 @@ Feature Engine
 Computes features from order book data over micro-windows (200-500ms).
 """
 
-import numpy as np
-from typing import Dict, List, Optional
-from collections import deque
+from typing import Optional
 
 from pkg.common import get_logger, get_metrics_collector, get_timestamp_ns, calculate_latency_us
 from pkg.schemas import TopOfBook, FeatureFrame
 
 
-class SymbolWindow:
-    """Window of TopOfBook updates for a single symbol"""
-    
-    def __init__(self, symbol_id: int, window_ms: int = 500):
-        self.symbol_id = symbol_id
-        self.window_ns = window_ms * 1_000_000  # Convert to nanoseconds
-        self.updates: deque[TopOfBook] = deque()
-        
-    def add_update(self, tob: TopOfBook):
-        """Add TopOfBook update to window"""
-        self.updates.append(tob)
-        
-        # Remove old updates outside window
-        cutoff_ns = tob.ts_ns - self.window_ns
-        while self.updates and self.updates[0].ts_ns < cutoff_ns:
-            self.updates.popleft()
-    
-    def get_updates(self) -> List[TopOfBook]:
-        """Get all updates in window"""
-        return list(self.updates)
-    
-    def get_price_series(self) -> np.ndarray:
-        """Get mid price series"""
-        return np.array([u.mid_price for u in self.updates])
-    
-    def get_spread_series(self) -> np.ndarray:
-        """Get spread series"""
-        return np.array([u.spread for u in self.updates])
-
-
 class FeatureEngine:
     """
     Feature extraction engine.
     
-    Computes features over micro-windows for each symbol.
+    Computes features over micro-windows for each symbol. The service only
+    receives TopOfBook updates, so frames carry quote features alone; the
+    calculator's trade features are never fed.
     """
     
-    def __init__(self, config, publisher):
+    def __init__(self, config, publisher, calculator_factory):
+        """
+        Args:
+            config: Service config
+            publisher: Publishes feature frames
+            calculator_factory: Builds the window statistics from window_ms=...
+                and exposes on_tob(tob)
+        """
         self.config = config
         self.publisher = publisher
         self.logger = get_logger(__name__)
@@ -60,26 +37,18 @@ class FeatureEngine:
         if hasattr(config, 'features') and hasattr(config.features, 'window_ms'):
             window_ms = config.features.window_ms
         
-        # Windows for each symbol
-        self.windows: Dict[int, SymbolWindow] = {}
+        # Incremental window statistics for every symbol
+        self.calculator = calculator_factory(window_ms=window_ms)
         self.window_ms = window_ms
         
-        # Previous features for change calculation
-        self.prev_features: Dict[int, FeatureFrame] = {}
-        
         self.running = False
         
     async def start(self):
         """Start the feature engine"""
-        # Initialize windows for configured symbols
-        for symbol in self.config.symbols:
-            symbol_id = self._get_symbol_id(symbol)
-            self.windows[symbol_id] = SymbolWindow(symbol_id, self.window_ms)
-        
         self.running = True
         self.logger.info("feature_engine_started", 
                         window_ms=self.window_ms,
-                        num_symbols=len(self.windows))
+                        num_symbols=len(self.config.symbols))
     
     async def stop(self):
         """Stop the feature engine"""
@@ -95,24 +64,12 @@ class FeatureEngine:
         """
         try:
             start_ts = get_timestamp_ns()
-            
-            # Get or create window
             symbol_id = tob.symbol_id
-            if symbol_id not in self.windows:
-                self.windows[symbol_id] = SymbolWindow(symbol_id, self.window_ms)
-            
-            window = self.windows[symbol_id]
-            
-            # Add update to window
-            window.add_update(tob)
             
             # Compute features
-            features = self._compute_features(tob, window)
+            features = self._compute_features(tob)
             
             if features:
-                # Store for next iteration
-                self.prev_features[symbol_id] = features
-                
                 # Publish features
                 await self.publisher.publish(features)
                 
@@ -138,42 +95,25 @@ class FeatureEngine:
                             exc_info=True)
             self.metrics.record_error("process_tob_failed")
     
-    def _compute_features(self, tob: TopOfBook, window: SymbolWindow) -> Optional[FeatureFrame]:
-        """Compute feature frame from window"""
+    def _compute_features(self, tob: TopOfBook) -> Optional[FeatureFrame]:
+        """Add the update to the symbol's window and build its feature frame"""
         try:
-            # Create feature frame
-            features = FeatureFrame.create_empty(tob.symbol_id)
-            features.ts_ns = tob.ts_ns
-            
-            # Price features
-            features.mid_price = tob.mid_price
-            features.micro_price = tob.micro_price
-            features.spread = tob.spread
-            features.spread_bps = (tob.spread / tob.mid_price) * 10000 if tob.mid_price > 0 else 0
-            
-            # Volume features
-            features.bid_volume = tob.bid_sz
-            features.ask_volume = tob.ask_sz
-            total_volume = tob.bid_sz + tob.ask_sz
-            features.imbalance = (tob.bid_sz - tob.ask_sz) / total_volume if total_volume > 0 else 0
-            
-            # Volatility features
-            price_series = window.get_price_series()
-            if len(price_series) > 1:
-                # Realized volatility (std of returns)
-                returns = np.diff(price_series) / price_series[:-1]
-                features.realized_vol = float(np.std(returns)) if len(returns) > 0 else 0.0
-                
-                # Price range
-                features.price_range = float(np.max(price_series) - np.min(price_series))
-            
-            # Momentum features (change from previous)
-            prev = self.prev_features.get(tob.symbol_id)
-            if prev:
-                features.price_change = tob.mid_price - prev.mid_price
-                features.price_change_pct = (features.price_change / prev.mid_price * 100) if prev.mid_price > 0 else 0
-            
-            return features
+            frame = self.calculator.on_tob(tob)
+            return FeatureFrame(
+                ts_ns=frame.ts_ns,
+                symbol_id=frame.symbol_id,
+                mid_price=frame.mid_price,
+                micro_price=frame.micro_price,
+                spread=frame.spread,
+                spread_bps=frame.spread_bps,
+                bid_volume=frame.bid_volume,
+                ask_volume=frame.ask_volume,
+                imbalance=frame.imbalance,
+                realized_vol=frame.realized_vol,
+                price_range=frame.price_range,
+                price_change=frame.price_change,
+                price_change_pct=frame.price_change_pct
+            )
             
         except Exception as e:
             self.logger.error("compute_features_error", error=str(e), exc_info=True)
@@ -14,6 +14,7 @@ from pathlib import Path
 project_root = Path(__file__).parent.parent.parent
 sys.path.insert(0, str(project_root))
 
+from core.modules.rust_execution import FeatureCalculator
 from pkg.common import setup_logging, get_logger, load_config, get_metrics_collector
 from .subscriber import BookSubscriber
 from .feature_engine import FeatureEngine
@@ -30,7 +31,8 @@ class FeatureService:
         
         # Initialize components
         self.publisher = FeaturePublisher(config)
-        self.engine = FeatureEngine(config, self.publisher)
+        # The execution core's calculator, compiled when available.
+        self.engine = FeatureEngine(config, self.publisher, FeatureCalculator)
         self.subscriber = BookSubscriber(config, self.engine)
         
         self.running = False
@@ -14,6 +14,7 @@ from pathlib import Path
 project_root = Path(__file__).parent.parent.parent
 sys.path.insert(0, str(project_root))
 
+from core.modules.rust_execution import PositionLedger, SlidingWindowLimiter
 from pkg.common import setup_logging, get_logger, load_config, get_metrics_collector
 from .subscriber import OrderSubscriber
 from .risk_engine import RiskEngine
@@ -30,7 +31,14 @@ class RiskService:
         
         # Initialize components
         self.publisher = RiskPublisher(config)
-        self.engine = RiskEngine(config, self.publisher)
+        # The engine only uses the ledger's and limiter's interfaces; the
+        # execution core's implementations are wired in here.
+        self.engine = RiskEngine(
+            config,
+            self.publisher,
+            positions=PositionLedger(cost_method="fifo"),
+            rate_limiter=SlidingWindowLimiter(config.risk.rate_limit_per_sec, window_ms=1000),
+        )
         self.subscriber = OrderSubscriber(config, self.engine)
         
         self.running = False
@@ -5,27 +5,11 @@ Performs pre-trade risk checks with reason codes for deterministic replay.
 """
 
 from typing import Dict, Tuple
-from dataclasses import dataclass
-import time
 
 from pkg.common import get_logger, get_metrics_collector, get_timestamp_ns, calculate_latency_us
-from pkg.schemas import OrderIntent, Reject, RejectReason, get_trading_metrics
-
-
-@dataclass
-class PositionState:
-    """Current position state for a symbol"""
-    symbol_id: int
-    qty: float = 0.0
-    notional_usd: float = 0.0
-    avg_price: float = 0.0
-
-
-@dataclass
-class RateLimitState:
-    """Rate limiting state"""
-    count: int = 0
-    window_start_ns: int = 0
+from pkg.common.metrics import get_trading_metrics
+from pkg.schemas import OrderIntent, Reject
+from pkg.schemas.orders import RejectReason
 
 
 class RiskEngine:
@@ -40,7 +24,14 @@ class RiskEngine:
     - Basic sanity checks
     """
     
-    def __init__(self, config, publisher):
+    def __init__(self, config, publisher, positions, rate_limiter):
+        """
+        Args:
+            config: Service config; limits come from config.risk
+            publisher: Publishes approved orders and rejections
+            positions: Position ledger with get_position, set_mark_price and on_fill
+            rate_limiter: Per-symbol order budget with try_acquire(symbol_id=...)
+        """
         self.config = config
         self.publisher = publisher
         self.logger = get_logger(__name__)
@@ -60,8 +51,8 @@ class RiskEngine:
             self.rate_limit_per_sec = config.risk.rate_limit_per_sec
         
         # State tracking
-        self.positions: Dict[int, PositionState] = {}
-        self.rate_limits: Dict[int, RateLimitState] = {}
+        self.positions = positions
+        self.rate_limiter = rate_limiter
         
         # Price cache for band checks
         self.last_prices: Dict[int, float] = {}
@@ -160,27 +151,30 @@ class RiskEngine:
         if order.price is not None and order.price <= 0:
             return False, RejectReason.INVALID_PRICE, "Invalid price"
         
+        # Market orders are valued (and booked) at the last known price
+        price = order.price or self.last_prices.get(order.symbol_id)
+        if not price:
+            return False, RejectReason.INVALID_PRICE, "No price to value market order"
+        
         # 2. Order size check
-        if order.price:
-            order_notional = order.qty * order.price
-            if order_notional > self.max_order_usd:
-                return False, RejectReason.POSITION_LIMIT, \
-                       f"Order size ${order_notional:.2f} exceeds max ${self.max_order_usd}"
+        order_notional = order.qty * price
+        if order_notional > self.max_order_usd:
+            return False, RejectReason.NOTIONAL_LIMIT, \
+                   f"Order size ${order_notional:.2f} exceeds max ${self.max_order_usd}"
         
         # 3. Position limit check
-        position = self.positions.get(order.symbol_id, PositionState(order.symbol_id))
-        new_position_qty = position.qty
+        position = self.positions.get_position(order.symbol_id)
+        new_position_qty = position["net_qty"] if position else 0.0
         
         if order.side.name == "BUY":
             new_position_qty += order.qty
         else:
             new_position_qty -= order.qty
         
-        if order.price:
-            new_position_notional = abs(new_position_qty * order.price)
-            if new_position_notional > self.max_position_usd:
-                return False, RejectReason.POSITION_LIMIT, \
-                       f"Position would be ${new_position_notional:.2f}, exceeds max ${self.max_position_usd}"
+        new_position_notional = abs(new_position_qty * price)
+        if new_position_notional > self.max_position_usd:
+            return False, RejectReason.POSITION_LIMIT, \
+                   f"Position would be ${new_position_notional:.2f}, exceeds max ${self.max_position_usd}"
         
         # 4. Price band check
         if order.price and order.symbol_id in self.last_prices:
@@ -199,42 +193,17 @@ class RiskEngine:
         return True, 0, ""
     
     def _check_rate_limit(self, symbol_id: int) -> bool:
-        """Check rate limit for symbol"""
-        now_ns = time.time_ns()
-        window_ns = 1_000_000_000  # 1 second
-        
-        if symbol_id not in self.rate_limits:
-            self.rate_limits[symbol_id] = RateLimitState(count=1, window_start_ns=now_ns)
-            return True
-        
-        rl = self.rate_limits[symbol_id]
-        
-        # Check if we're in a new window
-        if now_ns - rl.window_start_ns > window_ns:
-            # New window
-            rl.count = 1
-            rl.window_start_ns = now_ns
-            return True
-        
-        # Same window - check count
-        if rl.count >= self.rate_limit_per_sec:
-            return False
-        
-        rl.count += 1
-        return True
+        """Check rate limit for symbol over a sliding 1s window"""
+        return self.rate_limiter.try_acquire(symbol_id=symbol_id)
     
     def _update_position_tracking(self, order: OrderIntent):
         """Update position tracking after order approval"""
-        if order.symbol_id not in self.positions:
-            self.positions[order.symbol_id] = PositionState(order.symbol_id)
-        
-        position = self.positions[order.symbol_id]
-        
-        if order.side.name == "BUY":
-            position.qty += order.qty
-        else:
-            position.qty -= order.qty
-        
         if order.price:
-            position.notional_usd = abs(position.qty * order.price)
             self.last_prices[order.symbol_id] = order.price
+            self.positions.set_mark_price(order.symbol_id, order.price)
+        
+        # Market orders are booked at the last known price; _perform_checks
+        # rejects them when there is none
+        price = order.price or self.last_prices[order.symbol_id]
+        side = 0 if order.side.name == "BUY" else 1
+        self.positions.on_fill(order.symbol_id, side, price, order.qty)
@@ -14,6 +14,7 @@ from pathlib import Path
 project_root = Path(__file__).parent.parent.parent
 sys.path.insert(0, str(project_root))
 
+from core.modules.rust_execution import TokenBucketLimiter
 from pkg.common import setup_logging, get_logger, load_config, get_metrics_collector
 from .subscriber import ApprovedOrderSubscriber
 from .routing_engine import RoutingEngine
@@ -30,7 +31,9 @@ class RouterService:
         
         # Initialize components
         self.publisher = RouterPublisher(config)
-        self.engine = RoutingEngine(config, self.publisher)
+        # One order per cooldown per venue, from the execution core's limiter.
+        venue_rate_limiter = TokenBucketLimiter(1000.0 / max(config.risk.cooldown_ms, 1e-6), burst=1)
+        self.engine = RoutingEngine(config, self.publisher, venue_rate_limiter)
         self.subscriber = ApprovedOrderSubscriber(config, self.engine)
         
         self.running = False
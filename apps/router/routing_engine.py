@@ -4,9 +4,6 @@ Routing Engine
 Smart order routing with venue selection and rate limiting.
 """
 
-from typing import Dict
-import time
-
 from pkg.common import get_logger, get_metrics_collector, get_timestamp_ns, calculate_latency_us
 from pkg.schemas import OrderIntent
 
@@ -22,7 +19,14 @@ class RoutingEngine:
     - Rate limiting
     """
     
-    def __init__(self, config, publisher):
+    def __init__(self, config, publisher, venue_rate_limiter):
+        """
+        Args:
+            config: Service config; the cooldown comes from config.risk
+            publisher: Publishes routed orders
+            venue_rate_limiter: Per-venue budget of one order per cooldown, with
+                try_acquire(venue=...) and release(venue=...)
+        """
         self.config = config
         self.publisher = publisher
         self.logger = get_logger(__name__)
@@ -37,15 +41,14 @@ class RoutingEngine:
             5: "binance",  # XRP/USDT
         }
         
-        # Rate limiting per venue
-        self.venue_rate_limits: Dict[str, int] = {}
-        self.venue_last_order_ns: Dict[str, int] = {}
-        
         # Min delay between orders (ms)
         self.min_delay_ms = 100
         if hasattr(config, 'risk'):
             self.min_delay_ms = getattr(config.risk, 'cooldown_ms', 100)
         
+        # Rate limiting per venue: one order per min_delay_ms
+        self.venue_rate_limiter = venue_rate_limiter
+        
         self.running = False
         
     async def start(self):
@@ -89,11 +92,13 @@ class RoutingEngine:
             # Update order route
             order.route = venue
             
-            # Publish to execution gateway
-            await self.publisher.publish(order, venue)
-            
-            # Update rate limit tracking
-            self.venue_last_order_ns[venue] = get_timestamp_ns()
+            # Publish to execution gateway; the rate limit is only charged
+            # for orders that actually went out
+            try:
+                await self.publisher.publish(order, venue)
+            except Exception:
+                self.venue_rate_limiter.release(venue=venue)
+                raise
             
             self.logger.info("order_routed",
                            client_id=order.client_id,
@@ -141,7 +146,9 @@ class RoutingEngine:
     
     def _check_venue_rate_limit(self, venue: str) -> bool:
         """
-        Check if venue rate limit allows this order.
+        Check if venue rate limit allows this order, taking its token.
+        
+        route_order gives the token back if publishing fails.
         
         Args:
             venue: Venue name
@@ -149,13 +156,4 @@ class RoutingEngine:
         Returns:
             True if allowed, False if rate limited
         """
-        now_ns = time.time_ns()
-        min_delay_ns = self.min_delay_ms * 1_000_000
-        
-        if venue not in self.venue_last_order_ns:
-            return True
-        
-        last_order_ns = self.venue_last_order_ns[venue]
-        elapsed_ns = now_ns - last_order_ns
-        
-        return elapsed_ns >= min_delay_ns
+        return self.venue_rate_limiter.try_acquire(venue=venue)
@@ -11,10 +11,42 @@ Performance: While not as fast as compiled Rust, this implementation uses:
 - Atomic-like operations where possible
 """
 
+import math
+import mmap
+import os
+import struct
 import time
-from typing import Dict, Any, List, Tuple
-from dataclasses import dataclass
+from typing import Dict, Any, List, Optional, Tuple, Union
+from dataclasses import dataclass, field
 import threading
+from collections import deque
+
+from pkg.schemas.common import OrderStatus
+from pkg.schemas.orders import RejectReason
+
+
+@dataclass
+class RustFill:
+    """Single fill of an execution (mimics Rust struct)"""
+    ts_ns: int
+    order_id: int
+    trade_id: int
+    price: float
+    qty: float
+    is_maker: bool
+    matched_order_id: int
+
+    def to_dict(self) -> Dict[str, Any]:
+        """Convert to dictionary"""
+        return {
+            "ts_ns": self.ts_ns,
+            "order_id": self.order_id,
+            "trade_id": self.trade_id,
+            "price": self.price,
+            "qty": self.qty,
+            "is_maker": self.is_maker,
+            "matched_order_id": self.matched_order_id
+        }
 
 
 @dataclass
@@ -25,39 +57,943 @@ class RustExecution:
     executed_quantity: float
     latency_ns: int
     slippage: float
+    remaining_quantity: float = 0.0
+    status: int = int(OrderStatus.FILLED)
+    fills: List[RustFill] = field(default_factory=list)
+    client_id: Optional[str] = None
 
     def to_dict(self) -> Dict[str, Any]:
         """Convert to dictionary"""
         return {
             "order_id": self.order_id,
+            "client_id": self.client_id,
             "executed_price": self.executed_price,
             "executed_quantity": self.executed_quantity,
+            "remaining_quantity": self.remaining_quantity,
+            "status": self.status,
+            "fills": [f.to_dict() for f in self.fills],
             "latency_ns": self.latency_ns,
             "slippage": self.slippage
         }
 
 
+@dataclass
+class RustReject:
+    """Order refused by the engine's risk gate (mimics Rust struct)"""
+    ts_ns: int
+    client_id: Optional[str]
+    symbol_id: int
+    reason_code: int
+    reason_msg: str
+    source: str = "risk"
+    venue_code: int = 0
+    latency_ns: int = 0
+    order_id: Optional[int] = None  # the order already holding client_id, for a duplicate
+
+    def to_dict(self) -> Dict[str, Any]:
+        """Convert to dictionary"""
+        return {
+            "ts_ns": self.ts_ns,
+            "client_id": self.client_id,
+            "symbol_id": self.symbol_id,
+            "reason_code": self.reason_code,
+            "reason_msg": self.reason_msg,
+            "source": self.source,
+            "venue_code": self.venue_code,
+            "latency_ns": self.latency_ns,
+            "order_id": self.order_id
+        }
+
+
+@dataclass
+class BookDelta:
+    """Change to one price level of the venue's book; quantity=0 deletes it"""
+    ts_ns: int
+    symbol_id: int
+    side: int
+    price: float
+    quantity: float
+    seq: int = 0
+
+
+@dataclass
+class BookSnapshot:
+    """Full book as of seq: (price, quantity) levels, best first"""
+    ts_ns: int
+    symbol_id: int
+    seq: int
+    bids: List[Tuple[float, float]]
+    asks: List[Tuple[float, float]]
+
+
+@dataclass
+class SequenceGap:
+    """Deltas between expected_seq and received_seq never arrived"""
+    ts_ns: int
+    symbol_id: int
+    expected_seq: int
+    received_seq: int
+
+
+@dataclass
+class MarketTrade:
+    """Trade printed by the venue (mimics Rust struct)"""
+    ts_ns: int
+    symbol_id: int
+    price: float
+    quantity: float
+    aggressor_side: int
+
+
+RateKey = Tuple[Optional[int], Optional[Any], Optional[str]]
+
+
+class TokenBucketLimiter:
+    """Token bucket per symbol/venue/account key (mimics Rust class)"""
+
+    def __init__(self, rate: float, burst: int = 1):
+        if not 0 < rate <= 1e9:
+            raise ValueError(f"invalid rate: {rate}")
+        if burst < 1:
+            raise ValueError("burst must be at least 1")
+        self.rate = rate
+        self.burst = burst
+        self._interval_ns = round(1e9 / rate)
+        self._tat_ns: Dict[RateKey, int] = {}
+        self._lock = threading.Lock()
+
+    def try_acquire(self, symbol_id: Optional[int] = None, venue: Optional[Any] = None,
+                    account: Optional[str] = None, cost: int = 1,
+                    now_ns: Optional[int] = None) -> bool:
+        """Take `cost` tokens, or return False and take nothing"""
+        now_ns = time.time_ns() if now_ns is None else now_ns
+        key = (symbol_id, venue, account)
+        with self._lock:
+            new_tat = max(self._tat_ns.get(key, 0), now_ns) + cost * self._interval_ns
+            if new_tat - now_ns > self.burst * self._interval_ns:
+                return False
+            self._tat_ns[key] = new_tat
+            return True
+
+    def release(self, symbol_id: Optional[int] = None, venue: Optional[Any] = None,
+                account: Optional[str] = None, cost: int = 1,
+                now_ns: Optional[int] = None):
+        """Return tokens taken by try_acquire"""
+        key = (symbol_id, venue, account)
+        with self._lock:
+            if key in self._tat_ns:
+                self._tat_ns[key] = max(0, self._tat_ns[key] - cost * self._interval_ns)
+
+    def reset(self):
+        """Refill every bucket"""
+        with self._lock:
+            self._tat_ns.clear()
+
+
+class SlidingWindowLimiter:
+    """
+    At most `limit` units per sliding window per key (mimics Rust class)
+
+    Like the compiled limiter, the window is counted in 16 sub-windows and
+    moves in 1/16th steps of `window_ms`.
+    """
+
+    SLOTS = 16
+
+    def __init__(self, limit: int, window_ms: int = 1000):
+        if not 1 <= limit < 1 << 20:
+            raise ValueError(f"limit must be between 1 and {(1 << 20) - 1}")
+        if window_ms < 1:
+            raise ValueError(f"invalid window: {window_ms}ms")
+        self.limit = limit
+        self.window_ms = window_ms
+        self._slot_ns = -(-window_ms * 1_000_000 // self.SLOTS)
+        # key -> [[epoch, count]] per sub-window
+        self._slots: Dict[RateKey, List[List[int]]] = {}
+        self._lock = threading.Lock()
+
+    def try_acquire(self, symbol_id: Optional[int] = None, venue: Optional[Any] = None,
+                    account: Optional[str] = None, cost: int = 1,
+                    now_ns: Optional[int] = None) -> bool:
+        """Count `cost` units, or return False and count nothing"""
+        now_ns = time.time_ns() if now_ns is None else now_ns
+        key = (symbol_id, venue, account)
+        epoch = now_ns // self._slot_ns
+        with self._lock:
+            slots = self._slots.setdefault(key, [[0, 0] for _ in range(self.SLOTS)])
+            total = sum(count for slot_epoch, count in slots if 0 <= epoch - slot_epoch < self.SLOTS)
+            if total + cost > self.limit:
+                return False
+            slot = slots[epoch % self.SLOTS]
+            if slot[0] != epoch:
+                slot[:] = [epoch, 0]
+            slot[1] += cost
+            return True
+
+    def release(self, symbol_id: Optional[int] = None, venue: Optional[Any] = None,
+                account: Optional[str] = None, cost: int = 1,
+                now_ns: Optional[int] = None):
+        """Uncount units from try_acquire still inside the current sub-window"""
+        now_ns = time.time_ns() if now_ns is None else now_ns
+        key = (symbol_id, venue, account)
+        epoch = now_ns // self._slot_ns
+        with self._lock:
+            slots = self._slots.get(key)
+            if slots is not None and slots[epoch % self.SLOTS][0] == epoch:
+                slot = slots[epoch % self.SLOTS]
+                slot[1] = max(0, slot[1] - cost)
+
+    def reset(self):
+        """Empty every window"""
+        with self._lock:
+            self._slots.clear()
+
+
+class _L2Book:
+    __slots__ = ("bids", "asks", "seq", "ts_ns", "synced", "gaps")
+
+    def __init__(self):
+        self.bids: Dict[float, float] = {}
+        self.asks: Dict[float, float] = {}
+        self.seq = 0
+        self.ts_ns = 0
+        self.synced = True
+        self.gaps = 0
+
+    def set_level(self, side: int, price: float, quantity: float):
+        levels = self.bids if side == 0 else self.asks
+        if quantity <= 1e-12:
+            levels.pop(price, None)
+        else:
+            levels[price] = quantity
+
+    def depth(self, side: int, levels: int) -> List[Tuple[float, float]]:
+        book = self.bids if side == 0 else self.asks
+        return sorted(book.items(), reverse=side == 0)[:levels]
+
+    def top(self) -> Optional[Tuple[Tuple[float, float], Tuple[float, float]]]:
+        if not self.bids or not self.asks:
+            return None
+        return self.depth(0, 1)[0], self.depth(1, 1)[0]
+
+
+class BookBuilder:
+    """L2 books fed from snapshots and deltas (mimics Rust class)"""
+
+    # Bound here: the module-level name is replaced by the compiled class.
+    _Gap = SequenceGap
+
+    def __init__(self):
+        self._books: Dict[int, _L2Book] = {}
+        self._lock = threading.Lock()
+
+    @staticmethod
+    def _check_level(price: float, quantity: float):
+        if not (0 < price < float("inf") and 0 <= quantity < float("inf")):
+            raise ValueError(f"invalid level: {quantity} @ {price}")
+
+    def apply_snapshot(self, snapshot: BookSnapshot) -> bool:
+        """Replace the whole book and resynchronise at snapshot.seq; False if older than the book"""
+        for price, quantity in list(snapshot.bids) + list(snapshot.asks):
+            self._check_level(price, quantity)
+        with self._lock:
+            book = self._books.setdefault(snapshot.symbol_id, _L2Book())
+            if snapshot.seq != 0 and snapshot.seq < book.seq:
+                return False
+            book.bids.clear()
+            book.asks.clear()
+            for price, quantity in snapshot.bids:
+                book.set_level(0, price, quantity)
+            for price, quantity in snapshot.asks:
+                book.set_level(1, price, quantity)
+            book.seq = snapshot.seq
+            book.ts_ns = snapshot.ts_ns
+            book.synced = True
+            return True
+
+    def apply_delta(self, delta: BookDelta) -> Optional[SequenceGap]:
+        """Apply a delta, returning the gap it revealed, if any"""
+        if delta.side not in (0, 1):
+            raise ValueError(f"invalid side: {delta.side}")
+        self._check_level(delta.price, delta.quantity)
+        with self._lock:
+            book = self._books.setdefault(delta.symbol_id, _L2Book())
+            if delta.seq != 0:
+                if not book.synced or (book.seq != 0 and delta.seq <= book.seq):
+                    return None
+                if book.seq != 0 and delta.seq != book.seq + 1:
+                    book.synced = False
+                    book.gaps += 1
+                    return self._Gap(delta.ts_ns, delta.symbol_id, book.seq + 1, delta.seq)
+                book.seq = delta.seq
+            book.set_level(delta.side, delta.price, delta.quantity)
+            book.ts_ns = delta.ts_ns
+            return None
+
+    def apply_deltas(self, deltas: List[BookDelta]) -> List[SequenceGap]:
+        """Apply deltas in order, returning the gaps they revealed"""
+        gaps = (self.apply_delta(delta) for delta in deltas)
+        return [gap for gap in gaps if gap is not None]
+
+    def top_of_book(self, symbol_id: int) -> Optional[Dict[str, Any]]:
+        """TopOfBook fields, or None unless both sides have a level"""
+        book = self._books.get(symbol_id)
+        top = book.top() if book else None
+        if top is None:
+            return None
+        (bid_px, bid_sz), (ask_px, ask_sz) = top
+        return {
+            "ts_ns": book.ts_ns,
+            "symbol_id": symbol_id,
+            "bid_px": bid_px,
+            "bid_sz": bid_sz,
+            "ask_px": ask_px,
+            "ask_sz": ask_sz
+        }
+
+    def get_depth(self, symbol_id: int, levels: int = 10) -> Dict[str, Any]:
+        """Best levels per side as (price, quantity)"""
+        book = self._books.get(symbol_id)
+        return {
+            "symbol_id": symbol_id,
+            "seq": book.seq if book else 0,
+            "ts_ns": book.ts_ns if book else 0,
+            "bids": book.depth(0, levels) if book else [],
+            "asks": book.depth(1, levels) if book else []
+        }
+
+    def mid_price(self, symbol_id: int) -> Optional[float]:
+        top = self.top_of_book(symbol_id)
+        return (top["bid_px"] + top["ask_px"]) / 2.0 if top else None
+
+    def micro_price(self, symbol_id: int) -> Optional[float]:
+        top = self.top_of_book(symbol_id)
+        if top is None:
+            return None
+        total = top["bid_sz"] + top["ask_sz"]
+        if total <= 1e-12:
+            return (top["bid_px"] + top["ask_px"]) / 2.0
+        return (top["bid_px"] * top["ask_sz"] + top["ask_px"] * top["bid_sz"]) / total
+
+    def spread(self, symbol_id: int) -> Optional[float]:
+        top = self.top_of_book(symbol_id)
+        return top["ask_px"] - top["bid_px"] if top else None
+
+    def imbalance(self, symbol_id: int, depth: int = 5) -> float:
+        book = self._books.get(symbol_id)
+        if book is None:
+            return 0.0
+        bid = sum(quantity for _, quantity in book.depth(0, depth))
+        ask = sum(quantity for _, quantity in book.depth(1, depth))
+        total = bid + ask
+        return 0.0 if total <= 1e-12 else (bid - ask) / total
+
+    def sequence(self, symbol_id: int) -> int:
+        book = self._books.get(symbol_id)
+        return book.seq if book else 0
+
+    def is_synced(self, symbol_id: int) -> bool:
+        """False after a gap until the next snapshot"""
+        book = self._books.get(symbol_id)
+        return book.synced if book else True
+
+    def gap_count(self, symbol_id: int) -> int:
+        book = self._books.get(symbol_id)
+        return book.gaps if book else 0
+
+    def symbols(self) -> List[int]:
+        return sorted(self._books)
+
+    def clear(self, symbol_id: Optional[int] = None):
+        """Drop one symbol's book, or every book"""
+        with self._lock:
+            if symbol_id is None:
+                self._books.clear()
+            else:
+                self._books.pop(symbol_id, None)
+
+
+@dataclass
+class ResyncRequest:
+    """Ask the feed handler for a fresh snapshot of symbol_id"""
+    ts_ns: int
+    symbol_id: int
+    reason: str
+    expected_seq: int
+    received_seq: int
+
+
+class _SyncedBook:
+    def __init__(self):
+        self.book = _L2Book()
+        self.state = "awaiting_snapshot"
+        self.buffer: deque = deque()
+        self.requested_ns = 0
+        self.last_update_ns = 0
+        self.gaps = 0
+        self.resyncs = 0
+        self.discarded = 0
+
+    @property
+    def live(self) -> bool:
+        return self.state in ("synced", "stale")
+
+    def request(self, symbol_id: int, ts_ns: int, reason: str, received_seq: int) -> ResyncRequest:
+        self.requested_ns = ts_ns
+        self.resyncs += 1
+        expected = self.book.seq + 1 if self.book.seq else 0
+        return ResyncRequest(ts_ns, symbol_id, reason, expected, received_seq)
+
+    def apply(self, update: Tuple[int, int, int, List[Tuple[int, float, float]]]):
+        _, last_seq, ts_ns, levels = update
+        for side, price, quantity in levels:
+            self.book.set_level(side, price, quantity)
+        self.book.seq = last_seq
+        self.book.ts_ns = ts_ns
+        self.last_update_ns = ts_ns
+
+
+class BookSync:
+    """Snapshot-then-buffered-diffs resync per symbol (mimics Rust class)"""
+
+    def __init__(self, max_buffer: int = 10_000, stale_after_ms: int = 5_000,
+                 resync_timeout_ms: int = 10_000):
+        if max_buffer < 1:
+            raise ValueError("max_buffer must be at least 1")
+        self.max_buffer = max_buffer
+        self.stale_after_ns = stale_after_ms * 1_000_000
+        self.resync_timeout_ns = resync_timeout_ms * 1_000_000
+        self._books: Dict[int, _SyncedBook] = {}
+        self._lock = threading.Lock()
+
+    def subscribe(self, symbol_id: int, ts_ns: int = 0) -> ResyncRequest:
+        """Start (or restart) tracking a symbol; without ts_ns the timeout runs from the next check"""
+        with self._lock:
+            book = self._books[symbol_id] = _SyncedBook()
+            return book.request(symbol_id, ts_ns, "initial", 0)
+
+    def on_snapshot(self, snapshot: BookSnapshot) -> List[ResyncRequest]:
+        """Apply a snapshot and replay the buffered diffs that follow it"""
+        for price, quantity in list(snapshot.bids) + list(snapshot.asks):
+            BookBuilder._check_level(price, quantity)
+        requests = []
+        with self._lock:
+            book = self._books.setdefault(snapshot.symbol_id, _SyncedBook())
+            if book.live and snapshot.seq <= book.book.seq:
+                book.discarded += 1
+                return requests
+            book.book = _L2Book()
+            for price, quantity in snapshot.bids:
+                book.book.set_level(0, price, quantity)
+            for price, quantity in snapshot.asks:
+                book.book.set_level(1, price, quantity)
+            book.book.seq = snapshot.seq
+            book.book.ts_ns = snapshot.ts_ns
+            book.last_update_ns = snapshot.ts_ns
+            book.state = "synced"
+            while book.buffer:
+                update = book.buffer.popleft()
+                first_seq, last_seq = update[0], update[1]
+                if last_seq <= book.book.seq:
+                    book.discarded += 1
+                    continue
+                if first_seq > book.book.seq + 1:
+                    if book.book.seq == snapshot.seq:
+                        reason = "snapshot_behind"
+                    else:
+                        book.gaps += 1
+                        reason = "gap"
+                    requests.append(book.request(snapshot.symbol_id, snapshot.ts_ns, reason, first_seq))
+                    book.buffer.appendleft(update)
+                    book.state = "buffering"
+                    break
+                book.apply(update)
+        return requests
+
+    def on_delta(self, delta: BookDelta) -> List[ResyncRequest]:
+        """A diff carrying a single level change; delta.seq is required"""
+        if delta.seq == 0:
+            raise ValueError("delta has no sequence number")
+        return self.on_update([delta])
+
+    def on_update(self, deltas: List[BookDelta], first_seq: Optional[int] = None) -> List[ResyncRequest]:
+        """A venue message with several level changes sharing its last seq"""
+        if not deltas:
+            raise ValueError("update has no deltas")
+        head = deltas[0]
+        if head.seq == 0:
+            raise ValueError("update has no sequence number")
+        first_seq = head.seq if first_seq is None else first_seq
+        if not 0 < first_seq <= head.seq:
+            raise ValueError(f"first_seq {first_seq} outside 1..={head.seq}")
+        levels = []
+        for delta in deltas:
+            if delta.symbol_id != head.symbol_id or delta.seq != head.seq:
+                raise ValueError("deltas of one update must share symbol_id and seq")
+            if delta.side not in (0, 1):
+                raise ValueError(f"invalid side: {delta.side}")
+            BookBuilder._check_level(delta.price, delta.quantity)
+            levels.append((delta.side, delta.price, delta.quantity))
+        update = (first_seq, head.seq, head.ts_ns, levels)
+
+        requests = []
+        with self._lock:
+            book = self._books.get(head.symbol_id)
+            if book is None:
+                book = self._books[head.symbol_id] = _SyncedBook()
+                requests.append(book.request(head.symbol_id, head.ts_ns, "initial", first_seq))
+            if not book.live:
+                self._buffer(book, head.symbol_id, update, requests)
+            elif head.seq <= book.book.seq:
+                book.discarded += 1
+            elif first_seq <= book.book.seq + 1:
+                book.apply(update)
+                book.state = "synced"
+            else:
+                book.gaps += 1
+                requests.append(book.request(head.symbol_id, head.ts_ns, "gap", first_seq))
+                self._buffer(book, head.symbol_id, update, requests)
+        return requests
+
+    def _buffer(self, book: _SyncedBook, symbol_id: int, update, requests: List[ResyncRequest]):
+        if len(book.buffer) >= self.max_buffer:
+            book.buffer.clear()
+            requests.append(book.request(symbol_id, update[2], "buffer_overflow", update[0]))
+        book.buffer.append(update)
+        book.state = "buffering"
+
+    def check(self, now_ns: int) -> List[ResyncRequest]:
+        """Mark quiet books stale and repeat overdue snapshot requests"""
+        requests = []
+        with self._lock:
+            for symbol_id in sorted(self._books):
+                book = self._books[symbol_id]
+                if book.state == "synced" and now_ns - book.last_update_ns > self.stale_after_ns:
+                    book.state = "stale"
+                elif not book.live and book.requested_ns == 0:
+                    book.requested_ns = now_ns
+                elif not book.live and now_ns - book.requested_ns > self.resync_timeout_ns:
+                    requests.append(book.request(symbol_id, now_ns, "timeout", 0))
+        return requests
+
+    def state(self, symbol_id: int) -> Optional[str]:
+        book = self._books.get(symbol_id)
+        return book.state if book else None
+
+    def get_stats(self, symbol_id: int) -> Optional[Dict[str, Any]]:
+        book = self._books.get(symbol_id)
+        if book is None:
+            return None
+        return {
+            "symbol_id": symbol_id,
+            "state": book.state,
+            "seq": book.book.seq,
+            "buffered": len(book.buffer),
+            "gaps": book.gaps,
+            "resyncs": book.resyncs,
+            "discarded": book.discarded
+        }
+
+    def _live(self, symbol_id: int) -> Optional[BookBuilder]:
+        book = self._books.get(symbol_id)
+        if book is None or not book.live:
+            return None
+        view = BookBuilder()
+        view._books[symbol_id] = book.book
+        return view
+
+    def top_of_book(self, symbol_id: int) -> Optional[Dict[str, Any]]:
+        view = self._live(symbol_id)
+        return view.top_of_book(symbol_id) if view else None
+
+    def get_depth(self, symbol_id: int, levels: int = 10) -> Dict[str, Any]:
+        view = self._live(symbol_id) or BookBuilder()
+        depth = view.get_depth(symbol_id, levels)
+        depth["state"] = self.state(symbol_id)
+        return depth
+
+    def mid_price(self, symbol_id: int) -> Optional[float]:
+        view = self._live(symbol_id)
+        return view.mid_price(symbol_id) if view else None
+
+    def micro_price(self, symbol_id: int) -> Optional[float]:
+        view = self._live(symbol_id)
+        return view.micro_price(symbol_id) if view else None
+
+    def spread(self, symbol_id: int) -> Optional[float]:
+        view = self._live(symbol_id)
+        return view.spread(symbol_id) if view else None
+
+    def imbalance(self, symbol_id: int, depth: int = 5) -> Optional[float]:
+        view = self._live(symbol_id)
+        return view.imbalance(symbol_id, depth) if view else None
+
+    def symbols(self) -> List[int]:
+        return sorted(self._books)
+
+    def unsubscribe(self, symbol_id: int) -> bool:
+        with self._lock:
+            return self._books.pop(symbol_id, None) is not None
+
+
+@dataclass
+class RustFeatureFrame:
+    """FeatureFrame fields plus streaming window statistics (mimics Rust struct)"""
+    ts_ns: int = 0
+    symbol_id: int = 0
+    mid_price: float = 0.0
+    micro_price: float = 0.0
+    spread: float = 0.0
+    spread_bps: float = 0.0
+    bid_volume: float = 0.0
+    ask_volume: float = 0.0
+    imbalance: float = 0.0
+    realized_vol: float = 0.0
+    price_range: float = 0.0
+    price_change: float = 0.0
+    price_change_pct: float = 0.0
+    regime_bits: int = 0
+    window_return: float = 0.0
+    spread_mean: float = 0.0
+    spread_std: float = 0.0
+    spread_min: float = 0.0
+    spread_max: float = 0.0
+    ofi: float = 0.0
+    book_pressure: float = 0.0
+    trade_count: int = 0
+    trade_volume: float = 0.0
+    trade_intensity: float = 0.0
+    signed_volume: float = 0.0
+    vwap: float = 0.0
+    samples: int = 0
+
+    def has_regime(self, flag: int) -> bool:
+        return bool(self.regime_bits & flag)
+
+    def set_regime(self, flag: int):
+        self.regime_bits |= flag
+
+    def clear_regime(self, flag: int):
+        self.regime_bits &= ~flag
+
+    def to_dict(self) -> Dict[str, Any]:
+        return dict(self.__dict__)
+
+
+def _ratio(numerator: float, denominator: float) -> float:
+    return 0.0 if abs(denominator) <= 1e-12 else numerator / denominator
+
+
+def _pstd(values: List[float]) -> float:
+    if not values:
+        return 0.0
+    mean = sum(values) / len(values)
+    return math.sqrt(sum((v - mean) ** 2 for v in values) / len(values))
+
+
+class FeatureCalculator:
+    """Windowed microstructure features per symbol (mimics Rust class).
+
+    Recomputes over the window on every update; the compiled version is O(1).
+    """
+
+    # Bound here: the module-level name is replaced by the compiled class.
+    _Frame = RustFeatureFrame
+
+    def __init__(self, window_ms: int = 500, trade_window_ms: Optional[int] = None):
+        trade_window_ms = window_ms if trade_window_ms is None else trade_window_ms
+        if window_ms <= 0 or trade_window_ms <= 0:
+            raise ValueError("windows must be positive")
+        self.window_ns = window_ms * 1_000_000
+        self.trade_window_ns = trade_window_ms * 1_000_000
+        self._symbols: Dict[int, Dict[str, Any]] = {}
+        self._lock = threading.Lock()
+
+    def _state(self, symbol_id: int) -> Dict[str, Any]:
+        return self._symbols.setdefault(
+            symbol_id, {"quotes": deque(), "trades": deque(), "last": None, "now_ns": 0, "change": 0.0}
+        )
+
+    def _evict(self, state: Dict[str, Any]):
+        while state["quotes"] and state["quotes"][0][0] < state["now_ns"] - self.window_ns:
+            state["quotes"].popleft()
+        while state["trades"] and state["trades"][0][0] < state["now_ns"] - self.trade_window_ns:
+            state["trades"].popleft()
+
+    def on_quote(self, ts_ns: int, symbol_id: int, bid_px: float, bid_sz: float, ask_px: float,
+                 ask_sz: float, bid_depth: Optional[float] = None,
+                 ask_depth: Optional[float] = None) -> RustFeatureFrame:
+        """Add a top-of-book update and return the features it produces"""
+        if not all(0 <= v < float("inf") for v in (bid_px, bid_sz, ask_px, ask_sz)):
+            raise ValueError(f"invalid quote: {bid_sz} x {bid_px} / {ask_sz} x {ask_px}")
+        quote = (ts_ns, bid_px, bid_sz, ask_px, ask_sz,
+                 bid_sz if bid_depth is None else bid_depth,
+                 ask_sz if ask_depth is None else ask_depth)
+        with self._lock:
+            state = self._state(symbol_id)
+            state["now_ns"] = max(state["now_ns"], ts_ns)
+            last = state["last"]
+            mid = (bid_px + ask_px) / 2.0
+            state["change"] = mid - (last[1] + last[3]) / 2.0 if last else 0.0
+            state["quotes"].append(quote)
+            state["last"] = quote
+            self._evict(state)
+            return self._frame(symbol_id, state)
+
+    def on_tob(self, tob) -> RustFeatureFrame:
+        """on_quote for a TopOfBook"""
+        return self.on_quote(tob.ts_ns, tob.symbol_id, tob.bid_px, tob.bid_sz, tob.ask_px, tob.ask_sz)
+
+    def on_trade(self, ts_ns: int, symbol_id: int, price: float, quantity: float, aggressor_side: int):
+        """Add a trade (aggressor 0 = buyer, 1 = seller)"""
+        if not (0 < price < float("inf") and 0 < quantity < float("inf")):
+            raise ValueError(f"invalid trade: {quantity} @ {price}")
+        if aggressor_side not in (0, 1):
+            raise ValueError(f"invalid side: {aggressor_side}")
+        with self._lock:
+            state = self._state(symbol_id)
+            state["now_ns"] = max(state["now_ns"], ts_ns)
+            state["trades"].append((ts_ns, price, quantity, quantity if aggressor_side == 0 else -quantity))
+            self._evict(state)
+
+    def _frame(self, symbol_id: int, state: Dict[str, Any]) -> Optional[RustFeatureFrame]:
+        last = state["last"]
+        if last is None:
+            return None
+        _, bid_px, bid_sz, ask_px, ask_sz, _, _ = last
+        quotes = list(state["quotes"])
+        trades = list(state["trades"])
+        mid = (bid_px + ask_px) / 2.0
+        spread = ask_px - bid_px
+        total = bid_sz + ask_sz
+        mids = [(q[1] + q[3]) / 2.0 for q in quotes]
+        spreads = [q[3] - q[1] for q in quotes]
+        returns = [_ratio(mids[k], mids[k - 1]) - 1.0 for k in range(1, len(mids))]
+        ofi = 0.0
+        for prev, cur in zip(quotes, quotes[1:]):
+            ofi += (cur[2] if cur[1] >= prev[1] else 0.0) - (prev[2] if cur[1] <= prev[1] else 0.0)
+            ofi += (prev[4] if cur[3] >= prev[3] else 0.0) - (cur[4] if cur[3] <= prev[3] else 0.0)
+        volume = sum(t[2] for t in trades)
+        change = state["change"]
+        return self._Frame(
+            ts_ns=state["now_ns"],
+            symbol_id=symbol_id,
+            mid_price=mid,
+            micro_price=(bid_px * ask_sz + ask_px * bid_sz) / total if total > 1e-12 else mid,
+            spread=spread,
+            spread_bps=spread / mid * 10_000 if mid > 0 else 0.0,
+            bid_volume=bid_sz,
+            ask_volume=ask_sz,
+            imbalance=_ratio(bid_sz - ask_sz, total),
+            realized_vol=_pstd(returns),
+            price_range=max(mids) - min(mids) if mids else 0.0,
+            price_change=change,
+            price_change_pct=_ratio(change, mid - change) * 100,
+            window_return=_ratio(mid, mids[0]) - 1.0 if mids else 0.0,
+            spread_mean=sum(spreads) / len(spreads) if spreads else 0.0,
+            spread_std=_pstd(spreads),
+            spread_min=min(spreads) if spreads else 0.0,
+            spread_max=max(spreads) if spreads else 0.0,
+            ofi=ofi,
+            book_pressure=_ratio(sum(_ratio(q[5] - q[6], q[5] + q[6]) for q in quotes), len(quotes)),
+            trade_count=len(trades),
+            trade_volume=volume,
+            trade_intensity=len(trades) * 1e9 / self.trade_window_ns,
+            signed_volume=sum(t[3] for t in trades),
+            vwap=_ratio(sum(t[1] * t[2] for t in trades), volume),
+            samples=len(quotes)
+        )
+
+    def get_frame(self, symbol_id: int) -> Optional[RustFeatureFrame]:
+        """Latest features of a symbol, including trades since its last quote"""
+        state = self._symbols.get(symbol_id)
+        return self._frame(symbol_id, state) if state else None
+
+    def symbols(self) -> List[int]:
+        return sorted(self._symbols)
+
+    def reset(self, symbol_id: Optional[int] = None):
+        with self._lock:
+            if symbol_id is None:
+                self._symbols.clear()
+            else:
+                self._symbols.pop(symbol_id, None)
+
+
+class PositionLedger:
+    """Positions and PnL from caller-supplied fills (mimics Rust class)"""
+
+    _METHODS = {"fifo": "fifo", "lifo": "lifo", "average": "average", "average_cost": "average"}
+
+    def __init__(self, cost_method: str = "fifo"):
+        self.set_cost_method(cost_method)
+        self._positions: Dict[int, Dict[str, Any]] = {}
+        self._marks: Dict[int, float] = {}
+        self._lock = threading.Lock()
+
+    def set_cost_method(self, cost_method: str):
+        """Match closing fills FIFO, LIFO or against the average cost"""
+        if cost_method not in self._METHODS:
+            raise ValueError(f"invalid cost method: {cost_method}")
+        self._method = self._METHODS[cost_method]
+        if self._method == "average":
+            for position in getattr(self, "_positions", {}).values():
+                lots = position["lots"]
+                if len(lots) > 1:
+                    qty = sum(q for q, _ in lots)
+                    lots[:] = [[qty, sum(q * p for q, p in lots) / qty]]
+
+    def on_fill(self, symbol_id: int, side: int, price: float, quantity: float,
+                fee: float = 0.0) -> Optional[float]:
+        """Apply a fill; returns realized PnL if it closed part of the position"""
+        if price <= 0 or quantity <= 0:
+            raise ValueError(f"invalid fill: {quantity} @ {price}")
+        signed = quantity if side == 0 else -quantity
+        with self._lock:
+            pos = self._positions.setdefault(symbol_id, {
+                "lots": [], "net_qty": 0.0, "realized_pnl": 0.0, "fees": 0.0,
+                "traded_qty": 0.0, "fill_count": 0, "last_fill_price": 0.0,
+            })
+            pos["fees"] += fee
+            pos["traded_qty"] += quantity
+            pos["fill_count"] += 1
+            pos["last_fill_price"] = price
+
+            lots = pos["lots"]
+            remaining = quantity
+            realized = None
+            if pos["net_qty"] * signed < 0:
+                direction = 1.0 if pos["net_qty"] > 0 else -1.0
+                realized = 0.0
+                idx = -1 if self._method == "lifo" else 0
+                while remaining > 1e-9 and lots:
+                    lot = lots[idx]
+                    closed = min(remaining, lot[0])
+                    realized += closed * (price - lot[1]) * direction
+                    lot[0] -= closed
+                    remaining -= closed
+                    if lot[0] <= 1e-9:
+                        lots.pop(idx)
+                pos["realized_pnl"] += realized
+
+            if remaining > 1e-9:
+                if self._method == "average" and lots:
+                    total = lots[0][0] + remaining
+                    lots[0] = [total, (lots[0][1] * lots[0][0] + price * remaining) / total]
+                else:
+                    lots.append([remaining, price])
+
+            pos["net_qty"] += signed
+            if abs(pos["net_qty"]) <= 1e-9:
+                pos["net_qty"] = 0.0
+                lots.clear()
+            return realized
+
+    def set_mark_price(self, symbol_id: int, price: float):
+        """Price unrealized PnL is measured against"""
+        self._marks[symbol_id] = price
+
+    def get_position(self, symbol_id: int) -> Optional[Dict[str, Any]]:
+        """Snapshot of one symbol, or None if it never filled"""
+        with self._lock:
+            pos = self._positions.get(symbol_id)
+            if pos is None:
+                return None
+            qty = sum(q for q, _ in pos["lots"])
+            avg_entry = sum(q * p for q, p in pos["lots"]) / qty if qty > 1e-9 else 0.0
+            mark = self._marks.get(symbol_id, pos["last_fill_price"])
+            unrealized = pos["net_qty"] * (mark - avg_entry)
+            return {
+                "symbol_id": symbol_id,
+                "net_qty": pos["net_qty"],
+                "avg_entry_price": avg_entry,
+                "mark_price": mark,
+                "notional": pos["net_qty"] * mark,
+                "realized_pnl": pos["realized_pnl"],
+                "unrealized_pnl": unrealized,
+                "fees": pos["fees"],
+                "net_pnl": pos["realized_pnl"] + unrealized - pos["fees"],
+                "traded_qty": pos["traded_qty"],
+                "fill_count": pos["fill_count"],
+            }
+
+    def get_positions(self) -> List[Dict[str, Any]]:
+        """Snapshots of every symbol that has filled"""
+        return [self.get_position(symbol_id) for symbol_id in sorted(self._positions)]
+
+    def get_portfolio(self) -> Dict[str, Any]:
+        """Totals across positions"""
+        positions = self.get_positions()
+        return {
+            "cost_method": self._method,
+            "positions": sum(1 for p in positions if p["net_qty"] != 0.0),
+            "realized_pnl": sum(p["realized_pnl"] for p in positions),
+            "unrealized_pnl": sum(p["unrealized_pnl"] for p in positions),
+            "fees": sum(p["fees"] for p in positions),
+            "net_pnl": sum(p["net_pnl"] for p in positions),
+            "gross_exposure": sum(abs(p["notional"]) for p in positions),
+            "net_exposure": sum(p["notional"] for p in positions),
+        }
+
+    def reset(self):
+        """Forget every position and mark"""
+        with self._lock:
+            self._positions.clear()
+            self._marks.clear()
+
+
+class _Level:
+    """One price level: the engine's orders in FIFO order behind venue quantity"""
+
+    __slots__ = ("orders", "external")
+
+    def __init__(self):
+        # [order_id, leaves, queue_ahead, post_only]
+        self.orders: deque = deque()
+        self.external = 0.0
+
+    def quantity(self) -> float:
+        return self.external + sum(order[1] for order in self.orders)
+
+    def empty(self) -> bool:
+        return not self.orders and self.external <= 1e-12
+
+
 class RustExecutionEngine:
     """
     High-performance execution engine (Python implementation)
 
-    Note: This mimics the Rust module API. Replace with compiled Rust
-    module (sigmax_rust_execution.so) for true sub-100ns performance.
+    Note: This mimics the Rust module API for limit and market orders: a
+    price-time-priority book per symbol holding the engine's resting orders
+    behind venue liquidity from `on_book_level`, time in force, self-trade
+    prevention, cancel and amend. Conditional orders, queue models, passive
+    fills from venue trades, fees, risk limits and simulated latency need
+    the compiled Rust module (sigmax_rust_execution.so).
     """
 
-    def __init__(self, queue_capacity: int = 10000):
+    def __init__(self, queue_capacity: int = 10000, journal_dir: Optional[str] = None,
+                 journal_sync: bool = False):
         """
         Initialize execution engine
 
         Args:
             queue_capacity: Maximum queue size (for compatibility)
+            journal_dir: Order journal to recover from (compiled module only)
+            journal_sync: fsync every journal record (compiled module only)
         """
-        self._next_order_id = 0
+        if journal_dir is not None:
+            raise NotImplementedError("journaling requires the compiled sigmax_rust_execution module")
+        self._next_order_id = 1
+        self._next_trade_id = 0
+        self._books: Dict[int, Tuple[Dict[float, _Level], Dict[float, _Level]]] = {}
+        self._orders: Dict[int, Dict[str, Any]] = {}
+        self._client_ids: Dict[str, int] = {}
+        self._stp = "cancel_resting"
         self._total_executions = 0
         self._total_latency_ns = 0
         self._min_latency_ns = float('inf')
         self._max_latency_ns = 0
-        self._lock = threading.Lock()  # For thread safety
+        self._lock = threading.RLock()  # For thread safety
 
     def execute_order(
         self,
@@ -65,48 +1001,382 @@ class RustExecutionEngine:
         side: int,  # 0 = buy, 1 = sell
         order_type: int,  # 0 = limit, 1 = market
         price: float,
-        quantity: float
-    ) -> RustExecution:
+        quantity: float,
+        tif: int = 1,  # pkg.schemas.common.TimeInForce, GTC by default
+        trigger_price: Optional[float] = None,
+        trail_amount: Optional[float] = None,
+        trail_percent: Optional[float] = None,
+        trigger_source: int = 0,
+        client_id: Optional[str] = None,
+        account: Optional[str] = None
+    ) -> Union[RustExecution, RustReject]:
         """
-        Execute order with minimal latency
+        Match an order against the book
 
         Args:
             symbol_id: Symbol identifier (integer for speed)
             side: 0 for buy, 1 for sell
-            order_type: 0 for limit, 1 for market
-            price: Order price
+            order_type: 0 for limit, 1 for market (conditional types need the compiled module)
+            price: Limit price (ignored for market orders)
             quantity: Order quantity
+            tif: Time in force; IOC/FOK and market remainders never rest, GTX never takes
+            client_id: Caller's id; reusing one returns a DUPLICATE_CLIENT_ID RustReject naming the original order
 
         Returns:
-            Execution result
+            Execution result, or the RustReject for a duplicate client_id
         """
         # Use perf_counter_ns for high precision timing
         start_ns = time.perf_counter_ns()
 
-        # Generate order ID (atomic-like increment)
+        if side not in (0, 1):
+            raise ValueError(f"invalid side: {side}")
+        if order_type not in (0, 1):
+            if 2 <= order_type <= 6:
+                raise NotImplementedError("conditional orders require the compiled sigmax_rust_execution module")
+            raise ValueError(f"invalid order type: {order_type}")
+        if tif not in (1, 2, 3, 4):
+            raise ValueError(f"invalid time in force: {tif}")
+        if not (math.isfinite(quantity) and quantity > 0):
+            raise ValueError(f"invalid quantity: {quantity}")
+        limit = price if order_type == 0 else None
+        if limit is not None and not (math.isfinite(limit) and limit > 0):
+            raise ValueError(f"invalid limit price: {price}")
+
         with self._lock:
+            if client_id is not None and client_id in self._client_ids:
+                original = self._client_ids[client_id]
+                return RustReject(
+                    ts_ns=time.time_ns(),
+                    client_id=client_id,
+                    symbol_id=symbol_id,
+                    reason_code=RejectReason.DUPLICATE_CLIENT_ID,
+                    reason_msg=f"client_id already used by order {original}",
+                    source="router",
+                    latency_ns=time.perf_counter_ns() - start_ns,
+                    order_id=original,
+                )
+
             order_id = self._next_order_id
             self._next_order_id += 1
-
-        # Execute order (inline for speed)
-        executed_price = price  # Simplified
-        executed_quantity = quantity
-        slippage = 0.0001 if side == 0 else -0.0001
+            now_ns = time.time_ns()
+            self._orders[order_id] = {
+                "order_id": order_id,
+                "client_id": client_id,
+                "symbol_id": symbol_id,
+                "side": side,
+                "order_type": order_type,
+                "tif": tif,
+                "price": limit or 0.0,
+                "quantity": quantity,
+                "filled_quantity": 0.0,
+                "status": int(OrderStatus.SUBMITTED),
+                "created_ns": now_ns,
+                "updated_ns": now_ns,
+            }
+            if client_id is not None:
+                self._client_ids[client_id] = order_id
+            execution = self._match(order_id, symbol_id, side, tif, limit, quantity)
+            execution.client_id = client_id
 
         # Calculate latency
-        latency_ns = time.perf_counter_ns() - start_ns
+        execution.latency_ns = time.perf_counter_ns() - start_ns
 
         # Update stats
-        self._update_stats(latency_ns)
+        self._update_stats(execution.latency_ns)
+
+        return execution
+
+    @staticmethod
+    def _unfilled(order_id: int, remaining: float, status: OrderStatus) -> RustExecution:
+        return RustExecution(order_id=order_id, executed_price=0.0, executed_quantity=0.0,
+                             latency_ns=0, slippage=0.0, remaining_quantity=remaining,
+                             status=int(status))
+
+    def _book(self, symbol_id: int) -> Tuple[Dict[float, _Level], Dict[float, _Level]]:
+        return self._books.setdefault(symbol_id, ({}, {}))
+
+    @staticmethod
+    def _best(levels: Dict[float, _Level], side: int) -> Optional[float]:
+        """Highest bid (side 0) or lowest ask (side 1)"""
+        if not levels:
+            return None
+        return max(levels) if side == 0 else min(levels)
+
+    @staticmethod
+    def _crosses(side: int, limit: Optional[float], level_price: float) -> bool:
+        if limit is None:
+            return True
+        return level_price <= limit if side == 0 else level_price >= limit
+
+    def _available(self, levels: Dict[float, _Level], side: int, limit: Optional[float]) -> float:
+        """Venue quantity an incoming order could take, for FOK"""
+        available = 0.0
+        for level_price in sorted(levels, reverse=(side == 1)):
+            if not self._crosses(side, limit, level_price):
+                break
+            level = levels[level_price]
+            if level.orders and self._stp == "cancel_incoming":
+                return available + min(level.orders[0][2], level.external)
+            available += level.external
+        return available
+
+    def _take(self, level: _Level, quantity: float, cancelled: List[int]) -> Tuple[float, bool]:
+        """Take venue quantity at a level; returns (traded, stopped by self-trade prevention)"""
+        remaining = quantity
+        external_taken = 0.0
+        stopped = False
+        while level.orders:
+            ahead = max(level.orders[0][2] - external_taken, 0.0)
+            from_external = min(remaining, ahead)
+            external_taken += from_external
+            remaining -= from_external
+            if remaining <= 1e-12:
+                break
+            if self._stp == "cancel_incoming":
+                stopped = True
+                break
+            cancelled.append(level.orders.popleft()[0])
+        for order in level.orders:
+            order[2] = max(order[2] - external_taken, 0.0)
+        if not stopped:
+            behind = max(level.external - external_taken, 0.0)
+            external_taken += min(remaining, behind)
+            remaining -= min(remaining, behind)
+        level.external = max(level.external - external_taken, 0.0)
+        return quantity - remaining, stopped
+
+    def _match(self, order_id: int, symbol_id: int, side: int, tif: int,
+               limit: Optional[float], quantity: float) -> RustExecution:
+        bids, asks = self._book(symbol_id)
+        own, opposite = (bids, asks) if side == 0 else (asks, bids)
+        record = self._orders[order_id]
+        rejected = self._unfilled(order_id, quantity, OrderStatus.REJECTED)
+
+        touch = self._best(opposite, 1 - side)
+        if tif == 4 and (limit is None or (touch is not None and self._crosses(side, limit, touch))):
+            record["status"] = rejected.status
+            return rejected
+        if tif == 3 and self._available(opposite, side, limit) + 1e-12 < quantity:
+            record["status"] = rejected.status
+            return rejected
+
+        best_bid, best_ask = self._best(bids, 0), self._best(asks, 1)
+        if best_bid is not None and best_ask is not None:
+            arrival_price = (best_bid + best_ask) / 2
+        else:
+            arrival_price = touch if touch is not None else (limit or 0.0)
+
+        fills: List[RustFill] = []
+        cancelled: List[int] = []
+        remaining = quantity
+        self_trade = False
+        while remaining > 1e-12:
+            level_price = self._best(opposite, 1 - side)
+            if level_price is None or not self._crosses(side, limit, level_price):
+                break
+            level = opposite[level_price]
+            cancelled_before = len(cancelled)
+            traded, self_trade = self._take(level, remaining, cancelled)
+            remaining -= traded
+            if level.empty():
+                del opposite[level_price]
+            if traded > 1e-12:
+                self._next_trade_id += 1
+                fills.append(RustFill(ts_ns=time.time_ns(), order_id=order_id, trade_id=self._next_trade_id,
+                                      price=level_price, qty=traded, is_maker=False, matched_order_id=0))
+            if self_trade or (traded <= 1e-12 and len(cancelled) == cancelled_before):
+                break
+        for resting_id in cancelled:
+            self._set_status(resting_id, OrderStatus.CANCELLED)
+
+        executed = quantity - remaining
+        executed_price = sum(f.price * f.qty for f in fills) / executed if executed > 0 else 0.0
+        if remaining <= 1e-12:
+            status = OrderStatus.FILLED
+        elif limit is not None and tif in (1, 4) and not self_trade:
+            level = own.setdefault(limit, _Level())
+            level.orders.append([order_id, remaining, level.external, tif == 4])
+            status = OrderStatus.PARTIAL if executed > 0 else OrderStatus.SUBMITTED
+        else:
+            # IOC/FOK and market remainders never rest, nor does an order
+            # stopped by self-trade prevention.
+            status = OrderStatus.CANCELLED
+        record["filled_quantity"] += executed
+        record["status"] = int(status)
+        record["updated_ns"] = time.time_ns()
 
         return RustExecution(
             order_id=order_id,
             executed_price=executed_price,
-            executed_quantity=executed_quantity,
-            latency_ns=latency_ns,
-            slippage=slippage
+            executed_quantity=executed,
+            latency_ns=0,
+            slippage=executed_price - arrival_price if executed > 0 and arrival_price > 0 else 0.0,
+            remaining_quantity=max(remaining, 0.0),
+            status=int(status),
+            fills=fills,
         )
 
+    def _set_status(self, order_id: int, status: OrderStatus):
+        record = self._orders.get(order_id)
+        if record is not None:
+            record["status"] = int(status)
+            record["updated_ns"] = time.time_ns()
+
+    def _find(self, order_id: int) -> Optional[Tuple[Dict[float, _Level], float, _Level, List[Any]]]:
+        """(levels, price, level, entry) of a resting order"""
+        record = self._orders.get(order_id)
+        if record is None or record["status"] not in (OrderStatus.SUBMITTED, OrderStatus.PARTIAL):
+            return None
+        levels = self._book(record["symbol_id"])[record["side"]]
+        level = levels.get(record["price"])
+        for entry in level.orders if level else ():
+            if entry[0] == order_id:
+                return levels, record["price"], level, entry
+        return None
+
+    def _remove(self, order_id: int) -> bool:
+        found = self._find(order_id)
+        if found is None:
+            return False
+        levels, level_price, level, entry = found
+        level.orders.remove(entry)
+        if level.empty():
+            del levels[level_price]
+        return True
+
+    def _resolve(self, order_id: Optional[int], client_id: Optional[str]) -> Optional[int]:
+        if order_id is None and client_id is None:
+            raise ValueError("order_id or client_id is required")
+        if client_id is None:
+            return order_id
+        resolved = self._client_ids.get(client_id)
+        if order_id is not None and resolved is not None and resolved != order_id:
+            raise ValueError(f"client_id {client_id} belongs to order {resolved}, not {order_id}")
+        return order_id if order_id is not None else resolved
+
+    def on_book_level(self, symbol_id: int, side: int, price: float, quantity: float,
+                      ts_ns: Optional[int] = None):
+        """Set the venue's displayed quantity at one price level (0 deletes it)"""
+        if side not in (0, 1):
+            raise ValueError(f"invalid side: {side}")
+        if not (math.isfinite(price) and price > 0 and math.isfinite(quantity) and quantity >= 0):
+            raise ValueError(f"invalid level: {quantity} @ {price}")
+        with self._lock:
+            levels = self._book(symbol_id)[side]
+            level = levels.setdefault(price, _Level())
+            level.external = quantity
+            for order in level.orders:
+                order[2] = min(order[2], quantity)
+            if level.empty():
+                del levels[price]
+
+    def cancel_order(self, order_id: Optional[int] = None, client_id: Optional[str] = None) -> bool:
+        """Cancel a resting order by engine or client id"""
+        with self._lock:
+            order_id = self._resolve(order_id, client_id)
+            if order_id is None or not self._remove(order_id):
+                return False
+            self._set_status(order_id, OrderStatus.CANCELLED)
+            return True
+
+    def cancel_all(self, symbol_id: Optional[int] = None) -> List[int]:
+        """Cancel every resting order, optionally on one symbol"""
+        with self._lock:
+            cancelled = [order_id for order_id, record in self._orders.items()
+                         if symbol_id in (None, record["symbol_id"]) and self._remove(order_id)]
+            for order_id in cancelled:
+                self._set_status(order_id, OrderStatus.CANCELLED)
+            return cancelled
+
+    def amend_order(self, order_id: Optional[int] = None, new_price: Optional[float] = None,
+                    new_qty: Optional[float] = None, client_id: Optional[str] = None,
+                    account: Optional[str] = None) -> RustExecution:
+        """
+        Amend a resting order's price and/or total quantity
+
+        A pure size decrease keeps queue priority; a price change or size
+        increase re-enters the order at the back of the queue and may match.
+        """
+        start_ns = time.perf_counter_ns()
+        if new_price is None and new_qty is None:
+            raise ValueError("amend needs new_price and/or new_qty")
+        if new_price is not None and not (math.isfinite(new_price) and new_price > 0):
+            raise ValueError(f"invalid price: {new_price}")
+        if new_qty is not None and not (math.isfinite(new_qty) and new_qty >= 0):
+            raise ValueError(f"invalid quantity: {new_qty}")
+
+        with self._lock:
+            order_id = self._resolve(order_id, client_id)
+            found = self._find(order_id) if order_id is not None else None
+            if found is None:
+                execution = self._unfilled(order_id or 0, 0.0, OrderStatus.REJECTED)
+                execution.client_id = client_id
+                return execution
+            record = self._orders[order_id]
+            levels, level_price, level, entry = found
+            price = new_price if new_price is not None else level_price
+            filled = record["filled_quantity"]
+            leaves = (new_qty if new_qty is not None else record["quantity"]) - filled
+            touch = self._best(self._book(record["symbol_id"])[1 - record["side"]], 1 - record["side"])
+
+            if leaves <= 1e-12:
+                self._remove(order_id)
+                execution = self._unfilled(order_id, 0.0, OrderStatus.CANCELLED)
+            elif price == level_price and leaves <= entry[1]:
+                entry[1] = leaves
+                status = OrderStatus.PARTIAL if filled > 0 else OrderStatus.SUBMITTED
+                execution = self._unfilled(order_id, leaves, status)
+            elif entry[3] and touch is not None and self._crosses(record["side"], price, touch):
+                execution = self._unfilled(order_id, entry[1], OrderStatus.REJECTED)
+            else:
+                self._remove(order_id)
+                execution = self._match(order_id, record["symbol_id"], record["side"],
+                                        4 if entry[3] else 1, price, leaves)
+                if filled > 0 and execution.status == OrderStatus.SUBMITTED:
+                    execution.status = int(OrderStatus.PARTIAL)
+
+            if execution.status != OrderStatus.REJECTED:
+                record["price"] = price
+                record["quantity"] = new_qty if new_qty is not None else record["quantity"]
+                record["status"] = execution.status
+                record["updated_ns"] = time.time_ns()
+            execution.client_id = record["client_id"]
+
+        execution.latency_ns = time.perf_counter_ns() - start_ns
+        return execution
+
+    def set_self_trade_prevention(self, mode: str):
+        """
+        What an incoming order does when it reaches one of the engine's own resting orders
+
+        "cancel_resting" (the default) cancels the resting order and keeps
+        matching, "cancel_incoming" cancels the rest of the incoming order.
+        """
+        if mode not in ("cancel_resting", "cancel_incoming"):
+            raise ValueError(f"unknown self-trade prevention mode: {mode}")
+        with self._lock:
+            self._stp = mode
+
+    def get_self_trade_prevention(self) -> str:
+        return self._stp
+
+    def get_order(self, order_id: Optional[int] = None,
+                  client_id: Optional[str] = None) -> Optional[Dict[str, Any]]:
+        """Lifecycle state of an order by engine or client id"""
+        with self._lock:
+            record = self._orders.get(self._resolve(order_id, client_id))
+            return dict(record) if record is not None else None
+
+    def get_book(self, symbol_id: int, levels: int = 10) -> Dict[str, Any]:
+        """(price, quantity, engine order count) levels, best first"""
+        with self._lock:
+            bids, asks = self._books.get(symbol_id, ({}, {}))
+            return {
+                "symbol_id": symbol_id,
+                "bids": [(p, bids[p].quantity(), len(bids[p].orders)) for p in sorted(bids, reverse=True)[:levels]],
+                "asks": [(p, asks[p].quantity(), len(asks[p].orders)) for p in sorted(asks)[:levels]],
+            }
+
     def get_stats(self) -> Dict[str, Any]:
         """Get performance statistics"""
         with self._lock:
@@ -209,18 +1479,285 @@ def benchmark_latency(iterations: int = 1000) -> Dict[str, Any]:
     }
 
 
+class ShmRing:
+    """
+    Shared-memory frame queue (Python implementation)
+
+    Same file layout as the compiled ring, so either side of a stage may use
+    it, but single-producer only: there is no cross-process CAS here.
+    """
+
+    _MAGIC = int.from_bytes(b"SGMXRING", "little")
+    _HEADER_LEN = 192
+    _HEAD, _DROPPED, _TAIL = 64, 72, 128
+    _SLOT_DATA = 16
+
+    def __init__(self, name: str, create: bool = False, capacity: int = 4096,
+                 slot_size: int = 256, multi_producer: bool = False):
+        if not name:
+            raise ValueError("ring name must not be empty")
+        self.path = name if "/" in name else os.path.join("/dev/shm", name)
+        if create:
+            if multi_producer:
+                raise NotImplementedError(
+                    "multi-producer rings require the compiled sigmax_rust_execution module")
+            if capacity <= 0 or slot_size <= 0:
+                raise ValueError("capacity and slot_size must be positive")
+            capacity = 1 << (capacity - 1).bit_length()
+            stride = self._slot_stride(slot_size)
+            if os.path.exists(self.path):
+                os.remove(self.path)
+            fd = os.open(self.path, os.O_RDWR | os.O_CREAT | os.O_EXCL, 0o600)
+            try:
+                os.ftruncate(fd, self._HEADER_LEN + capacity * stride)
+                self._map = mmap.mmap(fd, 0)
+            finally:
+                os.close(fd)
+            for pos in range(capacity):
+                struct.pack_into("<Q", self._map, self._HEADER_LEN + pos * stride, pos)
+            struct.pack_into("<QQQQ", self._map, 8, 1, 0, capacity, slot_size)
+            struct.pack_into("<Q", self._map, 0, self._MAGIC)
+        else:
+            if not os.path.exists(self.path):
+                raise FileNotFoundError(f"no ring at {self.path}")
+            fd = os.open(self.path, os.O_RDWR)
+            try:
+                self._map = mmap.mmap(fd, 0)
+            finally:
+                os.close(fd)
+            if len(self._map) < self._HEADER_LEN or self._word(0) != self._MAGIC:
+                raise ValueError(f"{self.path} is not a ring buffer")
+            version, flags, capacity, slot_size = struct.unpack_from("<QQQQ", self._map, 8)
+            if version != 1:
+                raise ValueError(f"{self.path}: ring layout version {version} not supported")
+            multi_producer = bool(flags & 1)
+        self.capacity = capacity
+        self.slot_size = slot_size
+        self.multi_producer = multi_producer
+        self._stride = self._slot_stride(slot_size)
+        self._lock = threading.Lock()
+
+    @staticmethod
+    def _slot_stride(slot_size: int) -> int:
+        return -(-(ShmRing._SLOT_DATA + slot_size) // 64) * 64
+
+    def _word(self, offset: int) -> int:
+        return struct.unpack_from("<Q", self._map, offset)[0]
+
+    def _slot(self, pos: int) -> int:
+        return self._HEADER_LEN + (pos & (self.capacity - 1)) * self._stride
+
+    def publish(self, frame: bytes) -> bool:
+        """Enqueue one frame; False without blocking if the ring is full."""
+        if self.multi_producer:
+            raise NotImplementedError(
+                "multi-producer rings require the compiled sigmax_rust_execution module")
+        if len(frame) > self.slot_size:
+            raise ValueError(f"frame of {len(frame)} bytes exceeds slot size {self.slot_size}")
+        with self._lock:
+            pos = self._word(self._HEAD)
+            slot = self._slot(pos)
+            if self._word(slot) != pos:
+                struct.pack_into("<Q", self._map, self._DROPPED, self._word(self._DROPPED) + 1)
+                return False
+            struct.pack_into("<Q", self._map, self._HEAD, pos + 1)
+            struct.pack_into("<I", self._map, slot + 8, len(frame))
+            self._map[slot + self._SLOT_DATA:slot + self._SLOT_DATA + len(frame)] = frame
+            struct.pack_into("<Q", self._map, slot, pos + 1)
+            return True
+
+    def poll(self) -> Optional[bytes]:
+        """Next frame, or None if the ring is empty."""
+        with self._lock:
+            pos = self._word(self._TAIL)
+            slot = self._slot(pos)
+            if self._word(slot) != pos + 1:
+                return None
+            length = min(struct.unpack_from("<I", self._map, slot + 8)[0], self.slot_size)
+            frame = self._map[slot + self._SLOT_DATA:slot + self._SLOT_DATA + length]
+            struct.pack_into("<Q", self._map, slot, pos + self.capacity)
+            struct.pack_into("<Q", self._map, self._TAIL, pos + 1)
+            return frame
+
+    def poll_batch(self, max_messages: int = 64) -> List[bytes]:
+        """Up to max_messages frames, oldest first."""
+        frames = []
+        while len(frames) < max_messages:
+            frame = self.poll()
+            if frame is None:
+                break
+            frames.append(frame)
+        return frames
+
+    def wait(self, timeout_ms: Optional[float] = None) -> Optional[bytes]:
+        """Block until a frame arrives or timeout_ms passes; None or inf waits indefinitely."""
+        if timeout_ms is not None and math.isnan(timeout_ms):
+            raise ValueError("timeout_ms must not be NaN")
+        deadline = None if timeout_ms is None else time.monotonic() + max(timeout_ms, 0.0) / 1e3
+        while True:
+            frame = self.poll()
+            if frame is not None:
+                return frame
+            if deadline is not None and time.monotonic() >= deadline:
+                return None
+            time.sleep(50e-6)
+
+    def __len__(self) -> int:
+        return max(self._word(self._HEAD) - self._word(self._TAIL), 0)
+
+    def get_stats(self) -> Dict[str, Any]:
+        """Counters shared by every process attached to the ring."""
+        published, consumed = self._word(self._HEAD), self._word(self._TAIL)
+        return {
+            "capacity": self.capacity,
+            "slot_size": self.slot_size,
+            "multi_producer": self.multi_producer,
+            "published": published,
+            "consumed": consumed,
+            "pending": max(published - consumed, 0),
+            "dropped": self._word(self._DROPPED),
+        }
+
+    def unlink(self):
+        """Remove the backing file; attached processes keep their mapping."""
+        try:
+            os.remove(self.path)
+        except FileNotFoundError:
+            pass
+
+    def __repr__(self) -> str:
+        return (f"ShmRing(path={self.path!r}, capacity={self.capacity}, "
+                f"slot_size={self.slot_size}, multi_producer={self.multi_producer})")
+
+
+def run_backtest(
+    engine: RustExecutionEngine,
+    events,
+    strategy,
+    initial_equity: float = 0.0,
+    timer_interval_ns: Optional[int] = None,
+    equity_interval_ns: int = 0
+) -> Dict[str, Any]:
+    """
+    Replay market events through the engine's simulated clock
+
+    Needs the compiled module: the Python engine has no simulated clock.
+    """
+    raise NotImplementedError("run_backtest requires the compiled sigmax_rust_execution module")
+
+
+def read_journal(journal_dir: str) -> List[Dict[str, Any]]:
+    """
+    Every intact record in an engine journal directory.
+
+    Needs the compiled module, which owns the journal format.
+    """
+    raise NotImplementedError("read_journal requires the compiled sigmax_rust_execution module")
+
+
+class MarketDataReplayer:
+    """
+    Replays recorded market data into a BookBuilder and engine (mimics Rust class)
+
+    Needs the compiled module: the Python engine has no simulated clock.
+    """
+
+    def __init__(self, path: str, format: Optional[str] = None, symbols: Optional[List[int]] = None,
+                 speed: float = 0.0, book_builder: Optional[BookBuilder] = None,
+                 engine: Optional[RustExecutionEngine] = None):
+        raise NotImplementedError("MarketDataReplayer requires the compiled sigmax_rust_execution module")
+
+
+def write_market_data(path: str, events, compress: bool = False) -> int:
+    """
+    Record BookDeltas and MarketTrades in the binary replay format.
+
+    Needs the compiled module, which owns the recording format.
+    """
+    raise NotImplementedError("write_market_data requires the compiled sigmax_rust_execution module")
+
+
+def export_arrow(source, table: str, cost_method: Optional[str] = None):
+    """
+    A table of executions, fills, orders, positions or equity as a pyarrow.RecordBatch.
+
+    Needs the compiled module, which hands its buffers to pyarrow.
+    """
+    raise NotImplementedError("export_arrow requires the compiled sigmax_rust_execution module")
+
+
+def export_table(source, table: str, path: str, format: str = "parquet", compression: str = "zstd",
+                 cost_method: Optional[str] = None) -> int:
+    """
+    Write a table of executions, fills, orders, positions or equity to Parquet or Arrow IPC.
+
+    Needs the compiled module, which owns the Parquet and IPC writers.
+    """
+    raise NotImplementedError("export_table requires the compiled sigmax_rust_execution module")
+
+
+# The Python limiters, ledger, book builder and feature calculator stay
+# reachable so tests can hold both implementations to the same results.
+_PyTokenBucketLimiter = TokenBucketLimiter
+_PySlidingWindowLimiter = SlidingWindowLimiter
+_PyPositionLedger = PositionLedger
+_PyBookBuilder = BookBuilder
+_PyFeatureCalculator = FeatureCalculator
+
 # Try to import compiled Rust module, fall back to Python implementation
 RUST_MODULE_AVAILABLE = False
 try:
     from sigmax_rust_execution import (
         RustExecutionEngine as _RustEngineCompiled,
         RustExecution as _RustExecutionCompiled,
+        RustFill as _RustFillCompiled,
+        RustReject as _RustRejectCompiled,
+        TokenBucketLimiter as _TokenBucketLimiterCompiled,
+        SlidingWindowLimiter as _SlidingWindowLimiterCompiled,
+        PositionLedger as _PositionLedgerCompiled,
+        BookDelta as _BookDeltaCompiled,
+        BookSnapshot as _BookSnapshotCompiled,
+        SequenceGap as _SequenceGapCompiled,
+        BookBuilder as _BookBuilderCompiled,
+        ResyncRequest as _ResyncRequestCompiled,
+        BookSync as _BookSyncCompiled,
+        RustFeatureFrame as _RustFeatureFrameCompiled,
+        FeatureCalculator as _FeatureCalculatorCompiled,
+        MarketTrade as _MarketTradeCompiled,
+        ShmRing as _ShmRingCompiled,
+        MarketDataReplayer as _MarketDataReplayerCompiled,
+        run_backtest as _run_backtest_compiled,
+        read_journal as _read_journal_compiled,
+        write_market_data as _write_market_data_compiled,
+        export_arrow as _export_arrow_compiled,
+        export_table as _export_table_compiled,
         execute_batch as _execute_batch_compiled,
         benchmark_latency as _benchmark_latency_compiled
     )
     # If successful, use compiled versions
     RustExecutionEngine = _RustEngineCompiled
     RustExecution = _RustExecutionCompiled
+    RustFill = _RustFillCompiled
+    RustReject = _RustRejectCompiled
+    TokenBucketLimiter = _TokenBucketLimiterCompiled
+    SlidingWindowLimiter = _SlidingWindowLimiterCompiled
+    PositionLedger = _PositionLedgerCompiled
+    BookDelta = _BookDeltaCompiled
+    BookSnapshot = _BookSnapshotCompiled
+    SequenceGap = _SequenceGapCompiled
+    BookBuilder = _BookBuilderCompiled
+    ResyncRequest = _ResyncRequestCompiled
+    BookSync = _BookSyncCompiled
+    RustFeatureFrame = _RustFeatureFrameCompiled
+    FeatureCalculator = _FeatureCalculatorCompiled
+    MarketTrade = _MarketTradeCompiled
+    ShmRing = _ShmRingCompiled
+    MarketDataReplayer = _MarketDataReplayerCompiled
+    run_backtest = _run_backtest_compiled
+    read_journal = _read_journal_compiled
+    write_market_data = _write_market_data_compiled
+    export_arrow = _export_arrow_compiled
+    export_table = _export_table_compiled
     execute_batch = _execute_batch_compiled
     benchmark_latency = _benchmark_latency_compiled
     RUST_MODULE_AVAILABLE = True
@@ -232,7 +1769,28 @@ except ImportError:
 __all__ = [
     'RustExecutionEngine',
     'RustExecution',
+    'RustFill',
+    'RustReject',
+    'TokenBucketLimiter',
+    'SlidingWindowLimiter',
+    'PositionLedger',
+    'BookDelta',
+    'BookSnapshot',
+    'SequenceGap',
+    'BookBuilder',
+    'ResyncRequest',
+    'BookSync',
+    'RustFeatureFrame',
+    'FeatureCalculator',
+    'MarketTrade',
+    'ShmRing',
+    'MarketDataReplayer',
     'execute_batch',
     'benchmark_latency',
+    'run_backtest',
+    'read_journal',
+    'write_market_data',
+    'export_arrow',
+    'export_table',
     'RUST_MODULE_AVAILABLE'
 ]
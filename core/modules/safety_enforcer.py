@@ -49,8 +49,27 @@ class SafetyEnforcer:
         self.paused = False
         self.pause_reason: Optional[str] = None
 
+        # Execution engine whose kill switch follows pause/resume
+        self.execution_engine: Optional[Any] = None
+
         logger.info("✓ Safety enforcer initialized")
 
+    def attach_execution_engine(self, engine: Any):
+        """
+        Halt the execution engine's kill switch on auto-pause
+
+        Args:
+            engine: RustExecutionEngine (engines without a kill switch are ignored)
+        """
+        self.execution_engine = engine
+        if self.paused:
+            self._set_engine_mode(1, self.pause_reason)
+
+    def _set_engine_mode(self, mode: int, reason: Optional[str] = None):
+        """Switch the attached engine's trading mode, if it has one"""
+        if self.execution_engine is not None and hasattr(self.execution_engine, "set_trading_mode"):
+            self.execution_engine.set_trading_mode(mode, reason)
+
     def record_trade_result(self, trade: Dict[str, Any]):
         """
         Record trade result for safety monitoring
@@ -306,6 +325,7 @@ class SafetyEnforcer:
         """
         self.paused = True
         self.pause_reason = violation.message
+        self._set_engine_mode(1, f"{violation.trigger}: {violation.message}")
 
         logger.critical(f"🚨 AUTO-PAUSE TRIGGERED: {violation.trigger}")
         logger.critical(f"   Reason: {violation.message}")
@@ -334,6 +354,7 @@ class SafetyEnforcer:
 
         self.paused = False
         self.pause_reason = None
+        self._set_engine_mode(0)
         logger.info("✅ Trading resumed")
         return True
 
@@ -5,7 +5,7 @@ This package contains all message schemas and data models for the trading system
 Messages are designed for SBE encoding but initially implemented in Python for Profile A.
 """
 
-from .market_data import MdUpdate, TopOfBook, BookDelta, L2Book, BookLevel
+from .market_data import MdUpdate, TopOfBook, BookDelta, L2Book, BookLevel, MarketTrade
 from .orders import OrderIntent, OrderAck, Fill, Reject, Cancel, Amend
 from .features import FeatureFrame
 from .signals import SignalEvent
@@ -18,6 +18,7 @@ __all__ = [
     "BookDelta",
     "L2Book",
     "BookLevel",
+    "MarketTrade",
 
     # Orders
     "OrderIntent",
@@ -0,0 +1,169 @@
+"""
+Binary codec for the order and market-data schemas.
+
+Frames use the SBE-style layout implemented in `rust_execution/src/codec.rs`:
+an 8-byte little-endian header (block_length, template_id, schema_id,
+version), a fixed-layout root block, then u16-length-prefixed UTF-8 strings.
+The compiled codec is used when `sigmax_rust_execution` is importable; the
+struct-based fallback below produces byte-identical frames.
+"""
+
+import math
+import struct
+from typing import Any, Dict, Tuple, Union
+
+from .common import Side, OrderType, TimeInForce, OrderStatus
+from .market_data import MdUpdate, BookDelta, MarketTrade
+from .orders import OrderIntent, Cancel, Amend, OrderAck, Fill, Reject
+
+try:
+    from sigmax_rust_execution import sbe_encode, sbe_decode
+    RUST_CODEC = True
+except ImportError:
+    RUST_CODEC = False
+
+SCHEMA_ID = 1
+SCHEMA_VERSION = 1
+
+_HEADER = struct.Struct("<HHHH")
+_NULL_U64 = 2 ** 64 - 1
+
+Message = Union[OrderIntent, Cancel, Amend, OrderAck, Fill, Reject, MdUpdate, BookDelta,
+                MarketTrade]
+
+# name -> (template_id, block_length, [(field, struct format, offset)], [strings])
+# "F" marks an optional f64 (None as NaN), "O" an optional u64 (None as max).
+_TEMPLATES: Dict[str, Tuple[int, int, list, list]] = {
+    "OrderIntent": (1, 40, [
+        ("ts_ns", "Q", 0), ("symbol_id", "I", 8), ("side", "B", 12),
+        ("order_type", "B", 13), ("tif", "B", 14), ("decision_layer", "B", 15),
+        ("qty", "d", 16), ("price", "F", 24), ("confidence", "d", 32),
+    ], ["client_id", "route"]),
+    "Cancel": (2, 16, [
+        ("ts_ns", "Q", 0), ("symbol_id", "I", 8),
+    ], ["client_id", "reason"]),
+    "Amend": (3, 32, [
+        ("ts_ns", "Q", 0), ("symbol_id", "I", 8),
+        ("new_qty", "F", 16), ("new_price", "F", 24),
+    ], ["client_id"]),
+    "OrderAck": (4, 24, [
+        ("ts_ns", "Q", 0), ("submit_ts_ns", "O", 8), ("status", "B", 16),
+        ("venue_code", "H", 18),
+    ], ["client_id", "exchange_order_id"]),
+    "Fill": (5, 40, [
+        ("ts_ns", "Q", 0), ("price", "d", 8), ("qty", "d", 16), ("fee", "d", 24),
+        ("venue_code", "H", 32), ("is_maker", "?", 34),
+    ], ["client_id", "exchange_order_id", "fee_currency", "trade_id"]),
+    "Reject": (6, 16, [
+        ("ts_ns", "Q", 0), ("reason_code", "H", 8), ("venue_code", "H", 10),
+    ], ["client_id", "reason_msg", "source"]),
+    "MdUpdate": (10, 56, [
+        ("ts_ns", "Q", 0), ("symbol_id", "I", 8), ("bid_px", "d", 16),
+        ("bid_sz", "d", 24), ("ask_px", "d", 32), ("ask_sz", "d", 40),
+        ("seq", "Q", 48),
+    ], []),
+    "BookDelta": (11, 40, [
+        ("ts_ns", "Q", 0), ("symbol_id", "I", 8), ("is_bid", "?", 12),
+        ("price", "d", 16), ("size", "d", 24), ("seq", "Q", 32),
+    ], []),
+    "MarketTrade": (12, 32, [
+        ("ts_ns", "Q", 0), ("symbol_id", "I", 8), ("aggressor_side", "B", 12),
+        ("price", "d", 16), ("quantity", "d", 24),
+    ], []),
+}
+
+_BY_ID = {template[0]: name for name, template in _TEMPLATES.items()}
+
+_CLASSES = {
+    "OrderIntent": OrderIntent, "Cancel": Cancel, "Amend": Amend,
+    "OrderAck": OrderAck, "Fill": Fill, "Reject": Reject,
+    "MdUpdate": MdUpdate, "BookDelta": BookDelta, "MarketTrade": MarketTrade,
+}
+
+_ENUMS = {"side": Side, "order_type": OrderType, "tif": TimeInForce, "status": OrderStatus}
+
+
+def _py_encode(message: Any) -> bytes:
+    name = type(message).__name__
+    if name not in _TEMPLATES:
+        raise TypeError(f"no binary layout for {name}")
+    template_id, block_length, fields, strings = _TEMPLATES[name]
+    buf = bytearray(_HEADER.size + block_length)
+    _HEADER.pack_into(buf, 0, block_length, template_id, SCHEMA_ID, SCHEMA_VERSION)
+    for field_name, fmt, offset in fields:
+        value = getattr(message, field_name)
+        if fmt == "F":
+            fmt, value = "d", math.nan if value is None else value
+        elif fmt == "O":
+            fmt, value = "Q", _NULL_U64 if value is None else value
+        try:
+            struct.pack_into("<" + fmt, buf, _HEADER.size + offset, value)
+        except struct.error as e:
+            raise ValueError(f"invalid value for {field_name}: {value!r}") from e
+    for field_name in strings:
+        data = getattr(message, field_name).encode("utf-8")
+        if len(data) > 0xFFFF:
+            raise ValueError(f"string of {len(data)} bytes too long")
+        buf += struct.pack("<H", len(data)) + data
+    return bytes(buf)
+
+
+def _py_decode(data: bytes) -> Tuple[str, Dict[str, Any]]:
+    if len(data) < _HEADER.size:
+        raise ValueError(f"frame of {len(data)} bytes is shorter than its header")
+    block_length, template_id, schema_id, version = _HEADER.unpack_from(data, 0)
+    if schema_id != SCHEMA_ID:
+        raise ValueError(f"unknown schema id {schema_id}")
+    if version == 0 or version > SCHEMA_VERSION:
+        raise ValueError(f"schema version {version} not supported (up to {SCHEMA_VERSION})")
+    name = _BY_ID.get(template_id)
+    if name is None:
+        raise ValueError(f"unknown template id {template_id}")
+    _, min_length, fields, strings = _TEMPLATES[name]
+    end = _HEADER.size + block_length
+    if block_length < min_length or len(data) < end:
+        raise ValueError(f"truncated {name} frame")
+
+    values: Dict[str, Any] = {}
+    for field_name, fmt, offset in fields:
+        if fmt == "F":
+            value = struct.unpack_from("<d", data, _HEADER.size + offset)[0]
+            value = None if math.isnan(value) else value
+        elif fmt == "O":
+            value = struct.unpack_from("<Q", data, _HEADER.size + offset)[0]
+            value = None if value == _NULL_U64 else value
+        else:
+            value = struct.unpack_from("<" + fmt, data, _HEADER.size + offset)[0]
+        values[field_name] = value
+    for field_name in strings:
+        if len(data) < end + 2:
+            raise ValueError(f"truncated {field_name} in {name} frame")
+        length = struct.unpack_from("<H", data, end)[0]
+        if len(data) < end + 2 + length:
+            raise ValueError(f"truncated {field_name} in {name} frame")
+        values[field_name] = bytes(data[end + 2:end + 2 + length]).decode("utf-8")
+        end += 2 + length
+    return name, values
+
+
+def encode(message: Message) -> bytes:
+    """Encode a schema message into one binary frame."""
+    if RUST_CODEC:
+        return sbe_encode(message)
+    return _py_encode(message)
+
+
+def decode(data: bytes) -> Message:
+    """Decode one binary frame back into its schema dataclass."""
+    name, values = sbe_decode(data) if RUST_CODEC else _py_decode(data)
+    for field_name, enum in _ENUMS.items():
+        if field_name in values:
+            values[field_name] = enum(values[field_name])
+    return _CLASSES[name](**values)
+
+
+def header(data: bytes) -> Tuple[int, int, int, int]:
+    """Return (block_length, template_id, schema_id, version) of a frame."""
+    if len(data) < _HEADER.size:
+        raise ValueError(f"frame of {len(data)} bytes is shorter than its header")
+    return _HEADER.unpack_from(data, 0)
@@ -90,6 +90,20 @@ class BookDelta:
     is_bid: bool
     price: float
     size: float  # 0 = delete level
+    seq: int = 0  # Sequence number for gap detection
+
+
+@dataclass
+class MarketTrade:
+    """
+    Trade printed by the venue.
+    Recorded alongside book deltas for market-data replay.
+    """
+    ts_ns: int
+    symbol_id: int
+    price: float
+    quantity: float
+    aggressor_side: int  # 0 = buyer lifted the ask, 1 = seller hit the bid
     
     
 @dataclass
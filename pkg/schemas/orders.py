@@ -142,11 +142,13 @@ class RejectReason(object):
     RATE_LIMIT = 104
     INVENTORY_LIMIT = 105
     CONCENTRATION_LIMIT = 106
+    KILL_SWITCH = 107
     
     # Router rejections (2xx)
     NO_ROUTE = 201
     VENUE_UNAVAILABLE = 202
     INSUFFICIENT_BALANCE = 203
+    DUPLICATE_CLIENT_ID = 204
     
     # Exchange rejections (3xx)
     INVALID_PRICE = 301
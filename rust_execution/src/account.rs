@@ -0,0 +1,435 @@
+//! Per-currency balances, open-order reservations and perpetual margin
+//!
+//! Only symbols registered with `set_instrument` are accounted for; orders
+//! on other symbols never touch the balances.
+
+use crate::journal::{Reader, Writer};
+use crate::orderbook::{Side, QTY_EPSILON};
+use std::collections::HashMap;
+
+/// `pkg.schemas.orders.RejectReason.INSUFFICIENT_BALANCE`
+pub const INSUFFICIENT_BALANCE: u16 = 203;
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Balance {
+    pub total: f64,
+    /// Held for open orders.
+    pub reserved: f64,
+    /// Posted as margin for open perpetual positions.
+    pub margin: f64,
+}
+
+impl Balance {
+    #[inline(always)]
+    pub fn available(&self) -> f64 {
+        self.total - self.reserved - self.margin
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MarginMode {
+    /// The whole quote balance backs the position.
+    Cross,
+    /// Only the margin posted for the position backs it.
+    Isolated,
+}
+
+impl MarginMode {
+    pub fn from_name(name: &str) -> Option<MarginMode> {
+        match name {
+            "cross" => Some(MarginMode::Cross),
+            "isolated" => Some(MarginMode::Isolated),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            MarginMode::Cross => "cross",
+            MarginMode::Isolated => "isolated",
+        }
+    }
+}
+
+/// Linear perpetual settled in the quote currency.
+#[derive(Clone, Debug)]
+pub struct Perpetual {
+    pub leverage: f64,
+    pub max_leverage: f64,
+    /// Maintenance margin as a fraction of position notional.
+    pub maintenance_rate: f64,
+    pub mode: MarginMode,
+}
+
+#[derive(Clone, Debug)]
+pub struct Instrument {
+    pub base: String,
+    pub quote: String,
+    /// `None` for spot.
+    pub perpetual: Option<Perpetual>,
+}
+
+/// Balance an order needs while it is open.
+#[derive(Clone, Debug)]
+pub struct Reservation {
+    currency: String,
+    /// Amount held per unit of order quantity, before `price` is applied.
+    per_unit: f64,
+    /// Held amounts scale with the order price (quote and margin holds).
+    priced: bool,
+    price: f64,
+    /// Only this much of the order needs a hold, e.g. the part of a perpetual
+    /// order that opens rather than reduces a position.
+    max_qty: f64,
+    amount: f64,
+}
+
+impl Reservation {
+    #[inline(always)]
+    fn amount_for(&self, remaining: f64) -> f64 {
+        let per_unit = if self.priced { self.per_unit * self.price } else { self.per_unit };
+        remaining.min(self.max_qty).max(0.0) * per_unit
+    }
+}
+
+/// Margin state of one perpetual position.
+pub struct MarginSnapshot {
+    pub mode: MarginMode,
+    pub leverage: f64,
+    pub max_leverage: f64,
+    pub position_margin: f64,
+    pub maintenance_margin: f64,
+    pub liquidation_price: Option<f64>,
+}
+
+#[derive(Default)]
+pub struct Account {
+    balances: HashMap<String, Balance>,
+    instruments: HashMap<u32, Instrument>,
+    reservations: HashMap<u64, Reservation>,
+    position_margin: HashMap<u32, f64>,
+}
+
+impl Account {
+    pub fn set_instrument(&mut self, symbol_id: u32, instrument: Instrument) {
+        self.instruments.insert(symbol_id, instrument);
+    }
+
+    pub fn instrument(&self, symbol_id: u32) -> Option<&Instrument> {
+        self.instruments.get(&symbol_id)
+    }
+
+    pub fn set_leverage(&mut self, symbol_id: u32, leverage: f64) -> Result<(), String> {
+        let perpetual = self
+            .instruments
+            .get_mut(&symbol_id)
+            .and_then(|i| i.perpetual.as_mut())
+            .ok_or_else(|| format!("symbol {} is not a perpetual", symbol_id))?;
+        if !(leverage.is_finite() && leverage >= 1.0 && leverage <= perpetual.max_leverage) {
+            return Err(format!(
+                "leverage {} outside 1..={}",
+                leverage, perpetual.max_leverage
+            ));
+        }
+        perpetual.leverage = leverage;
+        Ok(())
+    }
+
+    pub fn deposit(&mut self, currency: &str, amount: f64) {
+        self.balances.entry(currency.to_string()).or_default().total += amount;
+    }
+
+    pub fn withdraw(&mut self, currency: &str, amount: f64) -> Result<(), String> {
+        let balance = self.balances.entry(currency.to_string()).or_default();
+        if amount > balance.available() + QTY_EPSILON {
+            return Err(format!(
+                "cannot withdraw {} {}: {} available",
+                amount,
+                currency,
+                balance.available()
+            ));
+        }
+        balance.total -= amount;
+        Ok(())
+    }
+
+    pub fn balance(&self, currency: &str) -> Balance {
+        self.balances.get(currency).copied().unwrap_or_default()
+    }
+
+    pub fn balances(&self) -> impl Iterator<Item = (&String, &Balance)> {
+        self.balances.iter()
+    }
+
+    /// What an order would hold, or why the account cannot afford it.
+    /// `price` is the limit or, for market orders, the reference price;
+    /// `position` is the current net position.
+    pub fn requirement(
+        &self,
+        symbol_id: u32,
+        side: Side,
+        price: Option<f64>,
+        quantity: f64,
+        position: f64,
+    ) -> Result<Option<Reservation>, String> {
+        self.requirement_with(symbol_id, side, price, quantity, position, 0.0)
+    }
+
+    /// `requirement` for an open order being amended, whose current hold is
+    /// counted as available since the new hold replaces it.
+    pub fn amend_requirement(
+        &self,
+        order_id: u64,
+        symbol_id: u32,
+        side: Side,
+        price: Option<f64>,
+        quantity: f64,
+        position: f64,
+    ) -> Result<Option<Reservation>, String> {
+        let held = self.reservations.get(&order_id).map_or(0.0, |held| held.amount);
+        self.requirement_with(symbol_id, side, price, quantity, position, held)
+    }
+
+    fn requirement_with(
+        &self,
+        symbol_id: u32,
+        side: Side,
+        price: Option<f64>,
+        quantity: f64,
+        position: f64,
+        held: f64,
+    ) -> Result<Option<Reservation>, String> {
+        let Some(instrument) = self.instruments.get(&symbol_id) else {
+            return Ok(None);
+        };
+        let reservation = match (&instrument.perpetual, side) {
+            (None, Side::Sell) => Reservation {
+                currency: instrument.base.clone(),
+                per_unit: 1.0,
+                priced: false,
+                price: 0.0,
+                max_qty: f64::INFINITY,
+                amount: 0.0,
+            },
+            (perpetual, _) => {
+                let price = price.ok_or_else(|| "no price to value the order at".to_string())?;
+                let (per_unit, max_qty) = match perpetual {
+                    None => (1.0, f64::INFINITY),
+                    Some(perpetual) => {
+                        let reducing = match side {
+                            Side::Buy => (-position).max(0.0),
+                            Side::Sell => position.max(0.0),
+                        };
+                        (1.0 / perpetual.leverage, (quantity - reducing).max(0.0))
+                    }
+                };
+                Reservation {
+                    currency: instrument.quote.clone(),
+                    per_unit,
+                    priced: true,
+                    price,
+                    max_qty,
+                    amount: 0.0,
+                }
+            }
+        };
+
+        let required = reservation.amount_for(quantity);
+        let available = self.balance(&reservation.currency).available() + held;
+        if required > available + QTY_EPSILON {
+            return Err(format!(
+                "Insufficient {}: order needs {:.8}, {:.8} available",
+                reservation.currency, required, available
+            ));
+        }
+        Ok(Some(reservation))
+    }
+
+    /// Hold the balance for an order that passed `requirement`.
+    pub fn hold(&mut self, order_id: u64, quantity: f64, mut reservation: Reservation) {
+        reservation.amount = reservation.amount_for(quantity);
+        self.balances
+            .entry(reservation.currency.clone())
+            .or_default()
+            .reserved += reservation.amount;
+        self.reservations.insert(order_id, reservation);
+    }
+
+    pub fn held_orders(&self) -> Vec<u64> {
+        self.reservations.keys().copied().collect()
+    }
+
+    /// Resize an order's hold to its unfilled quantity at its current price
+    /// (0 for a market order, which keeps its valuation price).
+    pub fn sync(&mut self, order_id: u64, remaining: f64, price: f64) {
+        let Some(reservation) = self.reservations.get_mut(&order_id) else {
+            return;
+        };
+        if price > 0.0 {
+            reservation.price = price;
+        }
+        let amount = reservation.amount_for(remaining);
+        if let Some(balance) = self.balances.get_mut(&reservation.currency) {
+            balance.reserved += amount - reservation.amount;
+        }
+        reservation.amount = amount;
+    }
+
+    /// Drop the hold of an order that is no longer open.
+    pub fn release(&mut self, order_id: u64) {
+        if let Some(reservation) = self.reservations.remove(&order_id) {
+            if let Some(balance) = self.balances.get_mut(&reservation.currency) {
+                balance.reserved -= reservation.amount;
+                if balance.reserved.abs() <= QTY_EPSILON {
+                    balance.reserved = 0.0;
+                }
+            }
+        }
+    }
+
+    /// Settle a fill: spot swaps base for quote, a perpetual posts or frees
+    /// margin and books the PnL it realized. `position` is the net position
+    /// before the fill.
+    #[inline(always)]
+    pub fn on_fill(
+        &mut self,
+        symbol_id: u32,
+        side: Side,
+        price: f64,
+        quantity: f64,
+        position: f64,
+        realized: Option<f64>,
+    ) {
+        let Some(instrument) = self.instruments.get(&symbol_id) else {
+            return;
+        };
+        let signed = match side {
+            Side::Buy => quantity,
+            Side::Sell => -quantity,
+        };
+
+        match &instrument.perpetual {
+            None => {
+                self.balances.entry(instrument.base.clone()).or_default().total += signed;
+                self.balances.entry(instrument.quote.clone()).or_default().total -= signed * price;
+            }
+            Some(perpetual) => {
+                let closing = if position * signed < 0.0 {
+                    quantity.min(position.abs())
+                } else {
+                    0.0
+                };
+                let margin = self.position_margin.entry(symbol_id).or_default();
+                let freed = if position.abs() > QTY_EPSILON {
+                    *margin * closing / position.abs()
+                } else {
+                    0.0
+                };
+                let posted = (quantity - closing) * price / perpetual.leverage;
+                *margin += posted - freed;
+
+                let quote = self.balances.entry(instrument.quote.clone()).or_default();
+                quote.margin += posted - freed;
+                quote.total += realized.unwrap_or(0.0);
+            }
+        }
+    }
+
+    /// Take a fee from the account if the symbol is accounted for.
+    #[inline(always)]
+    pub fn charge_fee(&mut self, symbol_id: u32, currency: &str, fee: f64) {
+        if fee != 0.0 && self.instruments.contains_key(&symbol_id) {
+            self.balances.entry(currency.to_string()).or_default().total -= fee;
+        }
+    }
+
+    /// Margin and liquidation price of a perpetual position. Cross positions
+    /// are backed by the free quote balance as well as their own margin.
+    pub fn margin(&self, symbol_id: u32, net_qty: f64, entry_price: f64, mark_price: Option<f64>) -> Option<MarginSnapshot> {
+        let instrument = self.instruments.get(&symbol_id)?;
+        let perpetual = instrument.perpetual.as_ref()?;
+        let position_margin = self.position_margin.get(&symbol_id).copied().unwrap_or(0.0);
+        let collateral = match perpetual.mode {
+            MarginMode::Isolated => position_margin,
+            MarginMode::Cross => self.balance(&instrument.quote).available() + position_margin,
+        };
+
+        // Solve collateral + q * (P - entry) = maintenance_rate * |q| * P for P.
+        let liquidation_price = (net_qty.abs() > QTY_EPSILON)
+            .then(|| {
+                (net_qty * entry_price - collateral)
+                    / (net_qty - perpetual.maintenance_rate * net_qty.abs())
+            })
+            .filter(|price| price.is_finite() && *price > 0.0);
+
+        Some(MarginSnapshot {
+            mode: perpetual.mode,
+            leverage: perpetual.leverage,
+            max_leverage: perpetual.max_leverage,
+            position_margin,
+            maintenance_margin: perpetual.maintenance_rate
+                * net_qty.abs()
+                * mark_price.unwrap_or(entry_price),
+            liquidation_price,
+        })
+    }
+
+    /// Balances, order holds and posted margin, for snapshots. Instruments
+    /// are configuration and stay as registered.
+    pub(crate) fn save(&self, w: &mut Writer<'_>) {
+        w.u32(self.balances.len() as u32);
+        for (currency, balance) in &self.balances {
+            w.str(currency);
+            w.f64(balance.total);
+            w.f64(balance.reserved);
+            w.f64(balance.margin);
+        }
+        w.u32(self.reservations.len() as u32);
+        for (order_id, reservation) in &self.reservations {
+            w.u64(*order_id);
+            w.str(&reservation.currency);
+            w.f64(reservation.per_unit);
+            w.u8(reservation.priced as u8);
+            w.f64(reservation.price);
+            w.f64(reservation.max_qty);
+            w.f64(reservation.amount);
+        }
+        w.u32(self.position_margin.len() as u32);
+        for (symbol_id, margin) in &self.position_margin {
+            w.u32(*symbol_id);
+            w.f64(*margin);
+        }
+    }
+
+    pub(crate) fn restore(&mut self, r: &mut Reader<'_>) -> Option<()> {
+        self.balances.clear();
+        self.reservations.clear();
+        self.position_margin.clear();
+        for _ in 0..r.u32()? {
+            let currency = r.str()?;
+            let balance = Balance {
+                total: r.f64()?,
+                reserved: r.f64()?,
+                margin: r.f64()?,
+            };
+            self.balances.insert(currency, balance);
+        }
+        for _ in 0..r.u32()? {
+            let order_id = r.u64()?;
+            let reservation = Reservation {
+                currency: r.str()?,
+                per_unit: r.f64()?,
+                priced: r.u8()? != 0,
+                price: r.f64()?,
+                max_qty: r.f64()?,
+                amount: r.f64()?,
+            };
+            self.reservations.insert(order_id, reservation);
+        }
+        for _ in 0..r.u32()? {
+            let symbol_id = r.u32()?;
+            self.position_margin.insert(symbol_id, r.f64()?);
+        }
+        Some(())
+    }
+}
@@ -0,0 +1,324 @@
+//! Event-driven backtests on the engine's simulated clock
+//!
+//! Market events are applied in timestamp order; the strategy reacts through
+//! callbacks and trades on the engine it is handed, so its orders go through
+//! the same matcher, risk checks and latency model as live ones.
+
+use crate::l2book::BookDelta;
+use crate::{RustExecution, RustExecutionEngine, RustFill};
+use pyo3::exceptions::{PyTypeError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+
+/// Trade printed by the venue.
+#[derive(Clone, Debug)]
+#[pyclass]
+pub struct MarketTrade {
+    #[pyo3(get)]
+    pub ts_ns: u64,
+    #[pyo3(get)]
+    pub symbol_id: u32,
+    #[pyo3(get)]
+    pub price: f64,
+    #[pyo3(get)]
+    pub quantity: f64,
+    /// 0 = buyer lifted the ask, 1 = seller hit the bid
+    #[pyo3(get)]
+    pub aggressor_side: u8,
+}
+
+#[pymethods]
+impl MarketTrade {
+    #[new]
+    pub fn new(ts_ns: u64, symbol_id: u32, price: f64, quantity: f64, aggressor_side: u8) -> Self {
+        Self {
+            ts_ns,
+            symbol_id,
+            price,
+            quantity,
+            aggressor_side,
+        }
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "MarketTrade(ts_ns={}, symbol_id={}, price={}, quantity={}, aggressor_side={})",
+            self.ts_ns, self.symbol_id, self.price, self.quantity, self.aggressor_side
+        )
+    }
+}
+
+#[derive(FromPyObject)]
+enum Event {
+    Book(BookDelta),
+    Trade(MarketTrade),
+}
+
+impl Event {
+    fn extract(event: &Bound<'_, PyAny>) -> PyResult<Event> {
+        event.extract().map_err(|_| {
+            PyTypeError::new_err(format!(
+                "expected BookDelta or MarketTrade, got {}",
+                event
+                    .get_type()
+                    .name()
+                    .map_or_else(|_| "?".to_string(), |name| name.to_string())
+            ))
+        })
+    }
+
+    fn ts_ns(&self) -> u64 {
+        match self {
+            Event::Book(delta) => delta.ts_ns,
+            Event::Trade(trade) => trade.ts_ns,
+        }
+    }
+}
+
+/// Strategy hooks, each optional.
+struct Callbacks<'py> {
+    on_start: Option<Bound<'py, PyAny>>,
+    on_book: Option<Bound<'py, PyAny>>,
+    on_trade: Option<Bound<'py, PyAny>>,
+    on_fill: Option<Bound<'py, PyAny>>,
+    on_execution: Option<Bound<'py, PyAny>>,
+    on_timer: Option<Bound<'py, PyAny>>,
+    on_finish: Option<Bound<'py, PyAny>>,
+}
+
+impl<'py> Callbacks<'py> {
+    fn new(strategy: &Bound<'py, PyAny>) -> Self {
+        let hook = |name: &str| {
+            strategy
+                .getattr(name)
+                .ok()
+                .filter(|hook| hook.is_callable())
+        };
+        Self {
+            on_start: hook("on_start"),
+            on_book: hook("on_book"),
+            on_trade: hook("on_trade"),
+            on_fill: hook("on_fill"),
+            on_execution: hook("on_execution"),
+            on_timer: hook("on_timer"),
+            on_finish: hook("on_finish"),
+        }
+    }
+}
+
+struct Run<'py> {
+    engine: Bound<'py, RustExecutionEngine>,
+    callbacks: Callbacks<'py>,
+    initial_equity: f64,
+    fills: Vec<RustFill>,
+    equity_curve: Vec<(u64, f64)>,
+}
+
+impl<'py> Run<'py> {
+    /// Hand acknowledged executions and new fills to the strategy until it
+    /// stops producing more.
+    fn dispatch(&mut self, ts_ns: u64) -> PyResult<()> {
+        let executions: Vec<RustExecution> = self.engine.borrow().advance_time(ts_ns);
+        if let Some(on_execution) = &self.callbacks.on_execution {
+            for execution in executions {
+                on_execution.call1((&self.engine, execution))?;
+            }
+        }
+        loop {
+            let fills = self.engine.borrow().drain_fills();
+            if fills.is_empty() {
+                return Ok(());
+            }
+            for fill in fills {
+                self.fills.push(fill.clone());
+                if let Some(on_fill) = &self.callbacks.on_fill {
+                    on_fill.call1((&self.engine, fill))?;
+                }
+            }
+        }
+    }
+
+    fn equity(&self) -> f64 {
+        self.initial_equity
+            + self
+                .engine
+                .borrow()
+                .position_snapshots()
+                .iter()
+                .map(|position| position.net_pnl())
+                .sum::<f64>()
+    }
+
+    /// One point per timestamp; later events at the same time overwrite it.
+    fn sample(&mut self, ts_ns: u64) {
+        let equity = self.equity();
+        match self.equity_curve.last_mut() {
+            Some((last_ns, last)) if *last_ns == ts_ns => *last = equity,
+            _ => self.equity_curve.push((ts_ns, equity)),
+        }
+    }
+}
+
+/// Drive the strategy through `events`, from `on_start` to `on_finish`,
+/// returning the number of events and the last timestamp.
+fn replay<'py>(
+    run: &mut Run<'py>,
+    events: impl Iterator<Item = PyResult<Bound<'py, PyAny>>>,
+    start_ns: u64,
+    timer_interval_ns: Option<u64>,
+    equity_interval_ns: u64,
+) -> PyResult<(u64, u64)> {
+    if let Some(on_start) = &run.callbacks.on_start {
+        on_start.call1((&run.engine,))?;
+    }
+
+    let mut last_ns = start_ns;
+    let mut next_timer = timer_interval_ns.map(|interval| start_ns + interval);
+    let mut next_sample = start_ns;
+    let mut count = 0u64;
+    for event in events {
+        let event = Event::extract(&event?)?;
+        let ts_ns = event.ts_ns();
+        if ts_ns < last_ns {
+            return Err(PyValueError::new_err(format!(
+                "event at {} is earlier than the previous one at {}",
+                ts_ns, last_ns
+            )));
+        }
+
+        while let (Some(timer_ns), Some(interval)) = (next_timer, timer_interval_ns) {
+            if timer_ns > ts_ns {
+                break;
+            }
+            run.dispatch(timer_ns)?;
+            if let Some(on_timer) = &run.callbacks.on_timer {
+                on_timer.call1((&run.engine, timer_ns))?;
+            }
+            run.dispatch(timer_ns)?;
+            next_timer = Some(timer_ns + interval);
+        }
+
+        match event {
+            Event::Book(delta) => {
+                run.engine.borrow().on_book_level(
+                    delta.symbol_id,
+                    delta.side,
+                    delta.price,
+                    delta.quantity,
+                    Some(ts_ns),
+                )?;
+                run.dispatch(ts_ns)?;
+                if let Some(on_book) = &run.callbacks.on_book {
+                    on_book.call1((&run.engine, delta))?;
+                }
+            }
+            Event::Trade(trade) => {
+                run.engine.borrow().on_market_trade(
+                    trade.symbol_id,
+                    trade.price,
+                    trade.quantity,
+                    trade.aggressor_side,
+                    Some(ts_ns),
+                )?;
+                run.dispatch(ts_ns)?;
+                if let Some(on_trade) = &run.callbacks.on_trade {
+                    on_trade.call1((&run.engine, trade))?;
+                }
+            }
+        }
+        run.dispatch(ts_ns)?;
+
+        if ts_ns >= next_sample {
+            run.sample(ts_ns);
+            next_sample = ts_ns.saturating_add(equity_interval_ns);
+        }
+        last_ns = ts_ns;
+        count += 1;
+    }
+
+    if let Some(on_finish) = &run.callbacks.on_finish {
+        on_finish.call1((&run.engine,))?;
+    }
+    run.dispatch(last_ns)?;
+    run.sample(last_ns);
+    Ok((count, last_ns))
+}
+
+/// Replay `events` (time-ordered `BookDelta`s and `MarketTrade`s, any
+/// iterable) through `engine` on its simulated clock, calling the optional
+/// strategy hooks `on_start(engine)`, `on_book(engine, delta)`,
+/// `on_trade(engine, trade)`, `on_fill(engine, fill)`,
+/// `on_execution(engine, execution)` (acks under a latency model),
+/// `on_timer(engine, ts_ns)` every `timer_interval_ns` and
+/// `on_finish(engine)`.
+///
+/// Returns the run's fills, final positions and portfolio, and an equity
+/// curve of `(ts_ns, initial_equity + net PnL)` sampled at most every
+/// `equity_interval_ns`.
+#[pyfunction]
+#[pyo3(signature = (engine, events, strategy, initial_equity=0.0, timer_interval_ns=None, equity_interval_ns=0))]
+pub fn run_backtest<'py>(
+    py: Python<'py>,
+    engine: Bound<'py, RustExecutionEngine>,
+    events: &Bound<'py, PyAny>,
+    strategy: &Bound<'py, PyAny>,
+    initial_equity: f64,
+    timer_interval_ns: Option<u64>,
+    equity_interval_ns: u64,
+) -> PyResult<PyObject> {
+    if timer_interval_ns == Some(0) {
+        return Err(PyValueError::new_err("timer_interval_ns must be positive"));
+    }
+
+    let mut run = Run {
+        engine,
+        callbacks: Callbacks::new(strategy),
+        initial_equity,
+        fills: Vec::new(),
+        equity_curve: Vec::new(),
+    };
+    let mut events = events.try_iter()?.peekable();
+    let start_ns = match events.peek() {
+        Some(Ok(event)) => Event::extract(event)?.ts_ns(),
+        _ => 0,
+    };
+    let (was_simulated, was_recording) = {
+        let engine = run.engine.borrow();
+        let was_simulated = engine.simulation_enabled();
+        engine.enable_simulation(start_ns);
+        let was_recording = engine.fill_recording();
+        engine.set_fill_recording(true);
+        (was_simulated, was_recording)
+    };
+    // A failing strategy must not leave the engine recording fills or on
+    // the simulated clock forever.
+    let replayed = replay(&mut run, events, start_ns, timer_interval_ns, equity_interval_ns);
+    {
+        let engine = run.engine.borrow();
+        if !was_recording {
+            engine.set_fill_recording(false);
+        }
+        if !was_simulated {
+            engine.disable_simulation();
+        }
+    }
+    let (count, last_ns) = replayed?;
+
+    let engine = run.engine.borrow();
+    let dict = PyDict::new(py);
+    dict.set_item("events", count)?;
+    dict.set_item("start_ns", start_ns)?;
+    dict.set_item("end_ns", last_ns)?;
+    dict.set_item("initial_equity", initial_equity)?;
+    dict.set_item(
+        "final_equity",
+        run.equity_curve
+            .last()
+            .map_or(initial_equity, |(_, equity)| *equity),
+    )?;
+    dict.set_item("fills", run.fills.clone())?;
+    dict.set_item("positions", engine.get_positions()?)?;
+    dict.set_item("portfolio", engine.get_portfolio()?)?;
+    dict.set_item("equity_curve", run.equity_curve.clone())?;
+    Ok(dict.into())
+}
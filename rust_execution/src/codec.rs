@@ -0,0 +1,474 @@
+//! SBE-style binary frames for the order and market-data schemas
+//!
+//! A frame is an 8-byte message header (`block_length`, `template_id`,
+//! `schema_id`, `version`, all little-endian `u16`), the message's
+//! fixed-layout root block, then its strings as `u16`-length-prefixed
+//! UTF-8. Decoding reads fields straight out of the caller's buffer; a root
+//! block longer than this version's (a newer producer appending fields) is
+//! skipped using the header's `block_length`.
+//!
+//! Optional floats are null as NaN, optional integers as `u64::MAX`.
+
+use pyo3::exceptions::{PyTypeError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::{PyBytes, PyDict};
+
+pub const SCHEMA_ID: u16 = 1;
+pub const SCHEMA_VERSION: u16 = 1;
+pub const HEADER_LEN: usize = 8;
+
+const NULL_U64: u64 = u64::MAX;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Kind {
+    U8,
+    U16,
+    U32,
+    U64,
+    /// `None` as `u64::MAX`.
+    OptU64,
+    F64,
+    /// `None` as NaN.
+    OptF64,
+    Bool,
+}
+
+impl Kind {
+    fn width(self) -> usize {
+        match self {
+            Kind::U8 | Kind::Bool => 1,
+            Kind::U16 => 2,
+            Kind::U32 => 4,
+            Kind::U64 | Kind::OptU64 | Kind::F64 | Kind::OptF64 => 8,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Field {
+    pub name: &'static str,
+    pub kind: Kind,
+    pub offset: usize,
+}
+
+const fn field(name: &'static str, kind: Kind, offset: usize) -> Field {
+    Field { name, kind, offset }
+}
+
+/// Layout of one message type.
+#[derive(Debug)]
+pub struct Template {
+    pub id: u16,
+    pub name: &'static str,
+    pub block_length: u16,
+    pub fields: &'static [Field],
+    /// Variable-length strings, in wire order after the root block.
+    pub strings: &'static [&'static str],
+}
+
+pub static TEMPLATES: [Template; 9] = [
+    Template {
+        id: 1,
+        name: "OrderIntent",
+        block_length: 40,
+        fields: &[
+            field("ts_ns", Kind::U64, 0),
+            field("symbol_id", Kind::U32, 8),
+            field("side", Kind::U8, 12),
+            field("order_type", Kind::U8, 13),
+            field("tif", Kind::U8, 14),
+            field("decision_layer", Kind::U8, 15),
+            field("qty", Kind::F64, 16),
+            field("price", Kind::OptF64, 24),
+            field("confidence", Kind::F64, 32),
+        ],
+        strings: &["client_id", "route"],
+    },
+    Template {
+        id: 2,
+        name: "Cancel",
+        block_length: 16,
+        fields: &[
+            field("ts_ns", Kind::U64, 0),
+            field("symbol_id", Kind::U32, 8),
+        ],
+        strings: &["client_id", "reason"],
+    },
+    Template {
+        id: 3,
+        name: "Amend",
+        block_length: 32,
+        fields: &[
+            field("ts_ns", Kind::U64, 0),
+            field("symbol_id", Kind::U32, 8),
+            field("new_qty", Kind::OptF64, 16),
+            field("new_price", Kind::OptF64, 24),
+        ],
+        strings: &["client_id"],
+    },
+    Template {
+        id: 4,
+        name: "OrderAck",
+        block_length: 24,
+        fields: &[
+            field("ts_ns", Kind::U64, 0),
+            field("submit_ts_ns", Kind::OptU64, 8),
+            field("status", Kind::U8, 16),
+            field("venue_code", Kind::U16, 18),
+        ],
+        strings: &["client_id", "exchange_order_id"],
+    },
+    Template {
+        id: 5,
+        name: "Fill",
+        block_length: 40,
+        fields: &[
+            field("ts_ns", Kind::U64, 0),
+            field("price", Kind::F64, 8),
+            field("qty", Kind::F64, 16),
+            field("fee", Kind::F64, 24),
+            field("venue_code", Kind::U16, 32),
+            field("is_maker", Kind::Bool, 34),
+        ],
+        strings: &["client_id", "exchange_order_id", "fee_currency", "trade_id"],
+    },
+    Template {
+        id: 6,
+        name: "Reject",
+        block_length: 16,
+        fields: &[
+            field("ts_ns", Kind::U64, 0),
+            field("reason_code", Kind::U16, 8),
+            field("venue_code", Kind::U16, 10),
+        ],
+        strings: &["client_id", "reason_msg", "source"],
+    },
+    Template {
+        id: 10,
+        name: "MdUpdate",
+        block_length: 56,
+        fields: &[
+            field("ts_ns", Kind::U64, 0),
+            field("symbol_id", Kind::U32, 8),
+            field("bid_px", Kind::F64, 16),
+            field("bid_sz", Kind::F64, 24),
+            field("ask_px", Kind::F64, 32),
+            field("ask_sz", Kind::F64, 40),
+            field("seq", Kind::U64, 48),
+        ],
+        strings: &[],
+    },
+    Template {
+        id: 11,
+        name: "BookDelta",
+        block_length: 40,
+        fields: &[
+            field("ts_ns", Kind::U64, 0),
+            field("symbol_id", Kind::U32, 8),
+            field("is_bid", Kind::Bool, 12),
+            field("price", Kind::F64, 16),
+            field("size", Kind::F64, 24),
+            field("seq", Kind::U64, 32),
+        ],
+        strings: &[],
+    },
+    Template {
+        id: 12,
+        name: "MarketTrade",
+        block_length: 32,
+        fields: &[
+            field("ts_ns", Kind::U64, 0),
+            field("symbol_id", Kind::U32, 8),
+            field("aggressor_side", Kind::U8, 12),
+            field("price", Kind::F64, 16),
+            field("quantity", Kind::F64, 24),
+        ],
+        strings: &[],
+    },
+];
+
+impl Template {
+    pub fn by_id(id: u16) -> Option<&'static Template> {
+        TEMPLATES.iter().find(|template| template.id == id)
+    }
+
+    pub fn by_name(name: &str) -> Option<&'static Template> {
+        TEMPLATES.iter().find(|template| template.name == name)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Header {
+    pub block_length: u16,
+    pub template_id: u16,
+    pub schema_id: u16,
+    pub version: u16,
+}
+
+/// A fixed-block field value.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Value {
+    Int(u64),
+    Float(f64),
+    Bool(bool),
+    Null,
+}
+
+/// Builds one frame: the header and a zeroed root block up front, strings
+/// appended in template order.
+pub struct Encoder {
+    buf: Vec<u8>,
+}
+
+impl Encoder {
+    pub fn new(template: &Template) -> Self {
+        let mut buf = Vec::with_capacity(HEADER_LEN + template.block_length as usize + 64);
+        for word in [
+            template.block_length,
+            template.id,
+            SCHEMA_ID,
+            SCHEMA_VERSION,
+        ] {
+            buf.extend_from_slice(&word.to_le_bytes());
+        }
+        buf.resize(HEADER_LEN + template.block_length as usize, 0);
+        Self { buf }
+    }
+
+    #[inline(always)]
+    fn put(&mut self, offset: usize, bytes: &[u8]) {
+        self.buf[HEADER_LEN + offset..HEADER_LEN + offset + bytes.len()].copy_from_slice(bytes);
+    }
+
+    /// Write a fixed field; values that do not fit its kind are an error.
+    pub fn set(&mut self, field: &Field, value: Value) -> Result<(), String> {
+        let out_of_range = || format!("{} out of range for {:?}", field.name, field.kind);
+        match (field.kind, value) {
+            (Kind::OptF64, Value::Null) => self.put(field.offset, &f64::NAN.to_le_bytes()),
+            (Kind::OptU64, Value::Null) => self.put(field.offset, &NULL_U64.to_le_bytes()),
+            (Kind::F64 | Kind::OptF64, Value::Float(v)) => self.put(field.offset, &v.to_le_bytes()),
+            (Kind::F64 | Kind::OptF64, Value::Int(v)) => {
+                self.put(field.offset, &(v as f64).to_le_bytes())
+            }
+            (Kind::Bool, Value::Bool(v)) => self.put(field.offset, &[v as u8]),
+            (Kind::U8, Value::Int(v)) => self.put(
+                field.offset,
+                &[u8::try_from(v).map_err(|_| out_of_range())?],
+            ),
+            (Kind::U16, Value::Int(v)) => self.put(
+                field.offset,
+                &u16::try_from(v).map_err(|_| out_of_range())?.to_le_bytes(),
+            ),
+            (Kind::U32, Value::Int(v)) => self.put(
+                field.offset,
+                &u32::try_from(v).map_err(|_| out_of_range())?.to_le_bytes(),
+            ),
+            (Kind::U64, Value::Int(v)) => self.put(field.offset, &v.to_le_bytes()),
+            (Kind::OptU64, Value::Int(v)) if v != NULL_U64 => {
+                self.put(field.offset, &v.to_le_bytes())
+            }
+            _ => return Err(format!("invalid value for {}: {:?}", field.name, value)),
+        }
+        Ok(())
+    }
+
+    /// Append the next string; callers go through `Template::strings` in order.
+    pub fn push_str(&mut self, value: &str) -> Result<(), String> {
+        let len = u16::try_from(value.len())
+            .map_err(|_| format!("string of {} bytes too long", value.len()))?;
+        self.buf.extend_from_slice(&len.to_le_bytes());
+        self.buf.extend_from_slice(value.as_bytes());
+        Ok(())
+    }
+
+    pub fn finish(self) -> Vec<u8> {
+        self.buf
+    }
+}
+
+/// Zero-copy view of one frame.
+pub struct Frame<'a> {
+    pub header: Header,
+    pub template: &'static Template,
+    block: &'a [u8],
+    strings: &'a [u8],
+}
+
+#[inline(always)]
+fn le_u16(buf: &[u8], at: usize) -> u16 {
+    u16::from_le_bytes([buf[at], buf[at + 1]])
+}
+
+#[inline(always)]
+fn le_u64(buf: &[u8], at: usize) -> u64 {
+    let mut bytes = [0u8; 8];
+    bytes.copy_from_slice(&buf[at..at + 8]);
+    u64::from_le_bytes(bytes)
+}
+
+pub fn read_header(buf: &[u8]) -> Result<Header, String> {
+    if buf.len() < HEADER_LEN {
+        return Err(format!(
+            "frame of {} bytes is shorter than its header",
+            buf.len()
+        ));
+    }
+    Ok(Header {
+        block_length: le_u16(buf, 0),
+        template_id: le_u16(buf, 2),
+        schema_id: le_u16(buf, 4),
+        version: le_u16(buf, 6),
+    })
+}
+
+impl<'a> Frame<'a> {
+    pub fn parse(buf: &'a [u8]) -> Result<Frame<'a>, String> {
+        let header = read_header(buf)?;
+        if header.schema_id != SCHEMA_ID {
+            return Err(format!("unknown schema id {}", header.schema_id));
+        }
+        if header.version == 0 || header.version > SCHEMA_VERSION {
+            return Err(format!(
+                "schema version {} not supported (up to {})",
+                header.version, SCHEMA_VERSION
+            ));
+        }
+        let template = Template::by_id(header.template_id)
+            .ok_or_else(|| format!("unknown template id {}", header.template_id))?;
+        let block_end = HEADER_LEN + header.block_length as usize;
+        if header.block_length < template.block_length || buf.len() < block_end {
+            return Err(format!("truncated {} frame", template.name));
+        }
+        Ok(Frame {
+            header,
+            template,
+            block: &buf[HEADER_LEN..block_end],
+            strings: &buf[block_end..],
+        })
+    }
+
+    #[inline(always)]
+    pub fn get(&self, field: &Field) -> Value {
+        let at = field.offset;
+        match field.kind {
+            Kind::U8 => Value::Int(self.block[at] as u64),
+            Kind::Bool => Value::Bool(self.block[at] != 0),
+            Kind::U16 => Value::Int(le_u16(self.block, at) as u64),
+            Kind::U32 => {
+                let mut bytes = [0u8; 4];
+                bytes.copy_from_slice(&self.block[at..at + field.kind.width()]);
+                Value::Int(u32::from_le_bytes(bytes) as u64)
+            }
+            Kind::U64 => Value::Int(le_u64(self.block, at)),
+            Kind::OptU64 => match le_u64(self.block, at) {
+                NULL_U64 => Value::Null,
+                value => Value::Int(value),
+            },
+            Kind::F64 => Value::Float(f64::from_bits(le_u64(self.block, at))),
+            Kind::OptF64 => match f64::from_bits(le_u64(self.block, at)) {
+                value if value.is_nan() => Value::Null,
+                value => Value::Float(value),
+            },
+        }
+    }
+
+    /// The template's strings, in order.
+    pub fn strings(&self) -> Result<Vec<&'a str>, String> {
+        let mut rest = self.strings;
+        let mut values = Vec::with_capacity(self.template.strings.len());
+        for name in self.template.strings {
+            if rest.len() < 2 {
+                return Err(format!(
+                    "truncated {} in {} frame",
+                    name, self.template.name
+                ));
+            }
+            let len = le_u16(rest, 0) as usize;
+            let bytes = rest
+                .get(2..2 + len)
+                .ok_or_else(|| format!("truncated {} in {} frame", name, self.template.name))?;
+            values.push(std::str::from_utf8(bytes).map_err(|_| format!("{} is not UTF-8", name))?);
+            rest = &rest[2 + len..];
+        }
+        Ok(values)
+    }
+}
+
+fn py_value(value: &Bound<'_, PyAny>) -> PyResult<Value> {
+    if value.is_none() {
+        Ok(Value::Null)
+    } else if let Ok(v) = value.extract::<bool>() {
+        Ok(Value::Bool(v))
+    } else if let Ok(v) = value.extract::<u64>() {
+        Ok(Value::Int(v))
+    } else {
+        Ok(Value::Float(value.extract::<f64>()?))
+    }
+}
+
+/// Encode a `pkg.schemas` order or market-data message (anything with the
+/// same class name and attributes) into one frame.
+#[pyfunction]
+pub fn sbe_encode<'py>(
+    py: Python<'py>,
+    message: &Bound<'py, PyAny>,
+) -> PyResult<Bound<'py, PyBytes>> {
+    let name = message.get_type().name()?.to_string();
+    let template = Template::by_name(&name)
+        .ok_or_else(|| PyTypeError::new_err(format!("no binary layout for {}", name)))?;
+    let mut encoder = Encoder::new(template);
+    for field in template.fields {
+        let value = py_value(&message.getattr(field.name)?)?;
+        // Bools sent as ints and ints as bools are still unambiguous.
+        let value = match (field.kind, value) {
+            (Kind::Bool, Value::Int(v)) => Value::Bool(v != 0),
+            (Kind::U8 | Kind::U16 | Kind::U32 | Kind::U64, Value::Bool(v)) => Value::Int(v as u64),
+            _ => value,
+        };
+        encoder.set(field, value).map_err(PyValueError::new_err)?;
+    }
+    for name in template.strings {
+        let value: String = message.getattr(*name)?.extract()?;
+        encoder.push_str(&value).map_err(PyValueError::new_err)?;
+    }
+    Ok(PyBytes::new(py, &encoder.finish()))
+}
+
+/// Decode one frame into its message name and a dict of its fields.
+#[pyfunction]
+pub fn sbe_decode<'py>(
+    py: Python<'py>,
+    data: &[u8],
+) -> PyResult<(&'static str, Bound<'py, PyDict>)> {
+    let frame = Frame::parse(data).map_err(PyValueError::new_err)?;
+    let dict = PyDict::new(py);
+    for field in frame.template.fields {
+        match frame.get(field) {
+            Value::Int(v) => dict.set_item(field.name, v)?,
+            Value::Float(v) => dict.set_item(field.name, v)?,
+            Value::Bool(v) => dict.set_item(field.name, v)?,
+            Value::Null => dict.set_item(field.name, py.None())?,
+        }
+    }
+    for (name, value) in frame
+        .template
+        .strings
+        .iter()
+        .zip(frame.strings().map_err(PyValueError::new_err)?)
+    {
+        dict.set_item(*name, value)?;
+    }
+    Ok((frame.template.name, dict))
+}
+
+/// `(block_length, template_id, schema_id, version)` of a frame.
+#[pyfunction]
+pub fn sbe_header(data: &[u8]) -> PyResult<(u16, u16, u16, u16)> {
+    let header = read_header(data).map_err(PyValueError::new_err)?;
+    Ok((
+        header.block_length,
+        header.template_id,
+        header.schema_id,
+        header.version,
+    ))
+}
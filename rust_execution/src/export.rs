@@ -0,0 +1,592 @@
+//! Columnar export of executions, fills, order events, positions and equity
+//!
+//! Tables are built as Arrow record batches from a live engine, a journal
+//! directory, the `RustExecution`/`RustFill` objects the engine returned or
+//! a backtest's `(ts_ns, equity)` curve,
+//! then written to Parquet or an Arrow IPC file, or handed to `pyarrow`
+//! through the Arrow C data interface. The handoff moves ownership of the
+//! Rust buffers to pyarrow, so nothing is copied.
+//!
+//! Column names follow `to_dict` and `read_journal`; ids and timestamps are
+//! unsigned integers, prices and quantities `float64`.
+
+use crate::journal::{self, JournalEvent};
+use crate::ledger::{CostMethod, Ledger, PositionSnapshot};
+use crate::orderbook::Side;
+use crate::{RustExecution, RustExecutionEngine, RustFill};
+use arrow::array::{Array, ArrayRef, BooleanArray, Float64Array, StringArray, StructArray, UInt16Array, UInt32Array, UInt64Array, UInt8Array};
+use arrow::datatypes::{Field, Schema};
+use arrow::error::ArrowError;
+use arrow::ffi::{to_ffi, FFI_ArrowArray, FFI_ArrowSchema};
+use arrow::ipc::writer::FileWriter;
+use arrow::record_batch::RecordBatch;
+use parquet::arrow::ArrowWriter;
+use parquet::basic::{Compression, ZstdLevel};
+use parquet::file::properties::WriterProperties;
+use pyo3::exceptions::{PyFileNotFoundError, PyIOError, PyTypeError, PyValueError};
+use pyo3::prelude::*;
+use std::collections::HashMap;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TableKind {
+    Executions,
+    Fills,
+    /// Order lifecycle events: acceptance, state changes and rejects.
+    Orders,
+    Positions,
+    /// `run_backtest`'s equity curve.
+    Equity,
+}
+
+impl TableKind {
+    pub fn parse(name: &str) -> Option<TableKind> {
+        match name {
+            "executions" => Some(TableKind::Executions),
+            "fills" => Some(TableKind::Fills),
+            "orders" => Some(TableKind::Orders),
+            "positions" => Some(TableKind::Positions),
+            "equity" => Some(TableKind::Equity),
+            _ => None,
+        }
+    }
+}
+
+/// Columns collected in schema order.
+#[derive(Default)]
+struct Columns {
+    fields: Vec<Field>,
+    arrays: Vec<ArrayRef>,
+}
+
+impl Columns {
+    fn add(&mut self, name: &str, nullable: bool, array: impl Array + 'static) {
+        self.fields.push(Field::new(name, array.data_type().clone(), nullable));
+        self.arrays.push(Arc::new(array));
+    }
+
+    fn finish(self) -> Result<RecordBatch, ArrowError> {
+        RecordBatch::try_new(Arc::new(Schema::new(self.fields)), self.arrays)
+    }
+}
+
+fn executions_batch(executions: &[RustExecution]) -> Result<RecordBatch, ArrowError> {
+    let mut c = Columns::default();
+    let rows = executions.iter();
+    c.add("order_id", false, UInt64Array::from_iter_values(rows.clone().map(|e| e.order_id)));
+    c.add("client_id", true, rows.clone().map(|e| e.client_id.as_deref()).collect::<StringArray>());
+    c.add("status", false, UInt8Array::from_iter_values(rows.clone().map(|e| e.status)));
+    c.add("executed_price", false, Float64Array::from_iter_values(rows.clone().map(|e| e.executed_price)));
+    c.add("executed_quantity", false, Float64Array::from_iter_values(rows.clone().map(|e| e.executed_quantity)));
+    c.add("remaining_quantity", false, Float64Array::from_iter_values(rows.clone().map(|e| e.remaining_quantity)));
+    c.add("fill_count", false, UInt32Array::from_iter_values(rows.clone().map(|e| e.fills.len() as u32)));
+    c.add("fee", false, Float64Array::from_iter_values(rows.clone().map(|e| e.fee)));
+    c.add("fee_currency", false, StringArray::from_iter_values(rows.clone().map(|e| &e.fee_currency)));
+    c.add("arrival_price", false, Float64Array::from_iter_values(rows.clone().map(|e| e.arrival_price)));
+    c.add("slippage", false, Float64Array::from_iter_values(rows.clone().map(|e| e.slippage)));
+    c.add("latency_ns", false, UInt64Array::from_iter_values(rows.clone().map(|e| e.latency_ns)));
+    c.add("arrival_ns", false, UInt64Array::from_iter_values(rows.clone().map(|e| e.arrival_ns)));
+    c.add("ack_ns", false, UInt64Array::from_iter_values(rows.map(|e| e.ack_ns)));
+    c.finish()
+}
+
+/// One fill; the journal knows the symbol and side but not the resting
+/// order matched, a bare `RustFill` the reverse.
+struct FillRow {
+    seq: Option<u64>,
+    ts_ns: u64,
+    order_id: u64,
+    trade_id: u64,
+    symbol_id: Option<u32>,
+    side: Option<u8>,
+    is_maker: bool,
+    price: f64,
+    qty: f64,
+    fee: f64,
+    fee_currency: String,
+    matched_order_id: Option<u64>,
+}
+
+impl FillRow {
+    fn from_fill(fill: &RustFill, order: Option<(u32, u8)>) -> Self {
+        Self {
+            seq: None,
+            ts_ns: fill.ts_ns,
+            order_id: fill.order_id,
+            trade_id: fill.trade_id,
+            symbol_id: order.map(|(symbol_id, _)| symbol_id),
+            side: order.map(|(_, side)| side),
+            is_maker: fill.is_maker,
+            price: fill.price,
+            qty: fill.qty,
+            fee: fill.fee,
+            fee_currency: fill.fee_currency.clone(),
+            matched_order_id: Some(fill.matched_order_id),
+        }
+    }
+}
+
+fn fills_batch(fills: &[FillRow]) -> Result<RecordBatch, ArrowError> {
+    let mut c = Columns::default();
+    let rows = fills.iter();
+    c.add("seq", true, rows.clone().map(|f| f.seq).collect::<UInt64Array>());
+    c.add("ts_ns", false, UInt64Array::from_iter_values(rows.clone().map(|f| f.ts_ns)));
+    c.add("order_id", false, UInt64Array::from_iter_values(rows.clone().map(|f| f.order_id)));
+    c.add("trade_id", false, UInt64Array::from_iter_values(rows.clone().map(|f| f.trade_id)));
+    c.add("symbol_id", true, rows.clone().map(|f| f.symbol_id).collect::<UInt32Array>());
+    c.add("side", true, rows.clone().map(|f| f.side).collect::<UInt8Array>());
+    c.add("is_maker", false, rows.clone().map(|f| Some(f.is_maker)).collect::<BooleanArray>());
+    c.add("price", false, Float64Array::from_iter_values(rows.clone().map(|f| f.price)));
+    c.add("qty", false, Float64Array::from_iter_values(rows.clone().map(|f| f.qty)));
+    c.add("fee", false, Float64Array::from_iter_values(rows.clone().map(|f| f.fee)));
+    c.add("fee_currency", false, StringArray::from_iter_values(rows.clone().map(|f| &f.fee_currency)));
+    c.add("matched_order_id", true, rows.map(|f| f.matched_order_id).collect::<UInt64Array>());
+    c.finish()
+}
+
+/// One journaled lifecycle event. Updates carry the order's static fields
+/// from its acceptance, when the journal still holds it.
+#[derive(Default)]
+struct OrderRow {
+    seq: u64,
+    ts_ns: u64,
+    event: &'static str,
+    order_id: Option<u64>,
+    client_id: Option<String>,
+    symbol_id: Option<u32>,
+    side: Option<u8>,
+    order_type: Option<u8>,
+    tif: Option<u8>,
+    status: Option<u8>,
+    price: f64,
+    quantity: f64,
+    filled_quantity: Option<f64>,
+    trigger_price: Option<f64>,
+    reason_code: Option<u16>,
+    reason_msg: Option<String>,
+}
+
+fn order_rows(records: &[journal::Record]) -> Vec<OrderRow> {
+    let mut accepted = HashMap::new();
+    let mut rows = Vec::new();
+    for record in records {
+        let mut row = OrderRow {
+            seq: record.seq,
+            ts_ns: record.ts_ns,
+            event: record.event.name(),
+            ..OrderRow::default()
+        };
+        match &record.event {
+            JournalEvent::Accepted(a) => {
+                accepted.insert(a.order_id, a.clone());
+                row.order_id = Some(a.order_id);
+                row.client_id = a.client_id.clone();
+                row.symbol_id = Some(a.symbol_id);
+                row.side = Some(a.side);
+                row.order_type = Some(a.order_type);
+                row.tif = Some(a.tif);
+                row.price = a.price;
+                row.quantity = a.quantity;
+                row.trigger_price = a.trigger_price;
+            }
+            JournalEvent::Update(u) => {
+                if let Some(a) = accepted.get(&u.order_id) {
+                    row.client_id = a.client_id.clone();
+                    row.symbol_id = Some(a.symbol_id);
+                    row.side = Some(a.side);
+                    row.order_type = Some(a.order_type);
+                    row.tif = Some(a.tif);
+                    row.trigger_price = a.trigger_price;
+                }
+                row.order_id = Some(u.order_id);
+                row.status = Some(u.status);
+                row.price = u.price;
+                row.quantity = u.quantity;
+                row.filled_quantity = Some(u.filled);
+            }
+            JournalEvent::Reject(r) => {
+                row.client_id = r.client_id.clone();
+                row.symbol_id = Some(r.symbol_id);
+                row.side = Some(r.side);
+                row.price = r.price;
+                row.quantity = r.quantity;
+                row.reason_code = Some(r.reason_code);
+                row.reason_msg = Some(r.reason_msg.clone());
+            }
+            JournalEvent::Fill(_) => continue,
+        }
+        rows.push(row);
+    }
+    rows
+}
+
+fn orders_batch(orders: &[OrderRow]) -> Result<RecordBatch, ArrowError> {
+    let mut c = Columns::default();
+    let rows = orders.iter();
+    c.add("seq", false, UInt64Array::from_iter_values(rows.clone().map(|o| o.seq)));
+    c.add("ts_ns", false, UInt64Array::from_iter_values(rows.clone().map(|o| o.ts_ns)));
+    c.add("event", false, StringArray::from_iter_values(rows.clone().map(|o| o.event)));
+    c.add("order_id", true, rows.clone().map(|o| o.order_id).collect::<UInt64Array>());
+    c.add("client_id", true, rows.clone().map(|o| o.client_id.as_deref()).collect::<StringArray>());
+    c.add("symbol_id", true, rows.clone().map(|o| o.symbol_id).collect::<UInt32Array>());
+    c.add("side", true, rows.clone().map(|o| o.side).collect::<UInt8Array>());
+    c.add("order_type", true, rows.clone().map(|o| o.order_type).collect::<UInt8Array>());
+    c.add("tif", true, rows.clone().map(|o| o.tif).collect::<UInt8Array>());
+    c.add("status", true, rows.clone().map(|o| o.status).collect::<UInt8Array>());
+    c.add("price", false, Float64Array::from_iter_values(rows.clone().map(|o| o.price)));
+    c.add("quantity", false, Float64Array::from_iter_values(rows.clone().map(|o| o.quantity)));
+    c.add("filled_quantity", true, rows.clone().map(|o| o.filled_quantity).collect::<Float64Array>());
+    c.add("trigger_price", true, rows.clone().map(|o| o.trigger_price).collect::<Float64Array>());
+    c.add("reason_code", true, rows.clone().map(|o| o.reason_code).collect::<UInt16Array>());
+    c.add("reason_msg", true, rows.map(|o| o.reason_msg.as_deref()).collect::<StringArray>());
+    c.finish()
+}
+
+/// A position at one point in time, with the net PnL of every position at
+/// that time.
+struct PositionRow {
+    seq: Option<u64>,
+    ts_ns: u64,
+    position: PositionSnapshot,
+    portfolio_net_pnl: f64,
+}
+
+/// Positions after each journaled fill, valued at each symbol's last fill.
+fn position_rows(records: &[journal::Record], method: CostMethod) -> Vec<PositionRow> {
+    let mut ledger = Ledger::new(method);
+    let mut rows = Vec::new();
+    for record in records {
+        let JournalEvent::Fill(fill) = &record.event else {
+            continue;
+        };
+        let Some(side) = Side::from_u8(fill.side) else {
+            continue;
+        };
+        ledger.on_fill(fill.symbol_id, side, fill.price, fill.qty, fill.quote_fee());
+        let portfolio_net_pnl = ledger
+            .symbols()
+            .into_iter()
+            .filter_map(|symbol_id| ledger.snapshot(symbol_id, None))
+            .map(|position| position.net_pnl())
+            .sum();
+        if let Some(position) = ledger.snapshot(fill.symbol_id, None) {
+            rows.push(PositionRow {
+                seq: Some(record.seq),
+                ts_ns: record.ts_ns,
+                position,
+                portfolio_net_pnl,
+            });
+        }
+    }
+    rows
+}
+
+fn positions_batch(positions: &[PositionRow]) -> Result<RecordBatch, ArrowError> {
+    let mut c = Columns::default();
+    let rows = positions.iter();
+    c.add("seq", true, rows.clone().map(|p| p.seq).collect::<UInt64Array>());
+    c.add("ts_ns", false, UInt64Array::from_iter_values(rows.clone().map(|p| p.ts_ns)));
+    c.add("symbol_id", false, UInt32Array::from_iter_values(rows.clone().map(|p| p.position.symbol_id)));
+    c.add("net_qty", false, Float64Array::from_iter_values(rows.clone().map(|p| p.position.net_qty)));
+    c.add("avg_entry_price", false, Float64Array::from_iter_values(rows.clone().map(|p| p.position.avg_entry_price)));
+    c.add("mark_price", true, rows.clone().map(|p| p.position.mark_price).collect::<Float64Array>());
+    c.add("realized_pnl", false, Float64Array::from_iter_values(rows.clone().map(|p| p.position.realized_pnl)));
+    c.add("unrealized_pnl", false, Float64Array::from_iter_values(rows.clone().map(|p| p.position.unrealized_pnl)));
+    c.add("fees", false, Float64Array::from_iter_values(rows.clone().map(|p| p.position.fees)));
+    c.add("net_pnl", false, Float64Array::from_iter_values(rows.clone().map(|p| p.position.net_pnl())));
+    c.add("traded_qty", false, Float64Array::from_iter_values(rows.clone().map(|p| p.position.traded_qty)));
+    c.add("fill_count", false, UInt64Array::from_iter_values(rows.clone().map(|p| p.position.fill_count)));
+    c.add("portfolio_net_pnl", false, Float64Array::from_iter_values(rows.map(|p| p.portfolio_net_pnl)));
+    c.finish()
+}
+
+fn equity_batch(curve: &[(u64, f64)]) -> Result<RecordBatch, ArrowError> {
+    let mut c = Columns::default();
+    let rows = curve.iter();
+    c.add("ts_ns", false, UInt64Array::from_iter_values(rows.clone().map(|(ts_ns, _)| *ts_ns)));
+    c.add("equity", false, Float64Array::from_iter_values(rows.map(|(_, equity)| *equity)));
+    c.finish()
+}
+
+fn read_records(dir: &Path) -> PyResult<Vec<journal::Record>> {
+    if !dir.is_dir() {
+        return Err(PyFileNotFoundError::new_err(format!("no journal at {}", dir.display())));
+    }
+    // Read-only: a torn tail is skipped, never truncated under a live writer.
+    Ok(journal::read_dir(dir, None, false).map_err(PyIOError::new_err)?.records)
+}
+
+fn journal_table(dir: &Path, kind: TableKind, method: CostMethod) -> PyResult<Result<RecordBatch, ArrowError>> {
+    let records = read_records(dir)?;
+    Ok(match kind {
+        TableKind::Executions => {
+            return Err(PyValueError::new_err(
+                "executions are not journaled; export the RustExecution objects instead",
+            ))
+        }
+        TableKind::Fills => fills_batch(
+            &records
+                .iter()
+                .filter_map(|record| match &record.event {
+                    JournalEvent::Fill(f) => Some(FillRow {
+                        seq: Some(record.seq),
+                        ts_ns: record.ts_ns,
+                        order_id: f.order_id,
+                        trade_id: f.trade_id,
+                        symbol_id: Some(f.symbol_id),
+                        side: Some(f.side),
+                        is_maker: f.is_maker,
+                        price: f.price,
+                        qty: f.qty,
+                        fee: f.fee,
+                        fee_currency: f.fee_currency.clone(),
+                        matched_order_id: None,
+                    }),
+                    _ => None,
+                })
+                .collect::<Vec<_>>(),
+        ),
+        TableKind::Orders => orders_batch(&order_rows(&records)),
+        TableKind::Positions => positions_batch(&position_rows(&records, method)),
+        TableKind::Equity => {
+            return Err(PyValueError::new_err(
+                "equity comes from run_backtest; export its equity_curve, or positions for net PnL",
+            ))
+        }
+    })
+}
+
+fn engine_table(engine: &RustExecutionEngine, kind: TableKind) -> PyResult<Result<RecordBatch, ArrowError>> {
+    Ok(match kind {
+        TableKind::Executions => {
+            return Err(PyValueError::new_err(
+                "the engine does not keep executions; export the RustExecution objects it returned",
+            ))
+        }
+        TableKind::Fills => {
+            let state = engine.state();
+            let rows: Vec<FillRow> = state
+                .fill_log
+                .iter()
+                .map(|fill| {
+                    let order = state.orders.get(fill.order_id).map(|o| (o.symbol_id, o.side));
+                    FillRow::from_fill(fill, order)
+                })
+                .collect();
+            fills_batch(&rows)
+        }
+        TableKind::Orders => {
+            let dir = engine.state().journal.as_ref().map(|journal| journal.dir().to_path_buf());
+            let dir = dir.ok_or_else(|| {
+                PyValueError::new_err("order events come from the journal; open one with journal_dir or open_journal")
+            })?;
+            orders_batch(&order_rows(&read_records(&dir)?))
+        }
+        TableKind::Positions => {
+            let positions = engine.position_snapshots();
+            let ts_ns = engine.state().timestamp();
+            let portfolio_net_pnl = positions.iter().map(PositionSnapshot::net_pnl).sum();
+            let rows: Vec<PositionRow> = positions
+                .into_iter()
+                .map(|position| PositionRow {
+                    seq: None,
+                    ts_ns,
+                    position,
+                    portfolio_net_pnl,
+                })
+                .collect();
+            positions_batch(&rows)
+        }
+        TableKind::Equity => {
+            return Err(PyValueError::new_err(
+                "the engine does not keep an equity curve; export run_backtest's equity_curve",
+            ))
+        }
+    })
+}
+
+fn objects_table(source: &Bound<'_, PyAny>, kind: TableKind) -> PyResult<Result<RecordBatch, ArrowError>> {
+    let mut executions = Vec::new();
+    let mut fills = Vec::new();
+    let mut curve = Vec::new();
+    for item in source.try_iter()? {
+        let item = item?;
+        if let Ok(point) = item.extract::<(u64, f64)>() {
+            curve.push(point);
+        } else if let Ok(execution) = item.downcast::<RustExecution>() {
+            let execution = execution.borrow();
+            fills.extend(execution.fills.iter().map(|fill| FillRow::from_fill(fill, None)));
+            executions.push(execution.clone());
+        } else if let Ok(fill) = item.downcast::<RustFill>() {
+            fills.push(FillRow::from_fill(&fill.borrow(), None));
+        } else {
+            return Err(PyTypeError::new_err(format!(
+                "expected RustExecution, RustFill or (ts_ns, equity), got {}",
+                item.get_type().name()?
+            )));
+        }
+    }
+    Ok(match kind {
+        TableKind::Executions if executions.is_empty() && !fills.is_empty() => {
+            return Err(PyValueError::new_err("executions need RustExecution objects, got fills"))
+        }
+        TableKind::Executions => executions_batch(&executions),
+        TableKind::Fills => fills_batch(&fills),
+        TableKind::Equity if curve.is_empty() && !(executions.is_empty() && fills.is_empty()) => {
+            return Err(PyValueError::new_err("equity needs (ts_ns, equity) pairs, got executions or fills"))
+        }
+        TableKind::Equity => equity_batch(&curve),
+        TableKind::Orders | TableKind::Positions => {
+            return Err(PyValueError::new_err(
+                "orders and positions come from an engine or a journal directory",
+            ))
+        }
+    })
+}
+
+/// Build `table` from a `RustExecutionEngine`, a journal directory or an
+/// iterable of `RustExecution`/`RustFill` objects or `(ts_ns, equity)` pairs.
+/// The journal does not record the engine's cost method, so its positions
+/// are rebuilt with `cost_method`, FIFO by default.
+fn build(source: &Bound<'_, PyAny>, table: &str, cost_method: Option<&str>) -> PyResult<RecordBatch> {
+    let kind = TableKind::parse(table).ok_or_else(|| {
+        PyValueError::new_err(format!(
+            "unknown table {:?}; expected executions, fills, orders, positions or equity",
+            table
+        ))
+    })?;
+    let batch = if let Ok(engine) = source.downcast::<RustExecutionEngine>() {
+        if cost_method.is_some() {
+            return Err(PyValueError::new_err(
+                "an engine reports positions under its own cost method; change it with set_cost_method",
+            ));
+        }
+        engine_table(&engine.borrow(), kind)?
+    } else if let Ok(dir) = source.extract::<PathBuf>() {
+        let method = cost_method.map_or(Ok(CostMethod::default()), crate::parse_cost_method)?;
+        journal_table(&dir, kind, method)?
+    } else {
+        objects_table(source, kind)?
+    };
+    batch.map_err(|e| PyValueError::new_err(e.to_string()))
+}
+
+/// `table` (`"executions"`, `"fills"`, `"orders"`, `"positions"` or
+/// `"equity"`) from `source` as a `pyarrow.RecordBatch` that takes over the
+/// Rust buffers without copying. `source` is a `RustExecutionEngine`, a
+/// journal directory, an iterable of the `RustExecution`/`RustFill` objects
+/// the engine returned, or `run_backtest`'s `equity_curve`. `cost_method`
+/// (`"fifo"`, `"lifo"` or `"average"`) values positions rebuilt from a
+/// journal.
+#[pyfunction]
+#[pyo3(signature = (source, table, cost_method=None))]
+pub fn export_arrow(
+    py: Python<'_>,
+    source: &Bound<'_, PyAny>,
+    table: &str,
+    cost_method: Option<&str>,
+) -> PyResult<PyObject> {
+    let batch = build(source, table, cost_method)?;
+    let (array, schema) =
+        to_ffi(&StructArray::from(batch).into_data()).map_err(|e| PyValueError::new_err(e.to_string()))?;
+    // pyarrow moves the structs out, leaving them released; if the import
+    // fails they still own the buffers and free them on drop.
+    let array: Box<FFI_ArrowArray> = Box::new(array);
+    let schema: Box<FFI_ArrowSchema> = Box::new(schema);
+    let batch = py.import("pyarrow")?.getattr("RecordBatch")?.call_method1(
+        "_import_from_c",
+        (
+            &*array as *const FFI_ArrowArray as usize,
+            &*schema as *const FFI_ArrowSchema as usize,
+        ),
+    )?;
+    Ok(batch.unbind())
+}
+
+/// Write `table` from `source` (as for `export_arrow`) to `path` as Parquet,
+/// compressed with `compression` (`"zstd"`, `"snappy"` or `"none"`), or
+/// with `format="ipc"` as an uncompressed Arrow IPC file. Returns the number
+/// of rows written.
+#[pyfunction]
+#[pyo3(signature = (source, table, path, format="parquet", compression="zstd", cost_method=None))]
+pub fn export_table(
+    source: &Bound<'_, PyAny>,
+    table: &str,
+    path: PathBuf,
+    format: &str,
+    compression: &str,
+    cost_method: Option<&str>,
+) -> PyResult<usize> {
+    let compression = match compression {
+        "zstd" => Compression::ZSTD(ZstdLevel::default()),
+        "snappy" => Compression::SNAPPY,
+        "none" => Compression::UNCOMPRESSED,
+        other => {
+            return Err(PyValueError::new_err(format!(
+                "unknown compression {:?}; expected zstd, snappy or none",
+                other
+            )))
+        }
+    };
+    if format != "parquet" && format != "ipc" {
+        return Err(PyValueError::new_err(format!(
+            "unknown format {:?}; expected parquet or ipc",
+            format
+        )));
+    }
+    let batch = build(source, table, cost_method)?;
+    let io_err = |e: std::io::Error| PyIOError::new_err(format!("{}: {}", path.display(), e));
+    let write_err = |e: String| PyIOError::new_err(format!("{}: {}", path.display(), e));
+    let file = File::create(&path).map_err(io_err)?;
+    if format == "parquet" {
+        let properties = WriterProperties::builder().set_compression(compression).build();
+        let mut writer = ArrowWriter::try_new(file, batch.schema(), Some(properties))
+            .map_err(|e| write_err(e.to_string()))?;
+        writer.write(&batch).map_err(|e| write_err(e.to_string()))?;
+        writer.close().map_err(|e| write_err(e.to_string()))?;
+    } else {
+        let mut writer = FileWriter::try_new(file, &batch.schema()).map_err(|e| write_err(e.to_string()))?;
+        writer.write(&batch).map_err(|e| write_err(e.to_string()))?;
+        writer.finish().map_err(|e| write_err(e.to_string()))?;
+    }
+    Ok(batch.num_rows())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::journal::{JournalFill, Record};
+
+    fn fill(seq: u64, side: u8, price: f64, qty: f64) -> Record {
+        Record {
+            seq,
+            ts_ns: seq * 1_000,
+            event: JournalEvent::Fill(JournalFill {
+                order_id: seq,
+                trade_id: seq,
+                symbol_id: 1,
+                side,
+                is_maker: false,
+                price,
+                qty,
+                fee: qty * 0.001,
+                fee_currency: "BTC".to_string(),
+                fee_in_base: true,
+            }),
+        }
+    }
+
+    #[test]
+    fn positions_value_base_fees_with_the_given_method() {
+        let records = [fill(1, 0, 100.0, 1.0), fill(2, 0, 110.0, 1.0), fill(3, 1, 120.0, 1.5)];
+        let lifo = position_rows(&records, CostMethod::Lifo);
+        let last = &lifo.last().unwrap().position;
+        assert_eq!((last.net_qty, last.avg_entry_price, last.realized_pnl), (0.5, 100.0, 20.0));
+        assert!((last.fees - 0.39).abs() < 1e-12);
+        assert_eq!(lifo.iter().map(|row| row.seq).collect::<Vec<_>>(), [Some(1), Some(2), Some(3)]);
+        let fifo = position_rows(&records, CostMethod::Fifo);
+        assert_eq!(fifo.last().unwrap().position.realized_pnl, 25.0);
+    }
+}
@@ -0,0 +1,689 @@
+//! Streaming microstructure features over sliding time windows
+//!
+//! Every window keeps running sums next to its samples, and min/max through
+//! monotonic deques, so each update costs amortized O(1) however many
+//! samples the window holds. Increments between consecutive quotes
+//! (returns, order-flow imbalance) only count when both quotes are inside
+//! the window, matching `np.diff` over the window's samples.
+
+use crate::orderbook::QTY_EPSILON;
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Mutex, MutexGuard};
+
+/// Feature vector with the fields of `pkg.schemas.features.FeatureFrame`
+/// plus the streaming window statistics.
+#[derive(Clone, Debug, Default)]
+#[pyclass(get_all, set_all)]
+pub struct RustFeatureFrame {
+    pub ts_ns: u64,
+    pub symbol_id: u32,
+    pub mid_price: f64,
+    pub micro_price: f64,
+    pub spread: f64,
+    pub spread_bps: f64,
+    pub bid_volume: f64,
+    pub ask_volume: f64,
+    /// Top-of-book size imbalance, -1 to 1.
+    pub imbalance: f64,
+    /// Population std of quote-to-quote returns in the window.
+    pub realized_vol: f64,
+    pub price_range: f64,
+    /// Against the previous quote.
+    pub price_change: f64,
+    pub price_change_pct: f64,
+    pub regime_bits: u32,
+    /// Mid return from the oldest quote in the window.
+    pub window_return: f64,
+    pub spread_mean: f64,
+    pub spread_std: f64,
+    pub spread_min: f64,
+    pub spread_max: f64,
+    /// Summed order-flow imbalance (Cont, Kukanov and Stoikov).
+    pub ofi: f64,
+    /// Mean depth imbalance over the window, -1 to 1.
+    pub book_pressure: f64,
+    pub trade_count: u64,
+    pub trade_volume: f64,
+    /// Trades per second over the trade window.
+    pub trade_intensity: f64,
+    /// Buy minus sell aggressor volume.
+    pub signed_volume: f64,
+    /// 0 without trades in the window.
+    pub vwap: f64,
+    /// Quotes in the window.
+    pub samples: u64,
+}
+
+#[pymethods]
+impl RustFeatureFrame {
+    pub fn has_regime(&self, flag: u32) -> bool {
+        self.regime_bits & flag != 0
+    }
+
+    pub fn set_regime(&mut self, flag: u32) {
+        self.regime_bits |= flag;
+    }
+
+    pub fn clear_regime(&mut self, flag: u32) {
+        self.regime_bits &= !flag;
+    }
+
+    pub fn to_dict(&self) -> PyResult<PyObject> {
+        Python::with_gil(|py| {
+            let dict = PyDict::new(py);
+            dict.set_item("ts_ns", self.ts_ns)?;
+            dict.set_item("symbol_id", self.symbol_id)?;
+            dict.set_item("mid_price", self.mid_price)?;
+            dict.set_item("micro_price", self.micro_price)?;
+            dict.set_item("spread", self.spread)?;
+            dict.set_item("spread_bps", self.spread_bps)?;
+            dict.set_item("bid_volume", self.bid_volume)?;
+            dict.set_item("ask_volume", self.ask_volume)?;
+            dict.set_item("imbalance", self.imbalance)?;
+            dict.set_item("realized_vol", self.realized_vol)?;
+            dict.set_item("price_range", self.price_range)?;
+            dict.set_item("price_change", self.price_change)?;
+            dict.set_item("price_change_pct", self.price_change_pct)?;
+            dict.set_item("regime_bits", self.regime_bits)?;
+            dict.set_item("window_return", self.window_return)?;
+            dict.set_item("spread_mean", self.spread_mean)?;
+            dict.set_item("spread_std", self.spread_std)?;
+            dict.set_item("spread_min", self.spread_min)?;
+            dict.set_item("spread_max", self.spread_max)?;
+            dict.set_item("ofi", self.ofi)?;
+            dict.set_item("book_pressure", self.book_pressure)?;
+            dict.set_item("trade_count", self.trade_count)?;
+            dict.set_item("trade_volume", self.trade_volume)?;
+            dict.set_item("trade_intensity", self.trade_intensity)?;
+            dict.set_item("signed_volume", self.signed_volume)?;
+            dict.set_item("vwap", self.vwap)?;
+            dict.set_item("samples", self.samples)?;
+            Ok(dict.into())
+        })
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "RustFeatureFrame(ts_ns={}, symbol_id={}, mid_price={}, spread_bps={:.3}, realized_vol={:.6}, ofi={})",
+            self.ts_ns, self.symbol_id, self.mid_price, self.spread_bps, self.realized_vol, self.ofi
+        )
+    }
+}
+
+/// Top of book as the calculator sees it; `bid_depth`/`ask_depth` default
+/// to the top sizes.
+#[derive(Clone, Copy, Debug)]
+pub struct Quote {
+    pub ts_ns: u64,
+    pub bid_px: f64,
+    pub bid_sz: f64,
+    pub ask_px: f64,
+    pub ask_sz: f64,
+    pub bid_depth: f64,
+    pub ask_depth: f64,
+}
+
+impl Quote {
+    #[inline(always)]
+    fn mid(&self) -> f64 {
+        (self.bid_px + self.ask_px) / 2.0
+    }
+
+    /// Order-flow imbalance of the move from `prev` to `self`.
+    #[inline(always)]
+    fn ofi(&self, prev: &Quote) -> f64 {
+        let mut flow = 0.0;
+        if self.bid_px >= prev.bid_px {
+            flow += self.bid_sz;
+        }
+        if self.bid_px <= prev.bid_px {
+            flow -= prev.bid_sz;
+        }
+        if self.ask_px <= prev.ask_px {
+            flow -= self.ask_sz;
+        }
+        if self.ask_px >= prev.ask_px {
+            flow += prev.ask_sz;
+        }
+        flow
+    }
+}
+
+#[inline(always)]
+fn ratio(numerator: f64, denominator: f64) -> f64 {
+    if denominator.abs() <= QTY_EPSILON {
+        0.0
+    } else {
+        numerator / denominator
+    }
+}
+
+/// Count, sum and sum of squares of a windowed series, taken around the
+/// first value so a small spread around a large level keeps its precision.
+#[derive(Clone, Copy, Debug, Default)]
+struct Moments {
+    count: u64,
+    shift: f64,
+    sum: f64,
+    sum_sq: f64,
+}
+
+impl Moments {
+    #[inline(always)]
+    fn add(&mut self, value: f64) {
+        if self.count == 0 {
+            self.shift = value;
+        }
+        let value = value - self.shift;
+        self.count += 1;
+        self.sum += value;
+        self.sum_sq += value * value;
+    }
+
+    #[inline(always)]
+    fn remove(&mut self, value: f64) {
+        self.count -= 1;
+        if self.count == 0 {
+            // Drop the rounding error the subtractions accumulated.
+            *self = Moments::default();
+        } else {
+            let value = value - self.shift;
+            self.sum -= value;
+            self.sum_sq -= value * value;
+        }
+    }
+
+    #[inline(always)]
+    fn mean(&self) -> f64 {
+        if self.count == 0 {
+            0.0
+        } else {
+            self.shift + self.sum / self.count as f64
+        }
+    }
+
+    #[inline(always)]
+    fn std(&self) -> f64 {
+        if self.count == 0 {
+            return 0.0;
+        }
+        let mean = self.sum / self.count as f64;
+        (self.sum_sq / self.count as f64 - mean * mean)
+            .max(0.0)
+            .sqrt()
+    }
+}
+
+/// Windowed min and max: each deque holds the candidates still able to
+/// become the extreme, tagged with their sample id.
+#[derive(Debug, Default)]
+struct Extremes {
+    min: VecDeque<(u64, f64)>,
+    max: VecDeque<(u64, f64)>,
+}
+
+impl Extremes {
+    #[inline(always)]
+    fn push(&mut self, id: u64, value: f64) {
+        while self.min.back().is_some_and(|&(_, v)| v >= value) {
+            self.min.pop_back();
+        }
+        self.min.push_back((id, value));
+        while self.max.back().is_some_and(|&(_, v)| v <= value) {
+            self.max.pop_back();
+        }
+        self.max.push_back((id, value));
+    }
+
+    /// Forget samples older than `first_id`.
+    #[inline(always)]
+    fn evict(&mut self, first_id: u64) {
+        while self.min.front().is_some_and(|&(id, _)| id < first_id) {
+            self.min.pop_front();
+        }
+        while self.max.front().is_some_and(|&(id, _)| id < first_id) {
+            self.max.pop_front();
+        }
+    }
+
+    #[inline(always)]
+    fn range(&self) -> (f64, f64) {
+        (
+            self.min.front().map_or(0.0, |&(_, v)| v),
+            self.max.front().map_or(0.0, |&(_, v)| v),
+        )
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+struct QuoteSample {
+    id: u64,
+    ts_ns: u64,
+    mid: f64,
+    spread: f64,
+    pressure: f64,
+    /// Return and order-flow imbalance since the previous quote.
+    ret: f64,
+    ofi: f64,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct TradeSample {
+    ts_ns: u64,
+    quantity: f64,
+    notional: f64,
+    signed: f64,
+}
+
+#[derive(Debug, Default)]
+pub struct SymbolFeatures {
+    quotes: VecDeque<QuoteSample>,
+    trades: VecDeque<TradeSample>,
+    next_id: u64,
+    last: Option<Quote>,
+    /// Latest timestamp seen on either stream.
+    now_ns: u64,
+    /// Over `quotes[1..]`.
+    returns: Moments,
+    ofi: f64,
+    /// Over all of `quotes`.
+    spreads: Moments,
+    pressure: f64,
+    mids: Extremes,
+    spread_extremes: Extremes,
+    trade_volume: f64,
+    trade_notional: f64,
+    signed_volume: f64,
+    price_change: f64,
+}
+
+impl SymbolFeatures {
+    fn evict(&mut self, window_ns: u64, trade_window_ns: u64) {
+        let cutoff = self.now_ns.saturating_sub(window_ns);
+        while let Some(front) = self.quotes.front().copied() {
+            if front.ts_ns >= cutoff {
+                break;
+            }
+            self.quotes.pop_front();
+            self.spreads.remove(front.spread);
+            self.pressure -= front.pressure;
+            if let Some(next) = self.quotes.front() {
+                self.returns.remove(next.ret);
+                self.ofi -= next.ofi;
+            }
+        }
+        match self.quotes.front() {
+            Some(front) => {
+                self.mids.evict(front.id);
+                self.spread_extremes.evict(front.id);
+            }
+            None => {
+                self.mids = Extremes::default();
+                self.spread_extremes = Extremes::default();
+                self.returns = Moments::default();
+                self.ofi = 0.0;
+                self.pressure = 0.0;
+            }
+        }
+
+        let cutoff = self.now_ns.saturating_sub(trade_window_ns);
+        while let Some(trade) = self.trades.front().copied() {
+            if trade.ts_ns >= cutoff {
+                break;
+            }
+            self.trades.pop_front();
+            self.trade_volume -= trade.quantity;
+            self.trade_notional -= trade.notional;
+            self.signed_volume -= trade.signed;
+        }
+        if self.trades.is_empty() {
+            self.trade_volume = 0.0;
+            self.trade_notional = 0.0;
+            self.signed_volume = 0.0;
+        }
+    }
+
+    #[inline(always)]
+    pub fn on_quote(&mut self, quote: Quote, window_ns: u64, trade_window_ns: u64) {
+        self.now_ns = self.now_ns.max(quote.ts_ns);
+        self.evict(window_ns, trade_window_ns);
+
+        let mid = quote.mid();
+        let (ret, ofi) = match &self.last {
+            Some(prev) => (ratio(mid, prev.mid()) - 1.0, quote.ofi(prev)),
+            None => (0.0, 0.0),
+        };
+        self.price_change = self.last.map_or(0.0, |prev| mid - prev.mid());
+        let sample = QuoteSample {
+            id: self.next_id,
+            ts_ns: quote.ts_ns,
+            mid,
+            spread: quote.ask_px - quote.bid_px,
+            pressure: ratio(
+                quote.bid_depth - quote.ask_depth,
+                quote.bid_depth + quote.ask_depth,
+            ),
+            ret,
+            ofi,
+        };
+        self.next_id += 1;
+
+        if !self.quotes.is_empty() {
+            self.returns.add(sample.ret);
+            self.ofi += sample.ofi;
+        }
+        self.spreads.add(sample.spread);
+        self.pressure += sample.pressure;
+        self.mids.push(sample.id, sample.mid);
+        self.spread_extremes.push(sample.id, sample.spread);
+        self.quotes.push_back(sample);
+        self.last = Some(quote);
+    }
+
+    #[inline(always)]
+    pub fn on_trade(
+        &mut self,
+        ts_ns: u64,
+        price: f64,
+        quantity: f64,
+        buyer_aggressor: bool,
+        window_ns: u64,
+        trade_window_ns: u64,
+    ) {
+        self.now_ns = self.now_ns.max(ts_ns);
+        let trade = TradeSample {
+            ts_ns,
+            quantity,
+            notional: price * quantity,
+            signed: if buyer_aggressor { quantity } else { -quantity },
+        };
+        self.trade_volume += trade.quantity;
+        self.trade_notional += trade.notional;
+        self.signed_volume += trade.signed;
+        self.trades.push_back(trade);
+        self.evict(window_ns, trade_window_ns);
+    }
+
+    /// Features as of the latest quote; `None` before the first one.
+    pub fn frame(&self, symbol_id: u32, trade_window_ns: u64) -> Option<RustFeatureFrame> {
+        let quote = self.last?;
+        let mid = quote.mid();
+        let spread = quote.ask_px - quote.bid_px;
+        let total = quote.bid_sz + quote.ask_sz;
+        let micro_price = if total <= QTY_EPSILON {
+            mid
+        } else {
+            (quote.bid_px * quote.ask_sz + quote.ask_px * quote.bid_sz) / total
+        };
+        let (mid_min, mid_max) = self.mids.range();
+        let (spread_min, spread_max) = self.spread_extremes.range();
+        let previous_mid = mid - self.price_change;
+        let samples = self.quotes.len() as u64;
+        let trade_count = self.trades.len() as u64;
+
+        Some(RustFeatureFrame {
+            ts_ns: self.now_ns,
+            symbol_id,
+            mid_price: mid,
+            micro_price,
+            spread,
+            spread_bps: if mid > 0.0 {
+                spread / mid * 10_000.0
+            } else {
+                0.0
+            },
+            bid_volume: quote.bid_sz,
+            ask_volume: quote.ask_sz,
+            imbalance: ratio(quote.bid_sz - quote.ask_sz, total),
+            realized_vol: self.returns.std(),
+            price_range: mid_max - mid_min,
+            price_change: self.price_change,
+            price_change_pct: ratio(self.price_change, previous_mid) * 100.0,
+            regime_bits: 0,
+            window_return: self
+                .quotes
+                .front()
+                .map_or(0.0, |front| ratio(mid, front.mid) - 1.0),
+            spread_mean: self.spreads.mean(),
+            spread_std: self.spreads.std(),
+            spread_min,
+            spread_max,
+            ofi: self.ofi,
+            book_pressure: ratio(self.pressure, samples as f64),
+            trade_count,
+            trade_volume: self.trade_volume,
+            trade_intensity: trade_count as f64 * 1e9 / trade_window_ns as f64,
+            signed_volume: self.signed_volume,
+            vwap: ratio(self.trade_notional, self.trade_volume),
+            samples,
+        })
+    }
+}
+
+/// Attributes read from a `pkg.schemas.market_data.TopOfBook`.
+#[derive(FromPyObject)]
+struct TopOfBookArg {
+    ts_ns: u64,
+    symbol_id: u32,
+    bid_px: f64,
+    bid_sz: f64,
+    ask_px: f64,
+    ask_sz: f64,
+}
+
+/// Incremental feature calculator for any number of symbols.
+#[pyclass]
+pub struct FeatureCalculator {
+    window_ns: u64,
+    trade_window_ns: u64,
+    symbols: Mutex<HashMap<u32, SymbolFeatures>>,
+}
+
+#[pymethods]
+impl FeatureCalculator {
+    /// `window_ms` covers the quote features, `trade_window_ms` (default the
+    /// same) trade intensity and VWAP.
+    #[new]
+    #[pyo3(signature = (window_ms=500, trade_window_ms=None))]
+    pub fn new(window_ms: u64, trade_window_ms: Option<u64>) -> PyResult<Self> {
+        let trade_window_ms = trade_window_ms.unwrap_or(window_ms);
+        if window_ms == 0 || trade_window_ms == 0 {
+            return Err(PyValueError::new_err("windows must be positive"));
+        }
+        Ok(Self {
+            window_ns: window_ms.saturating_mul(1_000_000),
+            trade_window_ns: trade_window_ms.saturating_mul(1_000_000),
+            symbols: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// Add a top-of-book update and return the features it produces.
+    /// `bid_depth`/`ask_depth` (e.g. summed over N levels) feed book
+    /// pressure instead of the top sizes.
+    #[allow(clippy::too_many_arguments)]
+    #[pyo3(signature = (ts_ns, symbol_id, bid_px, bid_sz, ask_px, ask_sz, bid_depth=None, ask_depth=None))]
+    pub fn on_quote(
+        &self,
+        ts_ns: u64,
+        symbol_id: u32,
+        bid_px: f64,
+        bid_sz: f64,
+        ask_px: f64,
+        ask_sz: f64,
+        bid_depth: Option<f64>,
+        ask_depth: Option<f64>,
+    ) -> PyResult<RustFeatureFrame> {
+        if ![bid_px, bid_sz, ask_px, ask_sz]
+            .iter()
+            .all(|v| v.is_finite() && *v >= 0.0)
+        {
+            return Err(PyValueError::new_err(format!(
+                "invalid quote: {} x {} / {} x {}",
+                bid_sz, bid_px, ask_sz, ask_px
+            )));
+        }
+        let quote = Quote {
+            ts_ns,
+            bid_px,
+            bid_sz,
+            ask_px,
+            ask_sz,
+            bid_depth: bid_depth.unwrap_or(bid_sz),
+            ask_depth: ask_depth.unwrap_or(ask_sz),
+        };
+        let mut symbols = self.symbols_map();
+        let features = symbols.entry(symbol_id).or_default();
+        features.on_quote(quote, self.window_ns, self.trade_window_ns);
+        features
+            .frame(symbol_id, self.trade_window_ns)
+            .ok_or_else(|| PyValueError::new_err("no quote"))
+    }
+
+    /// `on_quote` for a `TopOfBook`.
+    pub fn on_tob(&self, tob: &Bound<'_, PyAny>) -> PyResult<RustFeatureFrame> {
+        let tob: TopOfBookArg = tob.extract()?;
+        self.on_quote(
+            tob.ts_ns,
+            tob.symbol_id,
+            tob.bid_px,
+            tob.bid_sz,
+            tob.ask_px,
+            tob.ask_sz,
+            None,
+            None,
+        )
+    }
+
+    /// Add a trade (aggressor 0 = buyer, 1 = seller).
+    pub fn on_trade(
+        &self,
+        ts_ns: u64,
+        symbol_id: u32,
+        price: f64,
+        quantity: f64,
+        aggressor_side: u8,
+    ) -> PyResult<()> {
+        if !(price.is_finite() && price > 0.0 && quantity.is_finite() && quantity > 0.0) {
+            return Err(PyValueError::new_err(format!(
+                "invalid trade: {} @ {}",
+                quantity, price
+            )));
+        }
+        if aggressor_side > 1 {
+            return Err(PyValueError::new_err(format!(
+                "invalid side: {}",
+                aggressor_side
+            )));
+        }
+        self.symbols_map().entry(symbol_id).or_default().on_trade(
+            ts_ns,
+            price,
+            quantity,
+            aggressor_side == 0,
+            self.window_ns,
+            self.trade_window_ns,
+        );
+        Ok(())
+    }
+
+    /// Latest features of a symbol, including trades since its last quote.
+    pub fn get_frame(&self, symbol_id: u32) -> Option<RustFeatureFrame> {
+        self.symbols_map()
+            .get(&symbol_id)
+            .and_then(|features| features.frame(symbol_id, self.trade_window_ns))
+    }
+
+    pub fn symbols(&self) -> Vec<u32> {
+        let mut symbols: Vec<u32> = self.symbols_map().keys().copied().collect();
+        symbols.sort_unstable();
+        symbols
+    }
+
+    #[pyo3(signature = (symbol_id=None))]
+    pub fn reset(&self, symbol_id: Option<u32>) {
+        match symbol_id {
+            Some(symbol_id) => {
+                self.symbols_map().remove(&symbol_id);
+            }
+            None => self.symbols_map().clear(),
+        }
+    }
+}
+
+impl FeatureCalculator {
+    fn symbols_map(&self) -> MutexGuard<'_, HashMap<u32, SymbolFeatures>> {
+        self.symbols
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() < 1e-9
+    }
+
+    #[test]
+    fn moments_over_a_sliding_window() {
+        let mut moments = Moments::default();
+        for value in [2.0, 4.0, 4.0, 4.0, 5.0, 5.0, 7.0, 9.0] {
+            moments.add(value);
+        }
+        assert!(close(moments.mean(), 5.0));
+        assert!(close(moments.std(), 2.0));
+
+        moments.remove(2.0);
+        moments.remove(4.0);
+        assert_eq!(moments.count, 6);
+        assert!(close(moments.mean(), 34.0 / 6.0));
+    }
+
+    #[test]
+    fn moments_keep_precision_around_a_large_level() {
+        let mut moments = Moments::default();
+        for value in [1e9 + 0.25, 1e9 + 0.75, 1e9 + 0.25, 1e9 + 0.75] {
+            moments.add(value);
+        }
+        assert!(close(moments.mean(), 1e9 + 0.5));
+        assert!(close(moments.std(), 0.25));
+    }
+
+    #[test]
+    fn emptied_moments_reset() {
+        let mut moments = Moments::default();
+        moments.add(0.1);
+        moments.add(0.2);
+        moments.remove(0.1);
+        moments.remove(0.2);
+        assert_eq!((moments.count, moments.sum, moments.sum_sq), (0, 0.0, 0.0));
+        assert_eq!((moments.mean(), moments.std()), (0.0, 0.0));
+        moments.add(3.0);
+        assert_eq!((moments.mean(), moments.std()), (3.0, 0.0));
+    }
+
+    #[test]
+    fn extremes_follow_evictions() {
+        let mut extremes = Extremes::default();
+        assert_eq!(extremes.range(), (0.0, 0.0));
+        for (id, value) in [3.0, 1.0, 4.0, 1.0, 5.0].into_iter().enumerate() {
+            extremes.push(id as u64, value);
+        }
+        assert_eq!(extremes.range(), (1.0, 5.0));
+        // Dominated candidates were dropped on push.
+        assert_eq!(extremes.min.len(), 2);
+        assert_eq!(extremes.max.len(), 1);
+
+        extremes.evict(2);
+        assert_eq!(extremes.range(), (1.0, 5.0));
+        extremes.evict(4);
+        assert_eq!(extremes.range(), (5.0, 5.0));
+        extremes.evict(5);
+        assert_eq!(extremes.range(), (0.0, 0.0));
+    }
+}
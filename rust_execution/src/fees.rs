@@ -0,0 +1,278 @@
+//! Maker/taker fee schedules with volume tiers
+
+use std::collections::{HashMap, VecDeque};
+
+const DAY_NS: u64 = 86_400_000_000_000;
+/// Days of traded notional that count towards a venue's tier.
+pub const VOLUME_WINDOW_DAYS: u64 = 30;
+
+/// Rates in basis points from `min_volume` of traded notional over the last
+/// `VOLUME_WINDOW_DAYS` up.
+/// Negative rates are rebates.
+#[derive(Clone, Copy, Debug)]
+pub struct FeeTier {
+    pub min_volume: f64,
+    pub maker_bps: f64,
+    pub taker_bps: f64,
+}
+
+#[derive(Clone, Debug)]
+pub struct FeeSchedule {
+    /// Ascending by `min_volume`.
+    tiers: Vec<FeeTier>,
+    pub currency: String,
+    /// Charge `rate * quantity` in the base asset instead of
+    /// `rate * notional` in the quote asset.
+    pub charge_in_base: bool,
+}
+
+impl FeeSchedule {
+    pub fn new(mut tiers: Vec<FeeTier>, currency: String, charge_in_base: bool) -> Result<Self, String> {
+        if tiers.is_empty() {
+            return Err("fee schedule needs at least one tier".to_string());
+        }
+        let valid = |tier: &FeeTier| {
+            tier.min_volume.is_finite() && tier.min_volume >= 0.0 && tier.maker_bps.is_finite() && tier.taker_bps.is_finite()
+        };
+        if let Some(tier) = tiers.iter().find(|tier| !valid(tier)) {
+            return Err(format!("invalid fee tier: {:?}", tier));
+        }
+        tiers.sort_by(|a, b| a.min_volume.total_cmp(&b.min_volume));
+        Ok(Self {
+            tiers,
+            currency,
+            charge_in_base,
+        })
+    }
+
+    /// Tier reached at `volume`; the lowest tier applies below every bracket.
+    #[inline(always)]
+    pub fn tier(&self, volume: f64) -> &FeeTier {
+        let reached = self.tiers.partition_point(|tier| tier.min_volume <= volume);
+        &self.tiers[reached.saturating_sub(1)]
+    }
+
+    /// Fee for one fill, positive for a cost and negative for a rebate.
+    #[inline(always)]
+    pub fn fee(&self, volume: f64, price: f64, quantity: f64, is_maker: bool) -> f64 {
+        let tier = self.tier(volume);
+        let bps = if is_maker { tier.maker_bps } else { tier.taker_bps };
+        let base = if self.charge_in_base { quantity } else { price * quantity };
+        base * bps / 10_000.0
+    }
+}
+
+/// Fee for one fill in its own currency, which is the base asset when the
+/// schedule charges in base.
+#[derive(Clone, Debug, Default)]
+pub struct Charge {
+    pub fee: f64,
+    pub currency: String,
+    pub in_base: bool,
+}
+
+impl Charge {
+    /// The fee in the quote asset, which is what PnL is counted in.
+    #[inline(always)]
+    pub fn quote_fee(&self, price: f64) -> f64 {
+        quote_fee(self.fee, self.in_base, price)
+    }
+}
+
+#[inline(always)]
+pub fn quote_fee(fee: f64, in_base: bool, price: f64) -> f64 {
+    if in_base {
+        fee * price
+    } else {
+        fee
+    }
+}
+
+/// A venue's traded notional in daily buckets over the last
+/// `VOLUME_WINDOW_DAYS`.
+#[derive(Clone, Debug, Default)]
+pub struct VenueVolume {
+    days: VecDeque<(u64, f64)>,
+}
+
+impl VenueVolume {
+    #[inline(always)]
+    fn in_window(day: u64, now_day: u64) -> bool {
+        now_day.saturating_sub(day) < VOLUME_WINDOW_DAYS
+    }
+
+    pub fn total(&self, ts_ns: u64) -> f64 {
+        let now_day = ts_ns / DAY_NS;
+        self.days
+            .iter()
+            .filter(|(day, _)| Self::in_window(*day, now_day))
+            .fold(0.0, |total, (_, notional)| total + notional)
+    }
+
+    pub fn add(&mut self, ts_ns: u64, notional: f64) {
+        let now_day = ts_ns / DAY_NS;
+        while self.days.front().is_some_and(|(day, _)| !Self::in_window(*day, now_day)) {
+            self.days.pop_front();
+        }
+        match self.days.back_mut() {
+            Some((day, total)) if *day >= now_day => *total += notional,
+            _ => self.days.push_back((now_day, notional)),
+        }
+    }
+
+    /// Replace the history with `volume` traded at `ts_ns`.
+    pub fn seed(ts_ns: u64, volume: f64) -> Self {
+        Self {
+            days: VecDeque::from([(ts_ns / DAY_NS, volume)]),
+        }
+    }
+}
+
+/// Fee schedules by venue with per-symbol overrides, plus the traded
+/// notional per venue that selects each schedule's tier.
+#[derive(Default)]
+pub struct FeeBook {
+    pub venue_schedules: HashMap<u8, FeeSchedule>,
+    pub symbol_schedules: HashMap<u32, FeeSchedule>,
+    pub symbol_venues: HashMap<u32, u8>,
+    pub volumes: HashMap<u8, VenueVolume>,
+}
+
+impl FeeBook {
+    #[inline(always)]
+    pub fn venue_of(&self, symbol_id: u32) -> u8 {
+        self.symbol_venues.get(&symbol_id).copied().unwrap_or(0)
+    }
+
+    pub fn schedule(&self, symbol_id: u32) -> Option<&FeeSchedule> {
+        self.symbol_schedules
+            .get(&symbol_id)
+            .or_else(|| self.venue_schedules.get(&self.venue_of(symbol_id)))
+    }
+
+    /// Traded notional selecting a venue's tier at `ts_ns`.
+    pub fn volume(&self, venue_code: u8, ts_ns: u64) -> f64 {
+        self.volumes.get(&venue_code).map_or(0.0, |volume| volume.total(ts_ns))
+    }
+
+    /// Fee for one fill, counting its notional towards the venue's volume
+    /// afterwards. Symbols without a schedule trade for free.
+    #[inline(always)]
+    pub fn charge(&mut self, symbol_id: u32, price: f64, quantity: f64, is_maker: bool, ts_ns: u64) -> Charge {
+        let venue = self.venue_of(symbol_id);
+        let volume = self.volumes.entry(venue).or_default();
+        let schedule = self
+            .symbol_schedules
+            .get(&symbol_id)
+            .or_else(|| self.venue_schedules.get(&venue));
+        let charged = schedule.map_or_else(Charge::default, |schedule| Charge {
+            fee: schedule.fee(volume.total(ts_ns), price, quantity, is_maker),
+            currency: schedule.currency.clone(),
+            in_base: schedule.charge_in_base,
+        });
+        volume.add(ts_ns, price * quantity);
+        charged
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn schedule(charge_in_base: bool) -> FeeSchedule {
+        let tiers = vec![
+            FeeTier { min_volume: 1_000.0, maker_bps: -0.5, taker_bps: 2.0 },
+            FeeTier { min_volume: 0.0, maker_bps: 1.0, taker_bps: 4.0 },
+        ];
+        FeeSchedule::new(tiers, "USDT".to_string(), charge_in_base).unwrap()
+    }
+
+    #[test]
+    fn tiers_sorted_and_selected_by_volume() {
+        let schedule = schedule(false);
+        assert_eq!(schedule.tier(0.0).taker_bps, 4.0);
+        assert_eq!(schedule.tier(999.9).taker_bps, 4.0);
+        assert_eq!(schedule.tier(1_000.0).taker_bps, 2.0);
+    }
+
+    #[test]
+    fn lowest_tier_applies_below_every_bracket() {
+        let tiers = vec![FeeTier { min_volume: 500.0, maker_bps: 1.0, taker_bps: 3.0 }];
+        let schedule = FeeSchedule::new(tiers, String::new(), false).unwrap();
+        assert_eq!(schedule.tier(0.0).taker_bps, 3.0);
+    }
+
+    #[test]
+    fn fees_in_quote_or_base() {
+        assert_eq!(schedule(false).fee(0.0, 100.0, 2.0, false), 0.08);
+        assert_eq!(schedule(false).fee(5_000.0, 100.0, 2.0, true), -0.01);
+        assert_eq!(schedule(true).fee(0.0, 100.0, 2.0, false), 0.0008);
+    }
+
+    #[test]
+    fn invalid_schedules_rejected() {
+        assert!(FeeSchedule::new(Vec::new(), String::new(), false).is_err());
+        let tiers = vec![FeeTier { min_volume: f64::NAN, maker_bps: 1.0, taker_bps: 1.0 }];
+        assert!(FeeSchedule::new(tiers, String::new(), false).is_err());
+        let tiers = vec![FeeTier { min_volume: -1.0, maker_bps: 1.0, taker_bps: 1.0 }];
+        assert!(FeeSchedule::new(tiers, String::new(), false).is_err());
+    }
+
+    #[test]
+    fn charge_counts_volume_after_the_fill() {
+        let mut book = FeeBook::default();
+        book.venue_schedules.insert(1, schedule(false));
+        book.symbol_venues.insert(7, 1);
+        // The fill that crosses into the next tier still pays the old rate.
+        let charge = book.charge(7, 100.0, 10.0, false, 0);
+        assert_eq!((charge.fee, charge.currency.as_str(), charge.in_base), (0.4, "USDT", false));
+        assert_eq!(book.charge(7, 100.0, 10.0, false, 0).fee, 0.2);
+        assert_eq!(book.volume(1, 0), 2_000.0);
+        // Symbols without a schedule trade for free but still count volume.
+        let charge = book.charge(8, 100.0, 1.0, false, 0);
+        assert_eq!((charge.fee, charge.currency.as_str()), (0.0, ""));
+        assert_eq!(book.volume(0, 0), 100.0);
+    }
+
+    #[test]
+    fn base_fees_valued_at_the_fill_price() {
+        let mut book = FeeBook::default();
+        book.venue_schedules.insert(0, schedule(true));
+        let charge = book.charge(1, 100.0, 2.0, false, 0);
+        assert_eq!((charge.fee, charge.in_base), (0.0008, true));
+        assert_eq!(charge.quote_fee(100.0), 0.08);
+        assert_eq!(quote_fee(0.08, false, 100.0), 0.08);
+    }
+
+    #[test]
+    fn volume_rolls_off_after_the_window() {
+        let mut volume = VenueVolume::default();
+        volume.add(0, 100.0);
+        volume.add(DAY_NS / 2, 50.0);
+        volume.add(DAY_NS, 10.0);
+        assert_eq!(volume.total(DAY_NS), 160.0);
+        // Day 0 leaves the window once day 30 starts.
+        assert_eq!(volume.total((VOLUME_WINDOW_DAYS - 1) * DAY_NS), 160.0);
+        assert_eq!(volume.total(VOLUME_WINDOW_DAYS * DAY_NS), 10.0);
+        volume.add(VOLUME_WINDOW_DAYS * DAY_NS, 1.0);
+        assert_eq!(volume.days.len(), 2);
+        assert_eq!(volume.total((VOLUME_WINDOW_DAYS + 1) * DAY_NS), 1.0);
+    }
+
+    #[test]
+    fn seeded_volume_counts_from_its_day() {
+        let volume = VenueVolume::seed(3 * DAY_NS + 1, 5_000.0);
+        assert_eq!(volume.total(3 * DAY_NS), 5_000.0);
+        assert_eq!(volume.total((VOLUME_WINDOW_DAYS + 3) * DAY_NS), 0.0);
+    }
+
+    #[test]
+    fn symbol_override_wins() {
+        let mut book = FeeBook::default();
+        book.venue_schedules.insert(0, schedule(false));
+        let tiers = vec![FeeTier { min_volume: 0.0, maker_bps: 0.0, taker_bps: 7.5 }];
+        book.symbol_schedules.insert(2, FeeSchedule::new(tiers, "BNB".to_string(), false).unwrap());
+        assert_eq!(book.schedule(1).unwrap().currency, "USDT");
+        assert_eq!(book.schedule(2).unwrap().currency, "BNB");
+    }
+}
@@ -0,0 +1,1094 @@
+//! Append-only order journal and crash recovery
+//!
+//! The journal is a directory of segment files, each named by the sequence
+//! number of its first record; every engine session appends to a new one.
+//! A segment starts with a 16-byte header (`SGMXJRNL`, layout version) and
+//! holds records framed as `len u32 | crc32 u32 | body`, where the body is
+//! `seq u64 | ts_ns u64 | kind u8 | payload`, all little-endian.
+//!
+//! Recovery reads the segments in order and stops at the first record that
+//! is short or fails its checksum. If it is the last thing in the newest
+//! segment, its declared length reaching the end of the file, it is a write
+//! torn by a crash and is cut off; anywhere else the journal is corrupt. With a
+//! snapshot (see `snapshot`), only the records after it are read, and the
+//! segments it covers may already have been pruned.
+
+use crate::fees;
+use crate::orderbook::{self, Side};
+use crate::orders::OrderRecord;
+use crate::triggers::{ConditionalOrder, TrailingOffset, TriggerSource};
+use crate::{EngineState, OrderStatus, OrderType, TimeInForce};
+use pyo3::exceptions::PyIOError;
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+use std::collections::btree_map::Entry;
+use std::collections::BTreeMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+const MAGIC: &[u8; 8] = b"SGMXJRNL";
+const LAYOUT_VERSION: u32 = 1;
+const SEGMENT_HEADER_LEN: usize = 16;
+const FRAME_LEN: usize = 8;
+const SEGMENT_SUFFIX: &str = ".journal";
+
+const KIND_ACCEPTED: u8 = 1;
+const KIND_UPDATE: u8 = 2;
+const KIND_FILL: u8 = 3;
+const KIND_REJECT: u8 = 4;
+
+const CRC_TABLE: [u32; 256] = {
+    let mut table = [0u32; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 != 0 {
+                0xEDB8_8320 ^ (crc >> 1)
+            } else {
+                crc >> 1
+            };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+};
+
+/// CRC-32 (IEEE), as in zlib.
+pub fn crc32(bytes: &[u8]) -> u32 {
+    !bytes.iter().fold(!0u32, |crc, &byte| {
+        CRC_TABLE[((crc ^ byte as u32) & 0xFF) as usize] ^ (crc >> 8)
+    })
+}
+
+/// An order the engine accepted, with everything needed to put it back.
+#[derive(Clone, Debug)]
+pub struct Accepted {
+    pub order_id: u64,
+    pub client_id: Option<String>,
+    pub symbol_id: u32,
+    pub side: u8,
+    pub order_type: u8,
+    pub tif: u8,
+    pub trigger_source: u8,
+    /// Limit price, 0 for market and plain stop orders.
+    pub price: f64,
+    pub quantity: f64,
+    pub trigger_price: Option<f64>,
+    pub trail_amount: Option<f64>,
+    pub trail_percent: Option<f64>,
+}
+
+/// An order's lifecycle state after an engine operation changed it.
+#[derive(Clone, Debug)]
+pub struct OrderUpdate {
+    pub order_id: u64,
+    pub status: u8,
+    pub price: f64,
+    pub quantity: f64,
+    pub filled: f64,
+}
+
+/// One position-changing fill; maker fills of the engine's own resting
+/// orders are journaled as their own record.
+#[derive(Clone, Debug)]
+pub struct JournalFill {
+    pub order_id: u64,
+    pub trade_id: u64,
+    pub symbol_id: u32,
+    pub side: u8,
+    pub is_maker: bool,
+    pub price: f64,
+    pub qty: f64,
+    pub fee: f64,
+    pub fee_currency: String,
+    /// `fee` is in the base asset rather than the quote.
+    pub fee_in_base: bool,
+}
+
+impl JournalFill {
+    /// The fee in the quote asset, as PnL is counted.
+    pub fn quote_fee(&self) -> f64 {
+        fees::quote_fee(self.fee, self.fee_in_base, self.price)
+    }
+}
+
+/// An order refused before it got an id.
+#[derive(Clone, Debug)]
+pub struct JournalReject {
+    pub client_id: Option<String>,
+    pub symbol_id: u32,
+    pub side: u8,
+    pub reason_code: u16,
+    pub price: f64,
+    pub quantity: f64,
+    pub reason_msg: String,
+}
+
+#[derive(Clone, Debug)]
+pub enum JournalEvent {
+    Accepted(Accepted),
+    Update(OrderUpdate),
+    Fill(JournalFill),
+    Reject(JournalReject),
+}
+
+#[derive(Clone, Debug)]
+pub struct Record {
+    pub seq: u64,
+    pub ts_ns: u64,
+    pub event: JournalEvent,
+}
+
+/// Little-endian encoder shared with the snapshot format.
+pub(crate) struct Writer<'a>(pub &'a mut Vec<u8>);
+
+impl Writer<'_> {
+    pub fn u8(&mut self, v: u8) {
+        self.0.push(v);
+    }
+    pub fn u16(&mut self, v: u16) {
+        self.0.extend_from_slice(&v.to_le_bytes());
+    }
+    pub fn u32(&mut self, v: u32) {
+        self.0.extend_from_slice(&v.to_le_bytes());
+    }
+    pub fn u64(&mut self, v: u64) {
+        self.0.extend_from_slice(&v.to_le_bytes());
+    }
+    pub fn f64(&mut self, v: f64) {
+        self.0.extend_from_slice(&v.to_le_bytes());
+    }
+    /// `None` as NaN.
+    pub fn opt_f64(&mut self, v: Option<f64>) {
+        self.f64(v.unwrap_or(f64::NAN));
+    }
+    /// Strings are cut at 64 KiB; `None` and `""` share the empty encoding.
+    pub fn str(&mut self, v: &str) {
+        let mut end = v.len().min(u16::MAX as usize);
+        while !v.is_char_boundary(end) {
+            end -= 1;
+        }
+        self.u16(end as u16);
+        self.0.extend_from_slice(&v.as_bytes()[..end]);
+    }
+}
+
+pub(crate) struct Reader<'a>(pub &'a [u8]);
+
+impl Reader<'_> {
+    pub fn take(&mut self, n: usize) -> Option<&[u8]> {
+        if self.0.len() < n {
+            return None;
+        }
+        let (head, rest) = self.0.split_at(n);
+        self.0 = rest;
+        Some(head)
+    }
+    pub fn u8(&mut self) -> Option<u8> {
+        self.take(1).map(|b| b[0])
+    }
+    pub fn u16(&mut self) -> Option<u16> {
+        self.take(2).map(|b| u16::from_le_bytes([b[0], b[1]]))
+    }
+    pub fn u32(&mut self) -> Option<u32> {
+        self.take(4)
+            .map(|b| u32::from_le_bytes(b.try_into().unwrap_or_default()))
+    }
+    pub fn u64(&mut self) -> Option<u64> {
+        self.take(8)
+            .map(|b| u64::from_le_bytes(b.try_into().unwrap_or_default()))
+    }
+    pub fn f64(&mut self) -> Option<f64> {
+        self.u64().map(f64::from_bits)
+    }
+    pub fn opt_f64(&mut self) -> Option<Option<f64>> {
+        self.f64().map(|v| (!v.is_nan()).then_some(v))
+    }
+    pub fn str(&mut self) -> Option<String> {
+        let len = self.u16()? as usize;
+        String::from_utf8(self.take(len)?.to_vec()).ok()
+    }
+    pub fn opt_str(&mut self) -> Option<Option<String>> {
+        self.str().map(|s| (!s.is_empty()).then_some(s))
+    }
+}
+
+impl JournalEvent {
+    fn encode(&self, out: &mut Vec<u8>) {
+        let mut w = Writer(out);
+        match self {
+            JournalEvent::Accepted(a) => {
+                w.u8(KIND_ACCEPTED);
+                w.u64(a.order_id);
+                w.u32(a.symbol_id);
+                w.u8(a.side);
+                w.u8(a.order_type);
+                w.u8(a.tif);
+                w.u8(a.trigger_source);
+                w.f64(a.price);
+                w.f64(a.quantity);
+                w.opt_f64(a.trigger_price);
+                w.opt_f64(a.trail_amount);
+                w.opt_f64(a.trail_percent);
+                w.str(a.client_id.as_deref().unwrap_or(""));
+            }
+            JournalEvent::Update(u) => {
+                w.u8(KIND_UPDATE);
+                w.u64(u.order_id);
+                w.u8(u.status);
+                w.f64(u.price);
+                w.f64(u.quantity);
+                w.f64(u.filled);
+            }
+            JournalEvent::Fill(f) => {
+                w.u8(KIND_FILL);
+                w.u64(f.order_id);
+                w.u64(f.trade_id);
+                w.u32(f.symbol_id);
+                w.u8(f.side);
+                w.u8(f.is_maker as u8);
+                w.f64(f.price);
+                w.f64(f.qty);
+                w.f64(f.fee);
+                w.str(&f.fee_currency);
+                w.u8(f.fee_in_base as u8);
+            }
+            JournalEvent::Reject(r) => {
+                w.u8(KIND_REJECT);
+                w.u32(r.symbol_id);
+                w.u8(r.side);
+                w.u16(r.reason_code);
+                w.f64(r.price);
+                w.f64(r.quantity);
+                w.str(r.client_id.as_deref().unwrap_or(""));
+                w.str(&r.reason_msg);
+            }
+        }
+    }
+
+    fn decode(r: &mut Reader<'_>) -> Option<JournalEvent> {
+        Some(match r.u8()? {
+            KIND_ACCEPTED => JournalEvent::Accepted(Accepted {
+                order_id: r.u64()?,
+                symbol_id: r.u32()?,
+                side: r.u8()?,
+                order_type: r.u8()?,
+                tif: r.u8()?,
+                trigger_source: r.u8()?,
+                price: r.f64()?,
+                quantity: r.f64()?,
+                trigger_price: r.opt_f64()?,
+                trail_amount: r.opt_f64()?,
+                trail_percent: r.opt_f64()?,
+                client_id: r.opt_str()?,
+            }),
+            KIND_UPDATE => JournalEvent::Update(OrderUpdate {
+                order_id: r.u64()?,
+                status: r.u8()?,
+                price: r.f64()?,
+                quantity: r.f64()?,
+                filled: r.f64()?,
+            }),
+            KIND_FILL => JournalEvent::Fill(JournalFill {
+                order_id: r.u64()?,
+                trade_id: r.u64()?,
+                symbol_id: r.u32()?,
+                side: r.u8()?,
+                is_maker: r.u8()? != 0,
+                price: r.f64()?,
+                qty: r.f64()?,
+                fee: r.f64()?,
+                fee_currency: r.str()?,
+                // Appended later; older fills end before it and were quote fees.
+                fee_in_base: r.u8().is_some_and(|flag| flag != 0),
+            }),
+            KIND_REJECT => JournalEvent::Reject(JournalReject {
+                symbol_id: r.u32()?,
+                side: r.u8()?,
+                reason_code: r.u16()?,
+                price: r.f64()?,
+                quantity: r.f64()?,
+                client_id: r.opt_str()?,
+                reason_msg: r.str()?,
+            }),
+            _ => return None,
+        })
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            JournalEvent::Accepted(_) => "accepted",
+            JournalEvent::Update(_) => "update",
+            JournalEvent::Fill(_) => "fill",
+            JournalEvent::Reject(_) => "reject",
+        }
+    }
+}
+
+fn segment_name(first_seq: u64) -> String {
+    format!("{:020}{}", first_seq, SEGMENT_SUFFIX)
+}
+
+/// Segment files in the directory, oldest first.
+pub fn segments(dir: &Path) -> io::Result<Vec<(u64, PathBuf)>> {
+    let mut segments = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        let first_seq = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .and_then(|name| name.strip_suffix(SEGMENT_SUFFIX))
+            .and_then(|stem| stem.parse::<u64>().ok());
+        if let Some(first_seq) = first_seq {
+            segments.push((first_seq, path));
+        }
+    }
+    segments.sort();
+    Ok(segments)
+}
+
+/// Everything a journal directory holds.
+#[derive(Debug, Default)]
+pub struct Recovered {
+    pub records: Vec<Record>,
+    pub segments: usize,
+    /// Bytes of torn tail found in the newest segment.
+    pub torn_bytes: u64,
+    /// Sequence number the next record gets.
+    pub next_seq: u64,
+}
+
+/// Read the records in `dir` from sequence number `from_seq` on, oldest
+/// first; `None` reads whatever the oldest segment still holds. Segments
+/// wholly before `from_seq` are not opened. With `truncate_torn`, a torn
+/// tail in the newest segment is cut off the file so appends resume cleanly.
+pub fn read_dir(dir: &Path, from_seq: Option<u64>, truncate_torn: bool) -> Result<Recovered, String> {
+    let mut recovered = Recovered {
+        next_seq: from_seq.unwrap_or(0),
+        ..Recovered::default()
+    };
+    if !dir.exists() {
+        return Ok(recovered);
+    }
+    let segments = segments(dir).map_err(|e| format!("{}: {}", dir.display(), e))?;
+    recovered.segments = segments.len();
+    let start = from_seq
+        .and_then(|from_seq| segments.iter().rposition(|(first_seq, _)| *first_seq <= from_seq))
+        .unwrap_or(0);
+    for (index, (first_seq, path)) in segments.iter().enumerate().skip(start) {
+        let newest = index + 1 == segments.len();
+        let data = fs::read(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        if index == start {
+            if from_seq.is_some_and(|from_seq| *first_seq > from_seq) {
+                return Err(format!(
+                    "{}: records {} to {} are missing",
+                    dir.display(),
+                    recovered.next_seq,
+                    first_seq
+                ));
+            }
+            recovered.next_seq = *first_seq;
+        } else if *first_seq != recovered.next_seq {
+            return Err(format!(
+                "{}: expected a segment starting at record {}",
+                path.display(),
+                recovered.next_seq
+            ));
+        }
+
+        let header_torn = data.len() < SEGMENT_HEADER_LEN;
+        let header_ok = !header_torn
+            && &data[..8] == MAGIC
+            && u32::from_le_bytes([data[8], data[9], data[10], data[11]]) == LAYOUT_VERSION;
+        let mut good = SEGMENT_HEADER_LEN;
+        if header_ok {
+            while let Some(record) = read_record(&data[good..], recovered.next_seq) {
+                good += FRAME_LEN + record.1;
+                if record.0.seq >= from_seq.unwrap_or(0) {
+                    recovered.records.push(record.0);
+                }
+                recovered.next_seq += 1;
+            }
+        } else {
+            good = 0;
+        }
+
+        if good < data.len() {
+            let torn = if header_ok { torn_frame(&data[good..]) } else { header_torn };
+            if !(newest && torn) {
+                return Err(format!(
+                    "{}: corrupt record at byte {}",
+                    path.display(),
+                    good
+                ));
+            }
+            recovered.torn_bytes = (data.len() - good) as u64;
+            if truncate_torn {
+                if good == 0 {
+                    fs::remove_file(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+                } else {
+                    let file = OpenOptions::new()
+                        .write(true)
+                        .open(path)
+                        .map_err(|e| format!("{}: {}", path.display(), e))?;
+                    file.set_len(good as u64)
+                        .and_then(|_| file.sync_all())
+                        .map_err(|e| format!("{}: {}", path.display(), e))?;
+                }
+            }
+        }
+    }
+    Ok(recovered)
+}
+
+/// Whether a record that failed to read is the tail of an interrupted write:
+/// its frame header, or the body it declares, runs to the end of `data`.
+fn torn_frame(data: &[u8]) -> bool {
+    let Some(len) = Reader(data).u32() else {
+        return true;
+    };
+    FRAME_LEN + len as usize >= data.len()
+}
+
+/// One framed record and its body length, if it is whole, checksums and
+/// carries the expected sequence number.
+fn read_record(data: &[u8], expected_seq: u64) -> Option<(Record, usize)> {
+    let mut frame = Reader(data);
+    let len = frame.u32()? as usize;
+    let crc = frame.u32()?;
+    let body = frame.take(len)?;
+    if crc32(body) != crc {
+        return None;
+    }
+    let mut body = Reader(body);
+    let seq = body.u64()?;
+    let ts_ns = body.u64()?;
+    let event = JournalEvent::decode(&mut body)?;
+    (seq == expected_seq && body.0.is_empty()).then_some((Record { seq, ts_ns, event }, len))
+}
+
+/// Appends records to the current session's segment. The first write error
+/// is kept and every later append is skipped, so the journal never has holes.
+pub struct Journal {
+    dir: PathBuf,
+    segment: PathBuf,
+    file: File,
+    sync: bool,
+    next_seq: u64,
+    /// Order id the engine hands out next, carried into snapshots.
+    next_order_id: u64,
+    written: u64,
+    segment_records: u64,
+    error: Option<String>,
+    buf: Vec<u8>,
+    since_snapshot: u64,
+    snapshots: u64,
+    last_snapshot_seq: Option<u64>,
+    snapshot_error: Option<String>,
+}
+
+fn create_segment(dir: &Path, first_seq: u64) -> io::Result<(PathBuf, File)> {
+    let segment = dir.join(segment_name(first_seq));
+    // A segment already named `first_seq` can only hold a header.
+    let mut file = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(&segment)?;
+    let mut header = Vec::with_capacity(SEGMENT_HEADER_LEN);
+    header.extend_from_slice(MAGIC);
+    header.extend_from_slice(&LAYOUT_VERSION.to_le_bytes());
+    header.extend_from_slice(&[0; 4]);
+    file.write_all(&header)?;
+    file.sync_all()?;
+    sync_dir(dir);
+    Ok((segment, file))
+}
+
+/// Make a rename or new file in `dir` durable; best effort.
+pub fn sync_dir(dir: &Path) {
+    if let Ok(dir) = File::open(dir) {
+        let _ = dir.sync_all();
+    }
+}
+
+impl Journal {
+    /// Start a new segment at `next_seq`.
+    pub fn open(dir: &Path, next_seq: u64, next_order_id: u64, sync: bool) -> io::Result<Self> {
+        fs::create_dir_all(dir)?;
+        let (segment, file) = create_segment(dir, next_seq)?;
+        Ok(Self {
+            dir: dir.to_path_buf(),
+            segment,
+            file,
+            sync,
+            next_seq,
+            next_order_id,
+            written: 0,
+            segment_records: 0,
+            error: None,
+            buf: Vec::with_capacity(256),
+            since_snapshot: 0,
+            snapshots: 0,
+            last_snapshot_seq: None,
+            snapshot_error: None,
+        })
+    }
+
+    /// Flush the current segment to disk and, if it holds any records,
+    /// continue in a new one starting at `next_seq`, so everything before
+    /// a snapshot lives in segments that can be pruned whole.
+    pub fn roll(&mut self) -> Result<(), String> {
+        if let Some(error) = &self.error {
+            return Err(error.clone());
+        }
+        let result = self.file.sync_data().and_then(|_| {
+            if self.segment_records > 0 {
+                let (segment, file) = create_segment(&self.dir, self.next_seq)?;
+                self.segment = segment;
+                self.file = file;
+                self.segment_records = 0;
+            }
+            Ok(())
+        });
+        result.map_err(|e| {
+            let error = format!("{}: {}", self.segment.display(), e);
+            self.error = Some(error.clone());
+            error
+        })
+    }
+
+    pub fn append(&mut self, ts_ns: u64, event: &JournalEvent) {
+        if self.error.is_some() {
+            return;
+        }
+        if let JournalEvent::Accepted(accepted) = event {
+            self.next_order_id = self.next_order_id.max(accepted.order_id + 1);
+        }
+        self.buf.clear();
+        self.buf.extend_from_slice(&[0; FRAME_LEN]);
+        self.buf.extend_from_slice(&self.next_seq.to_le_bytes());
+        self.buf.extend_from_slice(&ts_ns.to_le_bytes());
+        event.encode(&mut self.buf);
+        let len = (self.buf.len() - FRAME_LEN) as u32;
+        let crc = crc32(&self.buf[FRAME_LEN..]);
+        self.buf[..4].copy_from_slice(&len.to_le_bytes());
+        self.buf[4..8].copy_from_slice(&crc.to_le_bytes());
+
+        let result = self.file.write_all(&self.buf).and_then(|_| {
+            if self.sync {
+                self.file.sync_data()
+            } else {
+                Ok(())
+            }
+        });
+        match result {
+            Ok(()) => {
+                self.next_seq += 1;
+                self.written += 1;
+                self.segment_records += 1;
+                self.since_snapshot += 1;
+            }
+            Err(e) => self.error = Some(format!("{}: {}", self.segment.display(), e)),
+        }
+    }
+
+    pub fn error(&self) -> Option<&str> {
+        self.error.as_deref()
+    }
+
+    pub fn next_seq(&self) -> u64 {
+        self.next_seq
+    }
+
+    pub fn next_order_id(&self) -> u64 {
+        self.next_order_id
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Records appended since the last snapshot, or since opening.
+    pub fn since_snapshot(&self) -> u64 {
+        self.since_snapshot
+    }
+
+    /// Record a snapshot attempt. A failed one is retried after another
+    /// interval rather than on every settle.
+    pub fn on_snapshot(&mut self, result: &Result<u64, String>) {
+        self.since_snapshot = 0;
+        match result {
+            Ok(seq) => {
+                self.snapshots += 1;
+                self.last_snapshot_seq = Some(*seq);
+                self.snapshot_error = None;
+            }
+            Err(e) => self.snapshot_error = Some(e.clone()),
+        }
+    }
+
+    pub fn stats_dict<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        let dict = PyDict::new(py);
+        dict.set_item("journal_dir", self.dir.display().to_string())?;
+        dict.set_item("segment", self.segment.display().to_string())?;
+        dict.set_item("sync", self.sync)?;
+        dict.set_item("next_seq", self.next_seq)?;
+        dict.set_item("records_written", self.written)?;
+        dict.set_item("error", self.error.as_deref())?;
+        dict.set_item("records_since_snapshot", self.since_snapshot)?;
+        dict.set_item("snapshots_written", self.snapshots)?;
+        dict.set_item("last_snapshot_seq", self.last_snapshot_seq)?;
+        dict.set_item("snapshot_error", self.snapshot_error.as_deref())?;
+        Ok(dict)
+    }
+}
+
+/// What a replay rebuilt.
+#[derive(Default)]
+pub struct ReplaySummary {
+    pub records: u64,
+    /// Orders accepted in the replayed records.
+    pub orders: u64,
+    /// Orders open once the replay is done, snapshot ones included.
+    pub open_orders: u64,
+    pub fills: u64,
+    pub rejects: u64,
+    /// Open orders that could not be put back (market/IOC/FOK caught in
+    /// flight); they are marked cancelled.
+    pub abandoned: Vec<u64>,
+    pub max_order_id: u64,
+}
+
+impl EngineState {
+    /// Rebuild order records, resting and conditional orders, positions,
+    /// balances and the trade id counter from journal records, on top of
+    /// whatever a snapshot restored. Resting orders first seen here go back
+    /// at the back of their price level; venue liquidity is not journaled.
+    pub(crate) fn replay(&mut self, records: &[Record]) -> ReplaySummary {
+        let mut summary = ReplaySummary {
+            records: records.len() as u64,
+            ..ReplaySummary::default()
+        };
+        // Orders the records touch; snapshot orders carry no `Accepted`.
+        let mut orders: BTreeMap<u64, (Option<Accepted>, OrderRecord)> = BTreeMap::new();
+
+        for record in records {
+            match &record.event {
+                JournalEvent::Accepted(accepted) => {
+                    let conditional = OrderType::from_u8(accepted.order_type)
+                        .and_then(OrderType::trigger_kind)
+                        .is_some();
+                    let order = OrderRecord {
+                        order_id: accepted.order_id,
+                        client_id: accepted.client_id.clone(),
+                        symbol_id: accepted.symbol_id,
+                        side: accepted.side,
+                        order_type: accepted.order_type,
+                        tif: accepted.tif,
+                        price: accepted.price,
+                        quantity: accepted.quantity,
+                        filled: 0.0,
+                        status: if conditional {
+                            OrderStatus::Pending as u8
+                        } else {
+                            OrderStatus::Submitted as u8
+                        },
+                        created_ns: record.ts_ns,
+                        updated_ns: record.ts_ns,
+                    };
+                    summary.orders += 1;
+                    summary.max_order_id = summary.max_order_id.max(accepted.order_id);
+                    orders.insert(accepted.order_id, (Some(accepted.clone()), order));
+                }
+                JournalEvent::Update(update) => {
+                    let entry = match orders.entry(update.order_id) {
+                        Entry::Occupied(entry) => Some(entry.into_mut()),
+                        Entry::Vacant(entry) => self
+                            .orders
+                            .get(update.order_id)
+                            .cloned()
+                            .map(|order| entry.insert((None, order))),
+                    };
+                    if let Some((_, order)) = entry {
+                        order.status = update.status;
+                        order.price = update.price;
+                        order.quantity = update.quantity;
+                        order.filled = update.filled;
+                        order.updated_ns = record.ts_ns;
+                    }
+                }
+                JournalEvent::Fill(fill) => {
+                    if let Some(side) = Side::from_u8(fill.side) {
+                        let position = self.ledger.net_qty(fill.symbol_id);
+                        let realized = self
+                            .ledger
+                            .on_fill(fill.symbol_id, side, fill.price, fill.qty, fill.quote_fee());
+                        self.account
+                            .on_fill(fill.symbol_id, side, fill.price, fill.qty, position, realized);
+                        self.account
+                            .charge_fee(fill.symbol_id, &fill.fee_currency, fill.fee);
+                        self.kill_switch.on_fill(realized, fill.quote_fee(), record.ts_ns);
+                    }
+                    self.next_trade_id = self.next_trade_id.max(fill.trade_id);
+                    summary.fills += 1;
+                }
+                JournalEvent::Reject(_) => summary.rejects += 1,
+            }
+        }
+
+        for (_, (accepted, order)) in orders {
+            let restored = order.is_open() && self.restore_open_order(accepted.as_ref(), &order);
+            match (restored, &accepted) {
+                (false, _) => self.unplace(&order),
+                (true, Some(_)) => self.hold_restored(&order),
+                (true, None) => self.account.sync(
+                    order.order_id,
+                    order.quantity - order.filled,
+                    order.price,
+                ),
+            }
+            match self.orders.get_mut(order.order_id) {
+                Some(existing) => *existing = order,
+                None => self.orders.insert(order),
+            }
+        }
+
+        // Whatever is open but has nowhere to live was in flight.
+        let ts_ns = self.timestamp();
+        for order_id in self.orders.open_ids() {
+            if !self.is_placed(order_id) {
+                self.orders.set_status(order_id, OrderStatus::Cancelled as u8, ts_ns);
+                self.account.release(order_id);
+                summary.abandoned.push(order_id);
+            }
+        }
+        summary.abandoned.sort_unstable();
+        summary.open_orders = self.orders.open_ids().len() as u64;
+        // Only what changes after recovery is journaled again.
+        self.orders.clear_changed();
+        summary
+    }
+
+    fn is_placed(&self, order_id: u64) -> bool {
+        self.triggers.contains(order_id) || self.books.values().any(|book| book.contains(order_id))
+    }
+
+    /// Take a closed order off the trigger book and the book, and drop its
+    /// balance hold.
+    fn unplace(&mut self, order: &OrderRecord) {
+        self.triggers.remove(order.order_id);
+        self.account.release(order.order_id);
+        if let Some(book) = self.books.get_mut(&order.symbol_id) {
+            book.cancel(order.order_id);
+        }
+    }
+
+    /// Hold the balance a replayed open order needs, if the account can
+    /// still cover it; snapshot orders keep the hold they were saved with.
+    fn hold_restored(&mut self, order: &OrderRecord) {
+        let Some(side) = Side::from_u8(order.side) else {
+            return;
+        };
+        let leaves = order.quantity - order.filled;
+        let price = (order.price > 0.0).then_some(order.price);
+        let position = self.ledger.net_qty(order.symbol_id);
+        if let Ok(Some(reservation)) = self
+            .account
+            .requirement(order.symbol_id, side, price, leaves, position)
+        {
+            self.account.hold(order.order_id, leaves, reservation);
+        }
+    }
+
+    /// Put an open order back where it lives: the trigger book if it never
+    /// fired, else the book if it rests. Orders a snapshot already placed
+    /// are updated in place, keeping their queue position while the price
+    /// holds. Returns `false` if the order belongs in neither.
+    fn restore_open_order(&mut self, accepted: Option<&Accepted>, order: &OrderRecord) -> bool {
+        let (Some(side), Some(order_type)) = (
+            Side::from_u8(order.side),
+            OrderType::from_u8(order.order_type),
+        ) else {
+            return false;
+        };
+        let limit = order_type.has_limit_price().then_some(order.price);
+
+        if let Some(kind) = order_type.trigger_kind() {
+            if order.status == OrderStatus::Pending as u8 {
+                if let Some(pending) = self.triggers.get_mut(order.order_id) {
+                    pending.quantity = order.quantity;
+                    pending.limit_price = limit;
+                    return true;
+                }
+                let Some(accepted) = accepted else {
+                    return false;
+                };
+                let trailing = match (accepted.trail_amount, accepted.trail_percent) {
+                    (Some(amount), _) => Some(TrailingOffset::Absolute(amount)),
+                    (None, Some(pct)) => Some(TrailingOffset::Percent(pct)),
+                    _ => None,
+                };
+                // Nothing has printed yet, so the trigger book cannot fire it.
+                return self
+                    .triggers
+                    .insert(ConditionalOrder {
+                        order_id: order.order_id,
+                        symbol_id: order.symbol_id,
+                        side,
+                        kind,
+                        source: TriggerSource::from_u8(accepted.trigger_source)
+                            .unwrap_or(TriggerSource::LastTrade),
+                        trigger_price: accepted.trigger_price.unwrap_or(f64::NAN),
+                        limit_price: limit,
+                        quantity: order.quantity,
+                        tif: order.tif,
+                        trailing,
+                    })
+                    .is_none();
+            }
+            // It fired since the snapshot.
+            self.triggers.remove(order.order_id);
+        }
+
+        let leaves = order.quantity - order.filled;
+        let tif = TimeInForce::from_u8(order.tif);
+        let book = self.books.entry(order.symbol_id).or_default();
+        match (limit, tif) {
+            (Some(price), Some(tif @ (TimeInForce::Gtc | TimeInForce::Gtx)))
+                if leaves > orderbook::QTY_EPSILON =>
+            {
+                match book.get(order.order_id).map(|resting| (resting.price, resting.quantity)) {
+                    Some((resting_price, resting_qty))
+                        if resting_price == price && leaves <= resting_qty + orderbook::QTY_EPSILON =>
+                    {
+                        book.reduce(order.order_id, leaves.min(resting_qty));
+                    }
+                    _ => {
+                        book.cancel(order.order_id);
+                        book.add_order(order.order_id, side, price, leaves, tif == TimeInForce::Gtx);
+                    }
+                }
+                book.set_filled(order.order_id, order.filled);
+                true
+            }
+            _ => {
+                book.cancel(order.order_id);
+                false
+            }
+        }
+    }
+}
+
+fn record_dict<'py>(py: Python<'py>, record: &Record) -> PyResult<Bound<'py, PyDict>> {
+    let dict = PyDict::new(py);
+    dict.set_item("seq", record.seq)?;
+    dict.set_item("ts_ns", record.ts_ns)?;
+    dict.set_item("kind", record.event.name())?;
+    match &record.event {
+        JournalEvent::Accepted(a) => {
+            dict.set_item("order_id", a.order_id)?;
+            dict.set_item("client_id", &a.client_id)?;
+            dict.set_item("symbol_id", a.symbol_id)?;
+            dict.set_item("side", a.side)?;
+            dict.set_item("order_type", a.order_type)?;
+            dict.set_item("tif", a.tif)?;
+            dict.set_item("price", a.price)?;
+            dict.set_item("quantity", a.quantity)?;
+            dict.set_item("trigger_price", a.trigger_price)?;
+            dict.set_item("trigger_source", a.trigger_source)?;
+            dict.set_item("trail_amount", a.trail_amount)?;
+            dict.set_item("trail_percent", a.trail_percent)?;
+        }
+        JournalEvent::Update(u) => {
+            dict.set_item("order_id", u.order_id)?;
+            dict.set_item("status", u.status)?;
+            dict.set_item("price", u.price)?;
+            dict.set_item("quantity", u.quantity)?;
+            dict.set_item("filled_quantity", u.filled)?;
+        }
+        JournalEvent::Fill(f) => {
+            dict.set_item("order_id", f.order_id)?;
+            dict.set_item("trade_id", f.trade_id)?;
+            dict.set_item("symbol_id", f.symbol_id)?;
+            dict.set_item("side", f.side)?;
+            dict.set_item("is_maker", f.is_maker)?;
+            dict.set_item("price", f.price)?;
+            dict.set_item("qty", f.qty)?;
+            dict.set_item("fee", f.fee)?;
+            dict.set_item("fee_currency", &f.fee_currency)?;
+            dict.set_item("fee_in_base", f.fee_in_base)?;
+        }
+        JournalEvent::Reject(r) => {
+            dict.set_item("client_id", &r.client_id)?;
+            dict.set_item("symbol_id", r.symbol_id)?;
+            dict.set_item("side", r.side)?;
+            dict.set_item("reason_code", r.reason_code)?;
+            dict.set_item("reason_msg", &r.reason_msg)?;
+            dict.set_item("price", r.price)?;
+            dict.set_item("quantity", r.quantity)?;
+        }
+    }
+    Ok(dict)
+}
+
+/// Every intact record in a journal directory as dicts, oldest first. A
+/// torn tail is skipped, not truncated.
+#[pyfunction]
+pub fn read_journal<'py>(
+    py: Python<'py>,
+    journal_dir: PathBuf,
+) -> PyResult<Vec<Bound<'py, PyDict>>> {
+    let recovered = read_dir(&journal_dir, None, false).map_err(PyIOError::new_err)?;
+    recovered
+        .records
+        .iter()
+        .map(|record| record_dict(py, record))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("sigmax-journal-{}-{}", std::process::id(), name));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    fn update(order_id: u64) -> JournalEvent {
+        JournalEvent::Update(OrderUpdate {
+            order_id,
+            status: 2,
+            price: 100.0,
+            quantity: 1.0,
+            filled: 0.0,
+        })
+    }
+
+    fn write(dir: &Path, next_seq: u64, count: u64) -> PathBuf {
+        let mut journal = Journal::open(dir, next_seq, 0, false).unwrap();
+        for order_id in 0..count {
+            journal.append(1_000 + order_id, &update(order_id));
+        }
+        assert_eq!(journal.error(), None);
+        dir.join(segment_name(next_seq))
+    }
+
+    #[test]
+    fn crc32_matches_zlib() {
+        assert_eq!(crc32(b""), 0);
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+    }
+
+    #[test]
+    fn records_are_framed_and_checksummed() {
+        let dir = temp_dir("framing");
+        let data = fs::read(write(&dir, 0, 1)).unwrap();
+        assert_eq!(&data[..8], MAGIC);
+        assert_eq!(u32::from_le_bytes(data[8..12].try_into().unwrap()), LAYOUT_VERSION);
+
+        let frame = &data[SEGMENT_HEADER_LEN..];
+        let len = u32::from_le_bytes(frame[..4].try_into().unwrap()) as usize;
+        let crc = u32::from_le_bytes(frame[4..8].try_into().unwrap());
+        let body = &frame[FRAME_LEN..];
+        assert_eq!(body.len(), len);
+        assert_eq!(crc32(body), crc);
+        assert_eq!(u64::from_le_bytes(body[..8].try_into().unwrap()), 0);
+        assert_eq!(u64::from_le_bytes(body[8..16].try_into().unwrap()), 1_000);
+        assert_eq!(body[16], KIND_UPDATE);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn sessions_continue_the_sequence() {
+        let dir = temp_dir("sessions");
+        write(&dir, 0, 2);
+        write(&dir, 2, 3);
+        let recovered = read_dir(&dir, None, false).unwrap();
+        assert_eq!((recovered.segments, recovered.next_seq, recovered.torn_bytes), (2, 5, 0));
+        let seqs: Vec<u64> = recovered.records.iter().map(|record| record.seq).collect();
+        assert_eq!(seqs, [0, 1, 2, 3, 4]);
+        assert!(matches!(recovered.records[4].event, JournalEvent::Update(OrderUpdate { order_id: 2, .. })));
+
+        // A segment that does not start where the last one ended.
+        write(&dir, 9, 1);
+        assert!(read_dir(&dir, None, false).unwrap_err().contains("expected a segment starting at record 5"));
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn reading_from_a_sequence_skips_older_segments() {
+        let dir = temp_dir("from_seq");
+        let oldest = write(&dir, 0, 2);
+        write(&dir, 2, 3);
+        let recovered = read_dir(&dir, Some(3), false).unwrap();
+        assert_eq!((recovered.records[0].seq, recovered.next_seq), (3, 5));
+
+        fs::remove_file(oldest).unwrap();
+        assert!(read_dir(&dir, Some(0), false).unwrap_err().contains("records 0 to 2 are missing"));
+        assert_eq!(read_dir(&dir, None, false).unwrap().records.len(), 3);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn torn_tail_of_the_newest_segment_is_cut() {
+        let dir = temp_dir("torn");
+        let segment = write(&dir, 0, 2);
+        let good = fs::metadata(&segment).unwrap().len();
+        let mut file = OpenOptions::new().append(true).open(&segment).unwrap();
+        file.write_all(&[40, 0, 0, 0, 1, 2]).unwrap();
+
+        let recovered = read_dir(&dir, None, false).unwrap();
+        assert_eq!((recovered.records.len(), recovered.torn_bytes), (2, 6));
+        assert_eq!(fs::metadata(&segment).unwrap().len(), good + 6);
+        read_dir(&dir, None, true).unwrap();
+        assert_eq!(fs::metadata(&segment).unwrap().len(), good);
+        assert_eq!(read_dir(&dir, None, false).unwrap().torn_bytes, 0);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn bad_record_before_the_end_of_the_newest_segment_is_corrupt() {
+        let dir = temp_dir("corrupt_newest");
+        let segment = write(&dir, 0, 3);
+        let mut data = fs::read(&segment).unwrap();
+        let second = SEGMENT_HEADER_LEN + (data.len() - SEGMENT_HEADER_LEN) / 3;
+        data[second + FRAME_LEN] ^= 0xFF;
+        let len = data.len();
+        fs::write(&segment, data).unwrap();
+        assert!(read_dir(&dir, None, true).unwrap_err().contains(&format!("corrupt record at byte {}", second)));
+        assert_eq!(fs::metadata(&segment).unwrap().len(), len as u64);
+
+        // The same damage to the last record is a torn write.
+        let segment = write(&dir, 3, 2);
+        let mut data = fs::read(&segment).unwrap();
+        let last = data.len() - 1;
+        data[last] ^= 0xFF;
+        fs::write(&segment, data).unwrap();
+        fs::remove_file(dir.join(segment_name(0))).unwrap();
+        let recovered = read_dir(&dir, None, false).unwrap();
+        assert_eq!(recovered.records.len(), 1);
+        assert!(recovered.torn_bytes > 0);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn bad_checksum_in_an_older_segment_is_corrupt() {
+        let dir = temp_dir("corrupt");
+        let segment = write(&dir, 0, 2);
+        write(&dir, 2, 1);
+        let mut data = fs::read(&segment).unwrap();
+        let last = data.len() - 1;
+        data[last] ^= 0xFF;
+        fs::write(&segment, data).unwrap();
+        assert!(read_dir(&dir, None, false).unwrap_err().contains("corrupt record"));
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
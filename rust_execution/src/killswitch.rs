@@ -0,0 +1,230 @@
+//! Engine-wide kill switch with automatic trips
+//!
+//! The trading mode is an atomic shared with the engine so it can be read
+//! and flipped without taking the state lock.
+
+use crate::journal::{Reader, Writer};
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::Arc;
+
+const DAY_NS: u64 = 86_400_000_000_000;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(u8)]
+pub enum TradingMode {
+    Active = 0,
+    /// No new orders or amends; open orders are cancelled.
+    Halted = 1,
+    /// No new orders or amends; open orders may still be cancelled or fill.
+    CancelOnly = 2,
+    /// Only orders that shrink the current position are accepted.
+    ReduceOnly = 3,
+}
+
+impl TradingMode {
+    #[inline(always)]
+    pub fn from_u8(value: u8) -> Option<TradingMode> {
+        match value {
+            0 => Some(TradingMode::Active),
+            1 => Some(TradingMode::Halted),
+            2 => Some(TradingMode::CancelOnly),
+            3 => Some(TradingMode::ReduceOnly),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            TradingMode::Active => "active",
+            TradingMode::Halted => "halted",
+            TradingMode::CancelOnly => "cancel_only",
+            TradingMode::ReduceOnly => "reduce_only",
+        }
+    }
+}
+
+/// Automatic trip conditions; `None` disables one.
+#[derive(Clone, Debug)]
+pub struct TripLimits {
+    /// Realized loss (after fees) within one UTC day.
+    pub daily_loss_limit: Option<f64>,
+    pub max_consecutive_losses: Option<u32>,
+    /// Errors tolerated within `error_window_ns`.
+    pub max_errors: Option<u32>,
+    pub error_window_ns: u64,
+    pub trip_mode: TradingMode,
+}
+
+impl Default for TripLimits {
+    fn default() -> Self {
+        Self {
+            daily_loss_limit: None,
+            max_consecutive_losses: None,
+            max_errors: None,
+            error_window_ns: 60_000_000_000,
+            trip_mode: TradingMode::Halted,
+        }
+    }
+}
+
+pub struct KillSwitch {
+    mode: Arc<AtomicU8>,
+    pub limits: TripLimits,
+    pub reason: Option<String>,
+    pub tripped_ns: u64,
+    day: u64,
+    pub daily_pnl: f64,
+    pub consecutive_losses: u32,
+    errors: VecDeque<u64>,
+    /// Halt entered while the books were borrowed; cancel on the next settle.
+    halt_pending: bool,
+}
+
+impl Default for KillSwitch {
+    fn default() -> Self {
+        Self {
+            mode: Arc::new(AtomicU8::new(TradingMode::Active as u8)),
+            limits: TripLimits::default(),
+            reason: None,
+            tripped_ns: 0,
+            day: 0,
+            daily_pnl: 0.0,
+            consecutive_losses: 0,
+            errors: VecDeque::new(),
+            halt_pending: false,
+        }
+    }
+}
+
+impl KillSwitch {
+    /// Handle to the mode for lock-free reads.
+    pub fn mode_handle(&self) -> Arc<AtomicU8> {
+        Arc::clone(&self.mode)
+    }
+
+    #[inline(always)]
+    pub fn mode(&self) -> TradingMode {
+        TradingMode::from_u8(self.mode.load(Ordering::Acquire)).unwrap_or(TradingMode::Halted)
+    }
+
+    /// Switch mode by hand. Going back to active clears the trip and the
+    /// loss and error streaks.
+    pub fn set_mode(&mut self, mode: TradingMode, reason: Option<String>, ts_ns: u64) {
+        self.mode.store(mode as u8, Ordering::Release);
+        if mode == TradingMode::Active {
+            self.reason = None;
+            self.tripped_ns = 0;
+            self.consecutive_losses = 0;
+            self.errors.clear();
+        } else {
+            self.reason = reason;
+            self.tripped_ns = ts_ns;
+            self.halt_pending |= mode == TradingMode::Halted;
+        }
+    }
+
+    fn trip(&mut self, reason: String, ts_ns: u64) {
+        if self.mode() == TradingMode::Active {
+            self.set_mode(self.limits.trip_mode, Some(reason), ts_ns);
+        }
+    }
+
+    /// Whether a halt is waiting for open orders to be cancelled.
+    #[inline(always)]
+    pub fn take_halt(&mut self) -> bool {
+        std::mem::take(&mut self.halt_pending)
+    }
+
+    /// Account for one of the engine's own fills, given the PnL it realized
+    /// if it closed part of a position, and trip on the daily loss or losing
+    /// streak limits.
+    pub fn on_fill(&mut self, realized: Option<f64>, fee: f64, ts_ns: u64) {
+        let day = ts_ns / DAY_NS;
+        if day != self.day {
+            self.day = day;
+            self.daily_pnl = 0.0;
+        }
+        self.daily_pnl += realized.unwrap_or(0.0) - fee;
+
+        if let Some(realized) = realized {
+            if realized - fee < 0.0 {
+                self.consecutive_losses += 1;
+            } else {
+                self.consecutive_losses = 0;
+            }
+        }
+
+        if let Some(limit) = self.limits.daily_loss_limit {
+            if self.daily_pnl <= -limit {
+                self.trip(
+                    format!("daily loss {:.2} reached limit {}", -self.daily_pnl, limit),
+                    ts_ns,
+                );
+            }
+        }
+        if let Some(max) = self.limits.max_consecutive_losses {
+            if self.consecutive_losses >= max {
+                self.trip(format!("{} consecutive losing fills", self.consecutive_losses), ts_ns);
+            }
+        }
+    }
+
+    /// Count an error and trip if too many fall inside the window.
+    pub fn on_error(&mut self, ts_ns: u64) {
+        self.errors.push_back(ts_ns);
+        while self
+            .errors
+            .front()
+            .is_some_and(|ts| ts_ns.saturating_sub(*ts) >= self.limits.error_window_ns)
+        {
+            self.errors.pop_front();
+        }
+        if let Some(max) = self.limits.max_errors {
+            if self.errors.len() > max as usize {
+                self.trip(
+                    format!(
+                        "{} errors within {}ms",
+                        self.errors.len(),
+                        self.limits.error_window_ns / 1_000_000
+                    ),
+                    ts_ns,
+                );
+            }
+        }
+    }
+
+    pub fn recent_errors(&self) -> usize {
+        self.errors.len()
+    }
+
+    /// Mode, trip and the loss and error counters, for snapshots. Trip
+    /// limits are configuration.
+    pub(crate) fn save(&self, w: &mut Writer<'_>) {
+        w.u8(self.mode() as u8);
+        w.str(self.reason.as_deref().unwrap_or(""));
+        w.u64(self.tripped_ns);
+        w.u64(self.day);
+        w.f64(self.daily_pnl);
+        w.u32(self.consecutive_losses);
+        w.u32(self.errors.len() as u32);
+        for ts_ns in &self.errors {
+            w.u64(*ts_ns);
+        }
+    }
+
+    pub(crate) fn restore(&mut self, r: &mut Reader<'_>) -> Option<()> {
+        let mode = TradingMode::from_u8(r.u8()?)?;
+        self.reason = r.opt_str()?;
+        self.tripped_ns = r.u64()?;
+        self.day = r.u64()?;
+        self.daily_pnl = r.f64()?;
+        self.consecutive_losses = r.u32()?;
+        self.errors.clear();
+        for _ in 0..r.u32()? {
+            self.errors.push_back(r.u64()?);
+        }
+        self.mode.store(mode as u8, Ordering::Release);
+        Some(())
+    }
+}
@@ -0,0 +1,449 @@
+//! Venue L2 books rebuilt from market-data snapshots and deltas
+//!
+//! Native counterpart of `pkg.schemas.market_data.L2Book`. Books only hold
+//! aggregated venue quantity per price; the engine's own orders live in
+//! `orderbook`.
+
+use crate::orderbook::{Price, Side, QTY_EPSILON};
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Mutex, MutexGuard};
+
+/// Change to one price level of the venue's book; `quantity=0` deletes it.
+/// `seq=0` marks a feed without sequence numbers.
+#[derive(Clone, Debug)]
+#[pyclass]
+pub struct BookDelta {
+    #[pyo3(get)]
+    pub ts_ns: u64,
+    #[pyo3(get)]
+    pub symbol_id: u32,
+    /// 0 = bid, 1 = ask
+    #[pyo3(get)]
+    pub side: u8,
+    #[pyo3(get)]
+    pub price: f64,
+    #[pyo3(get)]
+    pub quantity: f64,
+    #[pyo3(get)]
+    pub seq: u64,
+}
+
+#[pymethods]
+impl BookDelta {
+    #[new]
+    #[pyo3(signature = (ts_ns, symbol_id, side, price, quantity, seq=0))]
+    pub fn new(ts_ns: u64, symbol_id: u32, side: u8, price: f64, quantity: f64, seq: u64) -> Self {
+        Self {
+            ts_ns,
+            symbol_id,
+            side,
+            price,
+            quantity,
+            seq,
+        }
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "BookDelta(ts_ns={}, symbol_id={}, side={}, price={}, quantity={}, seq={})",
+            self.ts_ns, self.symbol_id, self.side, self.price, self.quantity, self.seq
+        )
+    }
+}
+
+/// Full book as of `seq`: `(price, quantity)` levels, best first.
+#[derive(Clone, Debug)]
+#[pyclass]
+pub struct BookSnapshot {
+    #[pyo3(get)]
+    pub ts_ns: u64,
+    #[pyo3(get)]
+    pub symbol_id: u32,
+    #[pyo3(get)]
+    pub seq: u64,
+    #[pyo3(get)]
+    pub bids: Vec<(f64, f64)>,
+    #[pyo3(get)]
+    pub asks: Vec<(f64, f64)>,
+}
+
+#[pymethods]
+impl BookSnapshot {
+    #[new]
+    pub fn new(ts_ns: u64, symbol_id: u32, seq: u64, bids: Vec<(f64, f64)>, asks: Vec<(f64, f64)>) -> Self {
+        Self {
+            ts_ns,
+            symbol_id,
+            seq,
+            bids,
+            asks,
+        }
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "BookSnapshot(ts_ns={}, symbol_id={}, seq={}, bids={}, asks={})",
+            self.ts_ns,
+            self.symbol_id,
+            self.seq,
+            self.bids.len(),
+            self.asks.len()
+        )
+    }
+}
+
+/// Deltas between `expected_seq` and `received_seq` never arrived; the book
+/// stays out of sync until the next snapshot.
+#[derive(Clone, Debug)]
+#[pyclass]
+pub struct SequenceGap {
+    #[pyo3(get)]
+    pub ts_ns: u64,
+    #[pyo3(get)]
+    pub symbol_id: u32,
+    #[pyo3(get)]
+    pub expected_seq: u64,
+    #[pyo3(get)]
+    pub received_seq: u64,
+}
+
+#[pymethods]
+impl SequenceGap {
+    fn __repr__(&self) -> String {
+        format!(
+            "SequenceGap(symbol_id={}, expected_seq={}, received_seq={})",
+            self.symbol_id, self.expected_seq, self.received_seq
+        )
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DeltaOutcome {
+    Applied,
+    /// Already covered by the book's sequence number; ignored.
+    Stale,
+    /// Skipped sequence numbers; the delta is dropped and the book goes out
+    /// of sync.
+    Gap { expected: u64, received: u64 },
+    /// Dropped while waiting for a snapshot after a gap.
+    OutOfSync,
+}
+
+#[derive(Debug, Default)]
+pub struct L2Book {
+    bids: BTreeMap<Price, f64>,
+    asks: BTreeMap<Price, f64>,
+    /// Last applied sequence number, 0 before the first sequenced update.
+    pub seq: u64,
+    pub ts_ns: u64,
+    out_of_sync: bool,
+    pub gaps: u64,
+}
+
+impl L2Book {
+    fn levels_mut(&mut self, side: Side) -> &mut BTreeMap<Price, f64> {
+        match side {
+            Side::Buy => &mut self.bids,
+            Side::Sell => &mut self.asks,
+        }
+    }
+
+    #[inline(always)]
+    fn set_level(&mut self, side: Side, price: f64, quantity: f64) {
+        let levels = self.levels_mut(side);
+        if quantity <= QTY_EPSILON {
+            levels.remove(&Price(price));
+        } else {
+            levels.insert(Price(price), quantity);
+        }
+    }
+
+    /// Replace the whole book and resynchronise at `seq`. A sequenced
+    /// snapshot older than the book is ignored; returns whether it applied.
+    pub fn apply_snapshot(&mut self, seq: u64, ts_ns: u64, bids: &[(f64, f64)], asks: &[(f64, f64)]) -> bool {
+        if seq != 0 && seq < self.seq {
+            return false;
+        }
+        self.bids.clear();
+        self.asks.clear();
+        for &(price, quantity) in bids {
+            self.set_level(Side::Buy, price, quantity);
+        }
+        for &(price, quantity) in asks {
+            self.set_level(Side::Sell, price, quantity);
+        }
+        self.seq = seq;
+        self.ts_ns = ts_ns;
+        self.out_of_sync = false;
+        true
+    }
+
+    /// Apply one level change. Unsequenced deltas (`seq == 0`) always apply;
+    /// sequenced ones must follow the book's sequence number.
+    #[inline(always)]
+    pub fn apply_delta(&mut self, seq: u64, ts_ns: u64, side: Side, price: f64, quantity: f64) -> DeltaOutcome {
+        if seq != 0 {
+            if self.out_of_sync {
+                return DeltaOutcome::OutOfSync;
+            }
+            if self.seq != 0 {
+                if seq <= self.seq {
+                    return DeltaOutcome::Stale;
+                }
+                if seq != self.seq + 1 {
+                    self.out_of_sync = true;
+                    self.gaps += 1;
+                    return DeltaOutcome::Gap {
+                        expected: self.seq + 1,
+                        received: seq,
+                    };
+                }
+            }
+            self.seq = seq;
+        }
+        self.set_level(side, price, quantity);
+        self.ts_ns = ts_ns;
+        DeltaOutcome::Applied
+    }
+
+    pub fn is_synced(&self) -> bool {
+        !self.out_of_sync
+    }
+
+    #[inline(always)]
+    pub fn best_bid(&self) -> Option<(f64, f64)> {
+        self.bids.iter().next_back().map(|(price, quantity)| (price.0, *quantity))
+    }
+
+    #[inline(always)]
+    pub fn best_ask(&self) -> Option<(f64, f64)> {
+        self.asks.iter().next().map(|(price, quantity)| (price.0, *quantity))
+    }
+
+    /// Up to `max_levels` `(price, quantity)` levels, best first.
+    pub fn depth(&self, side: Side, max_levels: usize) -> Vec<(f64, f64)> {
+        let levels = |iter: &mut dyn Iterator<Item = (&Price, &f64)>| {
+            iter.take(max_levels)
+                .map(|(price, quantity)| (price.0, *quantity))
+                .collect()
+        };
+        match side {
+            Side::Buy => levels(&mut self.bids.iter().rev()),
+            Side::Sell => levels(&mut self.asks.iter()),
+        }
+    }
+
+    #[inline(always)]
+    pub fn top(&self) -> Option<((f64, f64), (f64, f64))> {
+        Some((self.best_bid()?, self.best_ask()?))
+    }
+
+    pub fn mid_price(&self) -> Option<f64> {
+        self.top().map(|((bid, _), (ask, _))| (bid + ask) / 2.0)
+    }
+
+    pub fn spread(&self) -> Option<f64> {
+        self.top().map(|((bid, _), (ask, _))| ask - bid)
+    }
+
+    /// Top-of-book prices weighted by the opposite side's size, leaning the
+    /// mid towards the side more likely to trade through.
+    pub fn micro_price(&self) -> Option<f64> {
+        self.top().map(|((bid, bid_size), (ask, ask_size))| {
+            let total = bid_size + ask_size;
+            if total <= QTY_EPSILON {
+                (bid + ask) / 2.0
+            } else {
+                (bid * ask_size + ask * bid_size) / total
+            }
+        })
+    }
+
+    /// `(bid - ask) / (bid + ask)` volume over the best `depth` levels, from
+    /// -1 (all asks) to 1 (all bids).
+    pub fn imbalance(&self, depth: usize) -> f64 {
+        let bid: f64 = self.bids.values().rev().take(depth).sum();
+        let ask: f64 = self.asks.values().take(depth).sum();
+        let total = bid + ask;
+        if total <= QTY_EPSILON {
+            0.0
+        } else {
+            (bid - ask) / total
+        }
+    }
+}
+
+/// `TopOfBook` fields, or `None` unless both sides have a level.
+pub(crate) fn top_of_book_dict(py: Python<'_>, symbol_id: u32, book: &L2Book) -> PyResult<Option<PyObject>> {
+    let Some(((bid_px, bid_sz), (ask_px, ask_sz))) = book.top() else {
+        return Ok(None);
+    };
+    let dict = PyDict::new(py);
+    dict.set_item("ts_ns", book.ts_ns)?;
+    dict.set_item("symbol_id", symbol_id)?;
+    dict.set_item("bid_px", bid_px)?;
+    dict.set_item("bid_sz", bid_sz)?;
+    dict.set_item("ask_px", ask_px)?;
+    dict.set_item("ask_sz", ask_sz)?;
+    Ok(Some(dict.into()))
+}
+
+pub(crate) fn depth_dict<'py>(
+    py: Python<'py>,
+    symbol_id: u32,
+    book: Option<&L2Book>,
+    levels: usize,
+) -> PyResult<Bound<'py, PyDict>> {
+    let dict = PyDict::new(py);
+    dict.set_item("symbol_id", symbol_id)?;
+    dict.set_item("seq", book.map_or(0, |book| book.seq))?;
+    dict.set_item("ts_ns", book.map_or(0, |book| book.ts_ns))?;
+    dict.set_item("bids", book.map_or_else(Vec::new, |book| book.depth(Side::Buy, levels)))?;
+    dict.set_item("asks", book.map_or_else(Vec::new, |book| book.depth(Side::Sell, levels)))?;
+    Ok(dict)
+}
+
+#[inline(always)]
+pub(crate) fn parse_side(side: u8) -> PyResult<Side> {
+    Side::from_u8(side).ok_or_else(|| PyValueError::new_err(format!("invalid side: {}", side)))
+}
+
+pub(crate) fn check_level(price: f64, quantity: f64) -> PyResult<()> {
+    if !(price.is_finite() && price > 0.0 && quantity.is_finite() && quantity >= 0.0) {
+        return Err(PyValueError::new_err(format!("invalid level: {} @ {}", quantity, price)));
+    }
+    Ok(())
+}
+
+/// L2 books for any number of symbols, fed from snapshots and deltas.
+#[pyclass]
+#[derive(Default)]
+pub struct BookBuilder {
+    books: Mutex<HashMap<u32, L2Book>>,
+}
+
+#[pymethods]
+impl BookBuilder {
+    #[new]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Replace the symbol's book, returning `False` for a snapshot older
+    /// than the book, which is ignored.
+    pub fn apply_snapshot(&self, snapshot: &BookSnapshot) -> PyResult<bool> {
+        for &(price, quantity) in snapshot.bids.iter().chain(&snapshot.asks) {
+            check_level(price, quantity)?;
+        }
+        Ok(self.books().entry(snapshot.symbol_id).or_default().apply_snapshot(
+            snapshot.seq,
+            snapshot.ts_ns,
+            &snapshot.bids,
+            &snapshot.asks,
+        ))
+    }
+
+    /// Apply a delta, returning the gap it revealed, if any. Stale deltas
+    /// and those arriving while the book awaits a snapshot are dropped.
+    pub fn apply_delta(&self, delta: &BookDelta) -> PyResult<Option<SequenceGap>> {
+        let side = parse_side(delta.side)?;
+        check_level(delta.price, delta.quantity)?;
+        let outcome = self.books().entry(delta.symbol_id).or_default().apply_delta(
+            delta.seq,
+            delta.ts_ns,
+            side,
+            delta.price,
+            delta.quantity,
+        );
+        Ok(match outcome {
+            DeltaOutcome::Gap { expected, received } => Some(SequenceGap {
+                ts_ns: delta.ts_ns,
+                symbol_id: delta.symbol_id,
+                expected_seq: expected,
+                received_seq: received,
+            }),
+            DeltaOutcome::Applied | DeltaOutcome::Stale | DeltaOutcome::OutOfSync => None,
+        })
+    }
+
+    /// Apply deltas in order, returning the gaps they revealed.
+    pub fn apply_deltas(&self, deltas: Vec<PyRef<'_, BookDelta>>) -> PyResult<Vec<SequenceGap>> {
+        let mut gaps = Vec::new();
+        for delta in deltas {
+            gaps.extend(self.apply_delta(&delta)?);
+        }
+        Ok(gaps)
+    }
+
+    /// `TopOfBook` fields, or `None` unless both sides have a level.
+    pub fn top_of_book(&self, symbol_id: u32) -> PyResult<Option<PyObject>> {
+        let books = self.books();
+        let Some(book) = books.get(&symbol_id) else {
+            return Ok(None);
+        };
+        Python::with_gil(|py| top_of_book_dict(py, symbol_id, book))
+    }
+
+    /// Best `levels` `(price, quantity)` levels per side.
+    #[pyo3(signature = (symbol_id, levels=10))]
+    pub fn get_depth(&self, symbol_id: u32, levels: usize) -> PyResult<PyObject> {
+        let books = self.books();
+        Python::with_gil(|py| Ok(depth_dict(py, symbol_id, books.get(&symbol_id), levels)?.into()))
+    }
+
+    pub fn mid_price(&self, symbol_id: u32) -> Option<f64> {
+        self.books().get(&symbol_id).and_then(L2Book::mid_price)
+    }
+
+    pub fn micro_price(&self, symbol_id: u32) -> Option<f64> {
+        self.books().get(&symbol_id).and_then(L2Book::micro_price)
+    }
+
+    pub fn spread(&self, symbol_id: u32) -> Option<f64> {
+        self.books().get(&symbol_id).and_then(L2Book::spread)
+    }
+
+    #[pyo3(signature = (symbol_id, depth=5))]
+    pub fn imbalance(&self, symbol_id: u32, depth: usize) -> f64 {
+        self.books().get(&symbol_id).map_or(0.0, |book| book.imbalance(depth))
+    }
+
+    pub fn sequence(&self, symbol_id: u32) -> u64 {
+        self.books().get(&symbol_id).map_or(0, |book| book.seq)
+    }
+
+    /// False after a gap until the next snapshot.
+    pub fn is_synced(&self, symbol_id: u32) -> bool {
+        self.books().get(&symbol_id).is_none_or(L2Book::is_synced)
+    }
+
+    pub fn gap_count(&self, symbol_id: u32) -> u64 {
+        self.books().get(&symbol_id).map_or(0, |book| book.gaps)
+    }
+
+    pub fn symbols(&self) -> Vec<u32> {
+        let mut symbols: Vec<u32> = self.books().keys().copied().collect();
+        symbols.sort_unstable();
+        symbols
+    }
+
+    /// Drop one symbol's book, or every book.
+    #[pyo3(signature = (symbol_id=None))]
+    pub fn clear(&self, symbol_id: Option<u32>) {
+        match symbol_id {
+            Some(symbol_id) => {
+                self.books().remove(&symbol_id);
+            }
+            None => self.books().clear(),
+        }
+    }
+}
+
+impl BookBuilder {
+    pub(crate) fn books(&self) -> MutexGuard<'_, HashMap<u32, L2Book>> {
+        self.books.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
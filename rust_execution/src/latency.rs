@@ -0,0 +1,199 @@
+//! Simulated order-entry, ack and market-data latency
+//!
+//! Latencies are sampled in nanoseconds from a seeded generator so a
+//! simulation replays identically for the same seed and inputs.
+
+/// SplitMix64; small, fast and good enough for latency jitter.
+pub struct LatencyRng(u64);
+
+impl LatencyRng {
+    pub fn new(seed: u64) -> Self {
+        Self(seed)
+    }
+
+    #[inline(always)]
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform in `[0, 1)`.
+    #[inline(always)]
+    fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// Standard normal via Box-Muller.
+    #[inline(always)]
+    fn next_normal(&mut self) -> f64 {
+        let u1 = 1.0 - self.next_f64();
+        let u2 = self.next_f64();
+        (-2.0 * u1.ln()).sqrt() * (std::f64::consts::TAU * u2).cos()
+    }
+}
+
+impl Default for LatencyRng {
+    fn default() -> Self {
+        Self::new(0x5EED)
+    }
+}
+
+#[derive(Clone, Debug)]
+pub enum LatencyModel {
+    Constant(f64),
+    /// Truncated at zero.
+    Normal { mean: f64, std: f64 },
+    /// `exp(N(mu, sigma))`, parameters of the log of the latency in ns.
+    LogNormal { mu: f64, sigma: f64 },
+    /// Bin picked by weight, then uniform within the bin.
+    /// `edges` has one more entry than `cumulative`.
+    Histogram { edges: Vec<f64>, cumulative: Vec<f64> },
+    /// Latency recorded at points in time, linearly interpolated and held
+    /// flat outside the recorded range.
+    Interpolated { timestamps: Vec<u64>, latencies: Vec<f64> },
+}
+
+impl LatencyModel {
+    /// Histogram from bin edges and per-bin weights (e.g. counts).
+    pub fn histogram(edges: Vec<f64>, weights: &[f64]) -> Result<Self, String> {
+        if edges.len() != weights.len() + 1 || weights.is_empty() {
+            return Err(format!(
+                "histogram needs one more edge than weights, got {} edges and {} weights",
+                edges.len(),
+                weights.len()
+            ));
+        }
+        if edges.windows(2).any(|w| !(w[0] >= 0.0 && w[1] >= w[0])) {
+            return Err("histogram edges must be non-negative and ascending".to_string());
+        }
+        if weights.iter().any(|w| !(w.is_finite() && *w >= 0.0)) {
+            return Err("histogram weights must be non-negative".to_string());
+        }
+        let cumulative: Vec<f64> = weights
+            .iter()
+            .scan(0.0, |total, w| {
+                *total += w;
+                Some(*total)
+            })
+            .collect();
+        if cumulative.last().is_some_and(|total| *total <= 0.0) {
+            return Err("histogram weights sum to zero".to_string());
+        }
+        Ok(LatencyModel::Histogram { edges, cumulative })
+    }
+
+    /// Interpolation table from `(timestamp_ns, latency_ns)` samples.
+    pub fn interpolated(mut samples: Vec<(u64, f64)>) -> Result<Self, String> {
+        if samples.is_empty() {
+            return Err("interpolated latency needs at least one sample".to_string());
+        }
+        if samples.iter().any(|(_, latency)| !(latency.is_finite() && *latency >= 0.0)) {
+            return Err("recorded latencies must be non-negative".to_string());
+        }
+        samples.sort_by_key(|(ts, _)| *ts);
+        let (timestamps, latencies) = samples.into_iter().unzip();
+        Ok(LatencyModel::Interpolated { timestamps, latencies })
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            LatencyModel::Constant(_) => "constant",
+            LatencyModel::Normal { .. } => "normal",
+            LatencyModel::LogNormal { .. } => "lognormal",
+            LatencyModel::Histogram { .. } => "histogram",
+            LatencyModel::Interpolated { .. } => "interpolated",
+        }
+    }
+
+    /// Latency in ns for an event at simulated time `ts_ns`.
+    pub fn sample(&self, ts_ns: u64, rng: &mut LatencyRng) -> u64 {
+        let latency = match self {
+            LatencyModel::Constant(value) => *value,
+            LatencyModel::Normal { mean, std } => mean + std * rng.next_normal(),
+            LatencyModel::LogNormal { mu, sigma } => (mu + sigma * rng.next_normal()).exp(),
+            LatencyModel::Histogram { edges, cumulative } => {
+                let total = cumulative[cumulative.len() - 1];
+                let target = rng.next_f64() * total;
+                let bin = cumulative.partition_point(|c| *c <= target).min(cumulative.len() - 1);
+                edges[bin] + (edges[bin + 1] - edges[bin]) * rng.next_f64()
+            }
+            LatencyModel::Interpolated { timestamps, latencies } => {
+                let i = timestamps.partition_point(|t| *t <= ts_ns);
+                if i == 0 {
+                    latencies[0]
+                } else if i == timestamps.len() {
+                    latencies[i - 1]
+                } else {
+                    let (t0, t1) = (timestamps[i - 1] as f64, timestamps[i] as f64);
+                    let w = (ts_ns as f64 - t0) / (t1 - t0);
+                    latencies[i - 1] + (latencies[i] - latencies[i - 1]) * w
+                }
+            }
+        };
+        if latency.is_finite() {
+            latency.max(0.0).round() as u64
+        } else {
+            0
+        }
+    }
+}
+
+/// Which leg of the round trip a model applies to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LatencyKind {
+    /// Strategy to matcher.
+    OrderEntry,
+    /// Matcher back to the strategy.
+    Ack,
+    /// Venue to strategy; the strategy acts on a book this old.
+    MarketData,
+}
+
+impl LatencyKind {
+    pub fn from_name(name: &str) -> Option<LatencyKind> {
+        match name {
+            "order_entry" => Some(LatencyKind::OrderEntry),
+            "ack" => Some(LatencyKind::Ack),
+            "market_data" => Some(LatencyKind::MarketData),
+            _ => None,
+        }
+    }
+}
+
+/// Latency models for each leg. With none configured orders are matched
+/// the moment they are submitted.
+#[derive(Default)]
+pub struct LatencyProfile {
+    pub order_entry: Option<LatencyModel>,
+    pub ack: Option<LatencyModel>,
+    pub market_data: Option<LatencyModel>,
+    pub rng: LatencyRng,
+}
+
+impl LatencyProfile {
+    #[inline(always)]
+    pub fn is_enabled(&self) -> bool {
+        self.order_entry.is_some() || self.ack.is_some() || self.market_data.is_some()
+    }
+
+    pub fn model_mut(&mut self, kind: LatencyKind) -> &mut Option<LatencyModel> {
+        match kind {
+            LatencyKind::OrderEntry => &mut self.order_entry,
+            LatencyKind::Ack => &mut self.ack,
+            LatencyKind::MarketData => &mut self.market_data,
+        }
+    }
+
+    #[inline(always)]
+    pub fn sample(&mut self, kind: LatencyKind, ts_ns: u64) -> u64 {
+        let model = match kind {
+            LatencyKind::OrderEntry => &self.order_entry,
+            LatencyKind::Ack => &self.ack,
+            LatencyKind::MarketData => &self.market_data,
+        };
+        model.as_ref().map_or(0, |model| model.sample(ts_ns, &mut self.rng))
+    }
+}
@@ -0,0 +1,303 @@
+//! Positions, cost basis and PnL built up from fills
+
+use crate::journal::{Reader, Writer};
+use crate::orderbook::{Side, QTY_EPSILON};
+use std::collections::{HashMap, VecDeque};
+
+/// Which open lots a closing fill is matched against.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum CostMethod {
+    #[default]
+    Fifo,
+    Lifo,
+    /// A single lot at the running average entry price.
+    AverageCost,
+}
+
+impl CostMethod {
+    pub fn from_name(name: &str) -> Option<CostMethod> {
+        match name {
+            "fifo" => Some(CostMethod::Fifo),
+            "lifo" => Some(CostMethod::Lifo),
+            "average" | "average_cost" => Some(CostMethod::AverageCost),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            CostMethod::Fifo => "fifo",
+            CostMethod::Lifo => "lifo",
+            CostMethod::AverageCost => "average",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Lot {
+    /// Unsigned; the direction is the position's.
+    quantity: f64,
+    price: f64,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Position {
+    lots: VecDeque<Lot>,
+    /// Positive long, negative short.
+    pub net_qty: f64,
+    /// Gross of fees.
+    pub realized_pnl: f64,
+    pub fees: f64,
+    pub traded_qty: f64,
+    pub fill_count: u64,
+    pub last_fill_price: f64,
+}
+
+impl Position {
+    /// Quantity-weighted entry price of the open lots, 0 when flat.
+    pub fn avg_entry_price(&self) -> f64 {
+        let (quantity, cost) = self
+            .lots
+            .iter()
+            .fold((0.0, 0.0), |(q, c), lot| (q + lot.quantity, c + lot.quantity * lot.price));
+        if quantity > QTY_EPSILON {
+            cost / quantity
+        } else {
+            0.0
+        }
+    }
+
+    #[inline(always)]
+    pub fn unrealized_pnl(&self, mark_price: f64) -> f64 {
+        self.net_qty * (mark_price - self.avg_entry_price())
+    }
+
+    /// Apply a fill, returning the PnL it realized if it closed any lots.
+    #[inline(always)]
+    fn on_fill(&mut self, method: CostMethod, side: Side, price: f64, quantity: f64, fee: f64) -> Option<f64> {
+        let signed = match side {
+            Side::Buy => quantity,
+            Side::Sell => -quantity,
+        };
+        self.fees += fee;
+        self.traded_qty += quantity;
+        self.fill_count += 1;
+        self.last_fill_price = price;
+
+        let mut remaining = quantity;
+        let mut realized = None;
+        if self.net_qty * signed < 0.0 {
+            let direction = self.net_qty.signum();
+            let mut pnl = 0.0;
+            while remaining > QTY_EPSILON {
+                let lot = match method {
+                    CostMethod::Lifo => self.lots.back_mut(),
+                    CostMethod::Fifo | CostMethod::AverageCost => self.lots.front_mut(),
+                };
+                let Some(lot) = lot else { break };
+                let closed = remaining.min(lot.quantity);
+                pnl += closed * (price - lot.price) * direction;
+                lot.quantity -= closed;
+                remaining -= closed;
+                if lot.quantity <= QTY_EPSILON {
+                    match method {
+                        CostMethod::Lifo => self.lots.pop_back(),
+                        CostMethod::Fifo | CostMethod::AverageCost => self.lots.pop_front(),
+                    };
+                }
+            }
+            self.realized_pnl += pnl;
+            realized = Some(pnl);
+        }
+
+        // Whatever did not close anything opens (or, after a flip, reopens)
+        // the position at this price.
+        if remaining > QTY_EPSILON {
+            match (method, self.lots.front_mut()) {
+                (CostMethod::AverageCost, Some(lot)) => {
+                    let total = lot.quantity + remaining;
+                    lot.price = (lot.price * lot.quantity + price * remaining) / total;
+                    lot.quantity = total;
+                }
+                _ => self.lots.push_back(Lot {
+                    quantity: remaining,
+                    price,
+                }),
+            }
+        }
+
+        self.net_qty += signed;
+        if self.net_qty.abs() <= QTY_EPSILON {
+            self.net_qty = 0.0;
+            self.lots.clear();
+        }
+        realized
+    }
+
+    /// Collapse the open lots into one at their average price.
+    fn merge_lots(&mut self) {
+        if self.lots.len() > 1 {
+            let quantity = self.lots.iter().map(|lot| lot.quantity).sum();
+            let price = self.avg_entry_price();
+            self.lots.clear();
+            self.lots.push_back(Lot { quantity, price });
+        }
+    }
+}
+
+/// Point-in-time view of a position valued at a mark price.
+pub struct PositionSnapshot {
+    pub symbol_id: u32,
+    pub net_qty: f64,
+    pub avg_entry_price: f64,
+    pub mark_price: Option<f64>,
+    pub realized_pnl: f64,
+    /// 0 without a mark price.
+    pub unrealized_pnl: f64,
+    pub fees: f64,
+    pub traded_qty: f64,
+    pub fill_count: u64,
+}
+
+impl PositionSnapshot {
+    /// Realized plus unrealized, after fees.
+    pub fn net_pnl(&self) -> f64 {
+        self.realized_pnl + self.unrealized_pnl - self.fees
+    }
+
+    pub fn notional(&self) -> f64 {
+        self.net_qty * self.mark_price.unwrap_or(self.avg_entry_price)
+    }
+}
+
+#[derive(Default)]
+pub struct Ledger {
+    method: CostMethod,
+    positions: HashMap<u32, Position>,
+    marks: HashMap<u32, f64>,
+}
+
+impl Ledger {
+    pub fn new(method: CostMethod) -> Self {
+        Self {
+            method,
+            ..Self::default()
+        }
+    }
+
+    pub fn method(&self) -> CostMethod {
+        self.method
+    }
+
+    /// Applies to fills from now on; open lots are merged when switching to
+    /// average cost.
+    pub fn set_method(&mut self, method: CostMethod) {
+        if method == CostMethod::AverageCost {
+            self.positions.values_mut().for_each(Position::merge_lots);
+        }
+        self.method = method;
+    }
+
+    #[inline(always)]
+    pub fn on_fill(&mut self, symbol_id: u32, side: Side, price: f64, quantity: f64, fee: f64) -> Option<f64> {
+        self.positions
+            .entry(symbol_id)
+            .or_default()
+            .on_fill(self.method, side, price, quantity, fee)
+    }
+
+    #[inline(always)]
+    pub fn net_qty(&self, symbol_id: u32) -> f64 {
+        self.positions.get(&symbol_id).map_or(0.0, |p| p.net_qty)
+    }
+
+    pub fn set_mark(&mut self, symbol_id: u32, price: f64) {
+        self.marks.insert(symbol_id, price);
+    }
+
+    pub fn mark(&self, symbol_id: u32) -> Option<f64> {
+        self.marks.get(&symbol_id).copied()
+    }
+
+    pub fn symbols(&self) -> Vec<u32> {
+        let mut symbols: Vec<u32> = self.positions.keys().copied().collect();
+        symbols.sort_unstable();
+        symbols
+    }
+
+    /// Snapshot valued at `mark_price`, falling back to the position's
+    /// last fill price.
+    pub fn snapshot(&self, symbol_id: u32, mark_price: Option<f64>) -> Option<PositionSnapshot> {
+        let position = self.positions.get(&symbol_id)?;
+        let mark_price = mark_price.or((position.fill_count > 0).then_some(position.last_fill_price));
+        Some(PositionSnapshot {
+            symbol_id,
+            net_qty: position.net_qty,
+            avg_entry_price: position.avg_entry_price(),
+            mark_price,
+            realized_pnl: position.realized_pnl,
+            unrealized_pnl: mark_price.map_or(0.0, |mark| position.unrealized_pnl(mark)),
+            fees: position.fees,
+            traded_qty: position.traded_qty,
+            fill_count: position.fill_count,
+        })
+    }
+
+    pub fn clear(&mut self) {
+        self.positions.clear();
+        self.marks.clear();
+    }
+
+    /// Positions with their open lots, and the marks, for snapshots. The
+    /// cost method is configuration.
+    pub(crate) fn save(&self, w: &mut Writer<'_>) {
+        w.u32(self.positions.len() as u32);
+        for (symbol_id, position) in &self.positions {
+            w.u32(*symbol_id);
+            w.f64(position.net_qty);
+            w.f64(position.realized_pnl);
+            w.f64(position.fees);
+            w.f64(position.traded_qty);
+            w.u64(position.fill_count);
+            w.f64(position.last_fill_price);
+            w.u32(position.lots.len() as u32);
+            for lot in &position.lots {
+                w.f64(lot.quantity);
+                w.f64(lot.price);
+            }
+        }
+        w.u32(self.marks.len() as u32);
+        for (symbol_id, price) in &self.marks {
+            w.u32(*symbol_id);
+            w.f64(*price);
+        }
+    }
+
+    pub(crate) fn restore(&mut self, r: &mut Reader<'_>) -> Option<()> {
+        self.clear();
+        for _ in 0..r.u32()? {
+            let symbol_id = r.u32()?;
+            let mut position = Position {
+                net_qty: r.f64()?,
+                realized_pnl: r.f64()?,
+                fees: r.f64()?,
+                traded_qty: r.f64()?,
+                fill_count: r.u64()?,
+                last_fill_price: r.f64()?,
+                ..Position::default()
+            };
+            for _ in 0..r.u32()? {
+                position.lots.push_back(Lot {
+                    quantity: r.f64()?,
+                    price: r.f64()?,
+                });
+            }
+            self.positions.insert(symbol_id, position);
+        }
+        for _ in 0..r.u32()? {
+            let symbol_id = r.u32()?;
+            self.marks.insert(symbol_id, r.f64()?);
+        }
+        Some(())
+    }
+}
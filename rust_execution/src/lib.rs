@@ -1,9 +1,180 @@
 //! SIGMAX Ultra-Low-Latency Rust Execution Engine
 
+pub mod account;
+pub mod backtest;
+pub mod codec;
+pub mod export;
+pub mod features;
+pub mod fees;
+pub mod journal;
+pub mod killswitch;
+pub mod l2book;
+pub mod latency;
+pub mod ledger;
+pub mod orderbook;
+pub mod orders;
+pub mod queue;
+pub mod ratelimit;
+pub mod replay;
+pub mod resync;
+pub mod risk;
+pub mod shm;
+pub mod slippage;
+pub mod snapshot;
+pub mod triggers;
+
+use account::{Account, Instrument, MarginMode, Perpetual};
+use fees::{FeeBook, FeeSchedule, FeeTier, VenueVolume};
+use journal::{Accepted, Journal, JournalEvent, JournalFill, JournalReject, OrderUpdate, ReplaySummary};
+use killswitch::{KillSwitch, TradingMode};
+use latency::{LatencyKind, LatencyModel, LatencyProfile, LatencyRng};
+use ledger::{CostMethod, Ledger, PositionSnapshot};
+use orderbook::{BookFill, OrderBook, SelfTradePrevention, Side, EXTERNAL_ORDER_ID};
+use orders::{OrderRecord, OrderRegistry};
+use pyo3::exceptions::{PyIOError, PyTypeError, PyValueError};
+use slippage::{BookWalk, FixedBps, SlippageContext, SlippageModel, SpreadProportional, SquareRootImpact};
+use snapshot::SnapshotPolicy;
 use pyo3::prelude::*;
-use pyo3::types::PyDict;
-use std::sync::atomic::{AtomicU64, Ordering};
-use std::time::Instant;
+use pyo3::types::{PyDict, PyList};
+use queue::QueueModel;
+use ratelimit::{
+    Keyed, RateKey, RateLimiter, RateScope, SlidingWindow, SlidingWindowConfig, TokenBucket,
+    TokenBucketConfig, VenueKey,
+};
+use risk::{RiskCheck, RiskGate, RiskLimits};
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicU8, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::path::{Path, PathBuf};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+use triggers::{ConditionalOrder, TrailingOffset, TriggerBook, TriggerKind, TriggerSource};
+
+/// Order status codes, numerically identical to `pkg.schemas.common.OrderStatus`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(u8)]
+pub enum OrderStatus {
+    Pending = 1,
+    Submitted = 2,
+    Partial = 3,
+    Filled = 4,
+    Cancelled = 5,
+    Rejected = 6,
+}
+
+/// Engine order type codes. Limit/market keep their original `0`/`1` values;
+/// the conditional types are held off-book until triggered.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(u8)]
+pub enum OrderType {
+    Limit = 0,
+    Market = 1,
+    Stop = 2,
+    StopLimit = 3,
+    TakeProfit = 4,
+    TakeProfitLimit = 5,
+    TrailingStop = 6,
+}
+
+impl OrderType {
+    #[inline(always)]
+    pub fn from_u8(value: u8) -> Option<OrderType> {
+        match value {
+            0 => Some(OrderType::Limit),
+            1 => Some(OrderType::Market),
+            2 => Some(OrderType::Stop),
+            3 => Some(OrderType::StopLimit),
+            4 => Some(OrderType::TakeProfit),
+            5 => Some(OrderType::TakeProfitLimit),
+            6 => Some(OrderType::TrailingStop),
+            _ => None,
+        }
+    }
+
+    #[inline(always)]
+    pub fn has_limit_price(self) -> bool {
+        matches!(
+            self,
+            OrderType::Limit | OrderType::StopLimit | OrderType::TakeProfitLimit
+        )
+    }
+
+    #[inline(always)]
+    pub fn trigger_kind(self) -> Option<TriggerKind> {
+        match self {
+            OrderType::Limit | OrderType::Market => None,
+            OrderType::Stop | OrderType::StopLimit => Some(TriggerKind::Stop),
+            OrderType::TakeProfit | OrderType::TakeProfitLimit => Some(TriggerKind::TakeProfit),
+            OrderType::TrailingStop => Some(TriggerKind::TrailingStop),
+        }
+    }
+}
+
+/// Time-in-force codes, numerically identical to `pkg.schemas.common.TimeInForce`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(u8)]
+pub enum TimeInForce {
+    Gtc = 1,
+    Ioc = 2,
+    Fok = 3,
+    Gtx = 4,
+}
+
+impl TimeInForce {
+    #[inline(always)]
+    pub fn from_u8(value: u8) -> Option<TimeInForce> {
+        match value {
+            1 => Some(TimeInForce::Gtc),
+            2 => Some(TimeInForce::Ioc),
+            3 => Some(TimeInForce::Fok),
+            4 => Some(TimeInForce::Gtx),
+            _ => None,
+        }
+    }
+}
+
+/// One match of an order against the book, shaped like `pkg.schemas.orders.Fill`.
+#[derive(Clone)]
+#[pyclass]
+pub struct RustFill {
+    #[pyo3(get)]
+    pub ts_ns: u64,
+    #[pyo3(get)]
+    pub order_id: u64,
+    #[pyo3(get)]
+    pub trade_id: u64,
+    #[pyo3(get)]
+    pub price: f64,
+    #[pyo3(get)]
+    pub qty: f64,
+    #[pyo3(get)]
+    pub is_maker: bool,
+    #[pyo3(get)]
+    pub matched_order_id: u64,
+    /// Positive for a cost, negative for a rebate.
+    #[pyo3(get)]
+    pub fee: f64,
+    #[pyo3(get)]
+    pub fee_currency: String,
+}
+
+#[pymethods]
+impl RustFill {
+    fn to_dict(&self) -> PyResult<PyObject> {
+        Python::with_gil(|py| {
+            let dict = PyDict::new(py);
+            dict.set_item("ts_ns", self.ts_ns)?;
+            dict.set_item("order_id", self.order_id)?;
+            dict.set_item("trade_id", self.trade_id)?;
+            dict.set_item("price", self.price)?;
+            dict.set_item("qty", self.qty)?;
+            dict.set_item("is_maker", self.is_maker)?;
+            dict.set_item("matched_order_id", self.matched_order_id)?;
+            dict.set_item("fee", self.fee)?;
+            dict.set_item("fee_currency", &self.fee_currency)?;
+            Ok(dict.into())
+        })
+    }
+}
 
 #[derive(Clone)]
 #[pyclass]
@@ -11,13 +182,34 @@ pub struct RustExecution {
     #[pyo3(get)]
     pub order_id: u64,
     #[pyo3(get)]
+    pub client_id: Option<String>,
+    #[pyo3(get)]
     pub executed_price: f64,
     #[pyo3(get)]
     pub executed_quantity: f64,
     #[pyo3(get)]
+    pub remaining_quantity: f64,
+    #[pyo3(get)]
+    pub status: u8,
+    #[pyo3(get)]
+    pub fills: Vec<RustFill>,
+    #[pyo3(get)]
     pub latency_ns: u64,
     #[pyo3(get)]
+    pub arrival_price: f64,
+    #[pyo3(get)]
     pub slippage: f64,
+    /// Total fee over `fills`.
+    #[pyo3(get)]
+    pub fee: f64,
+    #[pyo3(get)]
+    pub fee_currency: String,
+    /// Simulated time the order reached the matcher, 0 without a latency model.
+    #[pyo3(get)]
+    pub arrival_ns: u64,
+    /// Simulated time the strategy sees this execution, 0 without a latency model.
+    #[pyo3(get)]
+    pub ack_ns: u64,
 }
 
 #[pymethods]
@@ -26,114 +218,2847 @@ impl RustExecution {
         Python::with_gil(|py| {
             let dict = PyDict::new(py);
             dict.set_item("order_id", self.order_id)?;
+            dict.set_item("client_id", &self.client_id)?;
             dict.set_item("executed_price", self.executed_price)?;
             dict.set_item("executed_quantity", self.executed_quantity)?;
+            dict.set_item("remaining_quantity", self.remaining_quantity)?;
+            dict.set_item("status", self.status)?;
+            let fills = self
+                .fills
+                .iter()
+                .map(|fill| fill.to_dict())
+                .collect::<PyResult<Vec<_>>>()?;
+            dict.set_item("fills", fills)?;
             dict.set_item("latency_ns", self.latency_ns)?;
+            dict.set_item("arrival_price", self.arrival_price)?;
             dict.set_item("slippage", self.slippage)?;
+            dict.set_item("fee", self.fee)?;
+            dict.set_item("fee_currency", &self.fee_currency)?;
+            dict.set_item("arrival_ns", self.arrival_ns)?;
+            dict.set_item("ack_ns", self.ack_ns)?;
+            Ok(dict.into())
+        })
+    }
+}
+
+impl RustExecution {
+    /// Execution with no fills, e.g. a reject or a pending conditional order.
+    fn unfilled(order_id: u64, remaining_quantity: f64, status: OrderStatus) -> Self {
+        Self {
+            order_id,
+            client_id: None,
+            executed_price: 0.0,
+            executed_quantity: 0.0,
+            remaining_quantity,
+            status: status as u8,
+            fills: Vec::new(),
+            latency_ns: 0,
+            arrival_price: 0.0,
+            slippage: 0.0,
+            fee: 0.0,
+            fee_currency: String::new(),
+            arrival_ns: 0,
+            ack_ns: 0,
+        }
+    }
+}
+
+/// Order refused before reaching the book, shaped like `pkg.schemas.orders.Reject`.
+#[derive(Clone)]
+#[pyclass]
+pub struct RustReject {
+    #[pyo3(get)]
+    pub ts_ns: u64,
+    #[pyo3(get)]
+    pub client_id: Option<String>,
+    #[pyo3(get)]
+    pub symbol_id: u32,
+    #[pyo3(get)]
+    pub side: u8,
+    #[pyo3(get)]
+    pub price: f64,
+    #[pyo3(get)]
+    pub quantity: f64,
+    /// `pkg.schemas.orders.RejectReason` code.
+    #[pyo3(get)]
+    pub reason_code: u16,
+    #[pyo3(get)]
+    pub reason_msg: String,
+    #[pyo3(get)]
+    pub source: String,
+    #[pyo3(get)]
+    pub venue_code: u8,
+    #[pyo3(get)]
+    pub latency_ns: u64,
+    /// The order already holding `client_id`, for a duplicate submission.
+    #[pyo3(get)]
+    pub order_id: Option<u64>,
+}
+
+#[pymethods]
+impl RustReject {
+    fn to_dict(&self) -> PyResult<PyObject> {
+        Python::with_gil(|py| {
+            let dict = PyDict::new(py);
+            dict.set_item("ts_ns", self.ts_ns)?;
+            dict.set_item("client_id", &self.client_id)?;
+            dict.set_item("symbol_id", self.symbol_id)?;
+            dict.set_item("side", self.side)?;
+            dict.set_item("price", self.price)?;
+            dict.set_item("quantity", self.quantity)?;
+            dict.set_item("reason_code", self.reason_code)?;
+            dict.set_item("reason_msg", &self.reason_msg)?;
+            dict.set_item("source", &self.source)?;
+            dict.set_item("venue_code", self.venue_code)?;
+            dict.set_item("latency_ns", self.latency_ns)?;
+            dict.set_item("order_id", self.order_id)?;
             Ok(dict.into())
         })
     }
 }
 
+/// Outcome of a submission: an execution, or a reject from the risk gate.
+#[derive(IntoPyObject)]
+pub enum Submission {
+    Execution(RustExecution),
+    Reject(RustReject),
+}
+
+impl Submission {
+    pub fn latency_ns(&self) -> u64 {
+        match self {
+            Submission::Execution(execution) => execution.latency_ns,
+            Submission::Reject(reject) => reject.latency_ns,
+        }
+    }
+}
+
+/// Venue as an engine code or a name.
+#[derive(FromPyObject)]
+pub enum VenueArg {
+    Code(u8),
+    Name(String),
+}
+
+fn rate_key(symbol_id: Option<u32>, venue: Option<VenueArg>, account: Option<String>) -> RateKey {
+    RateKey {
+        symbol_id,
+        venue: venue.map(|venue| match venue {
+            VenueArg::Code(code) => VenueKey::Code(code),
+            VenueArg::Name(name) => VenueKey::Name(name),
+        }),
+        account,
+    }
+}
+
+/// Token bucket refilled at `rate` tokens per second and holding at most
+/// `burst`, with a separate bucket for every symbol/venue/account key.
 #[pyclass]
-pub struct RustExecutionEngine {
-    next_order_id: AtomicU64,
-    total_executions: AtomicU64,
-    total_latency_ns: AtomicU64,
-    min_latency_ns: AtomicU64,
-    max_latency_ns: AtomicU64,
+pub struct TokenBucketLimiter {
+    inner: Arc<Keyed<TokenBucket>>,
 }
 
 #[pymethods]
-impl RustExecutionEngine {
+impl TokenBucketLimiter {
     #[new]
-    pub fn new() -> Self {
-        Self {
-            next_order_id: AtomicU64::new(1),
-            total_executions: AtomicU64::new(0),
-            total_latency_ns: AtomicU64::new(0),
-            min_latency_ns: AtomicU64::new(u64::MAX),
-            max_latency_ns: AtomicU64::new(0),
-        }
+    #[pyo3(signature = (rate, burst=1))]
+    pub fn new(rate: f64, burst: u64) -> PyResult<Self> {
+        let config = TokenBucketConfig::new(rate, burst).map_err(PyValueError::new_err)?;
+        Ok(Self {
+            inner: Arc::new(Keyed::new(config)),
+        })
     }
 
-    #[inline(always)]
-    pub fn execute_order(
+    /// Take `cost` tokens for the key at `now_ns` (wall clock by default).
+    /// Returns `False`, taking nothing, if the bucket is short.
+    #[pyo3(signature = (symbol_id=None, venue=None, account=None, cost=1, now_ns=None))]
+    pub fn try_acquire(
         &self,
-        symbol_id: u32,
-        side: u8,
-        order_type: u8,
-        price: f64,
-        quantity: f64,
-    ) -> PyResult<RustExecution> {
-        let start = Instant::now();
+        symbol_id: Option<u32>,
+        venue: Option<VenueArg>,
+        account: Option<String>,
+        cost: u64,
+        now_ns: Option<u64>,
+    ) -> bool {
+        let key = rate_key(symbol_id, venue, account);
+        self.inner.try_acquire(&key, cost, now_ns.unwrap_or_else(self::now_ns))
+    }
 
-        let order_id = self.next_order_id.fetch_add(1, Ordering::Relaxed);
+    /// Return tokens taken by `try_acquire`.
+    #[pyo3(signature = (symbol_id=None, venue=None, account=None, cost=1, now_ns=None))]
+    pub fn release(
+        &self,
+        symbol_id: Option<u32>,
+        venue: Option<VenueArg>,
+        account: Option<String>,
+        cost: u64,
+        now_ns: Option<u64>,
+    ) {
+        let key = rate_key(symbol_id, venue, account);
+        self.inner.release(&key, cost, now_ns.unwrap_or_else(self::now_ns));
+    }
 
-        let (executed_price, executed_quantity, slippage) = 
-            Self::execute_internal(price, quantity, side, order_type);
+    /// Refill every bucket.
+    pub fn reset(&self) {
+        self.inner.reset();
+    }
 
-        let latency_ns = start.elapsed().as_nanos() as u64;
+    #[getter]
+    pub fn rate(&self) -> f64 {
+        self.inner.config.rate
+    }
 
-        self.update_stats(latency_ns);
+    #[getter]
+    pub fn burst(&self) -> u64 {
+        self.inner.config.burst
+    }
+}
 
-        Ok(RustExecution {
-            order_id,
-            executed_price,
-            executed_quantity,
-            latency_ns,
-            slippage,
+/// At most `limit` units within any `window_ms`, with a separate window for
+/// every symbol/venue/account key. The window slides in steps of 1/16th.
+#[pyclass]
+pub struct SlidingWindowLimiter {
+    inner: Arc<Keyed<SlidingWindow>>,
+}
+
+#[pymethods]
+impl SlidingWindowLimiter {
+    #[new]
+    #[pyo3(signature = (limit, window_ms=1000))]
+    pub fn new(limit: u64, window_ms: u64) -> PyResult<Self> {
+        let config = SlidingWindowConfig::new(limit, window_ms.saturating_mul(1_000_000))
+            .map_err(PyValueError::new_err)?;
+        Ok(Self {
+            inner: Arc::new(Keyed::new(config)),
         })
     }
 
-    pub fn get_stats(&self) -> PyResult<PyObject> {
-        let total_executions = self.total_executions.load(Ordering::Relaxed);
-        let total_latency = self.total_latency_ns.load(Ordering::Relaxed);
-        let min_latency = self.min_latency_ns.load(Ordering::Relaxed);
-        let max_latency = self.max_latency_ns.load(Ordering::Relaxed);
+    /// Count `cost` units for the key at `now_ns` (wall clock by default).
+    /// Returns `False`, counting nothing, if the window is full.
+    #[pyo3(signature = (symbol_id=None, venue=None, account=None, cost=1, now_ns=None))]
+    pub fn try_acquire(
+        &self,
+        symbol_id: Option<u32>,
+        venue: Option<VenueArg>,
+        account: Option<String>,
+        cost: u64,
+        now_ns: Option<u64>,
+    ) -> bool {
+        let key = rate_key(symbol_id, venue, account);
+        self.inner.try_acquire(&key, cost, now_ns.unwrap_or_else(self::now_ns))
+    }
 
-        let avg_latency = if total_executions > 0 {
-            total_latency / total_executions
-        } else {
-            0
-        };
+    /// Uncount units from `try_acquire` still inside the current sub-window.
+    #[pyo3(signature = (symbol_id=None, venue=None, account=None, cost=1, now_ns=None))]
+    pub fn release(
+        &self,
+        symbol_id: Option<u32>,
+        venue: Option<VenueArg>,
+        account: Option<String>,
+        cost: u64,
+        now_ns: Option<u64>,
+    ) {
+        let key = rate_key(symbol_id, venue, account);
+        self.inner.release(&key, cost, now_ns.unwrap_or_else(self::now_ns));
+    }
 
-        Python::with_gil(|py| {
-            let dict = PyDict::new(py);
-            dict.set_item("total_executions", total_executions)?;
-            dict.set_item("avg_latency_ns", avg_latency)?;
-            dict.set_item("min_latency_ns", if min_latency == u64::MAX { 0 } else { min_latency })?;
-            dict.set_item("max_latency_ns", max_latency)?;
-            Ok(dict.into())
+    /// Empty every window.
+    pub fn reset(&self) {
+        self.inner.reset();
+    }
+
+    #[getter]
+    pub fn limit(&self) -> u64 {
+        self.inner.config.limit
+    }
+
+    #[getter]
+    pub fn window_ms(&self) -> u64 {
+        self.inner.config.window_ns / 1_000_000
+    }
+}
+
+fn position_dict(py: Python<'_>, position: &PositionSnapshot) -> PyResult<PyObject> {
+    let dict = PyDict::new(py);
+    dict.set_item("symbol_id", position.symbol_id)?;
+    dict.set_item("net_qty", position.net_qty)?;
+    dict.set_item("avg_entry_price", position.avg_entry_price)?;
+    dict.set_item("mark_price", position.mark_price)?;
+    dict.set_item("notional", position.notional())?;
+    dict.set_item("realized_pnl", position.realized_pnl)?;
+    dict.set_item("unrealized_pnl", position.unrealized_pnl)?;
+    dict.set_item("fees", position.fees)?;
+    dict.set_item("net_pnl", position.net_pnl())?;
+    dict.set_item("traded_qty", position.traded_qty)?;
+    dict.set_item("fill_count", position.fill_count)?;
+    Ok(dict.into())
+}
+
+/// Totals across positions: PnL, fees and gross/net exposure at mark.
+fn portfolio_dict(py: Python<'_>, positions: &[PositionSnapshot], method: CostMethod) -> PyResult<PyObject> {
+    let sum = |f: fn(&PositionSnapshot) -> f64| positions.iter().map(f).sum::<f64>();
+    let dict = PyDict::new(py);
+    dict.set_item("cost_method", method.name())?;
+    dict.set_item("positions", positions.iter().filter(|p| p.net_qty != 0.0).count())?;
+    dict.set_item("realized_pnl", sum(|p| p.realized_pnl))?;
+    dict.set_item("unrealized_pnl", sum(|p| p.unrealized_pnl))?;
+    dict.set_item("fees", sum(|p| p.fees))?;
+    dict.set_item("net_pnl", sum(PositionSnapshot::net_pnl))?;
+    dict.set_item("gross_exposure", sum(|p| p.notional().abs()))?;
+    dict.set_item("net_exposure", sum(PositionSnapshot::notional))?;
+    Ok(dict.into())
+}
+
+fn parse_cost_method(method: &str) -> PyResult<CostMethod> {
+    CostMethod::from_name(method)
+        .ok_or_else(|| PyValueError::new_err(format!("invalid cost method: {}", method)))
+}
+
+/// Position ledger fed by the caller rather than by the engine's matcher.
+/// `cost_method` is `"fifo"`, `"lifo"` or `"average"`.
+#[pyclass]
+pub struct PositionLedger {
+    ledger: Mutex<Ledger>,
+}
+
+#[pymethods]
+impl PositionLedger {
+    #[new]
+    #[pyo3(signature = (cost_method="fifo"))]
+    pub fn new(cost_method: &str) -> PyResult<Self> {
+        Ok(Self {
+            ledger: Mutex::new(Ledger::new(parse_cost_method(cost_method)?)),
         })
     }
 
-    pub fn reset_stats(&self) {
-        self.total_executions.store(0, Ordering::Relaxed);
-        self.total_latency_ns.store(0, Ordering::Relaxed);
-        self.min_latency_ns.store(u64::MAX, Ordering::Relaxed);
-        self.max_latency_ns.store(0, Ordering::Relaxed);
+    /// Apply a fill (side 0 = buy, 1 = sell) and return the PnL it realized,
+    /// or `None` if it only added to the position.
+    #[pyo3(signature = (symbol_id, side, price, quantity, fee=0.0))]
+    pub fn on_fill(&self, symbol_id: u32, side: u8, price: f64, quantity: f64, fee: f64) -> PyResult<Option<f64>> {
+        let side = Side::from_u8(side)
+            .ok_or_else(|| PyValueError::new_err(format!("invalid side: {}", side)))?;
+        if !(price.is_finite() && price > 0.0 && quantity.is_finite() && quantity > 0.0) {
+            return Err(PyValueError::new_err(format!("invalid fill: {} @ {}", quantity, price)));
+        }
+        Ok(self.ledger().on_fill(symbol_id, side, price, quantity, fee))
+    }
+
+    pub fn set_mark_price(&self, symbol_id: u32, price: f64) {
+        self.ledger().set_mark(symbol_id, price);
+    }
+
+    pub fn set_cost_method(&self, cost_method: &str) -> PyResult<()> {
+        self.ledger().set_method(parse_cost_method(cost_method)?);
+        Ok(())
+    }
+
+    pub fn get_position(&self, symbol_id: u32) -> PyResult<Option<PyObject>> {
+        let ledger = self.ledger();
+        let Some(position) = ledger.snapshot(symbol_id, ledger.mark(symbol_id)) else {
+            return Ok(None);
+        };
+        Python::with_gil(|py| position_dict(py, &position).map(Some))
+    }
+
+    pub fn get_positions(&self) -> PyResult<Vec<PyObject>> {
+        let positions = self.snapshots();
+        Python::with_gil(|py| positions.iter().map(|p| position_dict(py, p)).collect())
+    }
+
+    pub fn get_portfolio(&self) -> PyResult<PyObject> {
+        let positions = self.snapshots();
+        let method = self.ledger().method();
+        Python::with_gil(|py| portfolio_dict(py, &positions, method))
+    }
+
+    pub fn reset(&self) {
+        self.ledger().clear();
     }
 }
 
-impl RustExecutionEngine {
+impl PositionLedger {
+    fn ledger(&self) -> MutexGuard<'_, Ledger> {
+        self.ledger.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn snapshots(&self) -> Vec<PositionSnapshot> {
+        let ledger = self.ledger();
+        ledger
+            .symbols()
+            .into_iter()
+            .filter_map(|symbol_id| ledger.snapshot(symbol_id, ledger.mark(symbol_id)))
+            .collect()
+    }
+}
+
+#[inline(always)]
+fn now_ns() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos() as u64)
+        .unwrap_or(0)
+}
+
+/// Raw order parameters as received from Python, validated by `submit`.
+#[derive(Clone, Debug)]
+pub struct OrderRequest {
+    pub symbol_id: u32,
+    pub side: u8,
+    pub order_type: u8,
+    pub price: f64,
+    pub quantity: f64,
+    pub tif: u8,
+    pub trigger_price: Option<f64>,
+    pub trail_amount: Option<f64>,
+    pub trail_percent: Option<f64>,
+    pub trigger_source: u8,
+    pub client_id: Option<String>,
+    /// Key for account-scoped rate limits.
+    pub account: Option<String>,
+}
+
+impl OrderRequest {
+    pub fn new(symbol_id: u32, side: u8, order_type: u8, price: f64, quantity: f64) -> Self {
+        Self {
+            symbol_id,
+            side,
+            order_type,
+            price,
+            quantity,
+            tif: TimeInForce::Gtc as u8,
+            trigger_price: None,
+            trail_amount: None,
+            trail_percent: None,
+            trigger_source: 0,
+            client_id: None,
+            account: None,
+        }
+    }
+}
+
+/// Passive fills buffered for `drain_maker_fills` before the oldest are
+/// dropped.
+const MAKER_FILL_CAPACITY: usize = 65_536;
+
+#[derive(Default)]
+struct EngineState {
+    books: HashMap<u32, OrderBook>,
+    triggers: TriggerBook,
+    orders: OrderRegistry,
+    /// Passive fills not yet drained, newest `maker_fill_capacity` only.
+    maker_fills: VecDeque<RustFill>,
+    maker_fill_capacity: usize,
+    maker_fills_dropped: u64,
+    triggered: Vec<RustExecution>,
+    next_trade_id: u64,
+    tick_sizes: HashMap<u32, f64>,
+    slippage_models: HashMap<u32, Box<dyn SlippageModel>>,
+    fees: FeeBook,
+    risk: RiskGate,
+    ledger: Ledger,
+    account: Account,
+    kill_switch: KillSwitch,
+    blocked: Vec<RustReject>,
+    rate_limits: Vec<(RateScope, Arc<dyn RateLimiter>)>,
+    post_only_reprice: bool,
+    self_trade_prevention: SelfTradePrevention,
+    latency: LatencyProfile,
+    /// Simulated time, advanced by timestamped market data and `advance_time`.
+    clock_ns: u64,
+    simulated_clock: bool,
+    /// Every fill of the engine's own orders, while `record_fills` is on.
+    record_fills: bool,
+    fill_log: Vec<RustFill>,
+    in_flight: BTreeMap<(u64, u64), NewOrder>,
+    acks: BTreeMap<(u64, u64), RustExecution>,
+    journal: Option<Journal>,
+    snapshot_policy: SnapshotPolicy,
+}
+
+/// What `recover` found and rebuilt.
+struct Recovery {
+    summary: ReplaySummary,
+    snapshot_seq: Option<u64>,
+    skipped_snapshots: usize,
+    torn_bytes: u64,
+}
+
+/// An accepted order on its way to the matcher.
+struct NewOrder {
+    order_id: u64,
+    symbol_id: u32,
+    side: Side,
+    tif: TimeInForce,
+    limit: Option<f64>,
+    quantity: f64,
+    conditional: Option<ConditionalOrder>,
+}
+
+impl EngineState {
+    /// Simulated time when simulation is on or latency is modelled,
+    /// wall-clock time otherwise.
     #[inline(always)]
-    fn execute_internal(
-        price: f64,
-        quantity: f64,
-        side: u8,
-        order_type: u8
-    ) -> (f64, f64, f64) {
-        let executed_price = if order_type == 1 {
-            price
+    fn timestamp(&self) -> u64 {
+        if self.simulated_clock || self.latency.is_enabled() {
+            self.clock_ns
         } else {
-            price
+            now_ns()
+        }
+    }
+
+    /// Hand an order to the matcher, or park it in the trigger book if it is
+    /// conditional. Trades it prints cascade into the trigger book.
+    fn accept(&mut self, order: NewOrder) -> RustExecution {
+        let mut execution = match order.conditional {
+            None => self.match_order(
+                order.order_id,
+                order.symbol_id,
+                order.side,
+                order.tif,
+                order.limit,
+                order.quantity,
+            ),
+            Some(conditional) => match self.triggers.insert(conditional) {
+                Some(conditional) => self.release(conditional),
+                None => RustExecution::unfilled(order.order_id, order.quantity, OrderStatus::Pending),
+            },
         };
+        execution.client_id = self.orders.client_id(order.order_id);
+
+        if let Some(last) = execution.fills.last() {
+            let cascaded = self.process_triggers(order.symbol_id, last.price, TriggerSource::LastTrade);
+            self.triggered.extend(cascaded);
+        }
+        execution
+    }
+
+    /// Put an order in flight. It reaches the matcher after the order-entry
+    /// latency plus the market-data latency, since the strategy is acting on
+    /// a book that is already that old.
+    fn send(&mut self, order: NewOrder) -> RustExecution {
+        let sent_ns = self.clock_ns;
+        let arrival_ns = sent_ns
+            + self.latency.sample(LatencyKind::MarketData, sent_ns)
+            + self.latency.sample(LatencyKind::OrderEntry, sent_ns);
+        let mut execution = RustExecution::unfilled(order.order_id, order.quantity, OrderStatus::Pending);
+        execution.arrival_ns = arrival_ns;
+        self.in_flight.insert((arrival_ns, order.order_id), order);
+        execution
+    }
+
+    /// Move simulated time to `ts_ns`, matching every in-flight order that
+    /// arrives by then against the book as it stands at its arrival.
+    fn advance_to(&mut self, ts_ns: u64) {
+        while let Some(entry) = self.in_flight.first_entry() {
+            let (arrival_ns, _) = *entry.key();
+            if arrival_ns > ts_ns {
+                break;
+            }
+            let order = entry.remove();
+            self.clock_ns = self.clock_ns.max(arrival_ns);
+
+            let mut execution = self.accept(order);
+            execution.arrival_ns = arrival_ns;
+            execution.ack_ns = arrival_ns + self.latency.sample(LatencyKind::Ack, arrival_ns);
+            self.acks.insert((execution.ack_ns, execution.order_id), execution);
+        }
+        self.clock_ns = self.clock_ns.max(ts_ns);
+    }
+
+    fn remove_in_flight(&mut self, order_id: u64) -> bool {
+        let before = self.in_flight.len();
+        self.in_flight.retain(|(_, id), _| *id != order_id);
+        self.in_flight.len() != before
+    }
+
+    /// Match the order and record the outcome in the order registry.
+    fn match_order(
+        &mut self,
+        order_id: u64,
+        symbol_id: u32,
+        side: Side,
+        tif: TimeInForce,
+        limit: Option<f64>,
+        quantity: f64,
+    ) -> RustExecution {
+        let mut execution = self.match_incoming(order_id, symbol_id, side, tif, limit, quantity);
+        self.orders
+            .on_execution(order_id, execution.executed_quantity, execution.status, self.timestamp());
+        execution.client_id = self.orders.client_id(order_id);
+        execution
+    }
 
-        let slippage = if side == 0 { 0.0001 } else { -0.0001 };
-        let executed_quantity = quantity;
+    /// Match the order against the symbol's book and apply its time in force
+    /// to any unfilled remainder. `limit` is `None` for market orders.
+    fn match_incoming(
+        &mut self,
+        order_id: u64,
+        symbol_id: u32,
+        side: Side,
+        tif: TimeInForce,
+        mut limit: Option<f64>,
+        quantity: f64,
+    ) -> RustExecution {
+        let ts_ns = self.timestamp();
+        let tick_size = self.tick_sizes.get(&symbol_id).copied();
+        let book = self.books.entry(symbol_id).or_default();
+
+        let rejected = RustExecution::unfilled(order_id, quantity, OrderStatus::Rejected);
+
+        match (tif, limit) {
+            (TimeInForce::Gtx, None) => return rejected,
+            (TimeInForce::Gtx, Some(limit_price)) if book.would_cross(side, limit_price) => {
+                let repriced = match (self.post_only_reprice, tick_size, book.best(side.opposite())) {
+                    (true, Some(tick), Some((touch, _))) => match side {
+                        Side::Buy => touch - tick,
+                        Side::Sell => touch + tick,
+                    },
+                    _ => return rejected,
+                };
+                if repriced <= 0.0 {
+                    return rejected;
+                }
+                limit = Some(repriced);
+                if let Some(record) = self.orders.get_mut(order_id) {
+                    record.price = repriced;
+                }
+            }
+            (TimeInForce::Fok, _)
+                if book.available_to_match(side, limit, self.self_trade_prevention) + orderbook::QTY_EPSILON
+                    < quantity =>
+            {
+                return rejected;
+            }
+            _ => {}
+        }
 
-        (executed_price, executed_quantity, slippage)
+        let (best_bid, best_ask) = (book.best_bid(), book.best_ask());
+        let context = SlippageContext {
+            side,
+            quantity,
+            arrival_price: match (best_bid, best_ask, side) {
+                (Some((bid, _)), Some((ask, _)), _) => (bid + ask) / 2.0,
+                (_, Some((ask, _)), Side::Buy) => ask,
+                (Some((bid, _)), _, Side::Sell) => bid,
+                _ => limit.unwrap_or(0.0),
+            },
+            spread: match (best_bid, best_ask) {
+                (Some((bid, _)), Some((ask, _))) => Some(ask - bid),
+                _ => None,
+            },
+        };
+        let model: &dyn SlippageModel = match self.slippage_models.get(&symbol_id) {
+            Some(model) => model.as_ref(),
+            None => &BookWalk,
+        };
+
+        let matched = book.match_order(side, limit, quantity, self.self_trade_prevention);
+        for resting in &matched.cancelled {
+            self.orders.set_status(resting.order_id, OrderStatus::Cancelled as u8, ts_ns);
+        }
+
+        let mut fills = Vec::with_capacity(matched.fills.len());
+        let mut notional = 0.0;
+        let mut executed_quantity = 0.0;
+        let mut fee = 0.0;
+        let mut fee_currency = String::new();
+        for m in &matched.fills {
+            self.next_trade_id += 1;
+            let trade_id = self.next_trade_id;
+            let fill_price = slippage::adjust_fill_price(side, m.price, model.impact(&context, m.price), limit);
+            notional += fill_price * m.quantity;
+            executed_quantity += m.quantity;
+
+            let charge = self.fees.charge(symbol_id, fill_price, m.quantity, false, ts_ns);
+            let quote_fee = charge.quote_fee(fill_price);
+            let position = self.ledger.net_qty(symbol_id);
+            let realized = self.ledger.on_fill(symbol_id, side, fill_price, m.quantity, quote_fee);
+            self.account
+                .on_fill(symbol_id, side, fill_price, m.quantity, position, realized);
+            self.account.charge_fee(symbol_id, &charge.currency, charge.fee);
+            self.kill_switch.on_fill(realized, quote_fee, ts_ns);
+            if let Some(journal) = &mut self.journal {
+                journal.append(
+                    ts_ns,
+                    &JournalEvent::Fill(JournalFill {
+                        order_id,
+                        trade_id,
+                        symbol_id,
+                        side: side.as_u8(),
+                        is_maker: false,
+                        price: fill_price,
+                        qty: m.quantity,
+                        fee: charge.fee,
+                        fee_currency: charge.currency.clone(),
+                        fee_in_base: charge.in_base,
+                    }),
+                );
+            }
+            fee += charge.fee;
+            fee_currency.clone_from(&charge.currency);
+            fills.push(RustFill {
+                ts_ns,
+                order_id,
+                trade_id,
+                price: fill_price,
+                qty: m.quantity,
+                is_maker: false,
+                matched_order_id: m.maker_order_id,
+                fee: charge.fee,
+                fee_currency: charge.currency,
+            });
+            if self.record_fills {
+                self.fill_log.extend(fills.last().cloned());
+            }
+        }
+
+        let executed_price = if executed_quantity > 0.0 {
+            notional / executed_quantity
+        } else {
+            0.0
+        };
+
+        let remaining_quantity = (quantity - executed_quantity).max(0.0);
+        let rests = matches!(tif, TimeInForce::Gtc | TimeInForce::Gtx);
+        let status = match limit {
+            _ if remaining_quantity <= orderbook::QTY_EPSILON => OrderStatus::Filled,
+            Some(limit_price) if rests && !matched.self_trade => {
+                book.add_order(order_id, side, limit_price, remaining_quantity, tif == TimeInForce::Gtx);
+                if executed_quantity > 0.0 {
+                    book.set_filled(order_id, executed_quantity);
+                    OrderStatus::Partial
+                } else {
+                    OrderStatus::Submitted
+                }
+            }
+            // IOC/FOK and market remainders never rest, nor does an order
+            // stopped by self-trade prevention.
+            _ => OrderStatus::Cancelled,
+        };
+
+        // Signed like the fill price: positive paid above arrival on buys,
+        // negative received below arrival on sells.
+        let slippage = if executed_quantity > 0.0 && context.arrival_price > 0.0 {
+            executed_price - context.arrival_price
+        } else {
+            0.0
+        };
+
+        RustExecution {
+            order_id,
+            client_id: None,
+            executed_price,
+            executed_quantity,
+            remaining_quantity,
+            status: status as u8,
+            fills,
+            latency_ns: 0,
+            arrival_price: context.arrival_price,
+            slippage,
+            fee,
+            fee_currency,
+            arrival_ns: 0,
+            ack_ns: 0,
+        }
+    }
+
+    /// Record resting orders filled by market data rather than by an
+    /// incoming engine order; the counterparty is `EXTERNAL_ORDER_ID`.
+    fn record_passive_fills(&mut self, symbol_id: u32, fills: Vec<BookFill>) {
+        let ts_ns = self.timestamp();
+        for fill in fills {
+            self.next_trade_id += 1;
+            self.orders.on_maker_fill(
+                fill.maker_order_id,
+                fill.quantity,
+                fill.maker_remaining <= orderbook::QTY_EPSILON,
+                ts_ns,
+            );
+            let charge = self.fees.charge(symbol_id, fill.price, fill.quantity, true, ts_ns);
+            if let Some(side) = self.orders.get(fill.maker_order_id).and_then(|r| Side::from_u8(r.side)) {
+                let quote_fee = charge.quote_fee(fill.price);
+                let position = self.ledger.net_qty(symbol_id);
+                let realized = self.ledger.on_fill(symbol_id, side, fill.price, fill.quantity, quote_fee);
+                self.account
+                    .on_fill(symbol_id, side, fill.price, fill.quantity, position, realized);
+                self.account.charge_fee(symbol_id, &charge.currency, charge.fee);
+                self.kill_switch.on_fill(realized, quote_fee, ts_ns);
+                if let Some(journal) = &mut self.journal {
+                    journal.append(
+                        ts_ns,
+                        &JournalEvent::Fill(JournalFill {
+                            order_id: fill.maker_order_id,
+                            trade_id: self.next_trade_id,
+                            symbol_id,
+                            side: side.as_u8(),
+                            is_maker: true,
+                            price: fill.price,
+                            qty: fill.quantity,
+                            fee: charge.fee,
+                            fee_currency: charge.currency.clone(),
+                            fee_in_base: charge.in_base,
+                        }),
+                    );
+                }
+            }
+            let maker_fill = RustFill {
+                ts_ns,
+                order_id: fill.maker_order_id,
+                trade_id: self.next_trade_id,
+                price: fill.price,
+                qty: fill.quantity,
+                is_maker: true,
+                matched_order_id: EXTERNAL_ORDER_ID,
+                fee: charge.fee,
+                fee_currency: charge.currency,
+            };
+            if self.record_fills {
+                self.fill_log.push(maker_fill.clone());
+            }
+            self.buffer_maker_fill(maker_fill);
+        }
+    }
+
+    fn buffer_maker_fill(&mut self, fill: RustFill) {
+        if self.maker_fill_capacity == 0 {
+            self.maker_fills_dropped += 1;
+            return;
+        }
+        if self.maker_fills.len() == self.maker_fill_capacity {
+            self.maker_fills.pop_front();
+            self.maker_fills_dropped += 1;
+        }
+        self.maker_fills.push_back(fill);
+    }
+
+    /// Apply what was deferred while the books were borrowed: cancel
+    /// everything if the kill switch halted trading, resize balance holds
+    /// to what is left of each order, then journal every order that changed
+    /// and take a snapshot if the policy says one is due.
+    fn settle(&mut self) {
+        if self.kill_switch.take_halt() {
+            self.cancel_all(None);
+        }
+        for order_id in self.account.held_orders() {
+            match self.orders.get(order_id) {
+                Some(record) if record.is_open() => {
+                    self.account
+                        .sync(order_id, record.quantity - record.filled, record.price)
+                }
+                _ => self.account.release(order_id),
+            }
+        }
+        let Some(journal) = &mut self.journal else {
+            self.orders.clear_changed();
+            return;
+        };
+        for order_id in self.orders.take_changed() {
+            if let Some(record) = self.orders.get(order_id) {
+                journal.append(
+                    record.updated_ns,
+                    &JournalEvent::Update(OrderUpdate {
+                        order_id: record.order_id,
+                        status: record.status,
+                        price: record.price,
+                        quantity: record.quantity,
+                        filled: record.filled,
+                    }),
+                );
+            }
+        }
+        let due = self
+            .snapshot_policy
+            .every_records
+            .is_some_and(|every| journal.since_snapshot() >= every);
+        if due {
+            // Failures show up in the journal stats; trading carries on.
+            let _ = self.snapshot();
+        }
+    }
+
+    fn journal_reject(&mut self, ts_ns: u64, reject: &RustReject) {
+        if let Some(journal) = &mut self.journal {
+            journal.append(
+                ts_ns,
+                &JournalEvent::Reject(JournalReject {
+                    client_id: reject.client_id.clone(),
+                    symbol_id: reject.symbol_id,
+                    side: reject.side,
+                    reason_code: reject.reason_code,
+                    price: reject.price,
+                    quantity: reject.quantity,
+                    reason_msg: reject.reason_msg.clone(),
+                }),
+            );
+        }
+    }
+
+    /// Why the kill switch refuses an order in the current mode, if it does.
+    /// Reduce-only accepts orders that shrink the position without flipping it.
+    fn kill_switch_blocks(&self, symbol_id: u32, side: Side, quantity: f64) -> Option<String> {
+        let mode = self.kill_switch.mode();
+        let position = self.ledger.net_qty(symbol_id);
+        let reduces = match side {
+            Side::Buy => position < 0.0 && quantity <= -position + orderbook::QTY_EPSILON,
+            Side::Sell => position > 0.0 && quantity <= position + orderbook::QTY_EPSILON,
+        };
+        let blocked = match mode {
+            TradingMode::Active => false,
+            TradingMode::ReduceOnly => !reduces,
+            TradingMode::Halted | TradingMode::CancelOnly => true,
+        };
+        blocked.then(|| match &self.kill_switch.reason {
+            Some(reason) => format!("trading is {}: {}", mode.name(), reason),
+            None => format!("trading is {}", mode.name()),
+        })
+    }
+
+    /// Record an order refused by the kill switch.
+    #[allow(clippy::too_many_arguments)]
+    fn block(
+        &mut self,
+        ts_ns: u64,
+        client_id: Option<String>,
+        symbol_id: u32,
+        side: Side,
+        price: f64,
+        quantity: f64,
+        reason_msg: String,
+    ) -> RustReject {
+        let reject = RustReject {
+            ts_ns,
+            client_id,
+            symbol_id,
+            side: side.as_u8(),
+            price,
+            quantity,
+            reason_code: risk::KILL_SWITCH,
+            reason_msg,
+            source: "kill_switch".to_string(),
+            venue_code: self.fees.venue_of(symbol_id),
+            latency_ns: 0,
+            order_id: None,
+        };
+        self.journal_reject(ts_ns, &reject);
+        self.blocked.push(reject.clone());
+        reject
+    }
+
+    /// Take one unit from every engine rate limiter, giving back what was
+    /// taken if any of them refuses.
+    fn acquire_rate_limits(&self, symbol_id: u32, account: &str, ts_ns: u64) -> Result<(), String> {
+        let venue_code = self.fees.venue_of(symbol_id);
+        for (i, (scope, limiter)) in self.rate_limits.iter().enumerate() {
+            let key = scope.key(symbol_id, venue_code, account);
+            if !limiter.try_acquire(&key, 1, ts_ns) {
+                for (scope, limiter) in &self.rate_limits[..i] {
+                    limiter.release(&scope.key(symbol_id, venue_code, account), 1, ts_ns);
+                }
+                return Err(format!(
+                    "Rate limit exceeded ({} per {})",
+                    limiter.name(),
+                    scope.names().join("/")
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Give back the unit `acquire_rate_limits` took from every limiter.
+    fn release_rate_limits(&self, symbol_id: u32, account: &str, ts_ns: u64) {
+        let venue_code = self.fees.venue_of(symbol_id);
+        for (scope, limiter) in &self.rate_limits {
+            limiter.release(&scope.key(symbol_id, venue_code, account), 1, ts_ns);
+        }
+    }
+
+    /// Explicit mark, else last trade or mid.
+    fn mark_price(&self, symbol_id: u32) -> Option<f64> {
+        self.ledger.mark(symbol_id).or_else(|| self.reference_price(symbol_id))
+    }
+
+    /// Last trade price, or the mid if the symbol has not traded.
+    fn reference_price(&self, symbol_id: u32) -> Option<f64> {
+        if let Some(last) = self.triggers.last_price(symbol_id, TriggerSource::LastTrade) {
+            return Some(last);
+        }
+        let book = self.books.get(&symbol_id)?;
+        let ((bid, _), (ask, _)) = book.best_bid().zip(book.best_ask())?;
+        Some((bid + ask) / 2.0)
+    }
+
+    /// Price a market order would start filling at: the opposite touch, else
+    /// the reference price.
+    fn touch_price(&self, symbol_id: u32, side: Side) -> Option<f64> {
+        self.books
+            .get(&symbol_id)
+            .and_then(|book| book.best(side.opposite()))
+            .map(|(price, _)| price)
+            .or_else(|| self.reference_price(symbol_id))
+    }
+
+    /// Symbol of an order currently resting on a book.
+    fn symbol_of(&self, order_id: u64) -> Option<u32> {
+        let symbol_id = self.orders.get(order_id)?.symbol_id;
+        self.books
+            .get(&symbol_id)
+            .is_some_and(|book| book.contains(order_id))
+            .then_some(symbol_id)
+    }
+
+    /// Cancel a resting or pending conditional order.
+    fn cancel(&mut self, order_id: u64) -> bool {
+        let cancelled = self.triggers.remove(order_id).is_some()
+            || self.remove_in_flight(order_id)
+            || self
+                .symbol_of(order_id)
+                .and_then(|symbol_id| self.books.get_mut(&symbol_id))
+                .and_then(|book| book.cancel(order_id))
+                .is_some();
+        if cancelled {
+            let ts_ns = self.timestamp();
+            self.orders.set_status(order_id, OrderStatus::Cancelled as u8, ts_ns);
+        }
+        cancelled
+    }
+
+    fn cancel_all(&mut self, symbol_id: Option<u32>) -> Vec<u64> {
+        let mut cancelled: Vec<u64> = self
+            .triggers
+            .remove_all(symbol_id)
+            .into_iter()
+            .map(|order| order.order_id)
+            .collect();
+
+        self.in_flight.retain(|(_, order_id), order| {
+            let keep = symbol_id.is_some_and(|s| s != order.symbol_id);
+            if !keep {
+                cancelled.push(*order_id);
+            }
+            keep
+        });
+
+        for (book_symbol, book) in self.books.iter_mut() {
+            if symbol_id.is_some_and(|s| s != *book_symbol) {
+                continue;
+            }
+            for order_id in book.order_ids() {
+                if book.cancel(order_id).is_some() {
+                    cancelled.push(order_id);
+                }
+            }
+        }
+
+        let ts_ns = self.timestamp();
+        for order_id in &cancelled {
+            self.orders.set_status(*order_id, OrderStatus::Cancelled as u8, ts_ns);
+        }
+        cancelled
+    }
+
+    /// Amend price and/or total quantity. A pure quantity decrease is applied
+    /// in place and keeps queue priority; a price change or quantity increase
+    /// re-enters the order at the back of the queue and may match on arrival.
+    /// `new_qty` is the new total order size including anything already filled.
+    fn amend(&mut self, order_id: u64, new_price: Option<f64>, new_qty: Option<f64>, account: &str) -> Submission {
+        let ts_ns = self.timestamp();
+        // An amend of an open order is a venue request like a new order and
+        // pays the same rate limits, given back if the amend is refused.
+        let open_symbol = self
+            .triggers
+            .get(order_id)
+            .map(|order| order.symbol_id)
+            .or_else(|| self.symbol_of(order_id));
+        let result = match open_symbol {
+            Some(symbol_id) => self
+                .acquire_rate_limits(symbol_id, account, ts_ns)
+                .map_err(|reason_msg| (risk::RATE_LIMIT, reason_msg, "risk"))
+                .and_then(|()| {
+                    self.amend_inner(order_id, new_price, new_qty).inspect_err(|_| {
+                        self.release_rate_limits(symbol_id, account, ts_ns);
+                    })
+                }),
+            None => self.amend_inner(order_id, new_price, new_qty),
+        };
+        let mut execution = match result {
+            Ok(execution) => execution,
+            Err((reason_code, reason_msg, source)) => {
+                let Some(record) = self.orders.get(order_id).cloned() else {
+                    return Submission::Execution(RustExecution::unfilled(order_id, 0.0, OrderStatus::Rejected));
+                };
+                let reject = RustReject {
+                    ts_ns,
+                    client_id: record.client_id,
+                    symbol_id: record.symbol_id,
+                    side: record.side,
+                    price: new_price.unwrap_or(record.price),
+                    quantity: new_qty.unwrap_or(record.quantity),
+                    reason_code,
+                    reason_msg,
+                    source: source.to_string(),
+                    venue_code: self.fees.venue_of(record.symbol_id),
+                    latency_ns: 0,
+                    order_id: None,
+                };
+                self.journal_reject(ts_ns, &reject);
+                return Submission::Reject(reject);
+            }
+        };
+        if let Some(record) = self.orders.get_mut(order_id) {
+            if execution.status != OrderStatus::Rejected as u8 {
+                record.price = new_price.unwrap_or(record.price);
+                record.quantity = new_qty.unwrap_or(record.quantity);
+                record.status = execution.status;
+                record.updated_ns = ts_ns;
+            }
+            execution.client_id = record.client_id.clone();
+        }
+        Submission::Execution(execution)
+    }
+
+    /// Apply an amend, or say why it was refused as `(reason_code,
+    /// reason_msg, source)`. Unknown or complete orders come back rejected.
+    fn amend_inner(
+        &mut self,
+        order_id: u64,
+        new_price: Option<f64>,
+        new_qty: Option<f64>,
+    ) -> Result<RustExecution, (u16, String, &'static str)> {
+        let mut execution = RustExecution::unfilled(order_id, 0.0, OrderStatus::Rejected);
+
+        if let Some(order) = self.triggers.get(order_id).cloned() {
+            if new_price.is_some() && order.limit_price.is_none() {
+                execution.remaining_quantity = order.quantity;
+                return Ok(execution);
+            }
+            let limit_price = new_price.or(order.limit_price);
+            let quantity = new_qty.unwrap_or(order.quantity);
+            if quantity <= orderbook::QTY_EPSILON {
+                self.triggers.remove(order_id);
+                execution.status = OrderStatus::Cancelled as u8;
+                return Ok(execution);
+            }
+            if limit_price != order.limit_price || quantity > order.quantity {
+                let valuation = limit_price
+                    .or((!order.trigger_price.is_nan()).then_some(order.trigger_price))
+                    .or_else(|| self.touch_price(order.symbol_id, order.side));
+                self.admit_amend(order_id, order.symbol_id, order.side, valuation, quantity)?;
+            }
+            if let Some(order) = self.triggers.get_mut(order_id) {
+                order.limit_price = limit_price;
+                order.quantity = quantity;
+            }
+            execution.remaining_quantity = quantity;
+            execution.status = OrderStatus::Pending as u8;
+            return Ok(execution);
+        }
+
+        let Some(symbol_id) = self.symbol_of(order_id) else {
+            return Ok(execution);
+        };
+        let Some(book) = self.books.get_mut(&symbol_id) else {
+            return Ok(execution);
+        };
+        let Some(resting) = book.get(order_id).cloned() else {
+            return Ok(execution);
+        };
+
+        let price = new_price.unwrap_or(resting.price);
+        let leaves = new_qty.unwrap_or(resting.quantity + resting.filled) - resting.filled;
+        let price_changed = orderbook::Price(price) != orderbook::Price(resting.price);
+
+        if leaves <= orderbook::QTY_EPSILON {
+            book.cancel(order_id);
+            execution.status = OrderStatus::Cancelled as u8;
+            return Ok(execution);
+        }
+
+        if !price_changed && leaves <= resting.quantity {
+            book.reduce(order_id, leaves);
+            execution.remaining_quantity = leaves;
+            execution.status = if resting.filled > 0.0 {
+                OrderStatus::Partial as u8
+            } else {
+                OrderStatus::Submitted as u8
+            };
+            return Ok(execution);
+        }
+
+        if resting.post_only && book.would_cross(resting.side, price) {
+            execution.remaining_quantity = resting.quantity;
+            return Ok(execution);
+        }
+
+        // A re-priced or larger order is re-submitted, so it has to pass
+        // the same checks as a new one.
+        self.admit_amend(order_id, symbol_id, resting.side, Some(price), leaves)?;
+
+        if let Some(book) = self.books.get_mut(&symbol_id) {
+            book.cancel(order_id);
+        }
+        let tif = if resting.post_only { TimeInForce::Gtx } else { TimeInForce::Gtc };
+        let mut execution = self.match_order(order_id, symbol_id, resting.side, tif, Some(price), leaves);
+        if resting.filled > 0.0 {
+            // match_order only counted what the amend itself filled.
+            if let Some(book) = self.books.get_mut(&symbol_id) {
+                book.set_filled(order_id, resting.filled + execution.executed_quantity);
+            }
+            if execution.status == OrderStatus::Submitted as u8 {
+                execution.status = OrderStatus::Partial as u8;
+            }
+        }
+        if let Some(last) = execution.fills.last() {
+            let cascaded = self.process_triggers(symbol_id, last.price, TriggerSource::LastTrade);
+            self.triggered.extend(cascaded);
+        }
+        Ok(execution)
+    }
+
+    /// Risk and balance checks for an amend that re-prices or grows an
+    /// order, run on the `quantity` left to trade. On success the order's
+    /// hold is replaced with one for `quantity` at `price`.
+    fn admit_amend(
+        &mut self,
+        order_id: u64,
+        symbol_id: u32,
+        side: Side,
+        price: Option<f64>,
+        quantity: f64,
+    ) -> Result<(), (u16, String, &'static str)> {
+        let check = RiskCheck {
+            symbol_id,
+            side,
+            limit_price: price,
+            quantity,
+            reference_price: self.reference_price(symbol_id),
+            position: self.ledger.net_qty(symbol_id),
+            ts_ns: self.timestamp(),
+        };
+        self.risk
+            .check(&check)
+            .map_err(|(reason_code, reason_msg)| (reason_code, reason_msg, "risk"))?;
+        let reservation = self
+            .account
+            .amend_requirement(order_id, symbol_id, side, price, quantity, check.position)
+            .map_err(|reason_msg| (account::INSUFFICIENT_BALANCE, reason_msg, "router"))?;
+        self.risk.record(symbol_id, check.ts_ns);
+        self.account.release(order_id);
+        if let Some(reservation) = reservation {
+            self.account.hold(order_id, quantity, reservation);
+        }
+        Ok(())
+    }
+
+    /// Release a triggered conditional order into the matcher.
+    fn release(&mut self, order: ConditionalOrder) -> RustExecution {
+        let tif = TimeInForce::from_u8(order.tif).unwrap_or(TimeInForce::Gtc);
+        self.match_order(
+            order.order_id,
+            order.symbol_id,
+            order.side,
+            tif,
+            order.limit_price,
+            order.quantity,
+        )
+    }
+
+    /// Feed a price into the trigger book and release everything it fires.
+    /// Trades printed by released orders are fed back in as last-trade
+    /// updates until the cascade settles.
+    fn process_triggers(&mut self, symbol_id: u32, price: f64, source: TriggerSource) -> Vec<RustExecution> {
+        let mut executions = Vec::new();
+        let mut updates = VecDeque::from([(price, source)]);
+
+        while let Some((price, source)) = updates.pop_front() {
+            for order in self.triggers.on_price(symbol_id, price, source) {
+                let execution = self.release(order);
+                if let Some(last) = execution.fills.last() {
+                    updates.push_back((last.price, TriggerSource::LastTrade));
+                }
+                executions.push(execution);
+            }
+        }
+
+        executions
+    }
+}
+
+#[pyclass]
+pub struct RustExecutionEngine {
+    next_order_id: AtomicU64,
+    total_executions: AtomicU64,
+    total_latency_ns: AtomicU64,
+    min_latency_ns: AtomicU64,
+    max_latency_ns: AtomicU64,
+    /// `TradingMode` shared with the kill switch, readable without the lock.
+    trading_mode: Arc<AtomicU8>,
+    /// Report zero processing latency so simulated runs are reproducible.
+    simulated: AtomicBool,
+    state: Mutex<EngineState>,
+}
+
+impl Default for RustExecutionEngine {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[pymethods]
+impl RustExecutionEngine {
+    /// With `journal_dir`, replay that journal before trading and keep
+    /// appending to it; see `open_journal`.
+    #[new]
+    #[pyo3(signature = (journal_dir=None, journal_sync=false))]
+    pub fn py_new(journal_dir: Option<PathBuf>, journal_sync: bool) -> PyResult<Self> {
+        let engine = Self::new();
+        if let Some(journal_dir) = journal_dir {
+            engine.recover(&journal_dir, journal_sync)?;
+        }
+        Ok(engine)
+    }
+
+    /// Replay the journal in `journal_dir` (creating it if needed) to rebuild
+    /// open orders, positions and the order id counter, then journal every
+    /// order event from here on to a new segment. Replay starts from the
+    /// newest usable snapshot in the directory, if there is one. A torn
+    /// final record left by a crash is cut off. With `sync`, each record is
+    /// fsynced.
+    ///
+    /// Must be called before the engine takes any order. Returns a summary of
+    /// what was recovered.
+    #[pyo3(signature = (journal_dir, sync=false))]
+    pub fn open_journal(&self, py: Python<'_>, journal_dir: PathBuf, sync: bool) -> PyResult<PyObject> {
+        let recovery = self.recover(&journal_dir, sync)?;
+        let summary = recovery.summary;
+        let dict = PyDict::new(py);
+        dict.set_item("snapshot_seq", recovery.snapshot_seq)?;
+        dict.set_item("skipped_snapshots", recovery.skipped_snapshots)?;
+        dict.set_item("records", summary.records)?;
+        dict.set_item("orders", summary.orders)?;
+        dict.set_item("open_orders", summary.open_orders)?;
+        dict.set_item("fills", summary.fills)?;
+        dict.set_item("rejects", summary.rejects)?;
+        dict.set_item("abandoned_orders", summary.abandoned)?;
+        dict.set_item("torn_bytes", recovery.torn_bytes)?;
+        dict.set_item("next_order_id", self.next_order_id.load(Ordering::Relaxed))?;
+        Ok(dict.into())
+    }
+
+    /// Stop journaling. Returns `False` if no journal was open.
+    pub fn close_journal(&self) -> bool {
+        self.state().journal.take().is_some()
+    }
+
+    /// Where the journal is writing, whether a write has failed and how
+    /// snapshots are going, or `None` without a journal.
+    pub fn get_journal_stats(&self, py: Python<'_>) -> PyResult<Option<PyObject>> {
+        let state = self.state();
+        let Some(journal) = &state.journal else {
+            return Ok(None);
+        };
+        let dict = journal.stats_dict(py)?;
+        dict.set_item("snapshot_every_records", state.snapshot_policy.every_records)?;
+        dict.set_item("keep_snapshots", state.snapshot_policy.keep)?;
+        dict.set_item("prune_journal", state.snapshot_policy.prune_journal)?;
+        Ok(Some(dict.into()))
+    }
+
+    /// Write a snapshot of the full engine state next to the journal, then
+    /// prune per the snapshot policy. Recovery starts from the newest
+    /// snapshot and replays only the journal records after it.
+    pub fn snapshot(&self, py: Python<'_>) -> PyResult<PyObject> {
+        let info = self.state().snapshot().map_err(PyIOError::new_err)?;
+        let dict = PyDict::new(py);
+        dict.set_item("seq", info.seq)?;
+        dict.set_item("path", info.path.display().to_string())?;
+        dict.set_item("bytes", info.bytes)?;
+        dict.set_item("pruned_snapshots", info.pruned_snapshots)?;
+        dict.set_item("pruned_segments", info.pruned_segments)?;
+        Ok(dict.into())
+    }
+
+    /// Snapshot automatically once `every_records` records were journaled
+    /// since the last snapshot (`None` for manual snapshots only). Each
+    /// snapshot keeps the newest `keep` on disk and, with `prune_journal`,
+    /// deletes the journal segments the oldest of them covers.
+    #[pyo3(signature = (every_records=None, keep=2, prune_journal=true))]
+    pub fn set_snapshot_policy(&self, every_records: Option<u64>, keep: usize, prune_journal: bool) -> PyResult<()> {
+        if every_records == Some(0) {
+            return Err(PyValueError::new_err("every_records must be positive"));
+        }
+        if keep == 0 {
+            return Err(PyValueError::new_err("keep must be at least 1"));
+        }
+        self.state().snapshot_policy = SnapshotPolicy {
+            every_records,
+            keep,
+            prune_journal,
+        };
+        Ok(())
+    }
+
+    /// Submit an order. Conditional order types are parked off-book with
+    /// status `PENDING` until `trigger_price` is reached; trailing stops take
+    /// either `trail_amount` or `trail_percent` instead.
+    ///
+    /// A `client_id` already used inside the idempotency window is rejected
+    /// without touching the book; the reject carries the original order id.
+    #[allow(clippy::too_many_arguments)]
+    #[inline(always)]
+    #[pyo3(signature = (
+        symbol_id,
+        side,
+        order_type,
+        price,
+        quantity,
+        tif=TimeInForce::Gtc as u8,
+        trigger_price=None,
+        trail_amount=None,
+        trail_percent=None,
+        trigger_source=0,
+        client_id=None,
+        account=None,
+    ))]
+    pub fn execute_order(
+        &self,
+        symbol_id: u32,
+        side: u8,
+        order_type: u8,
+        price: f64,
+        quantity: f64,
+        tif: u8,
+        trigger_price: Option<f64>,
+        trail_amount: Option<f64>,
+        trail_percent: Option<f64>,
+        trigger_source: u8,
+        client_id: Option<String>,
+        account: Option<String>,
+    ) -> PyResult<Submission> {
+        self.submit(OrderRequest {
+            symbol_id,
+            side,
+            order_type,
+            price,
+            quantity,
+            tif,
+            trigger_price,
+            trail_amount,
+            trail_percent,
+            trigger_source,
+            client_id,
+            account,
+        })
+    }
+
+    /// Cancel a resting or pending conditional order by engine or client id.
+    /// Returns `False` if the order is unknown or already complete.
+    #[pyo3(signature = (order_id=None, client_id=None))]
+    pub fn cancel_order(&self, order_id: Option<u64>, client_id: Option<&str>) -> PyResult<bool> {
+        let mut state = self.state();
+        let cancelled = match Self::resolve_order_id(&state, order_id, client_id)? {
+            Some(order_id) => state.cancel(order_id),
+            None => false,
+        };
+        state.settle();
+        Ok(cancelled)
+    }
+
+    /// Cancel every open order, optionally restricted to one symbol, and
+    /// return the cancelled order ids.
+    #[pyo3(signature = (symbol_id=None))]
+    pub fn cancel_all(&self, symbol_id: Option<u32>) -> Vec<u64> {
+        let mut state = self.state();
+        let cancelled = state.cancel_all(symbol_id);
+        state.settle();
+        cancelled
+    }
+
+    /// Amend an open order's price and/or total quantity. Only a quantity
+    /// decrease keeps queue priority; a re-priced or larger order must pass
+    /// the balance check again and comes back as a `RustReject` if it fails.
+    /// Every amend of an open order counts towards the engine's rate
+    /// limiters, under `account` for account-scoped ones.
+    #[pyo3(signature = (order_id=None, new_price=None, new_qty=None, client_id=None, account=None))]
+    pub fn amend_order(
+        &self,
+        order_id: Option<u64>,
+        new_price: Option<f64>,
+        new_qty: Option<f64>,
+        client_id: Option<&str>,
+        account: Option<&str>,
+    ) -> PyResult<Submission> {
+        let start = Instant::now();
+
+        if new_price.is_none() && new_qty.is_none() {
+            return Err(PyValueError::new_err("amend needs new_price and/or new_qty"));
+        }
+        if let Some(price) = new_price.filter(|p| !(p.is_finite() && *p > 0.0)) {
+            return Err(PyValueError::new_err(format!("invalid price: {}", price)));
+        }
+        if let Some(qty) = new_qty.filter(|q| !(q.is_finite() && *q >= 0.0)) {
+            return Err(PyValueError::new_err(format!("invalid quantity: {}", qty)));
+        }
+
+        let mut state = self.state();
+        let mut submission = match Self::resolve_order_id(&state, order_id, client_id)? {
+            Some(order_id) if state.kill_switch.mode() != TradingMode::Active => {
+                // Outside active trading only pure size reductions go through.
+                let record = state.orders.get(order_id).cloned();
+                match record {
+                    Some(record) if new_price.is_none() && new_qty.is_some_and(|q| q < record.quantity) => {
+                        state.amend(order_id, new_price, new_qty, account.unwrap_or(""))
+                    }
+                    Some(record) => {
+                        let reason_msg = format!("amend refused, trading is {}", state.kill_switch.mode().name());
+                        let ts_ns = state.timestamp();
+                        Submission::Reject(state.block(
+                            ts_ns,
+                            record.client_id.clone(),
+                            record.symbol_id,
+                            Side::from_u8(record.side).unwrap_or(Side::Buy),
+                            new_price.unwrap_or(record.price),
+                            new_qty.unwrap_or(record.quantity),
+                            reason_msg,
+                        ))
+                    }
+                    None => Submission::Execution(RustExecution::unfilled(order_id, 0.0, OrderStatus::Rejected)),
+                }
+            }
+            Some(order_id) => state.amend(order_id, new_price, new_qty, account.unwrap_or("")),
+            None => {
+                let mut unknown = RustExecution::unfilled(0, 0.0, OrderStatus::Rejected);
+                unknown.client_id = client_id.map(str::to_owned);
+                Submission::Execution(unknown)
+            }
+        };
+        state.settle();
+        drop(state);
+        match &mut submission {
+            Submission::Execution(execution) => execution.latency_ns = self.elapsed_ns(start),
+            Submission::Reject(reject) => reject.latency_ns = self.elapsed_ns(start),
+        }
+        Ok(submission)
+    }
+
+    /// Lifecycle state of an order looked up by engine or client id, or
+    /// `None` if the engine no longer knows it.
+    #[pyo3(signature = (order_id=None, client_id=None))]
+    pub fn get_order(&self, order_id: Option<u64>, client_id: Option<&str>) -> PyResult<Option<PyObject>> {
+        let state = self.state();
+        let Some(record) = Self::resolve_order_id(&state, order_id, client_id)?
+            .and_then(|order_id| state.orders.get(order_id))
+        else {
+            return Ok(None);
+        };
+
+        Python::with_gil(|py| {
+            let dict = PyDict::new(py);
+            dict.set_item("order_id", record.order_id)?;
+            dict.set_item("client_id", &record.client_id)?;
+            dict.set_item("symbol_id", record.symbol_id)?;
+            dict.set_item("side", record.side)?;
+            dict.set_item("order_type", record.order_type)?;
+            dict.set_item("tif", record.tif)?;
+            dict.set_item("price", record.price)?;
+            dict.set_item("quantity", record.quantity)?;
+            dict.set_item("filled_quantity", record.filled)?;
+            dict.set_item("status", record.status)?;
+            dict.set_item("created_ns", record.created_ns)?;
+            dict.set_item("updated_ns", record.updated_ns)?;
+            Ok(Some(dict.into()))
+        })
+    }
+
+    /// How long a `client_id` is remembered for duplicate detection.
+    pub fn set_client_id_window(&self, window_ms: u64) {
+        self.state().orders.set_window_ns(window_ms.saturating_mul(1_000_000));
+    }
+
+    /// Feed an external last-trade (`source=0`) or mark (`source=1`) price and
+    /// return the executions of any conditional orders it triggers.
+    #[pyo3(signature = (symbol_id, price, source=0, ts_ns=None))]
+    pub fn update_market_price(
+        &self,
+        symbol_id: u32,
+        price: f64,
+        source: u8,
+        ts_ns: Option<u64>,
+    ) -> PyResult<Vec<RustExecution>> {
+        let source = TriggerSource::from_u8(source)
+            .ok_or_else(|| PyValueError::new_err(format!("invalid trigger source: {}", source)))?;
+        if !(price.is_finite() && price > 0.0) {
+            return Err(PyValueError::new_err(format!("invalid price: {}", price)));
+        }
+        let mut state = self.state();
+        if let Some(ts_ns) = ts_ns {
+            state.advance_to(ts_ns);
+        }
+        let executions = state.process_triggers(symbol_id, price, source);
+        state.settle();
+        Ok(executions)
+    }
+
+    /// Set the venue's displayed quantity at one price level (`quantity=0`
+    /// deletes it). Resting orders at that price move up their queue per the
+    /// symbol's queue model; any they are now crossed by are filled.
+    #[pyo3(signature = (symbol_id, side, price, quantity, ts_ns=None))]
+    pub fn on_book_level(
+        &self,
+        symbol_id: u32,
+        side: u8,
+        price: f64,
+        quantity: f64,
+        ts_ns: Option<u64>,
+    ) -> PyResult<()> {
+        let side = Side::from_u8(side).ok_or_else(|| PyValueError::new_err(format!("invalid side: {}", side)))?;
+        if !(price.is_finite() && price > 0.0 && quantity.is_finite() && quantity >= 0.0) {
+            return Err(PyValueError::new_err(format!("invalid level: {} @ {}", quantity, price)));
+        }
+        let mut state = self.state();
+        if let Some(ts_ns) = ts_ns {
+            state.advance_to(ts_ns);
+        }
+        let fills = state.books.entry(symbol_id).or_default().set_external_level(side, price, quantity);
+        state.record_passive_fills(symbol_id, fills);
+        state.settle();
+        Ok(())
+    }
+
+    /// Apply a trade printed by the venue. Resting orders fill once the trade
+    /// has worked through the quantity queued ahead of them; their fills are
+    /// returned by `drain_maker_fills`. The trade price also drives last-trade
+    /// triggers, whose executions are returned.
+    #[pyo3(signature = (symbol_id, price, quantity, aggressor_side, ts_ns=None))]
+    pub fn on_market_trade(
+        &self,
+        symbol_id: u32,
+        price: f64,
+        quantity: f64,
+        aggressor_side: u8,
+        ts_ns: Option<u64>,
+    ) -> PyResult<Vec<RustExecution>> {
+        let aggressor = Side::from_u8(aggressor_side)
+            .ok_or_else(|| PyValueError::new_err(format!("invalid side: {}", aggressor_side)))?;
+        if !(price.is_finite() && price > 0.0 && quantity.is_finite() && quantity > 0.0) {
+            return Err(PyValueError::new_err(format!("invalid trade: {} @ {}", quantity, price)));
+        }
+        let mut state = self.state();
+        if let Some(ts_ns) = ts_ns {
+            state.advance_to(ts_ns);
+        }
+        let fills = state
+            .books
+            .entry(symbol_id)
+            .or_default()
+            .apply_market_trade(aggressor, price, quantity);
+        state.record_passive_fills(symbol_id, fills);
+        let executions = state.process_triggers(symbol_id, price, TriggerSource::LastTrade);
+        state.settle();
+        Ok(executions)
+    }
+
+    /// Configure the `"order_entry"`, `"ack"` or `"market_data"` latency:
+    /// `"constant"` (`value` ns), `"normal"` (`mean`, `std` ns),
+    /// `"lognormal"` (`mu`, `sigma` of ln ns), `"histogram"` (bin `edges`
+    /// and `weights`), `"interpolated"` (recorded `(timestamp_ns, latency_ns)`
+    /// `samples`) or `"none"`. While any leg is modelled, submitted orders stay
+    /// pending until simulated time reaches their arrival at the matcher.
+    #[pyo3(signature = (
+        kind,
+        model,
+        value=None,
+        mean=None,
+        std=None,
+        mu=None,
+        sigma=None,
+        edges=None,
+        weights=None,
+        samples=None,
+    ))]
+    #[allow(clippy::too_many_arguments)]
+    pub fn set_latency_model(
+        &self,
+        kind: &str,
+        model: &str,
+        value: Option<f64>,
+        mean: Option<f64>,
+        std: Option<f64>,
+        mu: Option<f64>,
+        sigma: Option<f64>,
+        edges: Option<Vec<f64>>,
+        weights: Option<Vec<f64>>,
+        samples: Option<Vec<(u64, f64)>>,
+    ) -> PyResult<()> {
+        let kind = LatencyKind::from_name(kind)
+            .ok_or_else(|| PyValueError::new_err(format!("unknown latency kind: {}", kind)))?;
+        let finite = |name: &str, value: Option<f64>, min: f64| match value {
+            Some(v) if v.is_finite() && v >= min => Ok(v),
+            _ => Err(PyValueError::new_err(format!(
+                "latency model {:?} needs {} >= {}",
+                model, name, min
+            ))),
+        };
+        let missing = |name: &str| PyValueError::new_err(format!("latency model {:?} needs {}", model, name));
+
+        let latency_model = match model {
+            "none" => None,
+            "constant" => Some(LatencyModel::Constant(finite("value", value, 0.0)?)),
+            "normal" => Some(LatencyModel::Normal {
+                mean: finite("mean", mean, 0.0)?,
+                std: finite("std", std, 0.0)?,
+            }),
+            "lognormal" => Some(LatencyModel::LogNormal {
+                mu: finite("mu", mu, f64::MIN)?,
+                sigma: finite("sigma", sigma, 0.0)?,
+            }),
+            "histogram" => Some(
+                LatencyModel::histogram(
+                    edges.ok_or_else(|| missing("edges"))?,
+                    &weights.ok_or_else(|| missing("weights"))?,
+                )
+                .map_err(PyValueError::new_err)?,
+            ),
+            "interpolated" => Some(
+                LatencyModel::interpolated(samples.ok_or_else(|| missing("samples"))?)
+                    .map_err(PyValueError::new_err)?,
+            ),
+            other => return Err(PyValueError::new_err(format!("unknown latency model: {}", other))),
+        };
+
+        let mut state = self.state();
+        *state.latency.model_mut(kind) = latency_model;
+        if !state.latency.is_enabled() {
+            // Nothing is simulated any more; deliver what is still in flight.
+            state.advance_to(u64::MAX);
+        }
+        Ok(())
+    }
+
+    /// Name of the latency model for a leg, or `None` if it is not modelled.
+    pub fn get_latency_model(&self, kind: &str) -> PyResult<Option<&'static str>> {
+        let kind = LatencyKind::from_name(kind)
+            .ok_or_else(|| PyValueError::new_err(format!("unknown latency kind: {}", kind)))?;
+        Ok(self.state().latency.model_mut(kind).as_ref().map(LatencyModel::name))
+    }
+
+    /// Reseed latency sampling so a simulation replays identically.
+    pub fn set_latency_seed(&self, seed: u64) {
+        self.state().latency.rng = LatencyRng::new(seed);
+    }
+
+    /// Advance simulated time, matching orders that reach the matcher by
+    /// `ts_ns`, and return the executions acknowledged by then in ack order.
+    pub fn advance_time(&self, ts_ns: u64) -> Vec<RustExecution> {
+        let mut state = self.state();
+        state.advance_to(ts_ns);
+        state.settle();
+        let later = state.acks.split_off(&(ts_ns.saturating_add(1), 0));
+        std::mem::replace(&mut state.acks, later).into_values().collect()
+    }
+
+    /// Run on a simulated clock starting at `start_ns`, like
+    /// `pkg.common.timing.Clock.enable_simulation`: time only moves with
+    /// timestamped market data and `advance_time`, and reported processing
+    /// latency is 0, so the same inputs always produce the same outputs.
+    /// An engine already on the simulated clock never moves back to an
+    /// earlier `start_ns`.
+    pub fn enable_simulation(&self, start_ns: u64) {
+        let mut state = self.state();
+        state.clock_ns = if state.simulated_clock {
+            state.clock_ns.max(start_ns)
+        } else {
+            start_ns
+        };
+        state.simulated_clock = true;
+        self.simulated.store(true, Ordering::Relaxed);
+    }
+
+    pub fn disable_simulation(&self) {
+        self.state().simulated_clock = false;
+        self.simulated.store(false, Ordering::Relaxed);
+    }
+
+    /// Keep every fill of the engine's own orders, taker and maker, for
+    /// `drain_fills`.
+    pub fn set_fill_recording(&self, enabled: bool) {
+        let mut state = self.state();
+        state.record_fills = enabled;
+        if !enabled {
+            state.fill_log.clear();
+        }
+    }
+
+    pub fn drain_fills(&self) -> Vec<RustFill> {
+        std::mem::take(&mut self.state().fill_log)
+    }
+
+    /// Current simulated time in ns.
+    pub fn get_time(&self) -> u64 {
+        self.state().clock_ns
+    }
+
+    /// Install a fee schedule for a venue, or for one symbol overriding its
+    /// venue's. `tiers` are `(min_volume, maker_bps, taker_bps)` brackets of
+    /// traded notional; negative rates are rebates. Fees are charged on the
+    /// quote notional, or on the quantity in the base asset if
+    /// `charge_in_base`.
+    #[pyo3(signature = (tiers, venue_code=None, symbol_id=None, fee_currency="USDT", charge_in_base=false))]
+    pub fn set_fee_schedule(
+        &self,
+        tiers: Vec<(f64, f64, f64)>,
+        venue_code: Option<u8>,
+        symbol_id: Option<u32>,
+        fee_currency: &str,
+        charge_in_base: bool,
+    ) -> PyResult<()> {
+        let tiers = tiers
+            .into_iter()
+            .map(|(min_volume, maker_bps, taker_bps)| FeeTier {
+                min_volume,
+                maker_bps,
+                taker_bps,
+            })
+            .collect();
+        let schedule =
+            FeeSchedule::new(tiers, fee_currency.to_string(), charge_in_base).map_err(PyValueError::new_err)?;
+
+        let mut state = self.state();
+        match (venue_code, symbol_id) {
+            (Some(venue_code), None) => state.fees.venue_schedules.insert(venue_code, schedule),
+            (None, Some(symbol_id)) => state.fees.symbol_schedules.insert(symbol_id, schedule),
+            _ => return Err(PyValueError::new_err("exactly one of venue_code or symbol_id is required")),
+        };
+        Ok(())
+    }
+
+    /// Venue a symbol trades on (`pkg.schemas.common.VenueCode`), selecting
+    /// its fee schedule and the volume its fills count towards.
+    pub fn set_symbol_venue(&self, symbol_id: u32, venue_code: u8) {
+        self.state().fees.symbol_venues.insert(symbol_id, venue_code);
+    }
+
+    /// Seed a venue's traded notional, e.g. with the account's 30-day volume.
+    /// This replaces the volume counted so far; the seed rolls off in one
+    /// go once it is 30 days old.
+    pub fn set_trading_volume(&self, venue_code: u8, volume: f64) -> PyResult<()> {
+        if !(volume.is_finite() && volume >= 0.0) {
+            return Err(PyValueError::new_err(format!("invalid volume: {}", volume)));
+        }
+        let mut state = self.state();
+        let ts_ns = state.timestamp();
+        state.fees.volumes.insert(venue_code, VenueVolume::seed(ts_ns, volume));
+        Ok(())
+    }
+
+    /// Rates currently applied to a symbol, or `None` if it trades for free.
+    pub fn get_fee_rates(&self, symbol_id: u32) -> PyResult<Option<PyObject>> {
+        let state = self.state();
+        let Some(schedule) = state.fees.schedule(symbol_id) else {
+            return Ok(None);
+        };
+        let venue_code = state.fees.venue_of(symbol_id);
+        let volume = state.fees.volume(venue_code, state.timestamp());
+        let tier = schedule.tier(volume);
+
+        Python::with_gil(|py| {
+            let dict = PyDict::new(py);
+            dict.set_item("venue_code", venue_code)?;
+            dict.set_item("volume", volume)?;
+            dict.set_item("maker_bps", tier.maker_bps)?;
+            dict.set_item("taker_bps", tier.taker_bps)?;
+            dict.set_item("fee_currency", &schedule.currency)?;
+            dict.set_item("charge_in_base", schedule.charge_in_base)?;
+            Ok(Some(dict.into()))
+        })
+    }
+
+    /// Enable pre-trade risk checks on a symbol, replacing any earlier limits.
+    /// Orders breaching a limit come back as a `RustReject` carrying the
+    /// `pkg.schemas.orders.RejectReason` code; `None` disables a check.
+    #[pyo3(signature = (
+        symbol_id,
+        max_order_qty=None,
+        max_order_notional=None,
+        max_position_notional=None,
+        max_position_qty=None,
+        max_concentration_pct=None,
+        price_band_pct=None,
+        max_orders_per_sec=None,
+    ))]
+    #[allow(clippy::too_many_arguments)]
+    pub fn set_risk_limits(
+        &self,
+        symbol_id: u32,
+        max_order_qty: Option<f64>,
+        max_order_notional: Option<f64>,
+        max_position_notional: Option<f64>,
+        max_position_qty: Option<f64>,
+        max_concentration_pct: Option<f64>,
+        price_band_pct: Option<f64>,
+        max_orders_per_sec: Option<u32>,
+    ) -> PyResult<()> {
+        let limits = [
+            ("max_order_qty", max_order_qty),
+            ("max_order_notional", max_order_notional),
+            ("max_position_notional", max_position_notional),
+            ("max_position_qty", max_position_qty),
+            ("max_concentration_pct", max_concentration_pct),
+            ("price_band_pct", price_band_pct),
+        ];
+        for (name, limit) in limits {
+            if limit.is_some_and(|v| !(v.is_finite() && v >= 0.0)) {
+                return Err(PyValueError::new_err(format!("invalid {}: {:?}", name, limit)));
+            }
+        }
+
+        self.state().risk.set_limits(
+            symbol_id,
+            RiskLimits {
+                max_order_qty,
+                max_order_notional,
+                max_position_notional,
+                max_position_qty,
+                max_concentration_pct,
+                price_band_pct,
+                max_orders_per_sec,
+            },
+        );
+        Ok(())
+    }
+
+    /// Remove a symbol's risk limits. Returns `False` if it had none.
+    pub fn clear_risk_limits(&self, symbol_id: u32) -> bool {
+        self.state().risk.remove_limits(symbol_id)
+    }
+
+    /// Limits configured for a symbol and the net position they are checked
+    /// against, or `None` if the symbol is unchecked.
+    pub fn get_risk_limits(&self, symbol_id: u32) -> PyResult<Option<PyObject>> {
+        let state = self.state();
+        let Some(limits) = state.risk.limits(symbol_id) else {
+            return Ok(None);
+        };
+
+        Python::with_gil(|py| {
+            let dict = PyDict::new(py);
+            dict.set_item("symbol_id", symbol_id)?;
+            dict.set_item("max_order_qty", limits.max_order_qty)?;
+            dict.set_item("max_order_notional", limits.max_order_notional)?;
+            dict.set_item("max_position_notional", limits.max_position_notional)?;
+            dict.set_item("max_position_qty", limits.max_position_qty)?;
+            dict.set_item("max_concentration_pct", limits.max_concentration_pct)?;
+            dict.set_item("price_band_pct", limits.price_band_pct)?;
+            dict.set_item("max_orders_per_sec", limits.max_orders_per_sec)?;
+            dict.set_item("position", state.ledger.net_qty(symbol_id))?;
+            dict.set_item("capital", state.risk.capital)?;
+            Ok(Some(dict.into()))
+        })
+    }
+
+    /// Account capital that `max_concentration_pct` is measured against.
+    #[pyo3(signature = (capital=None))]
+    pub fn set_risk_capital(&self, capital: Option<f64>) -> PyResult<()> {
+        if capital.is_some_and(|c| !(c.is_finite() && c > 0.0)) {
+            return Err(PyValueError::new_err(format!("invalid capital: {:?}", capital)));
+        }
+        self.state().risk.capital = capital;
+        Ok(())
+    }
+
+    /// Switch the kill switch: 0 active, 1 halted (cancels every open order),
+    /// 2 cancel-only, 3 reduce-only. Returning to active clears the trip.
+    #[pyo3(signature = (mode, reason=None))]
+    pub fn set_trading_mode(&self, mode: u8, reason: Option<String>) -> PyResult<()> {
+        let mode = TradingMode::from_u8(mode)
+            .ok_or_else(|| PyValueError::new_err(format!("invalid trading mode: {}", mode)))?;
+        let mut state = self.state();
+        let ts_ns = state.timestamp();
+        state.kill_switch.set_mode(mode, reason, ts_ns);
+        state.settle();
+        Ok(())
+    }
+
+    /// Current `TradingMode` value, read without taking the engine lock.
+    pub fn get_trading_mode(&self) -> u8 {
+        self.trading_mode.load(Ordering::Acquire)
+    }
+
+    /// Conditions that trip the kill switch into `trip_mode` automatically:
+    /// realized daily loss after fees, consecutive losing closing fills, and
+    /// more than `max_errors` rejected or invalid submissions (or
+    /// `record_error` calls) within `error_window_ms`.
+    #[pyo3(signature = (
+        daily_loss_limit=None,
+        max_consecutive_losses=None,
+        max_errors=None,
+        error_window_ms=60_000,
+        trip_mode=1,
+    ))]
+    pub fn configure_kill_switch(
+        &self,
+        daily_loss_limit: Option<f64>,
+        max_consecutive_losses: Option<u32>,
+        max_errors: Option<u32>,
+        error_window_ms: u64,
+        trip_mode: u8,
+    ) -> PyResult<()> {
+        let trip_mode = match TradingMode::from_u8(trip_mode) {
+            Some(TradingMode::Active) | None => {
+                return Err(PyValueError::new_err(format!("invalid trip mode: {}", trip_mode)))
+            }
+            Some(mode) => mode,
+        };
+        if daily_loss_limit.is_some_and(|limit| !(limit.is_finite() && limit > 0.0)) {
+            return Err(PyValueError::new_err(format!(
+                "invalid daily loss limit: {:?}",
+                daily_loss_limit
+            )));
+        }
+
+        self.state().kill_switch.limits = killswitch::TripLimits {
+            daily_loss_limit,
+            max_consecutive_losses,
+            max_errors,
+            error_window_ns: error_window_ms.saturating_mul(1_000_000),
+            trip_mode,
+        };
+        Ok(())
+    }
+
+    /// Count an external error (e.g. exchange or API failure) towards the
+    /// kill switch's error burst limit.
+    pub fn record_error(&self) {
+        let mut state = self.state();
+        let ts_ns = state.timestamp();
+        state.kill_switch.on_error(ts_ns);
+        state.settle();
+    }
+
+    pub fn get_kill_switch(&self) -> PyResult<PyObject> {
+        let state = self.state();
+        let kill_switch = &state.kill_switch;
+        let mode = kill_switch.mode();
+
+        Python::with_gil(|py| {
+            let dict = PyDict::new(py);
+            dict.set_item("mode", mode as u8)?;
+            dict.set_item("mode_name", mode.name())?;
+            dict.set_item("reason", &kill_switch.reason)?;
+            dict.set_item("tripped_ns", kill_switch.tripped_ns)?;
+            dict.set_item("daily_pnl", kill_switch.daily_pnl)?;
+            dict.set_item("consecutive_losses", kill_switch.consecutive_losses)?;
+            dict.set_item("recent_errors", kill_switch.recent_errors())?;
+            dict.set_item("daily_loss_limit", kill_switch.limits.daily_loss_limit)?;
+            dict.set_item("max_consecutive_losses", kill_switch.limits.max_consecutive_losses)?;
+            dict.set_item("max_errors", kill_switch.limits.max_errors)?;
+            dict.set_item("blocked_orders", state.blocked.len())?;
+            Ok(dict.into())
+        })
+    }
+
+    /// Enforce a `TokenBucketLimiter` or `SlidingWindowLimiter` on every new
+    /// order, keyed by any of `"symbol"`, `"venue"` (see `set_symbol_venue`)
+    /// and `"account"`. Limiter state is shared with the Python object, so
+    /// the same limiter can also be used outside the engine. Refused orders
+    /// are rejected with `RATE_LIMIT`.
+    #[pyo3(signature = (limiter, scope=vec!["symbol".to_string()]))]
+    pub fn add_rate_limiter(&self, limiter: &Bound<'_, PyAny>, scope: Vec<String>) -> PyResult<()> {
+        let scope = RateScope::parse(&scope).map_err(PyValueError::new_err)?;
+        let limiter: Arc<dyn RateLimiter> = if let Ok(bucket) = limiter.downcast::<TokenBucketLimiter>() {
+            bucket.borrow().inner.clone()
+        } else if let Ok(window) = limiter.downcast::<SlidingWindowLimiter>() {
+            window.borrow().inner.clone()
+        } else {
+            return Err(PyTypeError::new_err(
+                "limiter must be a TokenBucketLimiter or SlidingWindowLimiter",
+            ));
+        };
+        self.state().rate_limits.push((scope, limiter));
+        Ok(())
+    }
+
+    pub fn clear_rate_limiters(&self) {
+        self.state().rate_limits.clear();
+    }
+
+    /// Account for a symbol's fills in balances: spot swaps `base` for
+    /// `quote`; `kind="perpetual"` posts `quote` margin at the symbol's
+    /// leverage (1x until `set_leverage`) in `"cross"` or `"isolated"` mode.
+    /// Orders that need more than is available are rejected with
+    /// `INSUFFICIENT_BALANCE`.
+    #[pyo3(signature = (
+        symbol_id,
+        base,
+        quote,
+        kind="spot",
+        max_leverage=1.0,
+        maintenance_rate=0.005,
+        margin_mode="cross",
+    ))]
+    #[allow(clippy::too_many_arguments)]
+    pub fn set_instrument(
+        &self,
+        symbol_id: u32,
+        base: String,
+        quote: String,
+        kind: &str,
+        max_leverage: f64,
+        maintenance_rate: f64,
+        margin_mode: &str,
+    ) -> PyResult<()> {
+        let perpetual = match kind {
+            "spot" => None,
+            "perpetual" => {
+                let mode = MarginMode::from_name(margin_mode)
+                    .ok_or_else(|| PyValueError::new_err(format!("invalid margin mode: {}", margin_mode)))?;
+                if !(max_leverage.is_finite() && max_leverage >= 1.0) {
+                    return Err(PyValueError::new_err(format!("invalid max leverage: {}", max_leverage)));
+                }
+                if !(0.0..1.0).contains(&maintenance_rate) {
+                    return Err(PyValueError::new_err(format!(
+                        "invalid maintenance rate: {}",
+                        maintenance_rate
+                    )));
+                }
+                Some(Perpetual {
+                    leverage: 1.0,
+                    max_leverage,
+                    maintenance_rate,
+                    mode,
+                })
+            }
+            _ => return Err(PyValueError::new_err(format!("invalid instrument kind: {}", kind))),
+        };
+        self.state().account.set_instrument(
+            symbol_id,
+            Instrument {
+                base,
+                quote,
+                perpetual,
+            },
+        );
+        Ok(())
+    }
+
+    /// Leverage for new perpetual exposure, up to the instrument's maximum.
+    pub fn set_leverage(&self, symbol_id: u32, leverage: f64) -> PyResult<()> {
+        self.state()
+            .account
+            .set_leverage(symbol_id, leverage)
+            .map_err(PyValueError::new_err)
+    }
+
+    pub fn deposit(&self, currency: &str, amount: f64) -> PyResult<()> {
+        if !(amount.is_finite() && amount > 0.0) {
+            return Err(PyValueError::new_err(format!("invalid amount: {}", amount)));
+        }
+        self.state().account.deposit(currency, amount);
+        Ok(())
+    }
+
+    /// Withdraw from the available (unreserved, unposted) balance.
+    pub fn withdraw(&self, currency: &str, amount: f64) -> PyResult<()> {
+        if !(amount.is_finite() && amount > 0.0) {
+            return Err(PyValueError::new_err(format!("invalid amount: {}", amount)));
+        }
+        self.state()
+            .account
+            .withdraw(currency, amount)
+            .map_err(PyValueError::new_err)
+    }
+
+    /// `{currency: {"total", "reserved", "margin", "available"}}`
+    pub fn get_balances(&self) -> PyResult<PyObject> {
+        let state = self.state();
+        Python::with_gil(|py| {
+            let balances = PyDict::new(py);
+            for (currency, balance) in state.account.balances() {
+                let dict = PyDict::new(py);
+                dict.set_item("total", balance.total)?;
+                dict.set_item("reserved", balance.reserved)?;
+                dict.set_item("margin", balance.margin)?;
+                dict.set_item("available", balance.available())?;
+                balances.set_item(currency, dict)?;
+            }
+            Ok(balances.into())
+        })
+    }
+
+    /// Margin, maintenance requirement and liquidation price of a perpetual
+    /// position, or `None` for other symbols.
+    pub fn get_margin(&self, symbol_id: u32) -> PyResult<Option<PyObject>> {
+        let state = self.state();
+        let mark_price = state.mark_price(symbol_id);
+        let (net_qty, entry_price) = state
+            .ledger
+            .snapshot(symbol_id, mark_price)
+            .map_or((0.0, 0.0), |p| (p.net_qty, p.avg_entry_price));
+        let Some(margin) = state.account.margin(symbol_id, net_qty, entry_price, mark_price) else {
+            return Ok(None);
+        };
+
+        Python::with_gil(|py| {
+            let dict = PyDict::new(py);
+            dict.set_item("symbol_id", symbol_id)?;
+            dict.set_item("margin_mode", margin.mode.name())?;
+            dict.set_item("leverage", margin.leverage)?;
+            dict.set_item("max_leverage", margin.max_leverage)?;
+            dict.set_item("net_qty", net_qty)?;
+            dict.set_item("entry_price", entry_price)?;
+            dict.set_item("mark_price", mark_price)?;
+            dict.set_item("position_margin", margin.position_margin)?;
+            dict.set_item("maintenance_margin", margin.maintenance_margin)?;
+            dict.set_item("liquidation_price", margin.liquidation_price)?;
+            Ok(Some(dict.into()))
+        })
+    }
+
+    /// How closing fills are matched to open lots for realized PnL:
+    /// `"fifo"` (default), `"lifo"` or `"average"`.
+    pub fn set_cost_method(&self, cost_method: &str) -> PyResult<()> {
+        self.state().ledger.set_method(parse_cost_method(cost_method)?);
+        Ok(())
+    }
+
+    /// Price unrealized PnL is measured against; without one the last trade
+    /// or mid is used.
+    pub fn set_mark_price(&self, symbol_id: u32, price: f64) -> PyResult<()> {
+        if !(price.is_finite() && price > 0.0) {
+            return Err(PyValueError::new_err(format!("invalid mark price: {}", price)));
+        }
+        self.state().ledger.set_mark(symbol_id, price);
+        Ok(())
+    }
+
+    /// Net quantity, average entry, realized/unrealized PnL and fees from the
+    /// engine's own fills, or `None` if the symbol never filled.
+    pub fn get_position(&self, symbol_id: u32) -> PyResult<Option<PyObject>> {
+        let state = self.state();
+        let Some(position) = state.ledger.snapshot(symbol_id, state.mark_price(symbol_id)) else {
+            return Ok(None);
+        };
+        Python::with_gil(|py| position_dict(py, &position).map(Some))
+    }
+
+    pub fn get_positions(&self) -> PyResult<Vec<PyObject>> {
+        let positions = self.position_snapshots();
+        Python::with_gil(|py| positions.iter().map(|p| position_dict(py, p)).collect())
+    }
+
+    pub fn get_portfolio(&self) -> PyResult<PyObject> {
+        let positions = self.position_snapshots();
+        let method = self.state().ledger.method();
+        Python::with_gil(|py| portfolio_dict(py, &positions, method))
+    }
+
+    /// Orders and amends refused by the kill switch since the last call.
+    pub fn drain_blocked_orders(&self) -> Vec<RustReject> {
+        std::mem::take(&mut self.state().blocked)
+    }
+
+    /// Queue model used to advance resting orders on a symbol when its level
+    /// shrinks: `"RiskAverse"`, `"PowerProb"` (with `power`) or `"LogProb"`.
+    #[pyo3(signature = (symbol_id, model, power=2.0))]
+    pub fn set_queue_model(&self, symbol_id: u32, model: &str, power: f64) -> PyResult<()> {
+        let model = match model {
+            "RiskAverse" => QueueModel::RiskAverse,
+            "PowerProb" if power.is_finite() && power > 0.0 => QueueModel::PowerProb(power),
+            "PowerProb" => return Err(PyValueError::new_err(format!("invalid power: {}", power))),
+            "LogProb" => QueueModel::LogProb,
+            other => return Err(PyValueError::new_err(format!("unknown queue model: {}", other))),
+        };
+        self.state().books.entry(symbol_id).or_default().queue_model = model;
+        Ok(())
+    }
+
+    /// Estimated queue position of a resting order, or `None` if it is not
+    /// on a book.
+    #[pyo3(signature = (order_id=None, client_id=None))]
+    pub fn get_queue_position(&self, order_id: Option<u64>, client_id: Option<&str>) -> PyResult<Option<PyObject>> {
+        let state = self.state();
+        let Some(order_id) = Self::resolve_order_id(&state, order_id, client_id)? else {
+            return Ok(None);
+        };
+        let Some(book) = state.symbol_of(order_id).and_then(|symbol_id| state.books.get(&symbol_id)) else {
+            return Ok(None);
+        };
+        let (Some(order), Some(level)) = (book.get(order_id), book.level_of(order_id)) else {
+            return Ok(None);
+        };
+
+        Python::with_gil(|py| {
+            let dict = PyDict::new(py);
+            dict.set_item("order_id", order_id)?;
+            dict.set_item("price", order.price)?;
+            dict.set_item("quantity", order.quantity)?;
+            dict.set_item("queue_ahead", order.queue_ahead)?;
+            dict.set_item("level_quantity", level.quantity())?;
+            dict.set_item("queue_model", book.queue_model.name())?;
+            Ok(Some(dict.into()))
+        })
+    }
+
+    /// Executions of conditional orders triggered by the engine's own trades.
+    pub fn drain_triggered(&self) -> Vec<RustExecution> {
+        std::mem::take(&mut self.state().triggered)
+    }
+
+    /// Conditional orders still waiting for their trigger on a symbol.
+    pub fn get_conditional_orders(&self, symbol_id: u32) -> PyResult<PyObject> {
+        let state = self.state();
+        Python::with_gil(|py| {
+            let orders = PyList::empty(py);
+            for order in state.triggers.orders(symbol_id) {
+                let dict = PyDict::new(py);
+                dict.set_item("order_id", order.order_id)?;
+                dict.set_item("side", order.side.as_u8())?;
+                dict.set_item("trigger_price", order.trigger_price)?;
+                dict.set_item("limit_price", order.limit_price)?;
+                dict.set_item("quantity", order.quantity)?;
+                dict.set_item("tif", order.tif)?;
+                orders.append(dict)?;
+            }
+            Ok(orders.into())
+        })
+    }
+
+    pub fn get_stats(&self) -> PyResult<PyObject> {
+        let total_executions = self.total_executions.load(Ordering::Relaxed);
+        let total_latency = self.total_latency_ns.load(Ordering::Relaxed);
+        let min_latency = self.min_latency_ns.load(Ordering::Relaxed);
+        let max_latency = self.max_latency_ns.load(Ordering::Relaxed);
+
+        let avg_latency = total_latency.checked_div(total_executions).unwrap_or(0);
+        let maker_fills_dropped = self.state().maker_fills_dropped;
+
+        Python::with_gil(|py| {
+            let dict = PyDict::new(py);
+            dict.set_item("total_executions", total_executions)?;
+            dict.set_item("avg_latency_ns", avg_latency)?;
+            dict.set_item("min_latency_ns", if min_latency == u64::MAX { 0 } else { min_latency })?;
+            dict.set_item("max_latency_ns", max_latency)?;
+            dict.set_item("maker_fills_dropped", maker_fills_dropped)?;
+            Ok(dict.into())
+        })
+    }
+
+    /// Aggregated L2 depth for a symbol as `(price, quantity, order_count)` tuples.
+    #[pyo3(signature = (symbol_id, levels=10))]
+    pub fn get_book(&self, symbol_id: u32, levels: usize) -> PyResult<PyObject> {
+        let state = self.state();
+        let (bids, asks) = match state.books.get(&symbol_id) {
+            Some(book) => (book.depth(Side::Buy, levels), book.depth(Side::Sell, levels)),
+            None => (Vec::new(), Vec::new()),
+        };
+
+        Python::with_gil(|py| {
+            let dict = PyDict::new(py);
+            dict.set_item("symbol_id", symbol_id)?;
+            dict.set_item("bids", bids)?;
+            dict.set_item("asks", asks)?;
+            Ok(dict.into())
+        })
+    }
+
+    /// Minimum price increment, used when repricing post-only orders.
+    pub fn set_tick_size(&self, symbol_id: u32, tick_size: f64) -> PyResult<()> {
+        if !(tick_size.is_finite() && tick_size > 0.0) {
+            return Err(PyValueError::new_err(format!("invalid tick size: {}", tick_size)));
+        }
+        self.state().tick_sizes.insert(symbol_id, tick_size);
+        Ok(())
+    }
+
+    /// Select the slippage model applied to taker fills on a symbol:
+    /// `"book_walk"` (default, fill at the levels walked), `"fixed_bps"`
+    /// (`bps`), `"spread"` (`spread_fraction` of the arrival spread) or
+    /// `"sqrt_impact"` (`coefficient`, `volatility`, `daily_volume`).
+    #[pyo3(signature = (
+        symbol_id,
+        model,
+        bps=None,
+        spread_fraction=None,
+        coefficient=1.0,
+        volatility=None,
+        daily_volume=None,
+    ))]
+    #[allow(clippy::too_many_arguments)]
+    pub fn set_slippage_model(
+        &self,
+        symbol_id: u32,
+        model: &str,
+        bps: Option<f64>,
+        spread_fraction: Option<f64>,
+        coefficient: f64,
+        volatility: Option<f64>,
+        daily_volume: Option<f64>,
+    ) -> PyResult<()> {
+        let non_negative = |name: &str, value: Option<f64>| match value {
+            Some(v) if v.is_finite() && v >= 0.0 => Ok(v),
+            _ => Err(PyValueError::new_err(format!(
+                "slippage model {:?} needs a non-negative {}",
+                model, name
+            ))),
+        };
+
+        let model: Box<dyn SlippageModel> = match model {
+            "book_walk" => Box::new(BookWalk),
+            "fixed_bps" => Box::new(FixedBps {
+                bps: non_negative("bps", bps)?,
+            }),
+            "spread" => Box::new(SpreadProportional {
+                fraction: non_negative("spread_fraction", spread_fraction)?,
+            }),
+            "sqrt_impact" => Box::new(SquareRootImpact {
+                coefficient: non_negative("coefficient", Some(coefficient))?,
+                volatility: non_negative("volatility", volatility)?,
+                daily_volume: non_negative("daily_volume", daily_volume)?,
+            }),
+            other => {
+                return Err(PyValueError::new_err(format!("unknown slippage model: {}", other)))
+            }
+        };
+        self.state().slippage_models.insert(symbol_id, model);
+        Ok(())
+    }
+
+    /// Name of the slippage model configured for a symbol.
+    pub fn get_slippage_model(&self, symbol_id: u32) -> &'static str {
+        self.state()
+            .slippage_models
+            .get(&symbol_id)
+            .map_or(BookWalk.name(), |model| model.name())
+    }
+
+    /// When enabled, crossing GTX orders are moved one tick behind the opposite
+    /// touch instead of being rejected.
+    pub fn set_post_only_reprice(&self, enabled: bool) {
+        self.state().post_only_reprice = enabled;
+    }
+
+    /// What an incoming order does when it reaches one of the engine's own
+    /// resting orders, since engine orders never trade with each other:
+    /// `"cancel_resting"` (the default) cancels the resting order and keeps
+    /// matching, `"cancel_incoming"` cancels the rest of the incoming order.
+    pub fn set_self_trade_prevention(&self, mode: &str) -> PyResult<()> {
+        let mode = SelfTradePrevention::from_name(mode)
+            .ok_or_else(|| PyValueError::new_err(format!("unknown self-trade prevention mode: {}", mode)))?;
+        self.state().self_trade_prevention = mode;
+        Ok(())
+    }
+
+    pub fn get_self_trade_prevention(&self) -> &'static str {
+        self.state().self_trade_prevention.name()
+    }
+
+    /// Fills against resting orders since the last call, from the maker's side.
+    pub fn drain_maker_fills(&self) -> Vec<RustFill> {
+        std::mem::take(&mut self.state().maker_fills).into()
+    }
+
+    /// Buffer at most `capacity` undrained maker fills (65536 by default),
+    /// dropping the oldest beyond that; 0 stops buffering them.
+    pub fn set_maker_fill_capacity(&self, capacity: usize) {
+        let mut state = self.state();
+        state.maker_fill_capacity = capacity;
+        let excess = state.maker_fills.len().saturating_sub(capacity);
+        state.maker_fills.drain(..excess);
+        state.maker_fills_dropped += excess as u64;
+    }
+
+    pub fn reset_stats(&self) {
+        self.total_executions.store(0, Ordering::Relaxed);
+        self.total_latency_ns.store(0, Ordering::Relaxed);
+        self.min_latency_ns.store(u64::MAX, Ordering::Relaxed);
+        self.max_latency_ns.store(0, Ordering::Relaxed);
+        self.state().maker_fills_dropped = 0;
+    }
+}
+
+impl RustExecutionEngine {
+    pub fn new() -> Self {
+        let state = EngineState {
+            maker_fill_capacity: MAKER_FILL_CAPACITY,
+            ..EngineState::default()
+        };
+        Self {
+            next_order_id: AtomicU64::new(1),
+            total_executions: AtomicU64::new(0),
+            total_latency_ns: AtomicU64::new(0),
+            min_latency_ns: AtomicU64::new(u64::MAX),
+            max_latency_ns: AtomicU64::new(0),
+            trading_mode: state.kill_switch.mode_handle(),
+            simulated: AtomicBool::new(false),
+            state: Mutex::new(state),
+        }
+    }
+
+    fn recover(&self, journal_dir: &Path, sync: bool) -> PyResult<Recovery> {
+        let mut state = self.state();
+        if state.journal.is_some() {
+            return Err(PyValueError::new_err("a journal is already open"));
+        }
+        if self.next_order_id.load(Ordering::Relaxed) != 1 {
+            return Err(PyValueError::new_err(
+                "the journal must be opened before the engine takes orders",
+            ));
+        }
+        let loaded = state.load_snapshot(journal_dir).map_err(PyIOError::new_err)?;
+        let summary = state.replay(&loaded.recovered.records);
+        let next_order_id = loaded
+            .snapshot
+            .map_or(1, |(_, next_order_id)| next_order_id)
+            .max(summary.max_order_id + 1);
+        self.next_order_id.store(next_order_id, Ordering::Relaxed);
+
+        let mut journal = Journal::open(journal_dir, loaded.recovered.next_seq, next_order_id, sync)
+            .map_err(|e| PyIOError::new_err(format!("{}: {}", journal_dir.display(), e)))?;
+        // Orders that could not be put back are closed on the record too.
+        for order_id in &summary.abandoned {
+            if let Some(record) = state.orders.get(*order_id) {
+                journal.append(
+                    record.updated_ns,
+                    &JournalEvent::Update(OrderUpdate {
+                        order_id: record.order_id,
+                        status: record.status,
+                        price: record.price,
+                        quantity: record.quantity,
+                        filled: record.filled,
+                    }),
+                );
+            }
+        }
+        state.journal = Some(journal);
+        Ok(Recovery {
+            summary,
+            snapshot_seq: loaded.snapshot.map(|(seq, _)| seq),
+            skipped_snapshots: loaded.skipped,
+            torn_bytes: loaded.recovered.torn_bytes,
+        })
+    }
+
+    /// Validate and run one order through the trigger book and matcher.
+    #[inline(always)]
+    pub fn submit(&self, request: OrderRequest) -> PyResult<Submission> {
+        let result = self.submit_inner(request);
+        let failed = match &result {
+            Err(_) => true,
+            Ok(Submission::Reject(reject)) => reject.reason_code != risk::KILL_SWITCH,
+            Ok(Submission::Execution(_)) => false,
+        };
+        if failed {
+            let mut state = self.state();
+            let ts_ns = state.timestamp();
+            state.kill_switch.on_error(ts_ns);
+            state.settle();
+        }
+        result
+    }
+
+    #[inline(always)]
+    fn submit_inner(&self, request: OrderRequest) -> PyResult<Submission> {
+        let start = Instant::now();
+        let OrderRequest {
+            symbol_id,
+            side,
+            order_type,
+            price,
+            quantity,
+            tif,
+            trigger_price,
+            trail_amount,
+            trail_percent,
+            trigger_source,
+            client_id,
+            account,
+        } = request;
+
+        let side = Side::from_u8(side)
+            .ok_or_else(|| PyValueError::new_err(format!("invalid side: {}", side)))?;
+        let order_type = OrderType::from_u8(order_type)
+            .ok_or_else(|| PyValueError::new_err(format!("invalid order type: {}", order_type)))?;
+        let tif_code = tif;
+        let tif = TimeInForce::from_u8(tif)
+            .ok_or_else(|| PyValueError::new_err(format!("invalid time in force: {}", tif)))?;
+        let source = TriggerSource::from_u8(trigger_source).ok_or_else(|| {
+            PyValueError::new_err(format!("invalid trigger source: {}", trigger_source))
+        })?;
+        if !(quantity.is_finite() && quantity > 0.0) {
+            return Err(PyValueError::new_err(format!("invalid quantity: {}", quantity)));
+        }
+        if order_type.has_limit_price() && !(price.is_finite() && price > 0.0) {
+            return Err(PyValueError::new_err(format!("invalid limit price: {}", price)));
+        }
+        let limit = order_type.has_limit_price().then_some(price);
+
+        let trigger = match order_type.trigger_kind() {
+            None => None,
+            Some(TriggerKind::TrailingStop) => {
+                let trailing = match (trail_amount, trail_percent) {
+                    (Some(amount), None) if amount.is_finite() && amount > 0.0 => {
+                        TrailingOffset::Absolute(amount)
+                    }
+                    (None, Some(pct)) if pct.is_finite() && pct > 0.0 && pct < 100.0 => {
+                        TrailingOffset::Percent(pct)
+                    }
+                    _ => {
+                        return Err(PyValueError::new_err(
+                            "trailing stop needs exactly one positive trail_amount or trail_percent",
+                        ))
+                    }
+                };
+                // NaN leaves the trigger to be anchored to the next price.
+                let trigger = match trigger_price {
+                    None => f64::NAN,
+                    Some(trigger) if trigger.is_finite() && trigger > 0.0 => trigger,
+                    Some(trigger) => {
+                        return Err(PyValueError::new_err(format!("invalid trigger price: {}", trigger)))
+                    }
+                };
+                Some((TriggerKind::TrailingStop, trigger, Some(trailing)))
+            }
+            Some(kind) => match trigger_price {
+                Some(trigger) if trigger.is_finite() && trigger > 0.0 => Some((kind, trigger, None)),
+                _ => {
+                    return Err(PyValueError::new_err(format!(
+                        "invalid trigger price: {:?}",
+                        trigger_price
+                    )))
+                }
+            },
+        };
+
+        let mut execution = {
+            let mut state = self.state();
+            if let Some(error) = state.journal.as_ref().and_then(Journal::error) {
+                return Err(PyIOError::new_err(format!(
+                    "journal write failed, refusing new orders: {}",
+                    error
+                )));
+            }
+            let ts_ns = state.timestamp();
+
+            if let Some(original) = client_id
+                .as_deref()
+                .and_then(|cid| state.orders.duplicate_of(cid, ts_ns))
+            {
+                let reject = RustReject {
+                    ts_ns,
+                    client_id,
+                    symbol_id,
+                    side: side.as_u8(),
+                    price,
+                    quantity,
+                    reason_code: orders::DUPLICATE_CLIENT_ID,
+                    reason_msg: format!("client_id already used by order {}", original),
+                    source: "router".to_string(),
+                    venue_code: state.fees.venue_of(symbol_id),
+                    latency_ns: self.elapsed_ns(start),
+                    order_id: Some(original),
+                };
+                state.journal_reject(ts_ns, &reject);
+                return Ok(Submission::Reject(reject));
+            }
+
+            if let Some(reason_msg) = state.kill_switch_blocks(symbol_id, side, quantity) {
+                let mut reject = state.block(ts_ns, client_id, symbol_id, side, price, quantity, reason_msg);
+                reject.latency_ns = self.elapsed_ns(start);
+                return Ok(Submission::Reject(reject));
+            }
+
+            // A trailing stop without a trigger yet is valued like a market
+            // order, at the price it will be anchored to.
+            let trigger_valuation = match trigger {
+                Some((_, trigger_price, _)) if trigger_price.is_nan() => state.touch_price(symbol_id, side),
+                trigger => trigger.map(|(_, trigger_price, _)| trigger_price),
+            };
+            let check = RiskCheck {
+                symbol_id,
+                side,
+                limit_price: limit.or(trigger_valuation),
+                quantity,
+                reference_price: state.reference_price(symbol_id),
+                position: state.ledger.net_qty(symbol_id),
+                ts_ns: state.timestamp(),
+            };
+            let mut reservation = None;
+            let rejected = state
+                .risk
+                .check(&check)
+                .err()
+                .map(|(reason_code, reason_msg)| (reason_code, reason_msg, "risk"))
+                .or_else(|| {
+                    let valuation = check.limit_price.or_else(|| state.touch_price(symbol_id, side));
+                    match state
+                        .account
+                        .requirement(symbol_id, side, valuation, quantity, check.position)
+                    {
+                        Ok(held) => {
+                            reservation = held;
+                            None
+                        }
+                        Err(reason_msg) => Some((account::INSUFFICIENT_BALANCE, reason_msg, "router")),
+                    }
+                })
+                .or_else(|| {
+                    state
+                        .acquire_rate_limits(symbol_id, account.as_deref().unwrap_or(""), check.ts_ns)
+                        .err()
+                        .map(|reason_msg| (risk::RATE_LIMIT, reason_msg, "risk"))
+                });
+            if let Some((reason_code, reason_msg, source)) = rejected {
+                let reject = RustReject {
+                    ts_ns,
+                    client_id,
+                    symbol_id,
+                    side: side.as_u8(),
+                    price,
+                    quantity,
+                    reason_code,
+                    reason_msg,
+                    source: source.to_string(),
+                    venue_code: state.fees.venue_of(symbol_id),
+                    latency_ns: self.elapsed_ns(start),
+                    order_id: None,
+                };
+                state.journal_reject(ts_ns, &reject);
+                drop(state);
+                self.update_stats(reject.latency_ns);
+                return Ok(Submission::Reject(reject));
+            }
+            state.risk.record(symbol_id, check.ts_ns);
+
+            let order_id = self.next_order_id.fetch_add(1, Ordering::Relaxed);
+            let simulated = state.latency.is_enabled();
+            state.orders.insert(OrderRecord {
+                order_id,
+                client_id: client_id.clone(),
+                symbol_id,
+                side: side.as_u8(),
+                order_type: order_type as u8,
+                tif: tif_code,
+                price: limit.unwrap_or(0.0),
+                quantity,
+                filled: 0.0,
+                status: if trigger.is_some() || simulated {
+                    OrderStatus::Pending as u8
+                } else {
+                    OrderStatus::Submitted as u8
+                },
+                created_ns: ts_ns,
+                updated_ns: ts_ns,
+            });
+            if let Some(reservation) = reservation {
+                state.account.hold(order_id, quantity, reservation);
+            }
+            if let Some(journal) = &mut state.journal {
+                journal.append(
+                    ts_ns,
+                    &JournalEvent::Accepted(Accepted {
+                        order_id,
+                        client_id: client_id.clone(),
+                        symbol_id,
+                        side: side.as_u8(),
+                        order_type: order_type as u8,
+                        tif: tif_code,
+                        trigger_source,
+                        price: limit.unwrap_or(0.0),
+                        quantity,
+                        trigger_price,
+                        trail_amount,
+                        trail_percent,
+                    }),
+                );
+            }
+
+            let order = NewOrder {
+                order_id,
+                symbol_id,
+                side,
+                tif,
+                limit,
+                quantity,
+                conditional: trigger.map(|(kind, trigger_price, trailing)| ConditionalOrder {
+                    order_id,
+                    symbol_id,
+                    side,
+                    kind,
+                    source,
+                    trigger_price,
+                    limit_price: limit,
+                    quantity,
+                    tif: tif_code,
+                    trailing,
+                }),
+            };
+            let mut execution = if simulated {
+                state.send(order)
+            } else {
+                state.accept(order)
+            };
+            state.settle();
+            execution.client_id = client_id;
+            execution
+        };
+
+        execution.latency_ns = self.elapsed_ns(start);
+
+        self.update_stats(execution.latency_ns);
+
+        Ok(Submission::Execution(execution))
+    }
+
+    /// Map an engine id or client id (at least one required) to an engine id.
+    fn resolve_order_id(
+        state: &EngineState,
+        order_id: Option<u64>,
+        client_id: Option<&str>,
+    ) -> PyResult<Option<u64>> {
+        match (order_id, client_id) {
+            (None, None) => Err(PyValueError::new_err("order_id or client_id is required")),
+            (Some(order_id), None) => Ok(Some(order_id)),
+            (None, Some(client_id)) => Ok(state.orders.resolve(client_id)),
+            (Some(order_id), Some(client_id)) => match state.orders.resolve(client_id) {
+                Some(resolved) if resolved != order_id => Err(PyValueError::new_err(format!(
+                    "client_id {} belongs to order {}, not {}",
+                    client_id, resolved, order_id
+                ))),
+                _ => Ok(Some(order_id)),
+            },
+        }
+    }
+
+    fn fill_recording(&self) -> bool {
+        self.state().record_fills
+    }
+
+    fn simulation_enabled(&self) -> bool {
+        self.state().simulated_clock
+    }
+
+    fn position_snapshots(&self) -> Vec<PositionSnapshot> {
+        let state = self.state();
+        state
+            .ledger
+            .symbols()
+            .into_iter()
+            .filter_map(|symbol_id| state.ledger.snapshot(symbol_id, state.mark_price(symbol_id)))
+            .collect()
+    }
+
+    /// Processing time since `start`, or 0 under the simulated clock.
+    #[inline(always)]
+    fn elapsed_ns(&self, start: Instant) -> u64 {
+        if self.simulated.load(Ordering::Relaxed) {
+            0
+        } else {
+            start.elapsed().as_nanos() as u64
+        }
+    }
+
+    #[inline(always)]
+    fn state(&self) -> MutexGuard<'_, EngineState> {
+        self.state.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
     }
 
     #[inline(always)]
@@ -173,17 +3098,17 @@ impl RustExecutionEngine {
 pub fn execute_batch(
     engine: &RustExecutionEngine,
     orders: Vec<(u32, u8, u8, f64, f64)>,
-) -> PyResult<Vec<RustExecution>> {
+) -> PyResult<Vec<Submission>> {
     let mut executions = Vec::with_capacity(orders.len());
 
     for (symbol_id, side, order_type, price, quantity) in orders {
-        let execution = engine.execute_order(
+        let execution = engine.submit(OrderRequest::new(
             symbol_id,
             side,
             order_type,
             price,
             quantity,
-        )?;
+        ))?;
         executions.push(execution);
     }
 
@@ -197,14 +3122,14 @@ pub fn benchmark_latency(iterations: usize) -> PyResult<PyObject> {
     let mut latencies: Vec<u64> = Vec::with_capacity(iterations);
 
     for _ in 0..100 {
-        let _ = engine.execute_order(1, 0, 0, 50000.0, 1.0);
+        let _ = engine.submit(OrderRequest::new(1, 0, 0, 50000.0, 1.0));
     }
 
     engine.reset_stats();
 
     for _ in 0..iterations {
-        let execution = engine.execute_order(1, 0, 0, 50000.0, 1.0)?;
-        latencies.push(execution.latency_ns);
+        let submission = engine.submit(OrderRequest::new(1, 0, 0, 50000.0, 1.0))?;
+        latencies.push(submission.latency_ns());
     }
 
     latencies.sort_unstable();
@@ -232,7 +3157,31 @@ pub fn benchmark_latency(iterations: usize) -> PyResult<PyObject> {
 fn sigmax_rust_execution(_py: Python, m: &Bound<'_, PyModule>) -> PyResult<()> {
     m.add_class::<RustExecutionEngine>()?;
     m.add_class::<RustExecution>()?;
+    m.add_class::<RustFill>()?;
+    m.add_class::<RustReject>()?;
+    m.add_class::<TokenBucketLimiter>()?;
+    m.add_class::<SlidingWindowLimiter>()?;
+    m.add_class::<PositionLedger>()?;
+    m.add_class::<l2book::BookDelta>()?;
+    m.add_class::<l2book::BookSnapshot>()?;
+    m.add_class::<l2book::SequenceGap>()?;
+    m.add_class::<l2book::BookBuilder>()?;
+    m.add_class::<resync::ResyncRequest>()?;
+    m.add_class::<resync::BookSync>()?;
+    m.add_class::<features::RustFeatureFrame>()?;
+    m.add_class::<features::FeatureCalculator>()?;
+    m.add_class::<backtest::MarketTrade>()?;
+    m.add_class::<shm::ShmRing>()?;
+    m.add_class::<replay::MarketDataReplayer>()?;
     m.add_function(wrap_pyfunction!(execute_batch, m)?)?;
+    m.add_function(wrap_pyfunction!(backtest::run_backtest, m)?)?;
     m.add_function(wrap_pyfunction!(benchmark_latency, m)?)?;
+    m.add_function(wrap_pyfunction!(codec::sbe_encode, m)?)?;
+    m.add_function(wrap_pyfunction!(codec::sbe_decode, m)?)?;
+    m.add_function(wrap_pyfunction!(codec::sbe_header, m)?)?;
+    m.add_function(wrap_pyfunction!(export::export_arrow, m)?)?;
+    m.add_function(wrap_pyfunction!(export::export_table, m)?)?;
+    m.add_function(wrap_pyfunction!(journal::read_journal, m)?)?;
+    m.add_function(wrap_pyfunction!(replay::write_market_data, m)?)?;
     Ok(())
 }
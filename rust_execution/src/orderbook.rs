@@ -0,0 +1,629 @@
+//! Per-symbol price-time-priority limit order book
+//!
+//! A level holds the engine's own resting orders in FIFO order and, when a
+//! market-data feed is attached, the venue quantity displayed at that price.
+//! Each resting order tracks how much of that external quantity is queued
+//! ahead of it, so passive fills follow the trades and cancels seen at the
+//! level instead of happening as soon as the price is touched.
+//!
+//! Engine orders never trade with each other: an incoming order only takes
+//! venue liquidity, and reaching one of the engine's own resting orders
+//! triggers self-trade prevention instead.
+
+use crate::journal::{Reader, Writer};
+use crate::queue::QueueModel;
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+
+pub const QTY_EPSILON: f64 = 1e-12;
+
+/// `maker_order_id` of fills against market-data liquidity.
+pub const EXTERNAL_ORDER_ID: u64 = 0;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Side {
+    Buy,
+    Sell,
+}
+
+impl Side {
+    #[inline(always)]
+    pub fn from_u8(value: u8) -> Option<Side> {
+        match value {
+            0 => Some(Side::Buy),
+            1 => Some(Side::Sell),
+            _ => None,
+        }
+    }
+
+    #[inline(always)]
+    pub fn as_u8(self) -> u8 {
+        match self {
+            Side::Buy => 0,
+            Side::Sell => 1,
+        }
+    }
+
+    #[inline(always)]
+    pub fn opposite(self) -> Side {
+        match self {
+            Side::Buy => Side::Sell,
+            Side::Sell => Side::Buy,
+        }
+    }
+}
+
+/// What happens when an incoming order reaches one of the engine's own
+/// resting orders on the other side.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SelfTradePrevention {
+    /// Cancel the resting order and keep matching.
+    #[default]
+    CancelResting,
+    /// Stop matching and cancel what is left of the incoming order.
+    CancelIncoming,
+}
+
+impl SelfTradePrevention {
+    pub fn from_name(name: &str) -> Option<SelfTradePrevention> {
+        match name {
+            "cancel_resting" => Some(SelfTradePrevention::CancelResting),
+            "cancel_incoming" => Some(SelfTradePrevention::CancelIncoming),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            SelfTradePrevention::CancelResting => "cancel_resting",
+            SelfTradePrevention::CancelIncoming => "cancel_incoming",
+        }
+    }
+}
+
+/// Totally ordered price key for the level maps.
+#[derive(Clone, Copy, Debug)]
+pub struct Price(pub f64);
+
+impl PartialEq for Price {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Price {}
+
+impl PartialOrd for Price {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Price {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.total_cmp(&other.0)
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct RestingOrder {
+    pub order_id: u64,
+    pub side: Side,
+    pub price: f64,
+    /// Open (leaves) quantity.
+    pub quantity: f64,
+    /// Quantity already executed over the order's lifetime.
+    pub filled: f64,
+    pub post_only: bool,
+    /// Estimated external quantity queued ahead of this order.
+    pub queue_ahead: f64,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct PriceLevel {
+    pub orders: VecDeque<RestingOrder>,
+    /// Open quantity of the engine's own orders.
+    pub total_quantity: f64,
+    /// Venue quantity at this price from the market-data feed.
+    pub external_quantity: f64,
+    /// Market trades seen at this price since the last depth update.
+    traded_since_update: f64,
+}
+
+impl PriceLevel {
+    #[inline(always)]
+    pub fn quantity(&self) -> f64 {
+        self.total_quantity + self.external_quantity
+    }
+
+    #[inline(always)]
+    fn is_empty(&self) -> bool {
+        self.orders.is_empty() && self.external_quantity <= QTY_EPSILON
+    }
+
+    #[inline(always)]
+    fn fill(order: &mut RestingOrder, price: f64, quantity: f64, fills: &mut Vec<BookFill>) {
+        order.quantity -= quantity;
+        order.filled += quantity;
+        fills.push(BookFill {
+            maker_order_id: order.order_id,
+            price,
+            quantity,
+            maker_remaining: order.quantity.max(0.0),
+        });
+    }
+
+    /// Trade a venue print of `quantity` through the level in queue order,
+    /// external quantity ahead of each resting order going first. The
+    /// external size is left to the next depth update, which already
+    /// reflects the trade. Returns the quantity traded.
+    fn consume(&mut self, price: f64, quantity: f64, fills: &mut Vec<BookFill>) -> f64 {
+        let mut remaining = quantity;
+        let mut external_taken = 0.0;
+
+        for order in self.orders.iter_mut() {
+            let ahead = (order.queue_ahead - external_taken).max(0.0);
+            let from_external = remaining.min(ahead);
+            external_taken += from_external;
+            remaining -= from_external;
+            if remaining <= QTY_EPSILON {
+                break;
+            }
+            let traded = remaining.min(order.quantity);
+            self.total_quantity -= traded;
+            remaining -= traded;
+            Self::fill(order, price, traded, fills);
+        }
+        for order in self.orders.iter_mut() {
+            order.queue_ahead = (order.queue_ahead - external_taken).max(0.0);
+        }
+
+        self.traded_since_update += quantity;
+        quantity - remaining
+    }
+
+    /// Take up to `quantity` of the venue liquidity at this level for an
+    /// incoming engine order. Resting engine orders it reaches are removed
+    /// into `cancelled`, or with `CancelIncoming` matching stops at the first
+    /// of them. Returns the quantity traded and whether matching stopped.
+    fn take(
+        &mut self,
+        price: f64,
+        quantity: f64,
+        stp: SelfTradePrevention,
+        fills: &mut Vec<BookFill>,
+        cancelled: &mut Vec<RestingOrder>,
+    ) -> (f64, bool) {
+        let mut remaining = quantity;
+        let mut external_taken = 0.0;
+        let mut stopped = false;
+
+        // Every order reached leaves the queue, so the next is at the front.
+        while let Some(order) = self.orders.front() {
+            let ahead = (order.queue_ahead - external_taken).max(0.0);
+            let from_external = remaining.min(ahead);
+            external_taken += from_external;
+            remaining -= from_external;
+            if remaining <= QTY_EPSILON {
+                break;
+            }
+            if stp == SelfTradePrevention::CancelIncoming {
+                stopped = true;
+                break;
+            }
+            if let Some(order) = self.orders.pop_front() {
+                self.total_quantity -= order.quantity;
+                cancelled.push(order);
+            }
+        }
+        for order in self.orders.iter_mut() {
+            order.queue_ahead = (order.queue_ahead - external_taken).max(0.0);
+        }
+
+        if !stopped {
+            let behind = (self.external_quantity - external_taken).max(0.0);
+            external_taken += remaining.min(behind);
+            remaining -= remaining.min(behind);
+        }
+        self.external_quantity = (self.external_quantity - external_taken).max(0.0);
+        if external_taken > QTY_EPSILON {
+            fills.push(BookFill {
+                maker_order_id: EXTERNAL_ORDER_ID,
+                price,
+                quantity: external_taken,
+                maker_remaining: self.external_quantity,
+            });
+        }
+        (quantity - remaining, stopped)
+    }
+
+    /// Fill the engine's orders FIFO up to `quantity`, ignoring the queue.
+    /// Used when the market has traded through the level.
+    fn fill_own(&mut self, price: f64, quantity: f64, fills: &mut Vec<BookFill>) -> f64 {
+        let mut remaining = quantity;
+        for order in self.orders.iter_mut() {
+            if remaining <= QTY_EPSILON {
+                break;
+            }
+            let traded = remaining.min(order.quantity);
+            self.total_quantity -= traded;
+            remaining -= traded;
+            Self::fill(order, price, traded, fills);
+        }
+        quantity - remaining
+    }
+}
+
+/// A single match between an incoming order and a resting order.
+#[derive(Clone, Copy, Debug)]
+pub struct BookFill {
+    pub maker_order_id: u64,
+    pub price: f64,
+    pub quantity: f64,
+    pub maker_remaining: f64,
+}
+
+/// What an incoming order did to the book.
+#[derive(Debug, Default)]
+pub struct Matched {
+    /// Fills against venue liquidity, best price first.
+    pub fills: Vec<BookFill>,
+    /// The engine's own resting orders cancelled by self-trade prevention.
+    pub cancelled: Vec<RestingOrder>,
+    /// Matching stopped at one of the engine's own orders; the remainder
+    /// must not rest.
+    pub self_trade: bool,
+}
+
+#[derive(Default)]
+pub struct OrderBook {
+    bids: BTreeMap<Price, PriceLevel>,
+    asks: BTreeMap<Price, PriceLevel>,
+    index: HashMap<u64, (Side, Price)>,
+    pub queue_model: QueueModel,
+}
+
+impl OrderBook {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[inline(always)]
+    fn levels_mut(&mut self, side: Side) -> &mut BTreeMap<Price, PriceLevel> {
+        match side {
+            Side::Buy => &mut self.bids,
+            Side::Sell => &mut self.asks,
+        }
+    }
+
+    pub fn best_bid(&self) -> Option<(f64, f64)> {
+        self.bids
+            .iter()
+            .next_back()
+            .map(|(p, level)| (p.0, level.quantity()))
+    }
+
+    pub fn best_ask(&self) -> Option<(f64, f64)> {
+        self.asks
+            .iter()
+            .next()
+            .map(|(p, level)| (p.0, level.quantity()))
+    }
+
+    /// Best level on `side`, i.e. highest bid or lowest ask.
+    #[inline(always)]
+    pub fn best(&self, side: Side) -> Option<(f64, f64)> {
+        match side {
+            Side::Buy => self.best_bid(),
+            Side::Sell => self.best_ask(),
+        }
+    }
+
+    /// Aggregated `(price, quantity, order_count)` levels, best first.
+    /// Quantity includes external liquidity; the count is the engine's orders.
+    pub fn depth(&self, side: Side, max_levels: usize) -> Vec<(f64, f64, usize)> {
+        let map = |(p, level): (&Price, &PriceLevel)| (p.0, level.quantity(), level.orders.len());
+        match side {
+            Side::Buy => self.bids.iter().rev().take(max_levels).map(map).collect(),
+            Side::Sell => self.asks.iter().take(max_levels).map(map).collect(),
+        }
+    }
+
+    #[inline(always)]
+    fn crosses(side: Side, limit: Option<f64>, level_price: f64) -> bool {
+        match (side, limit) {
+            (_, None) => true,
+            (Side::Buy, Some(limit)) => level_price <= limit,
+            (Side::Sell, Some(limit)) => level_price >= limit,
+        }
+    }
+
+    /// Whether a limit order at `price` would take liquidity on arrival.
+    #[inline(always)]
+    pub fn would_cross(&self, side: Side, price: f64) -> bool {
+        self.best(side.opposite())
+            .is_some_and(|(best, _)| Self::crosses(side, Some(price), best))
+    }
+
+    /// Opposite-side venue quantity an order could match right now. The
+    /// engine's own orders do not count; with `CancelIncoming` nothing behind
+    /// the first of them does either.
+    pub fn available_to_match(&self, side: Side, limit: Option<f64>, stp: SelfTradePrevention) -> f64 {
+        let levels: Box<dyn Iterator<Item = (&Price, &PriceLevel)>> = match side {
+            Side::Buy => Box::new(self.asks.iter()),
+            Side::Sell => Box::new(self.bids.iter().rev()),
+        };
+        let mut available = 0.0;
+        for (p, level) in levels {
+            if !Self::crosses(side, limit, p.0) {
+                break;
+            }
+            match level.orders.front() {
+                Some(first) if stp == SelfTradePrevention::CancelIncoming => {
+                    return available + first.queue_ahead.min(level.external_quantity);
+                }
+                _ => available += level.external_quantity,
+            }
+        }
+        available
+    }
+
+    /// Drop fully filled orders, and the level itself once nothing is left.
+    fn settle_level(&mut self, side: Side, key: Price) {
+        let Some(level) = self.levels_mut(side).get_mut(&key) else {
+            return;
+        };
+        let mut filled_ids = Vec::new();
+        level.orders.retain(|order| {
+            let open = order.quantity > QTY_EPSILON;
+            if !open {
+                filled_ids.push(order.order_id);
+            }
+            open
+        });
+        let level_empty = level.is_empty();
+        for order_id in filled_ids {
+            self.index.remove(&order_id);
+        }
+        if level_empty {
+            self.levels_mut(side).remove(&key);
+        }
+    }
+
+    /// Match an incoming order against the opposite side's venue liquidity,
+    /// walking levels from the best price while they cross `limit` (`None`
+    /// for market orders). The engine's own orders in the way are handled
+    /// per `stp`.
+    pub fn match_order(
+        &mut self,
+        side: Side,
+        limit: Option<f64>,
+        quantity: f64,
+        stp: SelfTradePrevention,
+    ) -> Matched {
+        let mut matched = Matched::default();
+        let mut remaining = quantity;
+
+        while remaining > QTY_EPSILON {
+            let level_price = match self.best(side.opposite()) {
+                Some((p, _)) if Self::crosses(side, limit, p) => p,
+                _ => break,
+            };
+            let key = Price(level_price);
+            let Some(level) = self.levels_mut(side.opposite()).get_mut(&key) else {
+                break;
+            };
+            let cancelled_before = matched.cancelled.len();
+            let (traded, stopped) =
+                level.take(level_price, remaining, stp, &mut matched.fills, &mut matched.cancelled);
+            remaining -= traded;
+            for order in &matched.cancelled[cancelled_before..] {
+                self.index.remove(&order.order_id);
+            }
+            self.settle_level(side.opposite(), key);
+            if stopped {
+                matched.self_trade = true;
+                break;
+            }
+            if traded <= QTY_EPSILON && matched.cancelled.len() == cancelled_before {
+                break;
+            }
+        }
+
+        matched
+    }
+
+    /// Apply a trade printed by the venue. Resting orders priced through the
+    /// trade are filled outright; orders at the trade price only fill with
+    /// what is left once the queue ahead of them has been worked off.
+    pub fn apply_market_trade(&mut self, aggressor: Side, price: f64, quantity: f64) -> Vec<BookFill> {
+        let passive = aggressor.opposite();
+        let keys: Vec<Price> = match passive {
+            Side::Buy => self.bids.range(Price(price)..).map(|(k, _)| *k).collect(),
+            Side::Sell => self.asks.range(..=Price(price)).map(|(k, _)| *k).collect(),
+        };
+
+        let mut fills = Vec::new();
+        for key in keys {
+            let Some(level) = self.levels_mut(passive).get_mut(&key) else {
+                continue;
+            };
+            if key == Price(price) {
+                level.consume(key.0, quantity, &mut fills);
+            } else {
+                level.fill_own(key.0, f64::INFINITY, &mut fills);
+            }
+            self.settle_level(passive, key);
+        }
+        fills
+    }
+
+    /// Set the venue quantity at one level (0 removes it), moving resting
+    /// orders up the queue per `queue_model`. Venue liquidity that crosses
+    /// the engine's own opposite orders trades against them at their price.
+    pub fn set_external_level(&mut self, side: Side, price: f64, quantity: f64) -> Vec<BookFill> {
+        let key = Price(price);
+        let model = self.queue_model;
+        let mut available = quantity.max(0.0);
+
+        let crossed: Vec<Price> = match side {
+            Side::Sell => self.bids.range(key..).rev().map(|(k, _)| *k).collect(),
+            Side::Buy => self.asks.range(..=key).map(|(k, _)| *k).collect(),
+        };
+        let mut fills = Vec::new();
+        for crossed_key in crossed {
+            if available <= QTY_EPSILON {
+                break;
+            }
+            let Some(level) = self.levels_mut(side.opposite()).get_mut(&crossed_key) else {
+                continue;
+            };
+            available -= level.fill_own(crossed_key.0, available, &mut fills);
+            self.settle_level(side.opposite(), crossed_key);
+        }
+
+        let level = self.levels_mut(side).entry(key).or_default();
+        let prev = level.external_quantity;
+        for order in level.orders.iter_mut() {
+            order.queue_ahead = model.on_depth_change(order.queue_ahead, prev, available, level.traded_since_update);
+        }
+        level.external_quantity = available;
+        level.traded_since_update = 0.0;
+        self.settle_level(side, key);
+        fills
+    }
+
+    /// Append an order to the back of its price level queue.
+    pub fn add_order(&mut self, order_id: u64, side: Side, price: f64, quantity: f64, post_only: bool) {
+        let key = Price(price);
+        let level = self.levels_mut(side).entry(key).or_default();
+        level.total_quantity += quantity;
+        let queue_ahead = level.external_quantity;
+        level.orders.push_back(RestingOrder {
+            order_id,
+            side,
+            price,
+            quantity,
+            filled: 0.0,
+            post_only,
+            queue_ahead,
+        });
+        self.index.insert(order_id, (side, key));
+    }
+
+    pub fn contains(&self, order_id: u64) -> bool {
+        self.index.contains_key(&order_id)
+    }
+
+    pub fn order_ids(&self) -> Vec<u64> {
+        self.index.keys().copied().collect()
+    }
+
+    pub fn get(&self, order_id: u64) -> Option<&RestingOrder> {
+        self.level_of(order_id)?.orders.iter().find(|o| o.order_id == order_id)
+    }
+
+    /// Price level currently holding an order.
+    pub fn level_of(&self, order_id: u64) -> Option<&PriceLevel> {
+        let (side, key) = self.index.get(&order_id)?;
+        match side {
+            Side::Buy => self.bids.get(key),
+            Side::Sell => self.asks.get(key),
+        }
+    }
+
+    fn get_mut(&mut self, order_id: u64) -> Option<(&mut PriceLevel, usize)> {
+        let (side, key) = *self.index.get(&order_id)?;
+        let level = self.levels_mut(side).get_mut(&key)?;
+        let position = level.orders.iter().position(|o| o.order_id == order_id)?;
+        Some((level, position))
+    }
+
+    /// Remove an order from the book, returning it as it stood.
+    pub fn cancel(&mut self, order_id: u64) -> Option<RestingOrder> {
+        let (level, position) = self.get_mut(order_id)?;
+        let order = level.orders.remove(position)?;
+        level.total_quantity -= order.quantity;
+        let level_empty = level.is_empty();
+
+        let (side, key) = self.index.remove(&order_id)?;
+        if level_empty {
+            self.levels_mut(side).remove(&key);
+        }
+        Some(order)
+    }
+
+    /// Shrink an order's open quantity in place, keeping its queue position.
+    pub fn reduce(&mut self, order_id: u64, quantity: f64) -> bool {
+        let Some((level, position)) = self.get_mut(order_id) else {
+            return false;
+        };
+        let order = &mut level.orders[position];
+        if quantity > order.quantity {
+            return false;
+        }
+        level.total_quantity -= order.quantity - quantity;
+        order.quantity = quantity;
+        true
+    }
+
+    /// Carry an amended order's lifetime fill count over to its new entry.
+    pub fn set_filled(&mut self, order_id: u64, filled: f64) {
+        if let Some((level, position)) = self.get_mut(order_id) {
+            level.orders[position].filled = filled;
+        }
+    }
+
+    /// Levels with their venue quantity and the engine's orders in queue
+    /// order, for snapshots. The queue model is configuration.
+    pub(crate) fn save(&self, w: &mut Writer<'_>) {
+        for levels in [&self.bids, &self.asks] {
+            w.u32(levels.len() as u32);
+            for (price, level) in levels {
+                w.f64(price.0);
+                w.f64(level.external_quantity);
+                w.f64(level.traded_since_update);
+                w.u32(level.orders.len() as u32);
+                for order in &level.orders {
+                    w.u64(order.order_id);
+                    w.f64(order.quantity);
+                    w.f64(order.filled);
+                    w.u8(order.post_only as u8);
+                    w.f64(order.queue_ahead);
+                }
+            }
+        }
+    }
+
+    pub(crate) fn restore(&mut self, r: &mut Reader<'_>) -> Option<()> {
+        self.bids.clear();
+        self.asks.clear();
+        self.index.clear();
+        for side in [Side::Buy, Side::Sell] {
+            for _ in 0..r.u32()? {
+                let price = r.f64()?;
+                let mut level = PriceLevel {
+                    external_quantity: r.f64()?,
+                    traded_since_update: r.f64()?,
+                    ..PriceLevel::default()
+                };
+                for _ in 0..r.u32()? {
+                    let order = RestingOrder {
+                        order_id: r.u64()?,
+                        side,
+                        price,
+                        quantity: r.f64()?,
+                        filled: r.f64()?,
+                        post_only: r.u8()? != 0,
+                        queue_ahead: r.f64()?,
+                    };
+                    level.total_quantity += order.quantity;
+                    self.index.insert(order.order_id, (side, Price(price)));
+                    level.orders.push_back(order);
+                }
+                self.levels_mut(side).insert(Price(price), level);
+            }
+        }
+        Some(())
+    }
+}
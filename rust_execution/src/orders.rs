@@ -0,0 +1,244 @@
+//! Order registry: lifecycle state per order plus the client_id index
+
+use crate::journal::{Reader, Writer};
+use crate::OrderStatus;
+use std::collections::{HashMap, VecDeque};
+
+pub const DEFAULT_CLIENT_ID_WINDOW_NS: u64 = 60_000_000_000;
+/// `RejectReason.DUPLICATE_CLIENT_ID`.
+pub const DUPLICATE_CLIENT_ID: u16 = 204;
+
+#[derive(Clone, Debug)]
+pub struct OrderRecord {
+    pub order_id: u64,
+    pub client_id: Option<String>,
+    pub symbol_id: u32,
+    pub side: u8,
+    pub order_type: u8,
+    pub tif: u8,
+    pub price: f64,
+    /// Total order size including anything already filled.
+    pub quantity: f64,
+    pub filled: f64,
+    pub status: u8,
+    pub created_ns: u64,
+    pub updated_ns: u64,
+}
+
+impl OrderRecord {
+    #[inline(always)]
+    pub fn is_open(&self) -> bool {
+        self.status == OrderStatus::Pending as u8
+            || self.status == OrderStatus::Submitted as u8
+            || self.status == OrderStatus::Partial as u8
+    }
+}
+
+/// Every order the engine has seen, indexed by order id and client id.
+/// Completed orders are forgotten once they are older than the idempotency
+/// window; open orders are kept until they complete.
+pub struct OrderRegistry {
+    records: HashMap<u64, OrderRecord>,
+    by_client_id: HashMap<String, u64>,
+    history: VecDeque<(u64, u64)>,
+    window_ns: u64,
+    /// Orders touched since the last `take_changed`.
+    changed: Vec<u64>,
+}
+
+impl Default for OrderRegistry {
+    fn default() -> Self {
+        Self {
+            records: HashMap::new(),
+            by_client_id: HashMap::new(),
+            history: VecDeque::new(),
+            window_ns: DEFAULT_CLIENT_ID_WINDOW_NS,
+            changed: Vec::new(),
+        }
+    }
+}
+
+impl OrderRegistry {
+    pub fn set_window_ns(&mut self, window_ns: u64) {
+        self.window_ns = window_ns;
+    }
+
+    /// Order id of an earlier submission with the same client id inside the
+    /// idempotency window.
+    pub fn duplicate_of(&self, client_id: &str, now_ns: u64) -> Option<u64> {
+        let order_id = *self.by_client_id.get(client_id)?;
+        let record = self.records.get(&order_id)?;
+        (now_ns.saturating_sub(record.created_ns) < self.window_ns).then_some(order_id)
+    }
+
+    pub fn insert(&mut self, record: OrderRecord) {
+        self.prune(record.created_ns);
+        if let Some(client_id) = &record.client_id {
+            self.by_client_id.insert(client_id.clone(), record.order_id);
+        }
+        self.history.push_back((record.created_ns, record.order_id));
+        self.changed.push(record.order_id);
+        self.records.insert(record.order_id, record);
+    }
+
+    fn prune(&mut self, now_ns: u64) {
+        let mut still_open = Vec::new();
+        while let Some(&(created_ns, order_id)) = self.history.front() {
+            if now_ns.saturating_sub(created_ns) < self.window_ns {
+                break;
+            }
+            self.history.pop_front();
+            match self.records.get(&order_id) {
+                Some(record) if record.is_open() => still_open.push((now_ns, order_id)),
+                Some(_) => self.remove(order_id),
+                None => {}
+            }
+        }
+        self.history.extend(still_open);
+    }
+
+    fn remove(&mut self, order_id: u64) {
+        if let Some(record) = self.records.remove(&order_id) {
+            if let Some(client_id) = record.client_id {
+                if self.by_client_id.get(&client_id) == Some(&order_id) {
+                    self.by_client_id.remove(&client_id);
+                }
+            }
+        }
+    }
+
+    pub fn get(&self, order_id: u64) -> Option<&OrderRecord> {
+        self.records.get(&order_id)
+    }
+
+    pub fn get_mut(&mut self, order_id: u64) -> Option<&mut OrderRecord> {
+        self.changed.push(order_id);
+        self.records.get_mut(&order_id)
+    }
+
+    /// Ids of orders changed since the last call, each once.
+    pub fn take_changed(&mut self) -> Vec<u64> {
+        let mut changed = std::mem::take(&mut self.changed);
+        changed.sort_unstable();
+        changed.dedup();
+        changed
+    }
+
+    pub fn clear_changed(&mut self) {
+        self.changed.clear();
+    }
+
+    pub fn resolve(&self, client_id: &str) -> Option<u64> {
+        self.by_client_id.get(client_id).copied()
+    }
+
+    pub fn client_id(&self, order_id: u64) -> Option<String> {
+        self.records.get(&order_id).and_then(|r| r.client_id.clone())
+    }
+
+    /// Apply an execution step to the order's lifetime totals.
+    pub fn on_execution(&mut self, order_id: u64, executed_quantity: f64, status: u8, ts_ns: u64) {
+        if let Some(record) = self.records.get_mut(&order_id) {
+            self.changed.push(order_id);
+            record.filled += executed_quantity;
+            record.status = status;
+            record.updated_ns = ts_ns;
+        }
+    }
+
+    /// A resting order was hit as maker.
+    pub fn on_maker_fill(&mut self, order_id: u64, quantity: f64, fully_filled: bool, ts_ns: u64) {
+        if let Some(record) = self.records.get_mut(&order_id) {
+            self.changed.push(order_id);
+            record.filled += quantity;
+            record.status = if fully_filled {
+                OrderStatus::Filled as u8
+            } else {
+                OrderStatus::Partial as u8
+            };
+            record.updated_ns = ts_ns;
+        }
+    }
+
+    pub fn set_status(&mut self, order_id: u64, status: u8, ts_ns: u64) {
+        if let Some(record) = self.records.get_mut(&order_id) {
+            self.changed.push(order_id);
+            record.status = status;
+            record.updated_ns = ts_ns;
+        }
+    }
+
+    /// Ids of every open order, lowest first.
+    pub fn open_ids(&self) -> Vec<u64> {
+        let mut ids: Vec<u64> = self
+            .records
+            .values()
+            .filter(|record| record.is_open())
+            .map(|record| record.order_id)
+            .collect();
+        ids.sort_unstable();
+        ids
+    }
+
+    /// Records, the client id index and the pruning history, for snapshots.
+    /// The idempotency window is configuration and is not saved.
+    pub(crate) fn save(&self, w: &mut Writer<'_>) {
+        w.u32(self.history.len() as u32);
+        for (ts_ns, order_id) in &self.history {
+            w.u64(*ts_ns);
+            w.u64(*order_id);
+        }
+        w.u32(self.records.len() as u32);
+        for record in self.records.values() {
+            w.u64(record.order_id);
+            w.str(record.client_id.as_deref().unwrap_or(""));
+            w.u32(record.symbol_id);
+            w.u8(record.side);
+            w.u8(record.order_type);
+            w.u8(record.tif);
+            w.f64(record.price);
+            w.f64(record.quantity);
+            w.f64(record.filled);
+            w.u8(record.status);
+            w.u64(record.created_ns);
+            w.u64(record.updated_ns);
+        }
+        w.u32(self.by_client_id.len() as u32);
+        for (client_id, order_id) in &self.by_client_id {
+            w.str(client_id);
+            w.u64(*order_id);
+        }
+    }
+
+    pub(crate) fn restore(&mut self, r: &mut Reader<'_>) -> Option<()> {
+        self.records.clear();
+        self.by_client_id.clear();
+        self.history.clear();
+        self.changed.clear();
+        for _ in 0..r.u32()? {
+            self.history.push_back((r.u64()?, r.u64()?));
+        }
+        for _ in 0..r.u32()? {
+            let record = OrderRecord {
+                order_id: r.u64()?,
+                client_id: r.opt_str()?,
+                symbol_id: r.u32()?,
+                side: r.u8()?,
+                order_type: r.u8()?,
+                tif: r.u8()?,
+                price: r.f64()?,
+                quantity: r.f64()?,
+                filled: r.f64()?,
+                status: r.u8()?,
+                created_ns: r.u64()?,
+                updated_ns: r.u64()?,
+            };
+            self.records.insert(record.order_id, record);
+        }
+        for _ in 0..r.u32()? {
+            let client_id = r.str()?;
+            self.by_client_id.insert(client_id, r.u64()?);
+        }
+        Some(())
+    }
+}
@@ -0,0 +1,102 @@
+//! Queue position models for resting orders behind market-data liquidity
+//!
+//! Native versions of the hftbacktest queue models used by
+//! `core/modules/hft_execution.py::QueuePositionManager`. Trades at a level
+//! always advance a resting order; these models decide how much of a level
+//! shrinking for any other reason (cancels) happened ahead of it.
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum QueueModel {
+    /// Cancels never advance the order; only trades do.
+    RiskAverse,
+    /// Share of cancels behind the order is `back^n / (back^n + front^n)`.
+    PowerProb(f64),
+    /// Share of cancels behind the order is `ln(1+back) / (ln(1+back) + ln(1+front))`.
+    LogProb,
+}
+
+impl Default for QueueModel {
+    fn default() -> Self {
+        QueueModel::PowerProb(2.0)
+    }
+}
+
+impl QueueModel {
+    pub fn name(&self) -> &'static str {
+        match self {
+            QueueModel::RiskAverse => "RiskAverse",
+            QueueModel::PowerProb(_) => "PowerProb",
+            QueueModel::LogProb => "LogProb",
+        }
+    }
+
+    /// Probability that a cancel happened behind the order.
+    #[inline(always)]
+    fn prob_behind(&self, front: f64, back: f64) -> f64 {
+        let (f, b) = match self {
+            QueueModel::RiskAverse => return 1.0,
+            QueueModel::PowerProb(n) => (front.max(0.0).powf(*n), back.max(0.0).powf(*n)),
+            QueueModel::LogProb => (front.max(0.0).ln_1p(), back.max(0.0).ln_1p()),
+        };
+        let prob = b / (f + b);
+        if prob.is_finite() {
+            prob
+        } else {
+            1.0
+        }
+    }
+
+    /// New quantity ahead of an order after the level moved from `prev_qty`
+    /// to `new_qty`, net of `traded_qty` already applied by trades.
+    #[inline(always)]
+    pub fn on_depth_change(&self, front: f64, prev_qty: f64, new_qty: f64, traded_qty: f64) -> f64 {
+        let chg = prev_qty - new_qty - traded_qty;
+        if chg <= 0.0 {
+            // Growth joins the back of the queue.
+            return front.min(new_qty).max(0.0);
+        }
+        let back = prev_qty - front;
+        let prob = self.prob_behind(front, back);
+        let est_front = front - (1.0 - prob) * chg + (back - prob * chg).min(0.0);
+        est_front.min(new_qty).max(0.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() < 1e-12
+    }
+
+    #[test]
+    fn cancels_split_by_model() {
+        // 6 ahead, 4 behind, then the level shrinks to 6 with no trades.
+        assert_eq!(QueueModel::RiskAverse.on_depth_change(6.0, 10.0, 6.0, 0.0), 6.0);
+        let power = QueueModel::PowerProb(2.0).on_depth_change(6.0, 10.0, 6.0, 0.0);
+        assert!(close(power, 6.0 - 4.0 * 36.0 / 52.0));
+        let log = QueueModel::LogProb.on_depth_change(6.0, 10.0, 6.0, 0.0);
+        let (ln7, ln5) = (7f64.ln(), 5f64.ln());
+        assert!(close(log, 6.0 - 4.0 * ln7 / (ln7 + ln5)));
+    }
+
+    #[test]
+    fn cancels_beyond_the_back_come_from_the_front() {
+        assert_eq!(QueueModel::RiskAverse.on_depth_change(6.0, 10.0, 2.0, 0.0), 2.0);
+        assert_eq!(QueueModel::PowerProb(3.0).on_depth_change(6.0, 10.0, 0.0, 0.0), 0.0);
+    }
+
+    #[test]
+    fn growth_and_trades_never_move_the_order_back() {
+        assert_eq!(QueueModel::LogProb.on_depth_change(6.0, 10.0, 12.0, 0.0), 6.0);
+        // Trades already took 7 off the front; the rest of the drop is not a cancel.
+        assert_eq!(QueueModel::PowerProb(2.0).on_depth_change(3.0, 10.0, 3.0, 7.0), 3.0);
+    }
+
+    #[test]
+    fn empty_queue_counts_cancels_behind() {
+        assert_eq!(QueueModel::PowerProb(2.0).prob_behind(0.0, 0.0), 1.0);
+        assert_eq!(QueueModel::LogProb.prob_behind(0.0, 0.0), 1.0);
+    }
+}
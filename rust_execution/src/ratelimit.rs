@@ -0,0 +1,300 @@
+//! Order rate limiters keyed by symbol, venue and account
+//!
+//! Per-key state is a handful of atomics updated with CAS loops, so checks
+//! never block; the key map is only write-locked when a key is first seen.
+//! Callers pass the time explicitly so simulations can use their own clock.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
+
+/// Venues are identified by code inside the engine and usually by name in
+/// Python; the two never share a key.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum VenueKey {
+    Code(u8),
+    Name(String),
+}
+
+/// Any combination of symbol, venue and account; unset parts match nothing
+/// else, so `{venue: 1}` and `{venue: 1, account: "a"}` are separate keys.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct RateKey {
+    pub symbol_id: Option<u32>,
+    pub venue: Option<VenueKey>,
+    pub account: Option<String>,
+}
+
+/// Which order attributes make up the key of a limiter enforced by the engine.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct RateScope {
+    pub symbol: bool,
+    pub venue: bool,
+    pub account: bool,
+}
+
+impl RateScope {
+    pub fn parse(names: &[String]) -> Result<Self, String> {
+        let mut scope = RateScope::default();
+        for name in names {
+            match name.as_str() {
+                "symbol" => scope.symbol = true,
+                "venue" => scope.venue = true,
+                "account" => scope.account = true,
+                other => return Err(format!("unknown rate limit scope: {}", other)),
+            }
+        }
+        Ok(scope)
+    }
+
+    #[inline(always)]
+    pub fn key(&self, symbol_id: u32, venue_code: u8, account: &str) -> RateKey {
+        RateKey {
+            symbol_id: self.symbol.then_some(symbol_id),
+            venue: self.venue.then_some(VenueKey::Code(venue_code)),
+            account: self.account.then(|| account.to_string()),
+        }
+    }
+
+    pub fn names(&self) -> Vec<&'static str> {
+        [(self.symbol, "symbol"), (self.venue, "venue"), (self.account, "account")]
+            .into_iter()
+            .filter_map(|(set, name)| set.then_some(name))
+            .collect()
+    }
+}
+
+pub trait RateLimiter: Send + Sync {
+    /// Take `cost` units for `key` at `now_ns`, or leave state untouched and
+    /// return `false` if that would exceed the limit.
+    fn try_acquire(&self, key: &RateKey, cost: u64, now_ns: u64) -> bool;
+
+    /// Give back units taken by `try_acquire`, e.g. when a later check fails.
+    fn release(&self, key: &RateKey, cost: u64, now_ns: u64);
+
+    fn reset(&self);
+
+    fn name(&self) -> &'static str;
+}
+
+pub trait Bucket: Send + Sync {
+    type Config: Send + Sync;
+    const NAME: &'static str;
+
+    fn new(config: &Self::Config) -> Self;
+    fn try_acquire(&self, config: &Self::Config, cost: u64, now_ns: u64) -> bool;
+    fn release(&self, config: &Self::Config, cost: u64, now_ns: u64);
+}
+
+/// One bucket per key, created on first use.
+pub struct Keyed<B: Bucket> {
+    pub config: B::Config,
+    buckets: RwLock<HashMap<RateKey, Arc<B>>>,
+}
+
+impl<B: Bucket> Keyed<B> {
+    pub fn new(config: B::Config) -> Self {
+        Self {
+            config,
+            buckets: RwLock::new(HashMap::new()),
+        }
+    }
+
+    #[inline(always)]
+    fn bucket(&self, key: &RateKey) -> Arc<B> {
+        if let Some(bucket) = self
+            .buckets
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .get(key)
+        {
+            return Arc::clone(bucket);
+        }
+        let mut buckets = self.buckets.write().unwrap_or_else(|poisoned| poisoned.into_inner());
+        Arc::clone(
+            buckets
+                .entry(key.clone())
+                .or_insert_with(|| Arc::new(B::new(&self.config))),
+        )
+    }
+}
+
+/// Token bucket as GCRA: a single atomic holds the theoretical arrival time
+/// of the next unit; a request fits while that stays within `burst` units
+/// of now.
+pub struct TokenBucket {
+    tat_ns: AtomicU64,
+}
+
+pub struct TokenBucketConfig {
+    pub rate: f64,
+    pub burst: u64,
+    interval_ns: u64,
+}
+
+impl TokenBucketConfig {
+    pub fn new(rate: f64, burst: u64) -> Result<Self, String> {
+        if !(rate.is_finite() && rate > 0.0 && rate <= 1e9) {
+            return Err(format!("invalid rate: {}", rate));
+        }
+        if burst == 0 {
+            return Err("burst must be at least 1".to_string());
+        }
+        Ok(Self {
+            rate,
+            burst,
+            interval_ns: (1e9 / rate).round() as u64,
+        })
+    }
+}
+
+impl Bucket for TokenBucket {
+    type Config = TokenBucketConfig;
+    const NAME: &'static str = "token_bucket";
+
+    fn new(_config: &TokenBucketConfig) -> Self {
+        Self {
+            tat_ns: AtomicU64::new(0),
+        }
+    }
+
+    #[inline(always)]
+    fn try_acquire(&self, config: &TokenBucketConfig, cost: u64, now_ns: u64) -> bool {
+        let tolerance = config.interval_ns.saturating_mul(config.burst);
+        let increment = config.interval_ns.saturating_mul(cost);
+        let mut tat = self.tat_ns.load(Ordering::Acquire);
+        loop {
+            let new_tat = tat.max(now_ns).saturating_add(increment);
+            if new_tat - now_ns > tolerance {
+                return false;
+            }
+            match self
+                .tat_ns
+                .compare_exchange_weak(tat, new_tat, Ordering::AcqRel, Ordering::Acquire)
+            {
+                Ok(_) => return true,
+                Err(current) => tat = current,
+            }
+        }
+    }
+
+    fn release(&self, config: &TokenBucketConfig, cost: u64, _now_ns: u64) {
+        let decrement = config.interval_ns.saturating_mul(cost);
+        let _ = self
+            .tat_ns
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |tat| Some(tat.saturating_sub(decrement)));
+    }
+}
+
+const WINDOW_SLOTS: usize = 16;
+const COUNT_BITS: u32 = 20;
+const COUNT_MASK: u64 = (1 << COUNT_BITS) - 1;
+const EPOCH_MASK: u64 = u64::MAX >> COUNT_BITS;
+
+/// Sliding window counted in `WINDOW_SLOTS` sub-windows, each an atomic
+/// packing the sub-window's epoch and count. A request is counted first
+/// and rolled back if the window is then over the limit, so concurrent
+/// callers can be refused early but never admitted past it.
+pub struct SlidingWindow {
+    slots: [AtomicU64; WINDOW_SLOTS],
+}
+
+pub struct SlidingWindowConfig {
+    pub limit: u64,
+    pub window_ns: u64,
+    slot_ns: u64,
+}
+
+impl SlidingWindowConfig {
+    pub fn new(limit: u64, window_ns: u64) -> Result<Self, String> {
+        if limit == 0 || limit > COUNT_MASK {
+            return Err(format!("limit must be between 1 and {}", COUNT_MASK));
+        }
+        if window_ns < WINDOW_SLOTS as u64 {
+            return Err(format!("invalid window: {}ns", window_ns));
+        }
+        Ok(Self {
+            limit,
+            window_ns,
+            slot_ns: window_ns.div_ceil(WINDOW_SLOTS as u64),
+        })
+    }
+}
+
+#[inline(always)]
+fn unpack(slot: u64) -> (u64, u64) {
+    (slot >> COUNT_BITS, slot & COUNT_MASK)
+}
+
+impl Bucket for SlidingWindow {
+    type Config = SlidingWindowConfig;
+    const NAME: &'static str = "sliding_window";
+
+    fn new(_config: &SlidingWindowConfig) -> Self {
+        Self {
+            slots: std::array::from_fn(|_| AtomicU64::new(0)),
+        }
+    }
+
+    #[inline(always)]
+    fn try_acquire(&self, config: &SlidingWindowConfig, cost: u64, now_ns: u64) -> bool {
+        if cost > config.limit {
+            return false;
+        }
+        let epoch = (now_ns / config.slot_ns) & EPOCH_MASK;
+        let slot = &self.slots[(now_ns / config.slot_ns) as usize % WINDOW_SLOTS];
+        let counted = slot.fetch_update(Ordering::AcqRel, Ordering::Acquire, |packed| {
+            let (slot_epoch, count) = unpack(packed);
+            let count = if slot_epoch == epoch { count + cost } else { cost };
+            (count <= COUNT_MASK).then_some(epoch << COUNT_BITS | count)
+        });
+        if counted.is_err() {
+            return false;
+        }
+
+        let total: u64 = self
+            .slots
+            .iter()
+            .map(|slot| unpack(slot.load(Ordering::Acquire)))
+            .filter(|(slot_epoch, _)| (epoch.wrapping_sub(*slot_epoch) & EPOCH_MASK) < WINDOW_SLOTS as u64)
+            .map(|(_, count)| count)
+            .sum();
+        if total > config.limit {
+            self.release(config, cost, now_ns);
+            return false;
+        }
+        true
+    }
+
+    fn release(&self, config: &SlidingWindowConfig, cost: u64, now_ns: u64) {
+        let epoch = (now_ns / config.slot_ns) & EPOCH_MASK;
+        let slot = &self.slots[(now_ns / config.slot_ns) as usize % WINDOW_SLOTS];
+        let _ = slot.fetch_update(Ordering::AcqRel, Ordering::Acquire, |packed| {
+            let (slot_epoch, count) = unpack(packed);
+            // A rolled-over sub-window has nothing of ours left to give back.
+            (slot_epoch == epoch).then(|| epoch << COUNT_BITS | count.saturating_sub(cost))
+        });
+    }
+}
+
+impl<B: Bucket> RateLimiter for Keyed<B> {
+    #[inline(always)]
+    fn try_acquire(&self, key: &RateKey, cost: u64, now_ns: u64) -> bool {
+        self.bucket(key).try_acquire(&self.config, cost, now_ns)
+    }
+
+    fn release(&self, key: &RateKey, cost: u64, now_ns: u64) {
+        self.bucket(key).release(&self.config, cost, now_ns);
+    }
+
+    fn reset(&self) {
+        self.buckets
+            .write()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .clear();
+    }
+
+    fn name(&self) -> &'static str {
+        B::NAME
+    }
+}
@@ -0,0 +1,1045 @@
+//! Recorded market-data replay
+//!
+//! Reads L2 deltas, book snapshots and venue trades from a recording and
+//! feeds them through a `BookBuilder` and, optionally, a
+//! `RustExecutionEngine` on its simulated clock. The engine's clock only
+//! moves with the events' own timestamps, so pacing decides when an event is
+//! applied but never what it does: a recording replayed as fast as possible,
+//! in real time or with pauses ends in the same state.
+//!
+//! Three formats are read, each optionally gzip-compressed. Compression is
+//! recognised by its magic bytes, and so is the format when none is given.
+//!
+//! - NDJSON, one object per line: `{"type": "delta", "ts_ns", "symbol_id",
+//!   "side", "price", "quantity", "seq"}`, `{"type": "trade", "ts_ns",
+//!   "symbol_id", "price", "quantity", "aggressor_side"}` or `{"type":
+//!   "snapshot", "ts_ns", "symbol_id", "seq", "bids", "asks"}` with
+//!   `[[price, quantity], ...]` levels. `seq` may be left out.
+//! - CSV with a header row naming `type,ts_ns,symbol_id,side,price,quantity`
+//!   and optionally `seq`, in any order. Rows are deltas or trades, `side`
+//!   being the aggressor's for trades.
+//! - Binary: `SGMXMKTD | version u32`, then `u32`-length-prefixed
+//!   `BookDelta` and `MarketTrade` frames of the `codec` schema, as written
+//!   by `write_market_data`. The codec has no snapshot frame, so a
+//!   recording with snapshots has to stay NDJSON.
+
+use crate::backtest::MarketTrade;
+use crate::codec::{Encoder, Frame, Template, Value};
+use crate::l2book::{check_level, parse_side, BookBuilder, BookDelta, BookSnapshot, DeltaOutcome, L2Book};
+use crate::orderbook::Side;
+use crate::RustExecutionEngine;
+use flate2::read::MultiGzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use pyo3::exceptions::{PyIOError, PyRuntimeError, PyTypeError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+use serde_json::Value as Json;
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, MutexGuard, TryLockError};
+use std::time::{Duration, Instant};
+
+const MAGIC: &[u8; 8] = b"SGMXMKTD";
+const LAYOUT_VERSION: u32 = 1;
+const GZIP_MAGIC: &[u8; 2] = b"\x1f\x8b";
+/// Longest frame a binary recording may declare. Codec frames are well
+/// under this, so a larger length means a corrupt file.
+const MAX_FRAME_LEN: usize = 4096;
+/// Longest a pacing sleep holds off `pause` and Ctrl-C.
+const WAIT_SLICE: Duration = Duration::from_millis(50);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    Ndjson,
+    Csv,
+    Binary,
+}
+
+impl Format {
+    pub fn parse(name: &str) -> Option<Format> {
+        match name {
+            "ndjson" | "jsonl" => Some(Format::Ndjson),
+            "csv" => Some(Format::Csv),
+            "binary" => Some(Format::Binary),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Format::Ndjson => "ndjson",
+            Format::Csv => "csv",
+            Format::Binary => "binary",
+        }
+    }
+
+    /// Guess from the first (decompressed) bytes of a recording.
+    fn sniff(head: &[u8]) -> Format {
+        if head.starts_with(MAGIC) {
+            Format::Binary
+        } else if head.iter().find(|byte| !byte.is_ascii_whitespace()) == Some(&b'{') {
+            Format::Ndjson
+        } else {
+            Format::Csv
+        }
+    }
+}
+
+/// Reading a recording failed: the file could not be read, or its contents
+/// are not a recording.
+pub enum ReplayError {
+    Io(String),
+    Format(String),
+}
+
+impl From<ReplayError> for PyErr {
+    fn from(error: ReplayError) -> PyErr {
+        match error {
+            ReplayError::Io(message) => PyIOError::new_err(message),
+            ReplayError::Format(message) => PyValueError::new_err(message),
+        }
+    }
+}
+
+fn io_error(path: &Path, error: io::Error) -> ReplayError {
+    ReplayError::Io(format!("{}: {}", path.display(), error))
+}
+
+/// One recorded market-data event.
+#[derive(Clone, Debug)]
+pub enum MarketEvent {
+    Delta(BookDelta),
+    Snapshot(BookSnapshot),
+    Trade(MarketTrade),
+}
+
+impl MarketEvent {
+    pub fn ts_ns(&self) -> u64 {
+        match self {
+            MarketEvent::Delta(delta) => delta.ts_ns,
+            MarketEvent::Snapshot(snapshot) => snapshot.ts_ns,
+            MarketEvent::Trade(trade) => trade.ts_ns,
+        }
+    }
+
+    pub fn symbol_id(&self) -> u32 {
+        match self {
+            MarketEvent::Delta(delta) => delta.symbol_id,
+            MarketEvent::Snapshot(snapshot) => snapshot.symbol_id,
+            MarketEvent::Trade(trade) => trade.symbol_id,
+        }
+    }
+
+    fn to_object(&self, py: Python<'_>) -> PyResult<PyObject> {
+        Ok(match self {
+            MarketEvent::Delta(delta) => Bound::new(py, delta.clone())?.into_any().unbind(),
+            MarketEvent::Snapshot(snapshot) => Bound::new(py, snapshot.clone())?.into_any().unbind(),
+            MarketEvent::Trade(trade) => Bound::new(py, trade.clone())?.into_any().unbind(),
+        })
+    }
+}
+
+fn narrow<T: TryFrom<u64>>(name: &str, value: u64) -> Result<T, String> {
+    T::try_from(value).map_err(|_| format!("{} out of range: {}", name, value))
+}
+
+fn parse_json(line: &str) -> Result<MarketEvent, String> {
+    let object: Json = serde_json::from_str(line).map_err(|e| e.to_string())?;
+    let int = |name: &str| {
+        object
+            .get(name)
+            .and_then(Json::as_u64)
+            .ok_or_else(|| format!("missing or invalid {}", name))
+    };
+    let float = |name: &str| {
+        object
+            .get(name)
+            .and_then(Json::as_f64)
+            .ok_or_else(|| format!("missing or invalid {}", name))
+    };
+    let seq = || match object.get("seq") {
+        None | Some(Json::Null) => Ok(0),
+        Some(_) => int("seq"),
+    };
+    let levels = |name: &str| -> Result<Vec<(f64, f64)>, String> {
+        let invalid = || format!("missing or invalid {}", name);
+        object
+            .get(name)
+            .and_then(Json::as_array)
+            .ok_or_else(invalid)?
+            .iter()
+            .map(|level| match level.as_array().map(Vec::as_slice) {
+                Some([price, quantity]) => price.as_f64().zip(quantity.as_f64()).ok_or_else(invalid),
+                _ => Err(invalid()),
+            })
+            .collect()
+    };
+    let kind = object
+        .get("type")
+        .and_then(Json::as_str)
+        .ok_or("missing or invalid type")?;
+    Ok(match kind {
+        "delta" => MarketEvent::Delta(BookDelta {
+            ts_ns: int("ts_ns")?,
+            symbol_id: narrow("symbol_id", int("symbol_id")?)?,
+            side: narrow("side", int("side")?)?,
+            price: float("price")?,
+            quantity: float("quantity")?,
+            seq: seq()?,
+        }),
+        "trade" => MarketEvent::Trade(MarketTrade {
+            ts_ns: int("ts_ns")?,
+            symbol_id: narrow("symbol_id", int("symbol_id")?)?,
+            price: float("price")?,
+            quantity: float("quantity")?,
+            aggressor_side: narrow("aggressor_side", int("aggressor_side")?)?,
+        }),
+        "snapshot" => MarketEvent::Snapshot(BookSnapshot {
+            ts_ns: int("ts_ns")?,
+            symbol_id: narrow("symbol_id", int("symbol_id")?)?,
+            seq: seq()?,
+            bids: levels("bids")?,
+            asks: levels("asks")?,
+        }),
+        other => return Err(format!("unknown event type {:?}", other)),
+    })
+}
+
+/// Column positions from a CSV header row.
+struct Columns {
+    kind: usize,
+    ts_ns: usize,
+    symbol_id: usize,
+    side: usize,
+    price: usize,
+    quantity: usize,
+    seq: Option<usize>,
+    width: usize,
+}
+
+impl Columns {
+    fn from_header(header: &str) -> Result<Columns, String> {
+        let names: Vec<&str> = header.split(',').map(str::trim).collect();
+        let find = |name: &str| names.iter().position(|column| *column == name);
+        let required = |name: &str| find(name).ok_or_else(|| format!("missing {} column", name));
+        Ok(Columns {
+            kind: required("type")?,
+            ts_ns: required("ts_ns")?,
+            symbol_id: required("symbol_id")?,
+            side: required("side")?,
+            price: required("price")?,
+            quantity: required("quantity")?,
+            seq: find("seq"),
+            width: names.len(),
+        })
+    }
+
+    fn parse(&self, line: &str) -> Result<MarketEvent, String> {
+        let cells: Vec<&str> = line.split(',').map(str::trim).collect();
+        if cells.len() != self.width {
+            return Err(format!("expected {} columns, got {}", self.width, cells.len()));
+        }
+        let int = |index: usize, name: &str| {
+            cells[index]
+                .parse::<u64>()
+                .map_err(|_| format!("invalid {}: {:?}", name, cells[index]))
+        };
+        let float = |index: usize, name: &str| {
+            cells[index]
+                .parse::<f64>()
+                .map_err(|_| format!("invalid {}: {:?}", name, cells[index]))
+        };
+        let ts_ns = int(self.ts_ns, "ts_ns")?;
+        let symbol_id = narrow("symbol_id", int(self.symbol_id, "symbol_id")?)?;
+        let side = narrow("side", int(self.side, "side")?)?;
+        let price = float(self.price, "price")?;
+        let quantity = float(self.quantity, "quantity")?;
+        Ok(match cells[self.kind] {
+            "delta" => MarketEvent::Delta(BookDelta {
+                ts_ns,
+                symbol_id,
+                side,
+                price,
+                quantity,
+                seq: match self.seq {
+                    Some(index) if !cells[index].is_empty() => int(index, "seq")?,
+                    _ => 0,
+                },
+            }),
+            "trade" => MarketEvent::Trade(MarketTrade {
+                ts_ns,
+                symbol_id,
+                price,
+                quantity,
+                aggressor_side: side,
+            }),
+            other => return Err(format!("unknown event type {:?}", other)),
+        })
+    }
+}
+
+/// A recording opened for reading front to back.
+pub struct Recording {
+    path: PathBuf,
+    format: Format,
+    input: Box<dyn BufRead + Send>,
+    /// Lines or frames read so far, for error messages.
+    position: u64,
+    columns: Option<Columns>,
+    line: String,
+    frame: Vec<u8>,
+}
+
+impl Recording {
+    pub fn open(path: &Path, format: Option<Format>) -> Result<Recording, ReplayError> {
+        let mut file = BufReader::new(File::open(path).map_err(|e| io_error(path, e))?);
+        let compressed = file
+            .fill_buf()
+            .map_err(|e| io_error(path, e))?
+            .starts_with(GZIP_MAGIC);
+        let mut input: Box<dyn BufRead + Send> = if compressed {
+            Box::new(BufReader::new(MultiGzDecoder::new(file)))
+        } else {
+            Box::new(file)
+        };
+        let format = match format {
+            Some(format) => format,
+            None => Format::sniff(input.fill_buf().map_err(|e| io_error(path, e))?),
+        };
+        let mut recording = Recording {
+            path: path.to_path_buf(),
+            format,
+            input,
+            position: 0,
+            columns: None,
+            line: String::new(),
+            frame: Vec::new(),
+        };
+        match format {
+            Format::Binary => recording.read_file_header()?,
+            Format::Csv => recording.read_columns()?,
+            Format::Ndjson => {}
+        }
+        Ok(recording)
+    }
+
+    pub fn format(&self) -> Format {
+        self.format
+    }
+
+    fn format_error(&self, message: impl std::fmt::Display) -> ReplayError {
+        let unit = match self.format {
+            Format::Binary => "frame ",
+            Format::Ndjson | Format::Csv => "line ",
+        };
+        ReplayError::Format(format!(
+            "{}: {}{}: {}",
+            self.path.display(),
+            unit,
+            self.position,
+            message
+        ))
+    }
+
+    /// Read the next `len` bytes into `frame`, reporting the end of the file
+    /// as a truncated recording.
+    fn read_exact(&mut self, len: usize, what: &str) -> Result<(), ReplayError> {
+        self.frame.resize(len, 0);
+        match self.input.read_exact(&mut self.frame) {
+            Ok(()) => Ok(()),
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => {
+                Err(self.format_error(format!("truncated {}", what)))
+            }
+            Err(e) => Err(io_error(&self.path, e)),
+        }
+    }
+
+    fn read_file_header(&mut self) -> Result<(), ReplayError> {
+        self.read_exact(MAGIC.len() + 4, "header")?;
+        if !self.frame.starts_with(MAGIC) {
+            return Err(self.format_error("not a binary market-data recording"));
+        }
+        let mut version = [0u8; 4];
+        version.copy_from_slice(&self.frame[MAGIC.len()..]);
+        let version = u32::from_le_bytes(version);
+        if version != LAYOUT_VERSION {
+            return Err(self.format_error(format!(
+                "recording layout version {} not supported (expected {})",
+                version, LAYOUT_VERSION
+            )));
+        }
+        Ok(())
+    }
+
+    /// Next line, or `false` at the end of the file.
+    fn read_line(&mut self) -> Result<bool, ReplayError> {
+        self.line.clear();
+        let read = self
+            .input
+            .read_line(&mut self.line)
+            .map_err(|e| io_error(&self.path, e))?;
+        self.position += 1;
+        Ok(read > 0)
+    }
+
+    /// Take the column layout from the first non-empty line.
+    fn read_columns(&mut self) -> Result<(), ReplayError> {
+        while self.read_line()? {
+            if !self.line.trim().is_empty() {
+                let columns = Columns::from_header(self.line.trim()).map_err(|e| self.format_error(e))?;
+                self.columns = Some(columns);
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    /// Next event, or `None` at the end of the recording.
+    pub fn next_event(&mut self) -> Result<Option<MarketEvent>, ReplayError> {
+        if self.format == Format::Binary {
+            return self.next_frame();
+        }
+        while self.read_line()? {
+            let line = self.line.trim();
+            if line.is_empty() {
+                continue;
+            }
+            let event = match (&self.columns, self.format) {
+                (Some(columns), Format::Csv) => columns.parse(line),
+                _ => parse_json(line),
+            };
+            return event.map(Some).map_err(|e| self.format_error(e));
+        }
+        Ok(None)
+    }
+
+    fn next_frame(&mut self) -> Result<Option<MarketEvent>, ReplayError> {
+        if self.input.fill_buf().map_err(|e| io_error(&self.path, e))?.is_empty() {
+            return Ok(None);
+        }
+        self.position += 1;
+        self.read_exact(4, "frame length")?;
+        let mut len = [0u8; 4];
+        len.copy_from_slice(&self.frame);
+        let len = u32::from_le_bytes(len) as usize;
+        if len > MAX_FRAME_LEN {
+            return Err(self.format_error(format!(
+                "frame length {} exceeds {} bytes",
+                len, MAX_FRAME_LEN
+            )));
+        }
+        self.read_exact(len, "frame")?;
+        let frame = Frame::parse(&self.frame).map_err(|e| self.format_error(e))?;
+        let value = |name: &str| {
+            frame
+                .template
+                .fields
+                .iter()
+                .find(|field| field.name == name)
+                .map_or(Value::Null, |field| frame.get(field))
+        };
+        let int = |name: &str| match value(name) {
+            Value::Int(v) => v,
+            Value::Bool(v) => v as u64,
+            Value::Float(_) | Value::Null => 0,
+        };
+        let float = |name: &str| match value(name) {
+            Value::Float(v) => v,
+            Value::Int(_) | Value::Bool(_) | Value::Null => f64::NAN,
+        };
+        Ok(Some(match frame.template.name {
+            "BookDelta" => MarketEvent::Delta(BookDelta {
+                ts_ns: int("ts_ns"),
+                symbol_id: int("symbol_id") as u32,
+                side: if int("is_bid") != 0 { 0 } else { 1 },
+                price: float("price"),
+                quantity: float("size"),
+                seq: int("seq"),
+            }),
+            "MarketTrade" => MarketEvent::Trade(MarketTrade {
+                ts_ns: int("ts_ns"),
+                symbol_id: int("symbol_id") as u32,
+                price: float("price"),
+                quantity: float("quantity"),
+                aggressor_side: int("aggressor_side") as u8,
+            }),
+            name => return Err(self.format_error(format!("{} frames cannot be replayed", name))),
+        }))
+    }
+}
+
+/// Frame of one template from `(field, value)` pairs; missing fields are null.
+fn encode(name: &str, values: &[(&str, Value)]) -> Result<Vec<u8>, String> {
+    let template = Template::by_name(name).ok_or_else(|| format!("no binary layout for {}", name))?;
+    let mut encoder = Encoder::new(template);
+    for field in template.fields {
+        let value = values
+            .iter()
+            .find(|(name, _)| *name == field.name)
+            .map_or(Value::Null, |(_, value)| *value);
+        encoder.set(field, value)?;
+    }
+    Ok(encoder.finish())
+}
+
+#[derive(FromPyObject)]
+enum Recordable {
+    Delta(BookDelta),
+    Trade(MarketTrade),
+}
+
+fn write_events<W: Write>(out: &mut W, path: &Path, events: &Bound<'_, PyAny>) -> PyResult<u64> {
+    out.write_all(MAGIC)
+        .and_then(|_| out.write_all(&LAYOUT_VERSION.to_le_bytes()))
+        .map_err(|e| PyIOError::new_err(format!("{}: {}", path.display(), e)))?;
+    let mut count = 0;
+    for event in events.try_iter()? {
+        let event = event?;
+        let frame = match event.extract::<Recordable>() {
+            Ok(Recordable::Delta(delta)) => {
+                parse_side(delta.side)?;
+                encode(
+                    "BookDelta",
+                    &[
+                        ("ts_ns", Value::Int(delta.ts_ns)),
+                        ("symbol_id", Value::Int(delta.symbol_id as u64)),
+                        ("is_bid", Value::Bool(delta.side == Side::Buy.as_u8())),
+                        ("price", Value::Float(delta.price)),
+                        ("size", Value::Float(delta.quantity)),
+                        ("seq", Value::Int(delta.seq)),
+                    ],
+                )
+            }
+            Ok(Recordable::Trade(trade)) => {
+                parse_side(trade.aggressor_side)?;
+                encode(
+                    "MarketTrade",
+                    &[
+                        ("ts_ns", Value::Int(trade.ts_ns)),
+                        ("symbol_id", Value::Int(trade.symbol_id as u64)),
+                        ("aggressor_side", Value::Int(trade.aggressor_side as u64)),
+                        ("price", Value::Float(trade.price)),
+                        ("quantity", Value::Float(trade.quantity)),
+                    ],
+                )
+            }
+            Err(_) if event.is_instance_of::<BookSnapshot>() => {
+                return Err(PyValueError::new_err(
+                    "BookSnapshot has no binary frame; record snapshots as NDJSON",
+                ))
+            }
+            Err(_) => {
+                return Err(PyTypeError::new_err(format!(
+                    "expected BookDelta or MarketTrade, got {}",
+                    event
+                        .get_type()
+                        .name()
+                        .map_or_else(|_| "?".to_string(), |name| name.to_string())
+                )))
+            }
+        }
+        .map_err(PyValueError::new_err)?;
+        out.write_all(&(frame.len() as u32).to_le_bytes())
+            .and_then(|_| out.write_all(&frame))
+            .map_err(|e| PyIOError::new_err(format!("{}: {}", path.display(), e)))?;
+        count += 1;
+    }
+    Ok(count)
+}
+
+/// Record `events` (`BookDelta`s and `MarketTrade`s, any iterable) to
+/// `path` in the binary replay format, gzip-compressed with `compress`.
+/// Returns the number of events written. A `BookSnapshot` is refused with
+/// `ValueError`, since the format cannot carry one.
+#[pyfunction]
+#[pyo3(signature = (path, events, compress=false))]
+pub fn write_market_data(path: PathBuf, events: &Bound<'_, PyAny>, compress: bool) -> PyResult<u64> {
+    let io_err = |e: io::Error| PyIOError::new_err(format!("{}: {}", path.display(), e));
+    let mut file = BufWriter::new(File::create(&path).map_err(io_err)?);
+    let count = if compress {
+        let mut out = GzEncoder::new(file, Compression::default());
+        let count = write_events(&mut out, &path, events)?;
+        file = out.finish().map_err(io_err)?;
+        count
+    } else {
+        write_events(&mut file, &path, events)?
+    };
+    file.flush().map_err(io_err)?;
+    Ok(count)
+}
+
+#[derive(Default)]
+struct Stats {
+    read: u64,
+    filtered: u64,
+    replayed: u64,
+    deltas: u64,
+    snapshots: u64,
+    trades: u64,
+    /// Deltas the book builder did not apply: stale, out of sync or past a gap.
+    dropped: u64,
+    /// Snapshots older than the book, which it ignored.
+    snapshots_dropped: u64,
+    gaps: u64,
+    first_ts_ns: Option<u64>,
+    last_ts_ns: Option<u64>,
+}
+
+/// Read position in the recording.
+struct Cursor {
+    recording: Recording,
+    /// Next event to replay, read ahead to check it against a stop time.
+    pending: Option<MarketEvent>,
+    symbols: Option<HashSet<u32>>,
+    speed: f64,
+    /// Wall-clock instant and event time that pacing is measured from.
+    anchor: Option<(Instant, u64)>,
+    finished: bool,
+    stats: Stats,
+}
+
+impl Cursor {
+    /// Timestamp of the next event that passes the symbol filter.
+    fn peek(&mut self) -> Result<Option<u64>, ReplayError> {
+        while self.pending.is_none() {
+            let Some(event) = self.recording.next_event()? else {
+                self.finished = true;
+                return Ok(None);
+            };
+            self.stats.read += 1;
+            if self.wanted(&event) {
+                self.pending = Some(event);
+            } else {
+                self.stats.filtered += 1;
+            }
+        }
+        Ok(self.pending.as_ref().map(MarketEvent::ts_ns))
+    }
+
+    fn wanted(&self, event: &MarketEvent) -> bool {
+        self.symbols
+            .as_ref()
+            .is_none_or(|symbols| symbols.contains(&event.symbol_id()))
+    }
+}
+
+/// When `advance` stops, besides the end of the recording.
+struct Stop {
+    max_events: Option<u64>,
+    /// Last event time to replay, inclusive.
+    until_ns: Option<u64>,
+    /// Pace events by `speed` and stop on `pause`.
+    live: bool,
+}
+
+fn check_speed(speed: f64) -> PyResult<()> {
+    if !(speed.is_finite() && speed >= 0.0) {
+        return Err(PyValueError::new_err(format!("invalid speed: {}", speed)));
+    }
+    Ok(())
+}
+
+/// Levels of `book` that `snapshot` does not carry, as `(side, price)`.
+fn removed_levels(book: &L2Book, snapshot: &BookSnapshot) -> Vec<(Side, f64)> {
+    let mut removed = Vec::new();
+    for (side, levels) in [(Side::Buy, &snapshot.bids), (Side::Sell, &snapshot.asks)] {
+        let kept: HashSet<u64> = levels.iter().map(|(price, _)| price.to_bits()).collect();
+        removed.extend(
+            book.depth(side, usize::MAX)
+                .into_iter()
+                .filter(|(price, _)| !kept.contains(&price.to_bits()))
+                .map(|(price, _)| (side, price)),
+        );
+    }
+    removed
+}
+
+/// Replays a recording (see the module docs for formats) into a
+/// `BookBuilder` and, if given, a `RustExecutionEngine`. The engine only
+/// sees deltas the builder applied, and a snapshot as level changes
+/// including deletions of the levels it drops. An engine not yet on a
+/// simulated clock is put on one starting at the first event.
+///
+/// `speed=0` replays as fast as possible, `1.0` in real time and other
+/// values scaled; `symbols` keeps only those symbol ids. While `run` is in
+/// progress only `pause`, `resume` and `paused` may be used.
+#[pyclass]
+pub struct MarketDataReplayer {
+    path: PathBuf,
+    format: Format,
+    books: Py<BookBuilder>,
+    engine: Option<Py<RustExecutionEngine>>,
+    paused: AtomicBool,
+    cursor: Mutex<Cursor>,
+}
+
+impl MarketDataReplayer {
+    fn cursor(&self) -> PyResult<MutexGuard<'_, Cursor>> {
+        match self.cursor.try_lock() {
+            Ok(cursor) => Ok(cursor),
+            Err(TryLockError::Poisoned(poisoned)) => Ok(poisoned.into_inner()),
+            Err(TryLockError::WouldBlock) => Err(PyRuntimeError::new_err("the replay is running")),
+        }
+    }
+
+    /// Sleep (GIL released) until `deadline`, or until paused if there is
+    /// none; `false` if paused meanwhile.
+    fn wait_until(&self, py: Python<'_>, deadline: Option<Instant>) -> PyResult<bool> {
+        loop {
+            if self.paused.load(Ordering::Acquire) {
+                return Ok(false);
+            }
+            let now = Instant::now();
+            let left = match deadline {
+                Some(deadline) if now >= deadline => return Ok(true),
+                Some(deadline) => (deadline - now).min(WAIT_SLICE),
+                None => WAIT_SLICE,
+            };
+            py.allow_threads(|| std::thread::sleep(left));
+            py.check_signals()?;
+        }
+    }
+
+    fn apply(&self, py: Python<'_>, stats: &mut Stats, event: &MarketEvent) -> PyResult<()> {
+        let builder = self.books.borrow(py);
+        let engine = self.engine.as_ref().map(|engine| engine.borrow(py));
+        let ts_ns = event.ts_ns();
+        if let Some(engine) = &engine {
+            if !engine.simulated.load(Ordering::Relaxed) {
+                engine.enable_simulation(ts_ns);
+            }
+        }
+        match event {
+            MarketEvent::Delta(delta) => {
+                let side = parse_side(delta.side)?;
+                check_level(delta.price, delta.quantity)?;
+                let outcome = builder.books().entry(delta.symbol_id).or_default().apply_delta(
+                    delta.seq,
+                    ts_ns,
+                    side,
+                    delta.price,
+                    delta.quantity,
+                );
+                stats.deltas += 1;
+                match outcome {
+                    DeltaOutcome::Applied => {
+                        if let Some(engine) = &engine {
+                            engine.on_book_level(delta.symbol_id, delta.side, delta.price, delta.quantity, Some(ts_ns))?;
+                        }
+                    }
+                    DeltaOutcome::Gap { .. } => {
+                        stats.gaps += 1;
+                        stats.dropped += 1;
+                    }
+                    DeltaOutcome::Stale | DeltaOutcome::OutOfSync => stats.dropped += 1,
+                }
+            }
+            MarketEvent::Snapshot(snapshot) => {
+                for &(price, quantity) in snapshot.bids.iter().chain(&snapshot.asks) {
+                    check_level(price, quantity)?;
+                }
+                let removed = {
+                    let mut books = builder.books();
+                    let book = books.entry(snapshot.symbol_id).or_default();
+                    let removed = removed_levels(book, snapshot);
+                    book.apply_snapshot(snapshot.seq, ts_ns, &snapshot.bids, &snapshot.asks)
+                        .then_some(removed)
+                };
+                stats.snapshots += 1;
+                match (removed, &engine) {
+                    (None, _) => stats.snapshots_dropped += 1,
+                    (Some(removed), Some(engine)) => {
+                        for (side, price) in removed {
+                            engine.on_book_level(snapshot.symbol_id, side.as_u8(), price, 0.0, Some(ts_ns))?;
+                        }
+                        for (side, levels) in [(Side::Buy, &snapshot.bids), (Side::Sell, &snapshot.asks)] {
+                            for &(price, quantity) in levels {
+                                engine.on_book_level(snapshot.symbol_id, side.as_u8(), price, quantity, Some(ts_ns))?;
+                            }
+                        }
+                    }
+                    (Some(_), None) => {}
+                }
+            }
+            MarketEvent::Trade(trade) => {
+                stats.trades += 1;
+                if let Some(engine) = &engine {
+                    engine.on_market_trade(
+                        trade.symbol_id,
+                        trade.price,
+                        trade.quantity,
+                        trade.aggressor_side,
+                        Some(ts_ns),
+                    )?;
+                }
+            }
+        }
+        stats.replayed += 1;
+        stats.first_ts_ns.get_or_insert(ts_ns);
+        stats.last_ts_ns = Some(ts_ns);
+        Ok(())
+    }
+
+    /// Replay events until `stop` or the end of the recording, handing each
+    /// to `callback` and adding it to `replayed` after it was applied.
+    /// Returns the number replayed.
+    fn advance(
+        &self,
+        py: Python<'_>,
+        cursor: &mut Cursor,
+        stop: Stop,
+        callback: Option<&Bound<'_, PyAny>>,
+        mut replayed: Option<&mut Vec<PyObject>>,
+    ) -> PyResult<u64> {
+        let mut count = 0;
+        while stop.max_events.is_none_or(|max_events| count < max_events) {
+            if stop.live && self.paused.load(Ordering::Acquire) {
+                break;
+            }
+            let Some(ts_ns) = cursor.peek()? else {
+                break;
+            };
+            if stop.until_ns.is_some_and(|until_ns| ts_ns > until_ns) {
+                break;
+            }
+            if stop.live && cursor.speed > 0.0 {
+                let (start, start_ns) = *cursor.anchor.get_or_insert((Instant::now(), ts_ns));
+                // A tiny speed puts the event past any representable time:
+                // it then waits until paused.
+                let offset = ts_ns.saturating_sub(start_ns) as f64 / 1e9 / cursor.speed;
+                let deadline = Duration::try_from_secs_f64(offset)
+                    .ok()
+                    .and_then(|offset| start.checked_add(offset));
+                if !self.wait_until(py, deadline)? {
+                    break;
+                }
+            }
+            let Some(event) = cursor.pending.take() else {
+                break;
+            };
+            self.apply(py, &mut cursor.stats, &event)?;
+            count += 1;
+            if callback.is_some() || replayed.is_some() {
+                let object = event.to_object(py)?;
+                if let Some(callback) = callback {
+                    callback.call1((object.bind(py),))?;
+                }
+                if let Some(replayed) = replayed.as_deref_mut() {
+                    replayed.push(object);
+                }
+            }
+        }
+        Ok(count)
+    }
+}
+
+#[pymethods]
+impl MarketDataReplayer {
+    /// Open a recording; `format` (`"ndjson"`, `"csv"` or `"binary"`) is
+    /// detected when left out. Without `book_builder` a new one is made.
+    #[new]
+    #[pyo3(signature = (path, format=None, symbols=None, speed=0.0, book_builder=None, engine=None))]
+    pub fn new(
+        py: Python<'_>,
+        path: PathBuf,
+        format: Option<&str>,
+        symbols: Option<Vec<u32>>,
+        speed: f64,
+        book_builder: Option<Py<BookBuilder>>,
+        engine: Option<Py<RustExecutionEngine>>,
+    ) -> PyResult<Self> {
+        check_speed(speed)?;
+        let format = format
+            .map(|name| {
+                Format::parse(name).ok_or_else(|| {
+                    PyValueError::new_err(format!(
+                        "unknown format {:?}; expected ndjson, csv or binary",
+                        name
+                    ))
+                })
+            })
+            .transpose()?;
+        let recording = Recording::open(&path, format)?;
+        let books = match book_builder {
+            Some(books) => books,
+            None => Py::new(py, BookBuilder::new())?,
+        };
+        Ok(Self {
+            path,
+            format: recording.format(),
+            books,
+            engine,
+            paused: AtomicBool::new(false),
+            cursor: Mutex::new(Cursor {
+                recording,
+                pending: None,
+                symbols: symbols.map(|symbols| symbols.into_iter().collect()),
+                speed,
+                anchor: None,
+                finished: false,
+                stats: Stats::default(),
+            }),
+        })
+    }
+
+    /// Replay until the recording ends, `max_events` were replayed, the next
+    /// event is later than `until_ns` or `pause` is called, passing each
+    /// event to `callback` after it was applied. Returns the number replayed.
+    #[pyo3(signature = (max_events=None, until_ns=None, callback=None))]
+    pub fn run(
+        &self,
+        py: Python<'_>,
+        max_events: Option<u64>,
+        until_ns: Option<u64>,
+        callback: Option<Bound<'_, PyAny>>,
+    ) -> PyResult<u64> {
+        let mut cursor = self.cursor()?;
+        cursor.anchor = None;
+        let stop = Stop {
+            max_events,
+            until_ns,
+            live: true,
+        };
+        self.advance(py, &mut cursor, stop, callback.as_ref(), None)
+    }
+
+    /// Apply the next `count` events at once, paused or not, and return them.
+    #[pyo3(signature = (count=1))]
+    pub fn step(&self, py: Python<'_>, count: u64) -> PyResult<Vec<PyObject>> {
+        let mut cursor = self.cursor()?;
+        let mut events = Vec::new();
+        let stop = Stop {
+            max_events: Some(count),
+            until_ns: None,
+            live: false,
+        };
+        self.advance(py, &mut cursor, stop, None, Some(&mut events))?;
+        Ok(events)
+    }
+
+    /// Apply every event before `ts_ns` at once, without callbacks, leaving
+    /// the books and engine as they were at that time. The builder and
+    /// engine cannot be rewound, so seeking backwards is an error; replay
+    /// into fresh ones instead. Returns the number of events applied.
+    pub fn seek(&self, py: Python<'_>, ts_ns: u64) -> PyResult<u64> {
+        let mut cursor = self.cursor()?;
+        if let Some(last_ns) = cursor.stats.last_ts_ns.filter(|last_ns| ts_ns < *last_ns) {
+            return Err(PyValueError::new_err(format!(
+                "cannot seek back to {} from {}",
+                ts_ns, last_ns
+            )));
+        }
+        cursor.anchor = None;
+        let Some(until_ns) = ts_ns.checked_sub(1) else {
+            return Ok(0);
+        };
+        let stop = Stop {
+            max_events: None,
+            until_ns: Some(until_ns),
+            live: false,
+        };
+        self.advance(py, &mut cursor, stop, None, None)
+    }
+
+    /// Stop `run` before its next event; it returns what it replayed so far.
+    pub fn pause(&self) {
+        self.paused.store(true, Ordering::Release);
+    }
+
+    pub fn resume(&self) {
+        self.paused.store(false, Ordering::Release);
+    }
+
+    #[getter]
+    pub fn paused(&self) -> bool {
+        self.paused.load(Ordering::Acquire)
+    }
+
+    /// Pace by `speed` times real time from the next `run`; 0 is as fast as
+    /// possible.
+    pub fn set_speed(&self, speed: f64) -> PyResult<()> {
+        check_speed(speed)?;
+        let mut cursor = self.cursor()?;
+        cursor.speed = speed;
+        cursor.anchor = None;
+        Ok(())
+    }
+
+    /// Replay only these symbol ids from the next event on; `None` replays
+    /// every symbol.
+    #[pyo3(signature = (symbols=None))]
+    pub fn set_symbols(&self, symbols: Option<Vec<u32>>) -> PyResult<()> {
+        let mut cursor = self.cursor()?;
+        cursor.symbols = symbols.map(|symbols| symbols.into_iter().collect());
+        if let Some(event) = cursor.pending.take() {
+            if cursor.wanted(&event) {
+                cursor.pending = Some(event);
+            } else {
+                cursor.stats.filtered += 1;
+            }
+        }
+        Ok(())
+    }
+
+    /// Timestamp of the last event replayed.
+    #[getter]
+    pub fn position_ns(&self) -> PyResult<Option<u64>> {
+        Ok(self.cursor()?.stats.last_ts_ns)
+    }
+
+    /// True once the end of the recording was reached.
+    #[getter]
+    pub fn finished(&self) -> PyResult<bool> {
+        let cursor = self.cursor()?;
+        Ok(cursor.finished && cursor.pending.is_none())
+    }
+
+    #[getter]
+    pub fn format(&self) -> &'static str {
+        self.format.name()
+    }
+
+    #[getter]
+    pub fn book_builder(&self, py: Python<'_>) -> Py<BookBuilder> {
+        self.books.clone_ref(py)
+    }
+
+    #[getter]
+    pub fn engine(&self, py: Python<'_>) -> Option<Py<RustExecutionEngine>> {
+        self.engine.as_ref().map(|engine| engine.clone_ref(py))
+    }
+
+    pub fn get_stats<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        let cursor = self.cursor()?;
+        let stats = &cursor.stats;
+        let dict = PyDict::new(py);
+        dict.set_item("format", self.format.name())?;
+        dict.set_item("events_read", stats.read)?;
+        dict.set_item("events_filtered", stats.filtered)?;
+        dict.set_item("events_replayed", stats.replayed)?;
+        dict.set_item("deltas", stats.deltas)?;
+        dict.set_item("snapshots", stats.snapshots)?;
+        dict.set_item("trades", stats.trades)?;
+        dict.set_item("deltas_dropped", stats.dropped)?;
+        dict.set_item("snapshots_dropped", stats.snapshots_dropped)?;
+        dict.set_item("gaps", stats.gaps)?;
+        dict.set_item("first_ts_ns", stats.first_ts_ns)?;
+        dict.set_item("last_ts_ns", stats.last_ts_ns)?;
+        dict.set_item("speed", cursor.speed)?;
+        dict.set_item("paused", self.paused())?;
+        dict.set_item("finished", cursor.finished && cursor.pending.is_none())?;
+        Ok(dict)
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "MarketDataReplayer(path={:?}, format={})",
+            self.path.display().to_string(),
+            self.format.name()
+        )
+    }
+}
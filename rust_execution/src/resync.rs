@@ -0,0 +1,526 @@
+//! Snapshot-then-buffered-diffs resync for sequenced depth feeds
+//!
+//! Each symbol's book moves through awaiting snapshot → buffering → synced,
+//! dropping back to buffering whenever the feed skips a sequence number.
+//! Diffs that arrive before or during a resync are buffered and replayed on
+//! top of the next snapshot, so the book never silently drifts from the
+//! venue's. Every transition is driven by the messages and the timestamps
+//! they carry, which makes a recorded feed replay identically.
+
+use crate::l2book::{
+    check_level, depth_dict, parse_side, top_of_book_dict, BookDelta, BookSnapshot, L2Book,
+};
+use crate::orderbook::Side;
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Mutex, MutexGuard};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SyncState {
+    /// Snapshot requested, no diffs received yet.
+    AwaitingSnapshot,
+    /// Snapshot requested, diffs buffered until it arrives.
+    Buffering,
+    Synced,
+    /// Synced, but no update for longer than the stale threshold.
+    Stale,
+}
+
+impl SyncState {
+    pub fn name(self) -> &'static str {
+        match self {
+            SyncState::AwaitingSnapshot => "awaiting_snapshot",
+            SyncState::Buffering => "buffering",
+            SyncState::Synced => "synced",
+            SyncState::Stale => "stale",
+        }
+    }
+
+    #[inline(always)]
+    fn is_live(self) -> bool {
+        matches!(self, SyncState::Synced | SyncState::Stale)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ResyncReason {
+    /// First subscription or first diff for the symbol.
+    Initial,
+    /// The feed skipped sequence numbers.
+    Gap,
+    /// The snapshot is older than the first buffered diff.
+    SnapshotBehind,
+    BufferOverflow,
+    /// The requested snapshot did not arrive in time.
+    Timeout,
+}
+
+impl ResyncReason {
+    pub fn name(self) -> &'static str {
+        match self {
+            ResyncReason::Initial => "initial",
+            ResyncReason::Gap => "gap",
+            ResyncReason::SnapshotBehind => "snapshot_behind",
+            ResyncReason::BufferOverflow => "buffer_overflow",
+            ResyncReason::Timeout => "timeout",
+        }
+    }
+}
+
+/// Ask the feed handler for a fresh snapshot of `symbol_id`.
+#[derive(Clone, Debug)]
+#[pyclass]
+pub struct ResyncRequest {
+    #[pyo3(get)]
+    pub ts_ns: u64,
+    #[pyo3(get)]
+    pub symbol_id: u32,
+    #[pyo3(get)]
+    pub reason: &'static str,
+    /// Next sequence number the book needed, 0 before any snapshot.
+    #[pyo3(get)]
+    pub expected_seq: u64,
+    /// First sequence number of the diff that triggered the request, 0 if
+    /// none did.
+    #[pyo3(get)]
+    pub received_seq: u64,
+}
+
+#[pymethods]
+impl ResyncRequest {
+    fn __repr__(&self) -> String {
+        format!(
+            "ResyncRequest(symbol_id={}, reason={}, expected_seq={}, received_seq={})",
+            self.symbol_id, self.reason, self.expected_seq, self.received_seq
+        )
+    }
+}
+
+/// One venue message: the level changes covering sequence numbers
+/// `first_seq..=last_seq`.
+#[derive(Clone, Debug)]
+pub struct Update {
+    pub first_seq: u64,
+    pub last_seq: u64,
+    pub ts_ns: u64,
+    pub levels: Vec<(Side, f64, f64)>,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct SyncConfig {
+    pub max_buffer: usize,
+    pub stale_after_ns: u64,
+    pub resync_timeout_ns: u64,
+}
+
+#[derive(Debug)]
+pub struct SyncedBook {
+    pub book: L2Book,
+    pub state: SyncState,
+    buffer: VecDeque<Update>,
+    requested_ns: u64,
+    last_update_ns: u64,
+    pub gaps: u64,
+    pub resyncs: u64,
+    /// Diffs and snapshots already covered by the book.
+    pub discarded: u64,
+}
+
+impl SyncedBook {
+    fn new() -> Self {
+        Self {
+            book: L2Book::default(),
+            state: SyncState::AwaitingSnapshot,
+            buffer: VecDeque::new(),
+            requested_ns: 0,
+            last_update_ns: 0,
+            gaps: 0,
+            resyncs: 0,
+            discarded: 0,
+        }
+    }
+
+    pub fn buffered(&self) -> usize {
+        self.buffer.len()
+    }
+
+    fn request(
+        &mut self,
+        symbol_id: u32,
+        ts_ns: u64,
+        reason: ResyncReason,
+        received_seq: u64,
+    ) -> ResyncRequest {
+        self.requested_ns = ts_ns;
+        self.resyncs += 1;
+        ResyncRequest {
+            ts_ns,
+            symbol_id,
+            reason: reason.name(),
+            expected_seq: if self.book.seq == 0 {
+                0
+            } else {
+                self.book.seq + 1
+            },
+            received_seq,
+        }
+    }
+
+    #[inline(always)]
+    fn apply(&mut self, update: &Update) {
+        for &(side, price, quantity) in &update.levels {
+            self.book
+                .apply_delta(0, update.ts_ns, side, price, quantity);
+        }
+        self.book.seq = update.last_seq;
+        self.book.ts_ns = update.ts_ns;
+        self.last_update_ns = update.ts_ns;
+    }
+
+    fn buffer(
+        &mut self,
+        symbol_id: u32,
+        config: &SyncConfig,
+        update: Update,
+        requests: &mut Vec<ResyncRequest>,
+    ) {
+        if self.buffer.len() >= config.max_buffer {
+            self.buffer.clear();
+            requests.push(self.request(
+                symbol_id,
+                update.ts_ns,
+                ResyncReason::BufferOverflow,
+                update.first_seq,
+            ));
+        }
+        self.buffer.push_back(update);
+        self.state = SyncState::Buffering;
+    }
+
+    pub fn on_update(
+        &mut self,
+        symbol_id: u32,
+        config: &SyncConfig,
+        update: Update,
+        requests: &mut Vec<ResyncRequest>,
+    ) {
+        if !self.state.is_live() {
+            self.buffer(symbol_id, config, update, requests);
+            return;
+        }
+        if update.last_seq <= self.book.seq {
+            self.discarded += 1;
+        } else if update.first_seq <= self.book.seq + 1 {
+            self.apply(&update);
+            self.state = SyncState::Synced;
+        } else {
+            self.gaps += 1;
+            requests.push(self.request(
+                symbol_id,
+                update.ts_ns,
+                ResyncReason::Gap,
+                update.first_seq,
+            ));
+            self.buffer(symbol_id, config, update, requests);
+        }
+    }
+
+    /// Apply a snapshot and replay the buffered diffs that follow it.
+    pub fn on_snapshot(&mut self, snapshot: &BookSnapshot, requests: &mut Vec<ResyncRequest>) {
+        if self.state.is_live() && snapshot.seq <= self.book.seq {
+            self.discarded += 1;
+            return;
+        }
+        // While resyncing the old book is of no use: even an older snapshot
+        // replaces it, and the buffered diffs tell whether it is behind.
+        self.book = L2Book::default();
+        self.book
+            .apply_snapshot(snapshot.seq, snapshot.ts_ns, &snapshot.bids, &snapshot.asks);
+        self.last_update_ns = snapshot.ts_ns;
+        self.state = SyncState::Synced;
+
+        while let Some(update) = self.buffer.pop_front() {
+            if update.last_seq <= self.book.seq {
+                self.discarded += 1;
+                continue;
+            }
+            if update.first_seq > self.book.seq + 1 {
+                let reason = if self.book.seq == snapshot.seq {
+                    ResyncReason::SnapshotBehind
+                } else {
+                    self.gaps += 1;
+                    ResyncReason::Gap
+                };
+                requests.push(self.request(
+                    snapshot.symbol_id,
+                    snapshot.ts_ns,
+                    reason,
+                    update.first_seq,
+                ));
+                self.buffer.push_front(update);
+                self.state = SyncState::Buffering;
+                return;
+            }
+            self.apply(&update);
+        }
+    }
+
+    /// Mark a quiet book stale and repeat snapshot requests that timed out.
+    /// A request made without a time starts its timeout at the first check.
+    pub fn check(
+        &mut self,
+        symbol_id: u32,
+        config: &SyncConfig,
+        now_ns: u64,
+        requests: &mut Vec<ResyncRequest>,
+    ) {
+        match self.state {
+            SyncState::Synced
+                if now_ns.saturating_sub(self.last_update_ns) > config.stale_after_ns =>
+            {
+                self.state = SyncState::Stale;
+            }
+            SyncState::AwaitingSnapshot | SyncState::Buffering if self.requested_ns == 0 => {
+                self.requested_ns = now_ns;
+            }
+            SyncState::AwaitingSnapshot | SyncState::Buffering
+                if now_ns.saturating_sub(self.requested_ns) > config.resync_timeout_ns =>
+            {
+                requests.push(self.request(symbol_id, now_ns, ResyncReason::Timeout, 0));
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Per-symbol resync state machines over L2 books. Every call returns the
+/// snapshot requests it produced; book reads return nothing until the
+/// symbol is synced (or stale).
+#[pyclass]
+pub struct BookSync {
+    config: SyncConfig,
+    books: Mutex<HashMap<u32, SyncedBook>>,
+}
+
+#[pymethods]
+impl BookSync {
+    #[new]
+    #[pyo3(signature = (max_buffer=10_000, stale_after_ms=5_000, resync_timeout_ms=10_000))]
+    pub fn new(max_buffer: usize, stale_after_ms: u64, resync_timeout_ms: u64) -> PyResult<Self> {
+        if max_buffer == 0 {
+            return Err(PyValueError::new_err("max_buffer must be at least 1"));
+        }
+        Ok(Self {
+            config: SyncConfig {
+                max_buffer,
+                stale_after_ns: stale_after_ms.saturating_mul(1_000_000),
+                resync_timeout_ns: resync_timeout_ms.saturating_mul(1_000_000),
+            },
+            books: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// Start (or restart) tracking a symbol from scratch. Without `ts_ns`
+    /// the snapshot timeout runs from the next `check`.
+    #[pyo3(signature = (symbol_id, ts_ns=0))]
+    pub fn subscribe(&self, symbol_id: u32, ts_ns: u64) -> ResyncRequest {
+        let mut books = self.books();
+        let book = books.entry(symbol_id).or_insert_with(SyncedBook::new);
+        *book = SyncedBook::new();
+        book.request(symbol_id, ts_ns, ResyncReason::Initial, 0)
+    }
+
+    pub fn on_snapshot(&self, snapshot: &BookSnapshot) -> PyResult<Vec<ResyncRequest>> {
+        for &(price, quantity) in snapshot.bids.iter().chain(&snapshot.asks) {
+            check_level(price, quantity)?;
+        }
+        let mut requests = Vec::new();
+        self.books()
+            .entry(snapshot.symbol_id)
+            .or_insert_with(SyncedBook::new)
+            .on_snapshot(snapshot, &mut requests);
+        Ok(requests)
+    }
+
+    /// A diff carrying a single level change; `delta.seq` is required.
+    pub fn on_delta(&self, delta: &BookDelta) -> PyResult<Vec<ResyncRequest>> {
+        let side = parse_side(delta.side)?;
+        check_level(delta.price, delta.quantity)?;
+        if delta.seq == 0 {
+            return Err(PyValueError::new_err("delta has no sequence number"));
+        }
+        Ok(self.update(
+            delta.symbol_id,
+            Update {
+                first_seq: delta.seq,
+                last_seq: delta.seq,
+                ts_ns: delta.ts_ns,
+                levels: vec![(side, delta.price, delta.quantity)],
+            },
+        ))
+    }
+
+    /// A venue message with several level changes for one symbol, all
+    /// carrying the message's last sequence number. `first_seq` is the
+    /// first number it covers (Binance `U`), defaulting to the last.
+    #[pyo3(signature = (deltas, first_seq=None))]
+    pub fn on_update(
+        &self,
+        deltas: Vec<PyRef<'_, BookDelta>>,
+        first_seq: Option<u64>,
+    ) -> PyResult<Vec<ResyncRequest>> {
+        let Some(head) = deltas.first() else {
+            return Err(PyValueError::new_err("update has no deltas"));
+        };
+        let (symbol_id, last_seq, ts_ns) = (head.symbol_id, head.seq, head.ts_ns);
+        if last_seq == 0 {
+            return Err(PyValueError::new_err("update has no sequence number"));
+        }
+        let first_seq = first_seq.unwrap_or(last_seq);
+        if first_seq == 0 || first_seq > last_seq {
+            return Err(PyValueError::new_err(format!(
+                "first_seq {} outside 1..={}",
+                first_seq, last_seq
+            )));
+        }
+        let mut levels = Vec::with_capacity(deltas.len());
+        for delta in &deltas {
+            if delta.symbol_id != symbol_id || delta.seq != last_seq {
+                return Err(PyValueError::new_err(
+                    "deltas of one update must share symbol_id and seq",
+                ));
+            }
+            check_level(delta.price, delta.quantity)?;
+            levels.push((parse_side(delta.side)?, delta.price, delta.quantity));
+        }
+        Ok(self.update(
+            symbol_id,
+            Update {
+                first_seq,
+                last_seq,
+                ts_ns,
+                levels,
+            },
+        ))
+    }
+
+    /// Advance time: mark quiet books stale and repeat overdue requests.
+    pub fn check(&self, now_ns: u64) -> Vec<ResyncRequest> {
+        let mut requests = Vec::new();
+        let mut books = self.books();
+        let mut symbols: Vec<u32> = books.keys().copied().collect();
+        symbols.sort_unstable();
+        for symbol_id in symbols {
+            if let Some(book) = books.get_mut(&symbol_id) {
+                book.check(symbol_id, &self.config, now_ns, &mut requests);
+            }
+        }
+        requests
+    }
+
+    /// `"awaiting_snapshot"`, `"buffering"`, `"synced"` or `"stale"`;
+    /// `None` for a symbol never seen.
+    pub fn state(&self, symbol_id: u32) -> Option<&'static str> {
+        self.books().get(&symbol_id).map(|book| book.state.name())
+    }
+
+    pub fn get_stats(&self, symbol_id: u32) -> PyResult<Option<PyObject>> {
+        let books = self.books();
+        let Some(book) = books.get(&symbol_id) else {
+            return Ok(None);
+        };
+        Python::with_gil(|py| {
+            let dict = PyDict::new(py);
+            dict.set_item("symbol_id", symbol_id)?;
+            dict.set_item("state", book.state.name())?;
+            dict.set_item("seq", book.book.seq)?;
+            dict.set_item("buffered", book.buffered())?;
+            dict.set_item("gaps", book.gaps)?;
+            dict.set_item("resyncs", book.resyncs)?;
+            dict.set_item("discarded", book.discarded)?;
+            Ok(Some(dict.into()))
+        })
+    }
+
+    pub fn top_of_book(&self, symbol_id: u32) -> PyResult<Option<PyObject>> {
+        let books = self.books();
+        let Some(book) = live(&books, symbol_id) else {
+            return Ok(None);
+        };
+        Python::with_gil(|py| top_of_book_dict(py, symbol_id, book))
+    }
+
+    #[pyo3(signature = (symbol_id, levels=10))]
+    pub fn get_depth(&self, symbol_id: u32, levels: usize) -> PyResult<PyObject> {
+        let books = self.books();
+        let state = books.get(&symbol_id).map(|book| book.state.name());
+        Python::with_gil(|py| {
+            let dict = depth_dict(py, symbol_id, live(&books, symbol_id), levels)?;
+            dict.set_item("state", state)?;
+            Ok(dict.into())
+        })
+    }
+
+    pub fn mid_price(&self, symbol_id: u32) -> Option<f64> {
+        live(&self.books(), symbol_id).and_then(L2Book::mid_price)
+    }
+
+    pub fn micro_price(&self, symbol_id: u32) -> Option<f64> {
+        live(&self.books(), symbol_id).and_then(L2Book::micro_price)
+    }
+
+    pub fn spread(&self, symbol_id: u32) -> Option<f64> {
+        live(&self.books(), symbol_id).and_then(L2Book::spread)
+    }
+
+    #[pyo3(signature = (symbol_id, depth=5))]
+    pub fn imbalance(&self, symbol_id: u32, depth: usize) -> Option<f64> {
+        live(&self.books(), symbol_id).map(|book| book.imbalance(depth))
+    }
+
+    pub fn symbols(&self) -> Vec<u32> {
+        let mut symbols: Vec<u32> = self.books().keys().copied().collect();
+        symbols.sort_unstable();
+        symbols
+    }
+
+    pub fn unsubscribe(&self, symbol_id: u32) -> bool {
+        self.books().remove(&symbol_id).is_some()
+    }
+}
+
+impl BookSync {
+    fn books(&self) -> MutexGuard<'_, HashMap<u32, SyncedBook>> {
+        self.books
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn update(&self, symbol_id: u32, update: Update) -> Vec<ResyncRequest> {
+        let mut requests = Vec::new();
+        let mut books = self.books();
+        let book = books.entry(symbol_id).or_insert_with(|| {
+            let mut book = SyncedBook::new();
+            requests.push(book.request(
+                symbol_id,
+                update.ts_ns,
+                ResyncReason::Initial,
+                update.first_seq,
+            ));
+            book
+        });
+        book.on_update(symbol_id, &self.config, update, &mut requests);
+        requests
+    }
+}
+
+/// The symbol's book if it can be read.
+#[inline(always)]
+fn live(books: &HashMap<u32, SyncedBook>, symbol_id: u32) -> Option<&L2Book> {
+    books
+        .get(&symbol_id)
+        .filter(|book| book.state.is_live())
+        .map(|book| &book.book)
+}
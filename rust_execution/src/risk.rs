@@ -0,0 +1,199 @@
+//! Pre-trade risk checks run inside the engine before an order is accepted
+//!
+//! Reject codes match `pkg.schemas.orders.RejectReason`.
+
+use crate::journal::{Reader, Writer};
+use crate::orderbook::Side;
+use std::collections::{HashMap, VecDeque};
+
+pub const POSITION_LIMIT: u16 = 101;
+pub const NOTIONAL_LIMIT: u16 = 102;
+pub const PRICE_BAND: u16 = 103;
+pub const RATE_LIMIT: u16 = 104;
+pub const INVENTORY_LIMIT: u16 = 105;
+pub const CONCENTRATION_LIMIT: u16 = 106;
+/// Refused by the kill switch rather than a limit.
+pub const KILL_SWITCH: u16 = 107;
+
+const RATE_WINDOW_NS: u64 = 1_000_000_000;
+
+/// Per-symbol limits; `None` disables a check.
+#[derive(Clone, Debug, Default)]
+pub struct RiskLimits {
+    pub max_order_qty: Option<f64>,
+    pub max_order_notional: Option<f64>,
+    /// Absolute position notional after the order fills.
+    pub max_position_notional: Option<f64>,
+    /// Absolute net position quantity after the order fills.
+    pub max_position_qty: Option<f64>,
+    /// Position notional as a percentage of the gate's capital.
+    pub max_concentration_pct: Option<f64>,
+    /// Maximum distance of a limit price from the reference price.
+    pub price_band_pct: Option<f64>,
+    pub max_orders_per_sec: Option<u32>,
+}
+
+/// Order as seen by the risk checks.
+pub struct RiskCheck {
+    pub symbol_id: u32,
+    pub side: Side,
+    pub limit_price: Option<f64>,
+    pub quantity: f64,
+    /// Last trade or mid, used to value market orders and for the price band.
+    pub reference_price: Option<f64>,
+    /// Net position before the order, positive long.
+    pub position: f64,
+    pub ts_ns: u64,
+}
+
+#[derive(Default)]
+pub struct RiskGate {
+    limits: HashMap<u32, RiskLimits>,
+    /// Account capital the concentration limit is measured against.
+    pub capital: Option<f64>,
+    order_times: HashMap<u32, VecDeque<u64>>,
+}
+
+impl RiskGate {
+    pub fn set_limits(&mut self, symbol_id: u32, limits: RiskLimits) {
+        self.limits.insert(symbol_id, limits);
+    }
+
+    pub fn limits(&self, symbol_id: u32) -> Option<&RiskLimits> {
+        self.limits.get(&symbol_id)
+    }
+
+    pub fn remove_limits(&mut self, symbol_id: u32) -> bool {
+        self.order_times.remove(&symbol_id);
+        self.limits.remove(&symbol_id).is_some()
+    }
+
+    /// Run every configured check, returning the reject code and message of
+    /// the first that fails. Nothing is counted towards the rate limit until
+    /// the order is accepted and passed to `record`.
+    #[inline(always)]
+    pub fn check(&self, order: &RiskCheck) -> Result<(), (u16, String)> {
+        let Some(limits) = self.limits.get(&order.symbol_id) else {
+            return Ok(());
+        };
+
+        if let Some(max) = limits.max_order_qty {
+            if order.quantity > max {
+                return Err((
+                    NOTIONAL_LIMIT,
+                    format!("Order quantity {} exceeds max {}", order.quantity, max),
+                ));
+            }
+        }
+
+        let new_position = match order.side {
+            Side::Buy => order.position + order.quantity,
+            Side::Sell => order.position - order.quantity,
+        };
+
+        if let Some(price) = order.limit_price.or(order.reference_price) {
+            let order_notional = order.quantity * price;
+            if let Some(max) = limits.max_order_notional {
+                if order_notional > max {
+                    return Err((
+                        NOTIONAL_LIMIT,
+                        format!("Order size ${:.2} exceeds max ${}", order_notional, max),
+                    ));
+                }
+            }
+
+            let position_notional = (new_position * price).abs();
+            if let Some(max) = limits.max_position_notional {
+                if position_notional > max {
+                    return Err((
+                        POSITION_LIMIT,
+                        format!("Position would be ${:.2}, exceeds max ${}", position_notional, max),
+                    ));
+                }
+            }
+
+            if let (Some(max_pct), Some(capital)) = (limits.max_concentration_pct, self.capital) {
+                let pct = position_notional / capital * 100.0;
+                if capital > 0.0 && pct > max_pct {
+                    return Err((
+                        CONCENTRATION_LIMIT,
+                        format!("Position would be {:.2}% of capital, exceeds max {}%", pct, max_pct),
+                    ));
+                }
+            }
+        }
+
+        if let Some(max) = limits.max_position_qty {
+            if new_position.abs() > max {
+                return Err((
+                    INVENTORY_LIMIT,
+                    format!("Position would be {} units, exceeds max {}", new_position, max),
+                ));
+            }
+        }
+
+        if let (Some(band), Some(price), Some(reference)) =
+            (limits.price_band_pct, order.limit_price, order.reference_price)
+        {
+            let change_pct = ((price - reference) / reference * 100.0).abs();
+            if reference > 0.0 && change_pct > band {
+                return Err((
+                    PRICE_BAND,
+                    format!("Price {} deviates {:.2}% from last {}", price, change_pct, reference),
+                ));
+            }
+        }
+
+        if let (Some(max), Some(times)) = (limits.max_orders_per_sec, self.order_times.get(&order.symbol_id)) {
+            let recent = times
+                .iter()
+                .filter(|ts| order.ts_ns.saturating_sub(**ts) < RATE_WINDOW_NS)
+                .count();
+            if recent >= max as usize {
+                return Err((RATE_LIMIT, "Rate limit exceeded".to_string()));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Count an accepted order or amend towards its symbol's rate limit.
+    pub fn record(&mut self, symbol_id: u32, ts_ns: u64) {
+        if self.limits.get(&symbol_id).and_then(|limits| limits.max_orders_per_sec).is_none() {
+            return;
+        }
+        let times = self.order_times.entry(symbol_id).or_default();
+        while times
+            .front()
+            .is_some_and(|ts| ts_ns.saturating_sub(*ts) >= RATE_WINDOW_NS)
+        {
+            times.pop_front();
+        }
+        times.push_back(ts_ns);
+    }
+
+    /// Order times still inside the rate window, for snapshots. Limits and
+    /// capital are configuration.
+    pub(crate) fn save(&self, w: &mut Writer<'_>) {
+        w.u32(self.order_times.len() as u32);
+        for (symbol_id, times) in &self.order_times {
+            w.u32(*symbol_id);
+            w.u32(times.len() as u32);
+            for ts_ns in times {
+                w.u64(*ts_ns);
+            }
+        }
+    }
+
+    pub(crate) fn restore(&mut self, r: &mut Reader<'_>) -> Option<()> {
+        self.order_times.clear();
+        for _ in 0..r.u32()? {
+            let symbol_id = r.u32()?;
+            let times = self.order_times.entry(symbol_id).or_default();
+            for _ in 0..r.u32()? {
+                times.push_back(r.u64()?);
+            }
+        }
+        Some(())
+    }
+}
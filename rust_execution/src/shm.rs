@@ -0,0 +1,589 @@
+//! Shared-memory ring buffers between processes on one host
+//!
+//! A ring is a memory-mapped file (under `/dev/shm` by default) holding a
+//! header and a power-of-two array of fixed-size slots. Each slot carries a
+//! sequence word: a producer claims position `pos` when the slot's sequence
+//! equals `pos`, writes the frame, then publishes it by storing `pos + 1`;
+//! the consumer releases the slot for the next lap by storing
+//! `pos + capacity`. Single-producer rings advance the head with a plain
+//! store, multi-producer rings with a CAS. Either way there is one consumer.
+//!
+//! A single-producer ring must have exactly one publisher. A second one is
+//! only noticed when it finds a slot claimed ahead of the head; if both read
+//! the same head they claim the same slot, and frames are lost or torn.
+//!
+//! A multi-producer ring is not robust to a producer dying between its head
+//! CAS and the sequence store that publishes the frame. The slot stays
+//! claimed but never published, and the consumer, which reads in order,
+//! blocks on it forever; frames published behind it are never delivered.
+//! Recreate the ring after such a crash.
+
+use memmap2::MmapMut;
+use pyo3::exceptions::{PyFileNotFoundError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::{PyBytes, PyDict};
+use std::fs::{self, OpenOptions};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+const MAGIC: u64 = u64::from_le_bytes(*b"SGMXRING");
+const LAYOUT_VERSION: u64 = 1;
+const FLAG_MULTI_PRODUCER: u64 = 1;
+const CACHE_LINE: usize = 64;
+
+// Header words. Producer and consumer cursors sit on their own cache lines.
+const OFF_MAGIC: usize = 0;
+const OFF_VERSION: usize = 8;
+const OFF_FLAGS: usize = 16;
+const OFF_CAPACITY: usize = 24;
+const OFF_SLOT_SIZE: usize = 32;
+const OFF_HEAD: usize = CACHE_LINE;
+const OFF_DROPPED: usize = CACHE_LINE + 8;
+const OFF_TAIL: usize = 2 * CACHE_LINE;
+const HEADER_LEN: usize = 3 * CACHE_LINE;
+
+// Slot: sequence word, frame length, then the frame.
+const SLOT_SEQ: usize = 0;
+const SLOT_LEN: usize = 8;
+const SLOT_DATA: usize = 16;
+
+pub const DEFAULT_SHM_DIR: &str = "/dev/shm";
+
+fn slot_stride(slot_size: usize) -> usize {
+    (SLOT_DATA + slot_size).div_ceil(CACHE_LINE) * CACHE_LINE
+}
+
+/// Bare names live in `/dev/shm`; anything with a `/` is used as a path.
+pub fn ring_path(name: &str) -> Result<PathBuf, String> {
+    if name.is_empty() {
+        return Err("ring name must not be empty".to_string());
+    }
+    Ok(if name.contains('/') {
+        PathBuf::from(name)
+    } else {
+        Path::new(DEFAULT_SHM_DIR).join(name)
+    })
+}
+
+pub struct Ring {
+    _map: MmapMut,
+    base: *mut u8,
+    capacity: u64,
+    slot_size: usize,
+    stride: usize,
+    multi_producer: bool,
+}
+
+// The mapping is shared with other processes anyway; all cross-thread
+// access goes through the atomics and the slot sequence protocol.
+unsafe impl Send for Ring {}
+unsafe impl Sync for Ring {}
+
+impl Ring {
+    /// Create a fresh ring at `path`, replacing any existing file. Processes
+    /// still mapping the old file keep their (now orphaned) copy.
+    pub fn create(
+        path: &Path,
+        capacity: u64,
+        slot_size: usize,
+        multi_producer: bool,
+    ) -> Result<Self, String> {
+        if capacity == 0 || slot_size == 0 {
+            return Err("capacity and slot_size must be positive".to_string());
+        }
+        if slot_size > u32::MAX as usize {
+            return Err(format!("slot_size {} too large", slot_size));
+        }
+        let capacity = capacity
+            .checked_next_power_of_two()
+            .ok_or_else(|| format!("capacity {} too large", capacity))?;
+        let stride = slot_stride(slot_size);
+        let len = (capacity as usize)
+            .checked_mul(stride)
+            .and_then(|slots| slots.checked_add(HEADER_LEN))
+            .ok_or_else(|| "ring too large".to_string())?;
+
+        let _ = fs::remove_file(path);
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create_new(true)
+            .open(path)
+            .map_err(|e| format!("{}: {}", path.display(), e))?;
+        file.set_len(len as u64)
+            .map_err(|e| format!("{}: {}", path.display(), e))?;
+        let ring = Self::map(file, capacity, slot_size, multi_producer)?;
+
+        for pos in 0..capacity {
+            ring.slot_seq(pos).store(pos, Ordering::Relaxed);
+        }
+        ring.word(OFF_VERSION)
+            .store(LAYOUT_VERSION, Ordering::Relaxed);
+        ring.word(OFF_FLAGS).store(
+            if multi_producer {
+                FLAG_MULTI_PRODUCER
+            } else {
+                0
+            },
+            Ordering::Relaxed,
+        );
+        ring.word(OFF_CAPACITY).store(capacity, Ordering::Relaxed);
+        ring.word(OFF_SLOT_SIZE)
+            .store(slot_size as u64, Ordering::Relaxed);
+        // Openers check the magic last-written, so they never see a half-built ring.
+        ring.word(OFF_MAGIC).store(MAGIC, Ordering::Release);
+        Ok(ring)
+    }
+
+    /// Attach to a ring another process created.
+    pub fn open(path: &Path) -> Result<Self, String> {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(path)
+            .map_err(|e| format!("{}: {}", path.display(), e))?;
+        let len = file
+            .metadata()
+            .map_err(|e| format!("{}: {}", path.display(), e))?
+            .len() as usize;
+        if len < HEADER_LEN {
+            return Err(format!("{} is not a ring buffer", path.display()));
+        }
+        // Map the header first to learn the geometry.
+        let probe = Self::map(file.try_clone().map_err(|e| e.to_string())?, 0, 0, false)?;
+        if probe.word(OFF_MAGIC).load(Ordering::Acquire) != MAGIC {
+            return Err(format!("{} is not a ring buffer", path.display()));
+        }
+        let version = probe.word(OFF_VERSION).load(Ordering::Relaxed);
+        if version != LAYOUT_VERSION {
+            return Err(format!(
+                "{}: ring layout version {} not supported",
+                path.display(),
+                version
+            ));
+        }
+        let capacity = probe.word(OFF_CAPACITY).load(Ordering::Relaxed);
+        let slot_size = probe.word(OFF_SLOT_SIZE).load(Ordering::Relaxed) as usize;
+        let multi_producer =
+            probe.word(OFF_FLAGS).load(Ordering::Relaxed) & FLAG_MULTI_PRODUCER != 0;
+        if !capacity.is_power_of_two()
+            || len != HEADER_LEN + capacity as usize * slot_stride(slot_size)
+        {
+            return Err(format!("{}: corrupt ring header", path.display()));
+        }
+        Self::map(file, capacity, slot_size, multi_producer)
+    }
+
+    fn map(
+        file: fs::File,
+        capacity: u64,
+        slot_size: usize,
+        multi_producer: bool,
+    ) -> Result<Self, String> {
+        let mut map = unsafe { MmapMut::map_mut(&file) }.map_err(|e| e.to_string())?;
+        let base = map.as_mut_ptr();
+        Ok(Self {
+            _map: map,
+            base,
+            capacity,
+            slot_size,
+            stride: slot_stride(slot_size),
+            multi_producer,
+        })
+    }
+
+    #[inline(always)]
+    fn word(&self, offset: usize) -> &AtomicU64 {
+        // Every offset is 8-aligned within a page-aligned mapping.
+        unsafe { &*(self.base.add(offset) as *const AtomicU64) }
+    }
+
+    #[inline(always)]
+    fn slot(&self, pos: u64) -> *mut u8 {
+        let index = (pos & (self.capacity - 1)) as usize;
+        unsafe { self.base.add(HEADER_LEN + index * self.stride) }
+    }
+
+    #[inline(always)]
+    fn slot_seq(&self, pos: u64) -> &AtomicU64 {
+        unsafe { &*(self.slot(pos).add(SLOT_SEQ) as *const AtomicU64) }
+    }
+
+    pub fn capacity(&self) -> u64 {
+        self.capacity
+    }
+
+    pub fn slot_size(&self) -> usize {
+        self.slot_size
+    }
+
+    pub fn multi_producer(&self) -> bool {
+        self.multi_producer
+    }
+
+    /// Copy `frame` into the next slot. Returns `Ok(false)` if the ring is
+    /// full; the frame is dropped and counted. The error for a second
+    /// producer on a single-producer ring is best effort, not a guard.
+    #[inline(always)]
+    pub fn publish(&self, frame: &[u8]) -> Result<bool, String> {
+        if frame.len() > self.slot_size {
+            return Err(format!(
+                "frame of {} bytes exceeds slot size {}",
+                frame.len(),
+                self.slot_size
+            ));
+        }
+        let head = self.word(OFF_HEAD);
+        let mut pos = head.load(Ordering::Relaxed);
+        loop {
+            let seq = self.slot_seq(pos).load(Ordering::Acquire);
+            let lag = seq.wrapping_sub(pos) as i64;
+            if lag == 0 {
+                if !self.multi_producer {
+                    head.store(pos + 1, Ordering::Relaxed);
+                    break;
+                }
+                match head.compare_exchange_weak(pos, pos + 1, Ordering::Relaxed, Ordering::Relaxed)
+                {
+                    Ok(_) => break,
+                    Err(current) => pos = current,
+                }
+            } else if lag < 0 {
+                self.word(OFF_DROPPED).fetch_add(1, Ordering::Relaxed);
+                return Ok(false);
+            } else if self.multi_producer {
+                pos = head.load(Ordering::Relaxed);
+            } else {
+                return Err("single-producer ring has another producer".to_string());
+            }
+        }
+
+        let slot = self.slot(pos);
+        unsafe {
+            (slot.add(SLOT_LEN) as *mut u32).write(frame.len() as u32);
+            std::ptr::copy_nonoverlapping(frame.as_ptr(), slot.add(SLOT_DATA), frame.len());
+        }
+        self.slot_seq(pos).store(pos + 1, Ordering::Release);
+        Ok(true)
+    }
+
+    /// Take the oldest published frame, if any. Consumer side only.
+    #[inline(always)]
+    pub fn poll(&self) -> Option<Vec<u8>> {
+        let tail = self.word(OFF_TAIL);
+        let pos = tail.load(Ordering::Relaxed);
+        if self.slot_seq(pos).load(Ordering::Acquire) != pos + 1 {
+            return None;
+        }
+        let slot = self.slot(pos);
+        let frame = unsafe {
+            let len = ((slot.add(SLOT_LEN) as *const u32).read() as usize).min(self.slot_size);
+            std::slice::from_raw_parts(slot.add(SLOT_DATA), len).to_vec()
+        };
+        self.slot_seq(pos)
+            .store(pos + self.capacity, Ordering::Release);
+        tail.store(pos + 1, Ordering::Release);
+        Some(frame)
+    }
+
+    /// Frames claimed by producers but not yet consumed.
+    pub fn pending(&self) -> u64 {
+        let tail = self.word(OFF_TAIL).load(Ordering::Acquire);
+        self.word(OFF_HEAD)
+            .load(Ordering::Acquire)
+            .saturating_sub(tail)
+    }
+
+    pub fn published(&self) -> u64 {
+        self.word(OFF_HEAD).load(Ordering::Acquire)
+    }
+
+    pub fn consumed(&self) -> u64 {
+        self.word(OFF_TAIL).load(Ordering::Acquire)
+    }
+
+    pub fn dropped(&self) -> u64 {
+        self.word(OFF_DROPPED).load(Ordering::Relaxed)
+    }
+}
+
+/// Spin, then yield, then sleep in short steps.
+struct Backoff {
+    step: u32,
+}
+
+impl Backoff {
+    fn snooze(&mut self) {
+        match self.step {
+            0..64 => std::hint::spin_loop(),
+            64..128 => std::thread::yield_now(),
+            _ => std::thread::sleep(Duration::from_micros(50)),
+        }
+        self.step = self.step.saturating_add(1);
+    }
+}
+
+/// Shared-memory frame queue. `ShmRing(name, create=True, ...)` makes a new
+/// ring (capacity rounded up to a power of two, frames up to `slot_size`
+/// bytes); `ShmRing(name)` attaches to an existing one and takes its
+/// geometry from the file. Any number of processes may publish to a
+/// `multi_producer` ring; other rings take exactly one producer, which is
+/// not enforced. Exactly one process should consume.
+#[pyclass]
+pub struct ShmRing {
+    ring: Ring,
+    path: PathBuf,
+    consumer: Mutex<()>,
+}
+
+impl ShmRing {
+    fn wait_slice(&self, deadline: Option<Instant>, slice: Duration) -> Option<Vec<u8>> {
+        let _consumer = self.consumer.lock().unwrap_or_else(|p| p.into_inner());
+        let slice_end = Instant::now() + slice;
+        let mut backoff = Backoff { step: 0 };
+        loop {
+            if let Some(frame) = self.ring.poll() {
+                return Some(frame);
+            }
+            let now = Instant::now();
+            if now >= slice_end || deadline.is_some_and(|deadline| now >= deadline) {
+                return None;
+            }
+            backoff.snooze();
+        }
+    }
+}
+
+#[pymethods]
+impl ShmRing {
+    #[new]
+    #[pyo3(signature = (name, create=false, capacity=4096, slot_size=256, multi_producer=false))]
+    pub fn new(
+        name: &str,
+        create: bool,
+        capacity: u64,
+        slot_size: usize,
+        multi_producer: bool,
+    ) -> PyResult<Self> {
+        let path = ring_path(name).map_err(PyValueError::new_err)?;
+        let ring = if create {
+            Ring::create(&path, capacity, slot_size, multi_producer)
+        } else {
+            if !path.exists() {
+                return Err(PyFileNotFoundError::new_err(format!(
+                    "no ring at {}",
+                    path.display()
+                )));
+            }
+            Ring::open(&path)
+        }
+        .map_err(PyValueError::new_err)?;
+        Ok(Self {
+            ring,
+            path,
+            consumer: Mutex::new(()),
+        })
+    }
+
+    /// Enqueue one frame (e.g. from `pkg.schemas.codec.encode`). Returns
+    /// `False` without blocking if the ring is full.
+    pub fn publish(&self, frame: &[u8]) -> PyResult<bool> {
+        self.ring.publish(frame).map_err(PyValueError::new_err)
+    }
+
+    /// Next frame, or `None` if the ring is empty.
+    pub fn poll<'py>(&self, py: Python<'py>) -> Option<Bound<'py, PyBytes>> {
+        let _consumer = self.consumer.lock().unwrap_or_else(|p| p.into_inner());
+        self.ring.poll().map(|frame| PyBytes::new(py, &frame))
+    }
+
+    /// Up to `max_messages` frames, oldest first.
+    #[pyo3(signature = (max_messages=64))]
+    pub fn poll_batch<'py>(
+        &self,
+        py: Python<'py>,
+        max_messages: usize,
+    ) -> Vec<Bound<'py, PyBytes>> {
+        let _consumer = self.consumer.lock().unwrap_or_else(|p| p.into_inner());
+        std::iter::from_fn(|| self.ring.poll())
+            .take(max_messages)
+            .map(|frame| PyBytes::new(py, &frame))
+            .collect()
+    }
+
+    /// Block (GIL released) until a frame arrives or `timeout_ms` passes;
+    /// `None`, or a timeout too long to represent such as `inf`, waits
+    /// indefinitely but still honours Ctrl-C.
+    #[pyo3(signature = (timeout_ms=None))]
+    pub fn wait<'py>(
+        &self,
+        py: Python<'py>,
+        timeout_ms: Option<f64>,
+    ) -> PyResult<Option<Bound<'py, PyBytes>>> {
+        if timeout_ms.is_some_and(f64::is_nan) {
+            return Err(PyValueError::new_err("timeout_ms must not be NaN"));
+        }
+        let deadline = timeout_ms.and_then(|ms| {
+            let timeout = Duration::try_from_secs_f64(ms.max(0.0) / 1e3).ok()?;
+            Instant::now().checked_add(timeout)
+        });
+        loop {
+            if let Some(frame) =
+                py.allow_threads(|| self.wait_slice(deadline, Duration::from_millis(50)))
+            {
+                return Ok(Some(PyBytes::new(py, &frame)));
+            }
+            if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                return Ok(None);
+            }
+            py.check_signals()?;
+        }
+    }
+
+    #[getter]
+    pub fn path(&self) -> String {
+        self.path.display().to_string()
+    }
+
+    #[getter]
+    pub fn capacity(&self) -> u64 {
+        self.ring.capacity()
+    }
+
+    #[getter]
+    pub fn slot_size(&self) -> usize {
+        self.ring.slot_size()
+    }
+
+    #[getter]
+    pub fn multi_producer(&self) -> bool {
+        self.ring.multi_producer()
+    }
+
+    pub fn __len__(&self) -> usize {
+        self.ring.pending() as usize
+    }
+
+    /// Counters shared by every process attached to the ring.
+    pub fn get_stats<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        let dict = PyDict::new(py);
+        dict.set_item("capacity", self.ring.capacity())?;
+        dict.set_item("slot_size", self.ring.slot_size())?;
+        dict.set_item("multi_producer", self.ring.multi_producer())?;
+        dict.set_item("published", self.ring.published())?;
+        dict.set_item("consumed", self.ring.consumed())?;
+        dict.set_item("pending", self.ring.pending())?;
+        dict.set_item("dropped", self.ring.dropped())?;
+        Ok(dict)
+    }
+
+    /// Remove the backing file; attached processes keep their mapping.
+    pub fn unlink(&self) -> PyResult<()> {
+        match fs::remove_file(&self.path) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(PyValueError::new_err(
+                format!("{}: {}", self.path.display(), e),
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "ShmRing(path={:?}, capacity={}, slot_size={}, multi_producer={})",
+            self.path.display().to_string(),
+            self.ring.capacity(),
+            self.ring.slot_size(),
+            self.ring.multi_producer()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_ring(name: &str, capacity: u64, slot_size: usize, multi_producer: bool) -> (PathBuf, Ring) {
+        let path = std::env::temp_dir().join(format!("sigmax-ring-{}-{}", std::process::id(), name));
+        let ring = Ring::create(&path, capacity, slot_size, multi_producer).unwrap();
+        (path, ring)
+    }
+
+    #[test]
+    fn geometry() {
+        let (path, ring) = temp_ring("geometry", 3, 20, false);
+        assert_eq!((ring.capacity(), ring.slot_size()), (4, 20));
+        assert_eq!(slot_stride(20), 64);
+        assert_eq!(slot_stride(49), 128);
+        assert_eq!(fs::metadata(&path).unwrap().len() as usize, HEADER_LEN + 4 * 64);
+        assert!(Ring::create(&path, 0, 8, false).is_err());
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn slot_sequences_follow_each_lap() {
+        let (path, ring) = temp_ring("laps", 2, 8, false);
+        assert_eq!(ring.slot_seq(0).load(Ordering::Relaxed), 0);
+        assert!(ring.publish(b"a").unwrap());
+        // Published slots carry pos + 1; consumed ones pos + capacity.
+        assert_eq!(ring.slot_seq(0).load(Ordering::Relaxed), 1);
+        assert_eq!(ring.poll().as_deref(), Some(&b"a"[..]));
+        assert_eq!(ring.slot_seq(0).load(Ordering::Relaxed), 2);
+        assert_eq!(ring.poll(), None);
+
+        for frame in [b"b", b"c"] {
+            assert!(ring.publish(frame).unwrap());
+        }
+        assert_eq!(ring.slot_seq(2).load(Ordering::Relaxed), 3);
+        assert_eq!(ring.poll().as_deref(), Some(&b"b"[..]));
+        assert_eq!(ring.poll().as_deref(), Some(&b"c"[..]));
+        assert_eq!((ring.published(), ring.consumed(), ring.pending()), (3, 3, 0));
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn full_ring_drops_and_counts() {
+        let (path, ring) = temp_ring("full", 2, 8, true);
+        assert!(ring.publish(b"1").unwrap());
+        assert!(ring.publish(b"2").unwrap());
+        assert!(!ring.publish(b"3").unwrap());
+        assert_eq!((ring.pending(), ring.dropped()), (2, 1));
+        assert!(ring.publish(&[0; 9]).is_err());
+
+        assert_eq!(ring.poll().as_deref(), Some(&b"1"[..]));
+        assert!(ring.publish(b"4").unwrap());
+        assert_eq!(ring.poll().as_deref(), Some(&b"2"[..]));
+        assert_eq!(ring.poll().as_deref(), Some(&b"4"[..]));
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn claimed_slot_blocks_the_consumer_until_published() {
+        let (path, ring) = temp_ring("claimed", 4, 8, true);
+        // A producer that won the head CAS but has not stored the sequence yet.
+        ring.word(OFF_HEAD).store(1, Ordering::Relaxed);
+        assert!(ring.publish(b"next").unwrap());
+        assert_eq!(ring.pending(), 2);
+        assert_eq!(ring.poll(), None);
+
+        ring.slot_seq(0).store(1, Ordering::Release);
+        assert_eq!(ring.poll().as_deref(), Some(&b""[..]));
+        assert_eq!(ring.poll().as_deref(), Some(&b"next"[..]));
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn second_mapping_shares_the_ring() {
+        let (path, producer) = temp_ring("shared", 8, 16, false);
+        let consumer = Ring::open(&path).unwrap();
+        assert_eq!((consumer.capacity(), consumer.slot_size(), consumer.multi_producer()), (8, 16, false));
+        assert!(producer.publish(b"tick").unwrap());
+        assert_eq!(consumer.poll().as_deref(), Some(&b"tick"[..]));
+        assert_eq!(producer.consumed(), 1);
+
+        fs::write(&path, [0u8; HEADER_LEN]).unwrap();
+        assert!(Ring::open(&path).is_err());
+        fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,104 @@
+//! Pluggable slippage models applied to simulated taker fills
+
+use crate::orderbook::Side;
+
+/// Market state seen by an order when it arrives at the matcher.
+#[derive(Clone, Copy, Debug)]
+pub struct SlippageContext {
+    pub side: Side,
+    pub quantity: f64,
+    /// Mid if both sides are quoted, otherwise the opposite touch.
+    pub arrival_price: f64,
+    pub spread: Option<f64>,
+}
+
+pub trait SlippageModel: Send + Sync {
+    /// Adverse price offset (in price units, always >= 0) added to a buy's
+    /// or subtracted from a sell's fill at `fill_price`.
+    fn impact(&self, ctx: &SlippageContext, fill_price: f64) -> f64;
+
+    fn name(&self) -> &'static str;
+}
+
+/// Fills at the prices of the levels walked; the walk is the slippage.
+pub struct BookWalk;
+
+impl SlippageModel for BookWalk {
+    #[inline(always)]
+    fn impact(&self, _ctx: &SlippageContext, _fill_price: f64) -> f64 {
+        0.0
+    }
+
+    fn name(&self) -> &'static str {
+        "book_walk"
+    }
+}
+
+/// Constant cost of `bps` basis points on every fill.
+pub struct FixedBps {
+    pub bps: f64,
+}
+
+impl SlippageModel for FixedBps {
+    #[inline(always)]
+    fn impact(&self, _ctx: &SlippageContext, fill_price: f64) -> f64 {
+        fill_price * self.bps / 10_000.0
+    }
+
+    fn name(&self) -> &'static str {
+        "fixed_bps"
+    }
+}
+
+/// Cost of `fraction` times the quoted spread at arrival.
+pub struct SpreadProportional {
+    pub fraction: f64,
+}
+
+impl SlippageModel for SpreadProportional {
+    #[inline(always)]
+    fn impact(&self, ctx: &SlippageContext, _fill_price: f64) -> f64 {
+        ctx.spread.map_or(0.0, |spread| spread * self.fraction)
+    }
+
+    fn name(&self) -> &'static str {
+        "spread"
+    }
+}
+
+/// Almgren-style square-root market impact:
+/// `coefficient * volatility * sqrt(quantity / daily_volume) * arrival_price`.
+pub struct SquareRootImpact {
+    pub coefficient: f64,
+    pub volatility: f64,
+    pub daily_volume: f64,
+}
+
+impl SlippageModel for SquareRootImpact {
+    #[inline(always)]
+    fn impact(&self, ctx: &SlippageContext, _fill_price: f64) -> f64 {
+        if self.daily_volume <= 0.0 {
+            return 0.0;
+        }
+        self.coefficient * self.volatility * (ctx.quantity / self.daily_volume).sqrt() * ctx.arrival_price
+    }
+
+    fn name(&self) -> &'static str {
+        "sqrt_impact"
+    }
+}
+
+/// Apply `impact` adversely to `fill_price`, never beyond the order's limit.
+#[inline(always)]
+pub fn adjust_fill_price(side: Side, fill_price: f64, impact: f64, limit: Option<f64>) -> f64 {
+    match side {
+        Side::Buy => {
+            let adjusted = fill_price + impact;
+            limit.map_or(adjusted, |limit| adjusted.min(limit.max(fill_price)))
+        }
+        Side::Sell => {
+            let adjusted = fill_price - impact;
+            limit.map_or(adjusted, |limit| adjusted.max(limit.min(fill_price)))
+        }
+    }
+}
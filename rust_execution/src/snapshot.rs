@@ -0,0 +1,266 @@
+//! Point-in-time engine snapshots and journal retention
+//!
+//! A snapshot holds what replaying the journal would rebuild plus what the
+//! journal does not carry: venue depth and queue positions, balances and
+//! holds, and the risk and kill-switch counters. It records the sequence
+//! number of the first journal record it does not cover, so recovery loads
+//! the newest snapshot that checks out and replays only the records after
+//! it. Files are named `{seq:020}.snapshot` in the journal directory and
+//! laid out as `SGMXSNAP | version u32 | crc32 u32 | body`, the checksum
+//! covering the body. Each is written to a temporary file, fsynced and
+//! renamed into place, so a crash leaves the previous set intact.
+//!
+//! Configuration (instruments, fees, limits, latency, rate limiters, cost
+//! method, queue models) is not saved; set it up before opening the
+//! journal, as without snapshots.
+
+use crate::journal::{self, crc32, Reader, Recovered, Writer};
+use crate::EngineState;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+const MAGIC: &[u8; 8] = b"SGMXSNAP";
+const LAYOUT_VERSION: u32 = 1;
+const HEADER_LEN: usize = 16;
+const SUFFIX: &str = ".snapshot";
+const TMP_SUFFIX: &str = ".snapshot.tmp";
+
+/// When snapshots are taken and how much history is kept.
+#[derive(Clone, Debug)]
+pub struct SnapshotPolicy {
+    /// Take a snapshot once this many records were journaled since the
+    /// last one; `None` only snapshots on request.
+    pub every_records: Option<u64>,
+    /// Snapshots kept on disk, newest first.
+    pub keep: usize,
+    /// Delete journal segments wholly covered by the oldest kept snapshot.
+    pub prune_journal: bool,
+}
+
+impl Default for SnapshotPolicy {
+    fn default() -> Self {
+        Self {
+            every_records: None,
+            keep: 2,
+            prune_journal: true,
+        }
+    }
+}
+
+/// A snapshot that was just written.
+pub struct SnapshotInfo {
+    pub seq: u64,
+    pub path: PathBuf,
+    pub bytes: usize,
+    pub pruned_snapshots: usize,
+    pub pruned_segments: usize,
+}
+
+/// Where recovery starts: the snapshot it loaded, if any, and the journal
+/// records after it.
+pub struct Loaded {
+    /// Sequence number and `next_order_id` of the loaded snapshot.
+    pub snapshot: Option<(u64, u64)>,
+    /// Newer snapshots passed over because they were damaged or ahead of
+    /// the journal.
+    pub skipped: usize,
+    pub recovered: Recovered,
+}
+
+fn snapshot_name(seq: u64) -> String {
+    format!("{:020}{}", seq, SUFFIX)
+}
+
+/// Snapshot files in the directory, oldest first.
+pub fn list(dir: &Path) -> io::Result<Vec<(u64, PathBuf)>> {
+    let mut snapshots = Vec::new();
+    if !dir.exists() {
+        return Ok(snapshots);
+    }
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        let seq = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .and_then(|name| name.strip_suffix(SUFFIX))
+            .and_then(|stem| stem.parse::<u64>().ok());
+        if let Some(seq) = seq {
+            snapshots.push((seq, path));
+        }
+    }
+    snapshots.sort();
+    Ok(snapshots)
+}
+
+/// Body of a snapshot file, if its header and checksum hold.
+fn read(path: &Path) -> Option<Vec<u8>> {
+    let mut data = fs::read(path).ok()?;
+    if data.len() < HEADER_LEN
+        || &data[..8] != MAGIC
+        || u32::from_le_bytes(data[8..12].try_into().ok()?) != LAYOUT_VERSION
+        || u32::from_le_bytes(data[12..16].try_into().ok()?) != crc32(&data[HEADER_LEN..])
+    {
+        return None;
+    }
+    Some(data.split_off(HEADER_LEN))
+}
+
+fn write(dir: &Path, seq: u64, body: &[u8]) -> io::Result<PathBuf> {
+    let path = dir.join(snapshot_name(seq));
+    let tmp = dir.join(format!("{:020}{}", seq, TMP_SUFFIX));
+    let mut file = File::create(&tmp)?;
+    file.write_all(MAGIC)?;
+    file.write_all(&LAYOUT_VERSION.to_le_bytes())?;
+    file.write_all(&crc32(body).to_le_bytes())?;
+    file.write_all(body)?;
+    file.sync_all()?;
+    fs::rename(&tmp, &path)?;
+    journal::sync_dir(dir);
+    Ok(path)
+}
+
+/// Apply the retention policy: keep the newest `keep` snapshots and, once
+/// there are that many, with `prune_journal` drop the segments whose records
+/// all precede the oldest of them. Returns how many snapshots and segments
+/// were deleted.
+fn prune(dir: &Path, policy: &SnapshotPolicy) -> io::Result<(usize, usize)> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.to_str().is_some_and(|name| name.ends_with(TMP_SUFFIX)) {
+            fs::remove_file(&path)?;
+        }
+    }
+    let keep = policy.keep.max(1);
+    let snapshots = list(dir)?;
+    let stale = snapshots.len().saturating_sub(keep);
+    for (_, path) in &snapshots[..stale] {
+        fs::remove_file(path)?;
+    }
+
+    // Until `keep` snapshots exist the whole journal is the fallback.
+    let mut pruned_segments = 0;
+    let oldest_kept = (snapshots.len() >= keep).then(|| snapshots[stale].0);
+    if let (true, Some(oldest_kept)) = (policy.prune_journal, oldest_kept) {
+        let segments = journal::segments(dir)?;
+        for pair in segments.windows(2) {
+            // A segment ends where the next one starts.
+            if pair[1].0 <= oldest_kept {
+                fs::remove_file(&pair[0].1)?;
+                pruned_segments += 1;
+            }
+        }
+    }
+    journal::sync_dir(dir);
+    Ok((stale, pruned_segments))
+}
+
+impl EngineState {
+    fn encode_snapshot(&self, seq: u64, next_order_id: u64) -> Vec<u8> {
+        let mut body = Vec::with_capacity(4096);
+        let mut w = Writer(&mut body);
+        w.u64(seq);
+        w.u64(self.timestamp());
+        w.u64(next_order_id);
+        w.u64(self.next_trade_id);
+        w.u64(self.clock_ns);
+        w.u32(self.books.len() as u32);
+        for (symbol_id, book) in &self.books {
+            w.u32(*symbol_id);
+            book.save(&mut w);
+        }
+        self.triggers.save(&mut w);
+        self.orders.save(&mut w);
+        self.ledger.save(&mut w);
+        self.account.save(&mut w);
+        self.risk.save(&mut w);
+        self.kill_switch.save(&mut w);
+        body
+    }
+
+    /// Replace the engine's state with a snapshot body, keeping its
+    /// configuration. Returns the snapshot's `next_order_id`.
+    fn restore_snapshot(&mut self, body: &[u8]) -> Option<u64> {
+        let mut r = Reader(body);
+        let _seq = r.u64()?;
+        let _ts_ns = r.u64()?;
+        let next_order_id = r.u64()?;
+        self.next_trade_id = r.u64()?;
+        self.clock_ns = self.clock_ns.max(r.u64()?);
+        for _ in 0..r.u32()? {
+            let symbol_id = r.u32()?;
+            self.books.entry(symbol_id).or_default().restore(&mut r)?;
+        }
+        self.triggers.restore(&mut r)?;
+        self.orders.restore(&mut r)?;
+        self.ledger.restore(&mut r)?;
+        self.account.restore(&mut r)?;
+        self.risk.restore(&mut r)?;
+        self.kill_switch.restore(&mut r)?;
+        r.0.is_empty().then_some(next_order_id)
+    }
+
+    /// Snapshot the engine into the open journal's directory and apply the
+    /// retention policy. The journal is flushed and moved to a new segment
+    /// first, so the snapshot never covers records that are not on disk.
+    pub(crate) fn snapshot(&mut self) -> Result<SnapshotInfo, String> {
+        let Some(journal) = &mut self.journal else {
+            return Err("no journal is open".to_string());
+        };
+        let result = journal
+            .roll()
+            .map(|_| (journal.dir().to_path_buf(), journal.next_seq(), journal.next_order_id()))
+            .and_then(|(dir, seq, next_order_id)| self.write_snapshot(&dir, seq, next_order_id));
+        if let Some(journal) = &mut self.journal {
+            journal.on_snapshot(&result.as_ref().map(|info| info.seq).map_err(Clone::clone));
+        }
+        result
+    }
+
+    fn write_snapshot(&self, dir: &Path, seq: u64, next_order_id: u64) -> Result<SnapshotInfo, String> {
+        let body = self.encode_snapshot(seq, next_order_id);
+        let path = write(dir, seq, &body).map_err(|e| format!("{}: {}", dir.display(), e))?;
+        let (pruned_snapshots, pruned_segments) =
+            prune(dir, &self.snapshot_policy).map_err(|e| format!("{}: {}", dir.display(), e))?;
+        Ok(SnapshotInfo {
+            seq,
+            path,
+            bytes: HEADER_LEN + body.len(),
+            pruned_snapshots,
+            pruned_segments,
+        })
+    }
+
+    /// Load the newest snapshot in `dir` that checks out and that the
+    /// journal reaches, then read the journal records after it; without
+    /// one, read the whole journal. A torn journal tail is cut off.
+    pub(crate) fn load_snapshot(&mut self, dir: &Path) -> Result<Loaded, String> {
+        let snapshots = list(dir).map_err(|e| format!("{}: {}", dir.display(), e))?;
+        let mut skipped = 0;
+        for (seq, path) in snapshots.iter().rev() {
+            let usable = read(path).filter(|body| EngineState::default().restore_snapshot(body).is_some());
+            let Some(body) = usable else {
+                skipped += 1;
+                continue;
+            };
+            match journal::read_dir(dir, Some(*seq), true) {
+                Ok(recovered) if recovered.next_seq >= *seq => {
+                    let next_order_id = self
+                        .restore_snapshot(&body)
+                        .ok_or_else(|| format!("{}: unreadable snapshot", path.display()))?;
+                    return Ok(Loaded {
+                        snapshot: Some((*seq, next_order_id)),
+                        skipped,
+                        recovered,
+                    });
+                }
+                _ => skipped += 1,
+            }
+        }
+        Ok(Loaded {
+            snapshot: None,
+            skipped,
+            recovered: journal::read_dir(dir, Some(0), true)?,
+        })
+    }
+}
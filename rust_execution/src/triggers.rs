@@ -0,0 +1,277 @@
+//! Conditional (stop / take-profit / trailing) orders held off-book until triggered
+
+use crate::journal::{Reader, Writer};
+use crate::orderbook::Side;
+use std::collections::HashMap;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum TriggerSource {
+    LastTrade,
+    Mark,
+}
+
+impl TriggerSource {
+    #[inline(always)]
+    pub fn from_u8(value: u8) -> Option<TriggerSource> {
+        match value {
+            0 => Some(TriggerSource::LastTrade),
+            1 => Some(TriggerSource::Mark),
+            _ => None,
+        }
+    }
+
+    #[inline(always)]
+    pub fn as_u8(self) -> u8 {
+        match self {
+            TriggerSource::LastTrade => 0,
+            TriggerSource::Mark => 1,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TriggerKind {
+    /// Buy above / sell below the trigger price.
+    Stop,
+    /// Buy below / sell above the trigger price.
+    TakeProfit,
+    /// Stop whose trigger follows the best price seen by a fixed offset.
+    TrailingStop,
+}
+
+impl TriggerKind {
+    fn from_u8(value: u8) -> Option<TriggerKind> {
+        match value {
+            0 => Some(TriggerKind::Stop),
+            1 => Some(TriggerKind::TakeProfit),
+            2 => Some(TriggerKind::TrailingStop),
+            _ => None,
+        }
+    }
+
+    fn as_u8(self) -> u8 {
+        match self {
+            TriggerKind::Stop => 0,
+            TriggerKind::TakeProfit => 1,
+            TriggerKind::TrailingStop => 2,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum TrailingOffset {
+    Absolute(f64),
+    Percent(f64),
+}
+
+#[derive(Clone, Debug)]
+pub struct ConditionalOrder {
+    pub order_id: u64,
+    pub symbol_id: u32,
+    pub side: Side,
+    pub kind: TriggerKind,
+    pub source: TriggerSource,
+    pub trigger_price: f64,
+    /// Limit price of the released order, `None` releases a market order.
+    pub limit_price: Option<f64>,
+    pub quantity: f64,
+    pub tif: u8,
+    pub trailing: Option<TrailingOffset>,
+}
+
+impl ConditionalOrder {
+    #[inline(always)]
+    fn trail_trigger(side: Side, offset: TrailingOffset, extreme: f64) -> f64 {
+        match (side, offset) {
+            (Side::Sell, TrailingOffset::Absolute(amount)) => extreme - amount,
+            (Side::Sell, TrailingOffset::Percent(pct)) => extreme * (1.0 - pct / 100.0),
+            (Side::Buy, TrailingOffset::Absolute(amount)) => extreme + amount,
+            (Side::Buy, TrailingOffset::Percent(pct)) => extreme * (1.0 + pct / 100.0),
+        }
+    }
+
+    /// Ratchet a trailing stop's trigger towards the market. Sell stops only
+    /// move up and buy stops only move down.
+    #[inline(always)]
+    fn follow(&mut self, price: f64) {
+        if let Some(offset) = self.trailing {
+            let candidate = Self::trail_trigger(self.side, offset, price);
+            let improves = match self.side {
+                Side::Sell => candidate > self.trigger_price,
+                Side::Buy => candidate < self.trigger_price,
+            };
+            if improves || self.trigger_price.is_nan() {
+                self.trigger_price = candidate;
+            }
+        }
+    }
+
+    #[inline(always)]
+    fn is_triggered(&self, price: f64) -> bool {
+        if self.trigger_price.is_nan() {
+            return false;
+        }
+        match (self.kind, self.side) {
+            (TriggerKind::Stop | TriggerKind::TrailingStop, Side::Buy) => price >= self.trigger_price,
+            (TriggerKind::Stop | TriggerKind::TrailingStop, Side::Sell) => price <= self.trigger_price,
+            (TriggerKind::TakeProfit, Side::Buy) => price <= self.trigger_price,
+            (TriggerKind::TakeProfit, Side::Sell) => price >= self.trigger_price,
+        }
+    }
+}
+
+/// Pending conditional orders per symbol plus the last observed prices
+/// they are evaluated against.
+#[derive(Default)]
+pub struct TriggerBook {
+    pending: HashMap<u32, Vec<ConditionalOrder>>,
+    last_prices: HashMap<(u32, TriggerSource), f64>,
+}
+
+impl TriggerBook {
+    pub fn last_price(&self, symbol_id: u32, source: TriggerSource) -> Option<f64> {
+        self.last_prices.get(&(symbol_id, source)).copied()
+    }
+
+    /// Park an order off-book, or hand it straight back if the last known
+    /// price of its source already triggers it. Trailing stops with no
+    /// trigger yet are anchored to that price, or to the next update.
+    pub fn insert(&mut self, mut order: ConditionalOrder) -> Option<ConditionalOrder> {
+        if let Some(price) = self.last_price(order.symbol_id, order.source) {
+            if order.is_triggered(price) {
+                return Some(order);
+            }
+            order.follow(price);
+        }
+        self.pending.entry(order.symbol_id).or_default().push(order);
+        None
+    }
+
+    /// Record a price update and remove and return every order it triggers,
+    /// in submission order.
+    pub fn on_price(&mut self, symbol_id: u32, price: f64, source: TriggerSource) -> Vec<ConditionalOrder> {
+        self.last_prices.insert((symbol_id, source), price);
+
+        let Some(orders) = self.pending.get_mut(&symbol_id) else {
+            return Vec::new();
+        };
+
+        let mut triggered = Vec::new();
+        orders.retain_mut(|order| {
+            if order.source != source {
+                return true;
+            }
+            if order.is_triggered(price) {
+                triggered.push(order.clone());
+                return false;
+            }
+            order.follow(price);
+            true
+        });
+        triggered
+    }
+
+    pub fn get(&self, order_id: u64) -> Option<&ConditionalOrder> {
+        self.pending
+            .values()
+            .flat_map(|orders| orders.iter())
+            .find(|order| order.order_id == order_id)
+    }
+
+    pub fn get_mut(&mut self, order_id: u64) -> Option<&mut ConditionalOrder> {
+        self.pending
+            .values_mut()
+            .flat_map(|orders| orders.iter_mut())
+            .find(|order| order.order_id == order_id)
+    }
+
+    pub fn remove(&mut self, order_id: u64) -> Option<ConditionalOrder> {
+        for orders in self.pending.values_mut() {
+            if let Some(position) = orders.iter().position(|o| o.order_id == order_id) {
+                return Some(orders.remove(position));
+            }
+        }
+        None
+    }
+
+    /// Remove every pending order, or only those of one symbol.
+    pub fn remove_all(&mut self, symbol_id: Option<u32>) -> Vec<ConditionalOrder> {
+        match symbol_id {
+            Some(symbol_id) => self.pending.remove(&symbol_id).unwrap_or_default(),
+            None => self.pending.drain().flat_map(|(_, orders)| orders).collect(),
+        }
+    }
+
+    pub fn orders(&self, symbol_id: u32) -> &[ConditionalOrder] {
+        self.pending.get(&symbol_id).map(Vec::as_slice).unwrap_or(&[])
+    }
+
+    pub fn contains(&self, order_id: u64) -> bool {
+        self.pending
+            .values()
+            .any(|orders| orders.iter().any(|order| order.order_id == order_id))
+    }
+
+    /// Pending orders in submission order and the last prices, for snapshots.
+    pub(crate) fn save(&self, w: &mut Writer<'_>) {
+        w.u32(self.pending.values().map(Vec::len).sum::<usize>() as u32);
+        for order in self.pending.values().flatten() {
+            w.u64(order.order_id);
+            w.u32(order.symbol_id);
+            w.u8(order.side.as_u8());
+            w.u8(order.kind.as_u8());
+            w.u8(order.source.as_u8());
+            w.f64(order.trigger_price);
+            w.opt_f64(order.limit_price);
+            w.f64(order.quantity);
+            w.u8(order.tif);
+            match order.trailing {
+                None => w.u8(0),
+                Some(TrailingOffset::Absolute(amount)) => {
+                    w.u8(1);
+                    w.f64(amount);
+                }
+                Some(TrailingOffset::Percent(pct)) => {
+                    w.u8(2);
+                    w.f64(pct);
+                }
+            }
+        }
+        w.u32(self.last_prices.len() as u32);
+        for ((symbol_id, source), price) in &self.last_prices {
+            w.u32(*symbol_id);
+            w.u8(source.as_u8());
+            w.f64(*price);
+        }
+    }
+
+    pub(crate) fn restore(&mut self, r: &mut Reader<'_>) -> Option<()> {
+        self.pending.clear();
+        self.last_prices.clear();
+        for _ in 0..r.u32()? {
+            let order = ConditionalOrder {
+                order_id: r.u64()?,
+                symbol_id: r.u32()?,
+                side: Side::from_u8(r.u8()?)?,
+                kind: TriggerKind::from_u8(r.u8()?)?,
+                source: TriggerSource::from_u8(r.u8()?)?,
+                trigger_price: r.f64()?,
+                limit_price: r.opt_f64()?,
+                quantity: r.f64()?,
+                tif: r.u8()?,
+                trailing: match r.u8()? {
+                    0 => None,
+                    1 => Some(TrailingOffset::Absolute(r.f64()?)),
+                    2 => Some(TrailingOffset::Percent(r.f64()?)),
+                    _ => return None,
+                },
+            };
+            self.pending.entry(order.symbol_id).or_default().push(order);
+        }
+        for _ in 0..r.u32()? {
+            let key = (r.u32()?, TriggerSource::from_u8(r.u8()?)?);
+            self.last_prices.insert(key, r.f64()?);
+        }
+        Some(())
+    }
+}
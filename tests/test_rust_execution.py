@@ -1,8 +1,25 @@
 """Tests for Rust Execution Engine"""
+import json
+import math
+import multiprocessing
+import struct
+import threading
+from pathlib import Path
+
 import pytest
 from core.modules.rust_execution import (
-    RustExecutionEngine, RustExecution, execute_batch, benchmark_latency
+    RustExecutionEngine, RustExecution, execute_batch, benchmark_latency,
+    BookBuilder, BookDelta, BookSnapshot, BookSync, ShmRing, read_journal, MarketTrade, MarketDataReplayer,
+    write_market_data, export_arrow, export_table, TokenBucketLimiter, SlidingWindowLimiter,
+    PositionLedger, FeatureCalculator, run_backtest, RUST_MODULE_AVAILABLE,
+    _PyTokenBucketLimiter, _PySlidingWindowLimiter, _PyPositionLedger, _PyBookBuilder, _PyFeatureCalculator
 )
+from pkg.schemas import MdUpdate
+from pkg.schemas import codec
+from pkg.schemas.common import OrderStatus
+from pkg.schemas.orders import RejectReason
+
+FIXTURES = Path(__file__).parent / "fixtures"
 
 class TestRustExecutionEngine:
     def test_engine_initialization(self):
@@ -11,12 +28,21 @@ class TestRustExecutionEngine:
 
     def test_execute_single_order(self):
         engine = RustExecutionEngine()
+        engine.on_book_level(1, 1, 50000.0, 1.0)  # venue ask to match against
         execution = engine.execute_order(1, 0, 0, 50000.0, 1.0)
         assert isinstance(execution, RustExecution)
         assert execution.order_id >= 0
         assert execution.executed_price == 50000.0
         assert execution.latency_ns > 0
 
+    def test_unmatched_order_rests(self):
+        engine = RustExecutionEngine()
+        engine.on_book_level(1, 1, 50010.0, 1.0)
+        execution = engine.execute_order(1, 0, 0, 50000.0, 1.0)
+        assert execution.executed_quantity == 0.0
+        assert execution.status == OrderStatus.SUBMITTED
+        assert engine.get_book(1, 5)["bids"] == [(50000.0, 1.0, 1)]
+
     def test_get_stats(self):
         engine = RustExecutionEngine()
         for _ in range(5):
@@ -35,5 +61,1460 @@ class TestRustExecutionEngine:
         executions = execute_batch(engine, orders)
         assert len(executions) == 10
 
+
+class TestOrderBook:
+    def test_levels_best_first(self):
+        engine = RustExecutionEngine()
+        for price in (99.0, 100.0, 98.5, 100.0):
+            engine.execute_order(1, 0, 0, price, 1.0)
+        for price in (102.0, 101.0):
+            engine.execute_order(1, 1, 0, price, 1.0)
+        book = engine.get_book(1, 2)
+        assert book["bids"] == [(100.0, 2.0, 2), (99.0, 1.0, 1)]
+        assert book["asks"] == [(101.0, 1.0, 1), (102.0, 1.0, 1)]
+        assert engine.get_book(2)["bids"] == []
+
+    def test_taker_walks_best_price_first(self):
+        engine = RustExecutionEngine()
+        for price, quantity in ((101.0, 1.0), (100.5, 0.5), (102.0, 2.0)):
+            engine.on_book_level(1, 1, price, quantity)
+        execution = engine.execute_order(1, 0, 0, 101.0, 2.0)
+        assert [(f.price, f.qty) for f in execution.fills] == [(100.5, 0.5), (101.0, 1.0)]
+        assert engine.get_book(1, 5) == {"symbol_id": 1, "bids": [(101.0, 0.5, 1)], "asks": [(102.0, 2.0, 0)]}
+
+    @pytest.mark.skipif(not RUST_MODULE_AVAILABLE, reason="passive fills need the compiled module")
+    def test_resting_orders_fill_in_time_order(self):
+        """Venue quantity displayed first is ahead of both orders, the earlier order ahead of the later"""
+        engine = RustExecutionEngine()
+        engine.on_book_level(1, 0, 100.0, 1.0)
+        first = engine.execute_order(1, 0, 0, 100.0, 1.0)
+        second = engine.execute_order(1, 0, 0, 100.0, 1.0)
+        engine.on_market_trade(1, 100.0, 1.5, aggressor_side=1)
+        engine.on_market_trade(1, 100.0, 1.0, aggressor_side=1)
+        fills = [(f.order_id, f.qty) for f in engine.drain_maker_fills()]
+        assert fills == [(first.order_id, 0.5), (first.order_id, 0.5), (second.order_id, 0.5)]
+
+        # A trade through the level fills everything resting there outright.
+        engine.on_market_trade(1, 99.5, 0.1, aggressor_side=1)
+        assert [(f.order_id, f.qty) for f in engine.drain_maker_fills()] == [(second.order_id, 0.5)]
+        assert engine.get_book(1, 5)["bids"] == [(100.0, 1.0, 0)]
+
+
+class TestPartialFills:
+    def test_multi_fill_execution(self):
+        """One execution carries a fill per level, priced at their VWAP"""
+        engine = RustExecutionEngine()
+        engine.on_book_level(1, 1, 100.0, 1.0)
+        engine.on_book_level(1, 1, 101.0, 1.0)
+        execution = engine.execute_order(1, 0, 0, 101.0, 3.0)
+        assert [(f.price, f.qty, f.is_maker) for f in execution.fills] == [(100.0, 1.0, False), (101.0, 1.0, False)]
+        assert execution.fills[0].trade_id < execution.fills[1].trade_id
+        assert (execution.executed_quantity, execution.executed_price) == (2.0, 100.5)
+        assert (execution.remaining_quantity, execution.status) == (1.0, OrderStatus.PARTIAL)
+        order = engine.get_order(execution.order_id)
+        assert (order["filled_quantity"], order["status"]) == (2.0, OrderStatus.PARTIAL)
+
+    def test_market_remainder_cancelled(self):
+        engine = RustExecutionEngine()
+        engine.on_book_level(1, 0, 99.0, 0.5)
+        execution = engine.execute_order(1, 1, 1, 0.0, 2.0)
+        assert (execution.executed_quantity, execution.remaining_quantity) == (0.5, 1.5)
+        assert execution.status == OrderStatus.CANCELLED
+        assert engine.get_book(1, 5)["asks"] == []
+
+    @pytest.mark.skipif(not RUST_MODULE_AVAILABLE, reason="passive fills need the compiled module")
+    def test_resting_order_fills_in_pieces(self):
+        engine = RustExecutionEngine()
+        bid = engine.execute_order(1, 0, 0, 100.0, 2.0)
+        engine.on_market_trade(1, 100.0, 0.75, aggressor_side=1)
+        assert engine.get_order(bid.order_id)["status"] == OrderStatus.PARTIAL
+        assert engine.get_book(1, 5)["bids"] == [(100.0, 1.25, 1)]
+        engine.on_market_trade(1, 100.0, 5.0, aggressor_side=1)
+        assert [f.qty for f in engine.drain_maker_fills()] == [0.75, 1.25]
+        order = engine.get_order(bid.order_id)
+        assert (order["filled_quantity"], order["status"]) == (2.0, OrderStatus.FILLED)
+        assert engine.get_book(1, 5)["bids"] == []
+
+
+class TestTimeInForce:
+    GTC, IOC, FOK, GTX = 1, 2, 3, 4
+
+    @staticmethod
+    def _engine():
+        engine = RustExecutionEngine()
+        engine.on_book_level(1, 1, 100.0, 1.0)
+        engine.on_book_level(1, 1, 101.0, 1.0)
+        return engine
+
+    def test_gtc_rests_remainder(self):
+        engine = self._engine()
+        execution = engine.execute_order(1, 0, 0, 100.0, 1.5, self.GTC)
+        assert (execution.executed_quantity, execution.status) == (1.0, OrderStatus.PARTIAL)
+        assert engine.get_book(1, 5)["bids"] == [(100.0, 0.5, 1)]
+
+    def test_ioc_cancels_remainder(self):
+        engine = self._engine()
+        execution = engine.execute_order(1, 0, 0, 100.0, 1.5, self.IOC)
+        assert (execution.executed_quantity, execution.remaining_quantity) == (1.0, 0.5)
+        assert execution.status == OrderStatus.CANCELLED
+        assert engine.get_book(1, 5)["bids"] == []
+
+    def test_fok_all_or_nothing(self):
+        engine = self._engine()
+        rejected = engine.execute_order(1, 0, 0, 100.5, 1.5, self.FOK)
+        assert (rejected.executed_quantity, rejected.status) == (0.0, OrderStatus.REJECTED)
+        assert engine.get_book(1, 5)["asks"] == [(100.0, 1.0, 0), (101.0, 1.0, 0)]
+
+        filled = engine.execute_order(1, 0, 0, 101.0, 1.5, self.FOK)
+        assert (filled.executed_quantity, filled.status) == (1.5, OrderStatus.FILLED)
+        assert engine.get_book(1, 5)["asks"] == [(101.0, 0.5, 0)]
+
+    def test_gtx_never_takes(self):
+        engine = self._engine()
+        assert engine.execute_order(1, 0, 0, 100.0, 1.0, self.GTX).status == OrderStatus.REJECTED
+        assert engine.execute_order(1, 0, 1, 0.0, 1.0, self.GTX).status == OrderStatus.REJECTED
+        resting = engine.execute_order(1, 0, 0, 99.5, 1.0, self.GTX)
+        assert resting.status == OrderStatus.SUBMITTED
+        assert engine.get_book(1, 5)["asks"] == [(100.0, 1.0, 0), (101.0, 1.0, 0)]
+
+        # A post-only amend that would cross leaves the order where it was.
+        assert engine.amend_order(resting.order_id, new_price=100.0).status == OrderStatus.REJECTED
+        assert engine.get_book(1, 5)["bids"] == [(99.5, 1.0, 1)]
+
+    @pytest.mark.skipif(not RUST_MODULE_AVAILABLE, reason="repricing needs the compiled module")
+    def test_gtx_reprice(self):
+        """A crossing post-only order rests one tick behind the touch, and its record says so"""
+        engine = self._engine()
+        engine.set_tick_size(1, 0.5)
+        engine.set_post_only_reprice(True)
+        execution = engine.execute_order(1, 0, 0, 102.0, 1.0, self.GTX)
+        assert (execution.executed_quantity, execution.status) == (0.0, OrderStatus.SUBMITTED)
+        assert engine.get_book(1, 5)["bids"] == [(99.5, 1.0, 1)]
+        assert engine.get_order(execution.order_id)["price"] == 99.5
+
+
+@pytest.mark.skipif(not RUST_MODULE_AVAILABLE, reason="conditional orders need the compiled module")
+class TestConditionalOrders:
+    def test_trailing_stop_checked_at_anchor_price(self):
+        """A trailing stop without trigger_price is valued at the market it anchors to"""
+        engine = RustExecutionEngine()
+        engine.set_instrument(1, "BTC", "USDT")
+        engine.deposit("USDT", 1000.0)
+        engine.set_risk_limits(1, max_order_notional=500.0)
+
+        # Nothing to value it at yet
+        assert engine.execute_order(1, 0, 6, 0.0, 1.0, trail_amount=5.0).reason_code == RejectReason.INSUFFICIENT_BALANCE
+
+        engine.update_market_price(1, 100.0)
+        assert engine.execute_order(1, 0, 6, 0.0, 6.0, trail_amount=5.0).reason_code == RejectReason.NOTIONAL_LIMIT
+        stop = engine.execute_order(1, 0, 6, 0.0, 4.0, trail_amount=5.0)
+        assert stop.status == OrderStatus.PENDING
+        assert engine.get_balances()["USDT"]["reserved"] == 400.0
+        assert engine.get_conditional_orders(1)[0]["trigger_price"] == 105.0
+
+        with pytest.raises(ValueError):
+            engine.execute_order(1, 0, 6, 0.0, 1.0, trail_amount=5.0, trigger_price=float("nan"))
+
+    def test_zero_quantity_amend_cancels_pending(self):
+        engine = RustExecutionEngine()
+        engine.set_instrument(1, "BTC", "USDT")
+        engine.deposit("USDT", 1000.0)
+        stop = engine.execute_order(1, 0, 2, 0.0, 2.0, trigger_price=110.0)
+        assert engine.get_balances()["USDT"]["reserved"] == 220.0
+
+        assert engine.amend_order(stop.order_id, new_qty=0.0).status == OrderStatus.CANCELLED
+        assert engine.get_order(stop.order_id)["status"] == OrderStatus.CANCELLED
+        assert engine.get_conditional_orders(1) == []
+        assert engine.get_balances()["USDT"]["reserved"] == 0.0
+        assert engine.update_market_price(1, 120.0) == []
+
+    @staticmethod
+    def _engine():
+        engine = RustExecutionEngine()
+        engine.on_book_level(1, 1, 110.0, 5.0)
+        engine.on_book_level(1, 0, 90.0, 5.0)
+        return engine
+
+    def test_trigger_directions(self):
+        """Stops fire when price moves through them, take-profits when it reaches the target"""
+        engine = self._engine()
+        # Mark-price triggers, so the fills below do not cascade through last-trade ones.
+        stop_buy = engine.execute_order(1, 0, 2, 0.0, 1.0, trigger_price=105.0, trigger_source=1)
+        take_profit = engine.execute_order(1, 1, 4, 0.0, 1.0, trigger_price=107.0, trigger_source=1)
+        stop_sell = engine.execute_order(1, 1, 2, 0.0, 1.0, trigger_price=95.0, trigger_source=1)
+        assert stop_buy.status == OrderStatus.PENDING
+        assert engine.update_market_price(1, 104.0, source=1) == []
+
+        fired = engine.update_market_price(1, 105.0, source=1)
+        assert [(e.order_id, e.executed_price, e.status) for e in fired] == \
+            [(stop_buy.order_id, 110.0, OrderStatus.FILLED)]
+        fired = engine.update_market_price(1, 108.0, source=1)
+        assert [(e.order_id, e.executed_price) for e in fired] == [(take_profit.order_id, 90.0)]
+        assert [o["order_id"] for o in engine.get_conditional_orders(1)] == [stop_sell.order_id]
+
+    def test_stop_limit_rests_at_its_limit(self):
+        engine = self._engine()
+        stop = engine.execute_order(1, 0, 3, 106.0, 1.0, trigger_price=105.0)
+        fired = engine.update_market_price(1, 105.0)
+        assert [(e.order_id, e.status) for e in fired] == [(stop.order_id, OrderStatus.SUBMITTED)]
+        assert engine.get_book(1, 5)["bids"] == [(106.0, 1.0, 1), (90.0, 5.0, 0)]
+        assert engine.get_conditional_orders(1) == []
+
+    def test_trailing_stop_follows_best_price(self):
+        engine = self._engine()
+        engine.update_market_price(1, 100.0)
+        stop = engine.execute_order(1, 1, 6, 0.0, 1.0, trail_amount=2.0)
+        assert engine.get_conditional_orders(1)[0]["trigger_price"] == 98.0
+        engine.update_market_price(1, 104.0)
+        engine.update_market_price(1, 103.0)
+        assert engine.get_conditional_orders(1)[0]["trigger_price"] == 102.0
+        fired = engine.update_market_price(1, 102.0)
+        assert [(e.order_id, e.executed_price) for e in fired] == [(stop.order_id, 90.0)]
+
+    def test_mark_price_source(self):
+        engine = self._engine()
+        stop = engine.execute_order(1, 1, 2, 0.0, 1.0, trigger_price=95.0, trigger_source=1)
+        assert engine.update_market_price(1, 94.0) == []
+        fired = engine.update_market_price(1, 94.0, source=1)
+        assert [e.order_id for e in fired] == [stop.order_id]
+
+    def test_own_trades_cascade(self):
+        """Fills of triggered orders set the last-trade price that fires the next ones"""
+        engine = self._engine()
+        stop_buy = engine.execute_order(1, 0, 2, 0.0, 1.0, trigger_price=105.0)
+        take_profit = engine.execute_order(1, 1, 4, 0.0, 1.0, trigger_price=107.0)
+        fired = engine.update_market_price(1, 105.0)
+        assert [(e.order_id, e.executed_price) for e in fired] == \
+            [(stop_buy.order_id, 110.0), (take_profit.order_id, 90.0)]
+
+        # Fired by an order's own fill rather than a market price.
+        stop = engine.execute_order(1, 0, 2, 0.0, 1.0, trigger_price=110.0)
+        engine.execute_order(1, 0, 0, 110.0, 1.0)
+        assert [(e.order_id, e.executed_price) for e in engine.drain_triggered()] == [(stop.order_id, 110.0)]
+
+
+class TestCancelAmend:
+    def test_cancel(self):
+        engine = RustExecutionEngine()
+        bid = engine.execute_order(1, 0, 0, 100.0, 1.0)
+        other = engine.execute_order(2, 0, 0, 100.0, 1.0)
+        ask = engine.execute_order(1, 1, 0, 105.0, 1.0)
+        assert engine.cancel_order(bid.order_id)
+        assert not engine.cancel_order(bid.order_id)
+        assert not engine.cancel_order(999)
+        assert engine.get_order(bid.order_id)["status"] == OrderStatus.CANCELLED
+
+        assert engine.cancel_all(symbol_id=1) == [ask.order_id]
+        assert engine.cancel_all() == [other.order_id]
+        assert engine.get_book(1, 5) == {"symbol_id": 1, "bids": [], "asks": []}
+
+    def test_amend_counts_earlier_fills(self):
+        """new_qty is the total size, including fills on entry and on earlier amends"""
+        engine = RustExecutionEngine()
+        engine.on_book_level(1, 1, 101.0, 1.0)
+        bid = engine.execute_order(1, 0, 0, 101.0, 1.5)
+        amended = engine.amend_order(bid.order_id, new_qty=1.25)
+        assert (amended.status, amended.remaining_quantity) == (OrderStatus.PARTIAL, 0.25)
+
+        engine.on_book_level(2, 1, 101.0, 0.5)
+        bid = engine.execute_order(2, 0, 0, 100.0, 2.0)
+        repriced = engine.amend_order(bid.order_id, new_price=101.0)
+        assert [(f.price, f.qty) for f in repriced.fills] == [(101.0, 0.5)]
+        assert (repriced.status, repriced.remaining_quantity) == (OrderStatus.PARTIAL, 1.5)
+        amended = engine.amend_order(bid.order_id, new_qty=1.0)
+        assert (amended.status, amended.remaining_quantity) == (OrderStatus.PARTIAL, 0.5)
+        assert engine.get_book(2, 5)["bids"] == [(101.0, 0.5, 1)]
+        assert engine.amend_order(bid.order_id, new_qty=0.5).status == OrderStatus.CANCELLED
+        assert engine.amend_order(bid.order_id, new_qty=2.0).status == OrderStatus.REJECTED
+
+    @pytest.mark.skipif(not RUST_MODULE_AVAILABLE, reason="passive fills need the compiled module")
+    def test_queue_priority(self):
+        """A size decrease keeps the order's place; a size increase sends it to the back"""
+        engine = RustExecutionEngine()
+        first = engine.execute_order(1, 0, 0, 100.0, 2.0)
+        second = engine.execute_order(1, 0, 0, 100.0, 1.0)
+        engine.amend_order(first.order_id, new_qty=1.0)
+        engine.on_market_trade(1, 100.0, 1.0, aggressor_side=1)
+        assert [f.order_id for f in engine.drain_maker_fills()] == [first.order_id]
+
+        third = engine.execute_order(1, 0, 0, 100.0, 1.0)
+        engine.amend_order(second.order_id, new_qty=1.5)
+        engine.on_market_trade(1, 100.0, 1.0, aggressor_side=1)
+        assert [f.order_id for f in engine.drain_maker_fills()] == [third.order_id]
+
+
+class TestClientIds:
+    def test_duplicate_never_reaches_book(self):
+        engine = RustExecutionEngine()
+        first = engine.execute_order(1, 0, 0, 100.0, 1.0, client_id="a")
+        retry = engine.execute_order(1, 0, 0, 100.0, 1.0, client_id="a")
+        assert (retry.order_id, retry.reason_code, retry.client_id) == (first.order_id, RejectReason.DUPLICATE_CLIENT_ID, "a")
+        assert retry.source == "router" and retry.to_dict()["order_id"] == first.order_id
+        assert engine.get_book(1, 5)["bids"] == [(100.0, 1.0, 1)]
+
+    def test_lookup_cancel_and_amend_by_client_id(self):
+        engine = RustExecutionEngine()
+        first = engine.execute_order(1, 0, 0, 100.0, 1.0, client_id="a")
+        engine.execute_order(1, 0, 0, 99.0, 1.0, client_id="b")
+        assert engine.get_order(client_id="a")["order_id"] == first.order_id
+        assert engine.get_order(client_id="unknown") is None
+        with pytest.raises(ValueError):
+            engine.cancel_order(first.order_id, client_id="b")
+
+        assert engine.amend_order(client_id="a", new_qty=0.5).client_id == "a"
+        assert engine.get_book(1, 5)["bids"] == [(100.0, 0.5, 1), (99.0, 1.0, 1)]
+        assert engine.cancel_order(client_id="a")
+        assert engine.get_book(1, 5)["bids"] == [(99.0, 1.0, 1)]
+
+    @pytest.mark.skipif(not RUST_MODULE_AVAILABLE, reason="the idempotency window needs the compiled module")
+    def test_window_expiry(self):
+        engine = RustExecutionEngine()
+        engine.enable_simulation(0)
+        engine.set_client_id_window(window_ms=5)
+        first = engine.execute_order(1, 0, 0, 100.0, 1.0, client_id="x")
+        engine.advance_time(4_000_000)
+        assert engine.execute_order(1, 0, 0, 100.0, 1.0, client_id="x").reason_code == RejectReason.DUPLICATE_CLIENT_ID
+
+        engine.advance_time(5_000_001)
+        reused = engine.execute_order(1, 0, 0, 100.0, 1.0, client_id="x")
+        assert reused.order_id != first.order_id and reused.status == OrderStatus.SUBMITTED
+        assert engine.get_order(client_id="x")["order_id"] == reused.order_id
+
+
+class TestSlippage:
+    @staticmethod
+    def _engine(model=None, **params):
+        engine = RustExecutionEngine()
+        engine.on_book_level(1, 0, 99.0, 10.0)
+        engine.on_book_level(1, 1, 101.0, 1.0)
+        engine.on_book_level(1, 1, 102.0, 10.0)
+        if model is not None:
+            engine.set_slippage_model(1, model, **params)
+        return engine
+
+    def test_book_walk_measured_from_mid(self):
+        """Positive for buys that paid up, negative for sells that gave up price"""
+        engine = self._engine()
+        buy = engine.execute_order(1, 0, 1, 0.0, 2.0)
+        assert [f.price for f in buy.fills] == [101.0, 102.0]
+        assert buy.slippage == pytest.approx(1.5)
+        sell = engine.execute_order(1, 1, 1, 0.0, 1.0)
+        assert sell.slippage == pytest.approx(99.0 - 100.5)
+
+    def test_one_sided_book_measured_from_touch(self):
+        engine = RustExecutionEngine()
+        engine.on_book_level(1, 1, 101.0, 1.0)
+        engine.on_book_level(1, 1, 103.0, 1.0)
+        assert engine.execute_order(1, 0, 1, 0.0, 2.0).slippage == pytest.approx(1.0)
+
+    @pytest.mark.skipif(not RUST_MODULE_AVAILABLE, reason="slippage models need the compiled module")
+    @pytest.mark.parametrize("model, params, buy_prices, sell_price", [
+        ("fixed_bps", {"bps": 100.0}, [102.01, 103.02], 98.01),
+        ("spread", {"spread_fraction": 0.5}, [102.0, 103.0], 97.5),
+        ("sqrt_impact", {"coefficient": 1.0, "volatility": 0.03, "daily_volume": 25_000.0},
+         [101.0 + 100.0 * 0.03 * (2 / 25_000) ** 0.5, 102.0 + 100.0 * 0.03 * (2 / 25_000) ** 0.5],
+         99.0 - 100.5 * 0.03 * (1 / 25_000) ** 0.5),
+    ])
+    def test_models_degrade_taker_prices(self, model, params, buy_prices, sell_price):
+        engine = self._engine(model, **params)
+        buy = engine.execute_order(1, 0, 1, 0.0, 2.0)
+        assert [f.price for f in buy.fills] == pytest.approx(buy_prices)
+        sell = engine.execute_order(1, 1, 1, 0.0, 1.0)
+        assert sell.fills[0].price == pytest.approx(sell_price)
+
+    @pytest.mark.skipif(not RUST_MODULE_AVAILABLE, reason="slippage models need the compiled module")
+    def test_never_beyond_limit(self):
+        engine = self._engine("fixed_bps", bps=100.0)
+        execution = engine.execute_order(1, 0, 0, 101.5, 2.0)
+        assert [(f.price, f.qty) for f in execution.fills] == [(101.5, 1.0)]
+        with pytest.raises(ValueError):
+            engine.set_slippage_model(1, "bogus")
+
+
+@pytest.mark.skipif(not RUST_MODULE_AVAILABLE, reason="queue models need the compiled module")
+class TestQueueModels:
+    @staticmethod
+    def _engine(model, power=3.0):
+        """One order queued behind 6, then 4 more join behind it"""
+        engine = RustExecutionEngine()
+        engine.set_queue_model(1, model, power=power)
+        engine.on_book_level(1, 0, 100.0, 6.0)
+        order_id = engine.execute_order(1, 0, 0, 100.0, 1.0).order_id
+        engine.on_book_level(1, 0, 100.0, 10.0)
+        return engine, order_id
+
+    @pytest.mark.parametrize("model, power, ahead", [
+        ("RiskAverse", 1.0, 6.0),
+        ("PowerProb", 2.0, 6.0 - 4.0 * 36 / 52),
+        ("LogProb", 1.0, 6.0 - 4.0 * math.log(7) / (math.log(7) + math.log(5))),
+    ])
+    def test_cancels_split_by_model(self, model, power, ahead):
+        engine, order_id = self._engine(model, power)
+        assert engine.get_queue_position(order_id)["queue_ahead"] == 6.0
+        engine.on_book_level(1, 0, 100.0, 6.0)
+        position = engine.get_queue_position(order_id)
+        assert position["queue_ahead"] == pytest.approx(ahead)
+        assert position["level_quantity"] == 7.0
+        assert position["queue_model"] == model
+
+    def test_cancels_beyond_the_back_come_from_the_front(self):
+        engine, order_id = self._engine("RiskAverse")
+        engine.on_book_level(1, 0, 100.0, 2.0)
+        assert engine.get_queue_position(order_id)["queue_ahead"] == 2.0
+
+    def test_traded_quantity_not_counted_twice(self):
+        engine, order_id = self._engine("PowerProb")
+        engine.on_market_trade(1, 100.0, 2.0, aggressor_side=1)
+        assert engine.get_queue_position(order_id)["queue_ahead"] == 4.0
+        engine.on_book_level(1, 0, 100.0, 8.0)
+        assert engine.get_queue_position(order_id)["queue_ahead"] == 4.0
+        engine.on_market_trade(1, 100.0, 5.0, aggressor_side=1)
+        assert [f.qty for f in engine.drain_maker_fills()] == [1.0]
+        assert engine.get_queue_position(order_id) is None
+
+    def test_invalid_model(self):
+        engine = RustExecutionEngine()
+        with pytest.raises(ValueError):
+            engine.set_queue_model(1, "PowerProb", power=0.0)
+        with pytest.raises(ValueError):
+            engine.set_queue_model(1, "bogus")
+
+
+@pytest.mark.skipif(not RUST_MODULE_AVAILABLE, reason="latency simulation needs the compiled module")
+class TestLatency:
+    def test_matched_against_book_at_arrival(self):
+        engine = RustExecutionEngine()
+        engine.set_latency_model("order_entry", "constant", value=1000)
+        engine.set_latency_model("ack", "constant", value=500)
+        engine.on_book_level(1, 1, 100.0, 1.0, ts_ns=10_000)
+        pending = engine.execute_order(1, 0, 0, 100.0, 1.0)
+        assert (pending.status, pending.arrival_ns) == (OrderStatus.PENDING, 11_000)
+
+        # The ask is pulled while the order is in flight.
+        engine.on_book_level(1, 1, 100.0, 0.0, ts_ns=10_500)
+        assert engine.advance_time(11_000) == []
+        acks = engine.advance_time(11_500)
+        assert [(a.order_id, a.status, a.ack_ns) for a in acks] == [(pending.order_id, OrderStatus.SUBMITTED, 11_500)]
+        assert acks[0].executed_quantity == 0.0
+        assert engine.get_book(1, 5)["bids"] == [(100.0, 1.0, 1)]
+
+    def test_orders_arrive_in_entry_order(self):
+        engine = RustExecutionEngine()
+        engine.set_latency_model("order_entry", "constant", value=1000)
+        engine.on_book_level(1, 1, 100.0, 1.5, ts_ns=0)
+        first = engine.execute_order(1, 0, 0, 100.0, 1.0)
+        engine.advance_time(400)
+        second = engine.execute_order(1, 0, 0, 100.0, 1.0)
+        cancelled = engine.execute_order(1, 0, 0, 100.0, 1.0)
+        assert engine.cancel_order(cancelled.order_id)
+        acks = engine.advance_time(2000)
+        assert [(a.order_id, a.arrival_ns, a.executed_quantity) for a in acks] == [
+            (first.order_id, 1000, 1.0), (second.order_id, 1400, 0.5)]
+        assert engine.get_order(cancelled.order_id)["status"] == OrderStatus.CANCELLED
+
+    def test_market_data_latency_delays_arrival(self):
+        engine = RustExecutionEngine()
+        engine.set_latency_model("market_data", "constant", value=300)
+        engine.set_latency_model("order_entry", "constant", value=200)
+        engine.on_book_level(1, 1, 100.0, 1.0, ts_ns=0)
+        assert engine.execute_order(1, 0, 0, 100.0, 1.0).arrival_ns == 500
+
+    def test_invalid_kind(self):
+        with pytest.raises(ValueError):
+            RustExecutionEngine().set_latency_model("bogus", "constant", value=1)
+
+
+@pytest.mark.skipif(not RUST_MODULE_AVAILABLE, reason="fee schedules need the compiled module")
+class TestFees:
+    DAY_NS = 86_400_000_000_000
+
+    def test_base_currency_fee_counts_in_quote(self, tmp_path):
+        engine = RustExecutionEngine(journal_dir=str(tmp_path))
+        engine.set_fee_schedule([(0.0, 0.0, 10.0)], symbol_id=1, fee_currency="BTC", charge_in_base=True)
+        engine.on_book_level(1, 1, 100.0, 5.0)
+        execution = engine.execute_order(1, 0, 0, 100.0, 2.0)
+        assert execution.fills[0].fee == pytest.approx(0.002)
+        assert execution.fills[0].fee_currency == "BTC"
+
+        positions = engine.get_positions()
+        assert positions[0]["fees"] == pytest.approx(0.2)
+        assert engine.get_kill_switch()["daily_pnl"] == pytest.approx(-0.2)
+        del engine
+
+        engine = RustExecutionEngine()
+        engine.open_journal(str(tmp_path))
+        assert engine.get_positions() == positions
+
+    def test_tier_volume_rolls_off(self):
+        engine = RustExecutionEngine()
+        engine.enable_simulation(0)
+        engine.set_fee_schedule([(0.0, 1.0, 4.0), (1000.0, 0.5, 2.0)], venue_code=0)
+        engine.on_book_level(1, 1, 100.0, 20.0, ts_ns=self.DAY_NS)
+        engine.execute_order(1, 0, 0, 100.0, 10.0)
+        assert engine.get_fee_rates(1)["taker_bps"] == 2.0
+
+        engine.advance_time(30 * self.DAY_NS)
+        assert engine.get_fee_rates(1)["volume"] == 1000.0
+        engine.advance_time(31 * self.DAY_NS)
+        rates = engine.get_fee_rates(1)
+        assert (rates["volume"], rates["taker_bps"]) == (0.0, 4.0)
+
+        engine.set_trading_volume(0, 5000.0)
+        assert engine.get_fee_rates(1)["maker_bps"] == 0.5
+
+    def test_maker_rebate_and_taker_fee(self):
+        engine = RustExecutionEngine()
+        engine.set_symbol_venue(1, venue_code=1)
+        engine.set_fee_schedule([(0, 1.0, 4.0), (5_000_000, -0.25, 3.0)], venue_code=1, fee_currency="USDT")
+        engine.set_trading_volume(1, 12_000_000.0)
+        engine.execute_order(1, 0, 0, 100.0, 2.0)
+        engine.on_market_trade(1, 99.0, 1.0, aggressor_side=1)
+        assert [(f.fee, f.fee_currency, f.is_maker) for f in engine.drain_maker_fills()] == [
+            (pytest.approx(-0.005), "USDT", True)]
+
+        engine.on_book_level(1, 1, 101.0, 5.0)
+        execution = engine.execute_order(1, 0, 1, 0.0, 1.0)
+        assert execution.fills[0].fee == pytest.approx(0.0303)
+        assert execution.fee == execution.fills[0].fee
+
+    def test_symbol_override(self):
+        engine = RustExecutionEngine()
+        engine.set_fee_schedule([(0, 1.0, 4.0)], venue_code=0)
+        engine.set_fee_schedule([(0, 0.0, 7.5)], symbol_id=2, fee_currency="BNB")
+        assert engine.get_fee_rates(1)["taker_bps"] == 4.0
+        assert engine.get_fee_rates(2)["taker_bps"] == 7.5
+        engine.on_book_level(2, 1, 10.0, 5.0)
+        fill = engine.execute_order(2, 0, 1, 0.0, 1.0).fills[0]
+        assert (fill.fee, fill.fee_currency) == (pytest.approx(0.0075), "BNB")
+
+
+@pytest.mark.skipif(not RUST_MODULE_AVAILABLE, reason="risk limits need the compiled module")
+class TestRiskLimits:
+    @staticmethod
+    def _engine(**limits):
+        engine = RustExecutionEngine()
+        engine.set_risk_limits(1, **limits)
+        engine.on_book_level(1, 1, 100.0, 100.0)
+        engine.on_book_level(1, 0, 99.0, 100.0)
+        return engine
+
+    def test_order_size(self):
+        engine = self._engine(max_order_qty=10.0, max_order_notional=500.0)
+        assert engine.execute_order(1, 0, 0, 95.0, 11.0).reason_code == RejectReason.NOTIONAL_LIMIT
+        reject = engine.execute_order(1, 0, 0, 95.0, 6.0, client_id="big")
+        assert (reject.reason_code, reject.source, reject.client_id) == (RejectReason.NOTIONAL_LIMIT, "risk", "big")
+        assert engine.get_book(1, 5)["bids"] == [(99.0, 100.0, 0)]
+        assert engine.execute_order(2, 0, 0, 95.0, 11.0).status == OrderStatus.SUBMITTED
+
+    def test_position(self):
+        engine = self._engine(max_position_notional=500.0)
+        assert engine.execute_order(1, 0, 0, 100.0, 6.0).reason_code == RejectReason.POSITION_LIMIT
+        assert engine.execute_order(1, 0, 0, 100.0, 4.0).status == OrderStatus.FILLED
+        assert engine.execute_order(1, 0, 0, 100.0, 2.0).reason_code == RejectReason.POSITION_LIMIT
+
+        engine = self._engine(max_position_qty=4.0)
+        assert engine.execute_order(1, 1, 1, 0.0, 3.0).status == OrderStatus.FILLED
+        assert engine.execute_order(1, 1, 1, 0.0, 2.0).reason_code == RejectReason.INVENTORY_LIMIT
+        assert engine.execute_order(1, 0, 1, 0.0, 7.0).status == OrderStatus.FILLED
+
+    def test_price_band(self):
+        engine = self._engine(price_band_pct=2.0)
+        assert engine.execute_order(1, 0, 0, 102.0, 1.0).reason_code == RejectReason.PRICE_BAND
+        assert engine.execute_order(1, 0, 0, 101.0, 1.0).status == OrderStatus.FILLED
+        # Measured from the last trade once there is one.
+        engine.on_market_trade(1, 104.0, 1.0, aggressor_side=0)
+        assert engine.execute_order(1, 0, 0, 102.0, 1.0).status == OrderStatus.FILLED
+
+    def test_concentration(self):
+        engine = self._engine(max_concentration_pct=30.0)
+        engine.set_risk_capital(1000.0)
+        assert engine.execute_order(1, 0, 0, 100.0, 3.0).status == OrderStatus.FILLED
+        reject = engine.execute_order(1, 0, 0, 100.0, 0.5)
+        assert reject.reason_code == RejectReason.CONCENTRATION_LIMIT
+        assert engine.execute_order(1, 1, 0, 99.0, 1.0).status == OrderStatus.FILLED
+
+
+@pytest.mark.skipif(not RUST_MODULE_AVAILABLE, reason="the kill switch needs the compiled module")
+class TestKillSwitch:
+    ACTIVE, HALTED, CANCEL_ONLY, REDUCE_ONLY = 0, 1, 2, 3
+
+    @staticmethod
+    def _engine(**config):
+        engine = RustExecutionEngine()
+        if config:
+            engine.configure_kill_switch(**config)
+        engine.on_book_level(1, 1, 100.0, 100.0)
+        engine.on_book_level(1, 0, 99.0, 100.0)
+        return engine
+
+    def test_reduce_only(self):
+        engine = self._engine()
+        engine.execute_order(1, 0, 1, 0.0, 2.0)
+        engine.set_trading_mode(self.REDUCE_ONLY, reason="wind down")
+        reject = engine.execute_order(1, 0, 0, 90.0, 1.0)
+        assert (reject.reason_code, reject.source) == (RejectReason.KILL_SWITCH, "kill_switch")
+        assert engine.execute_order(1, 1, 1, 0.0, 5.0).reason_code == RejectReason.KILL_SWITCH
+        assert engine.execute_order(1, 1, 1, 0.0, 1.0).status == OrderStatus.FILLED
+
+    def test_cancel_only(self):
+        engine = self._engine()
+        bid = engine.execute_order(1, 0, 0, 90.0, 2.0)
+        engine.set_trading_mode(self.CANCEL_ONLY)
+        assert engine.execute_order(1, 1, 1, 0.0, 1.0).reason_code == RejectReason.KILL_SWITCH
+        assert engine.amend_order(bid.order_id, new_price=91.0).reason_code == RejectReason.KILL_SWITCH
+        assert engine.amend_order(bid.order_id, new_qty=1.0).status == OrderStatus.SUBMITTED
+        assert engine.cancel_order(bid.order_id)
+        assert [r.reason_code for r in engine.drain_blocked_orders()] == [RejectReason.KILL_SWITCH] * 2
+        assert engine.get_kill_switch()["mode_name"] == "cancel_only"
+
+    def test_halt_cancels_open_orders(self):
+        engine = self._engine()
+        bid = engine.execute_order(1, 0, 0, 90.0, 1.0)
+        engine.set_trading_mode(self.HALTED, reason="halt")
+        assert engine.get_order(bid.order_id)["status"] == OrderStatus.CANCELLED
+        assert engine.get_book(1, 5)["bids"] == [(99.0, 100.0, 0)]
+        engine.set_trading_mode(self.ACTIVE)
+        assert engine.execute_order(1, 0, 0, 90.0, 1.0).status == OrderStatus.SUBMITTED
+
+    def test_daily_loss_trips(self):
+        engine = self._engine(daily_loss_limit=1.0, trip_mode=self.HALTED)
+        engine.execute_order(1, 0, 1, 0.0, 1.0)
+        engine.execute_order(1, 1, 1, 0.0, 1.0)
+        state = engine.get_kill_switch()
+        assert (state["mode"], state["daily_pnl"]) == (self.HALTED, -1.0)
+        assert state["reason"].startswith("daily loss")
+
+    def test_consecutive_losses_trip(self):
+        engine = self._engine(max_consecutive_losses=2, trip_mode=self.CANCEL_ONLY)
+        modes = []
+        for _ in range(2):
+            engine.execute_order(1, 0, 1, 0.0, 1.0)
+            engine.execute_order(1, 1, 1, 0.0, 1.0)
+            modes.append(engine.get_trading_mode())
+        assert modes == [self.ACTIVE, self.CANCEL_ONLY]
+
+    def test_error_burst_trips(self):
+        engine = self._engine(max_errors=2, error_window_ms=60_000, trip_mode=self.REDUCE_ONLY)
+        engine.record_error()
+        engine.record_error()
+        assert engine.get_trading_mode() == self.ACTIVE
+        engine.record_error()
+        assert engine.get_trading_mode() == self.REDUCE_ONLY
+
+
+@pytest.mark.skipif(not RUST_MODULE_AVAILABLE, reason="passive fills need the compiled module")
+class TestMakerFills:
+    def test_buffer_is_bounded(self):
+        """Undrained maker fills keep the newest ones and count what was dropped"""
+        engine = RustExecutionEngine()
+        engine.set_maker_fill_capacity(2)
+        bid = engine.execute_order(1, 0, 0, 100.0, 10.0)
+        for _ in range(3):
+            engine.on_market_trade(1, 100.0, 1.0, aggressor_side=1)
+        fills = engine.drain_maker_fills()
+        assert [(f.order_id, f.trade_id) for f in fills] == [(bid.order_id, 2), (bid.order_id, 3)]
+        assert engine.get_stats()["maker_fills_dropped"] == 1
+
+        engine.set_maker_fill_capacity(0)
+        engine.set_fill_recording(True)
+        engine.on_market_trade(1, 100.0, 1.0, aggressor_side=1)
+        assert engine.drain_maker_fills() == []
+        assert len(engine.drain_fills()) == 1
+        assert engine.get_stats()["maker_fills_dropped"] == 2
+
+
+class _MeanReversion:
+    """Buys dips below 100 and sells once the book is back above it"""
+
+    def __init__(self):
+        self.acks = []
+
+    def on_book(self, engine, delta):
+        book = engine.get_book(1, 1)
+        if not book["bids"] or not book["asks"]:
+            return
+        position = sum(p["net_qty"] for p in engine.get_positions())
+        ask, bid = book["asks"][0][0], book["bids"][0][0]
+        if ask < 100.0 and position < 3.0:
+            engine.execute_order(1, 0, 0, ask, 1.0, 2)
+        elif bid > 100.0 and position > 0.0:
+            engine.execute_order(1, 1, 0, bid, position, 2)
+
+    def on_execution(self, engine, execution):
+        self.acks.append((execution.order_id, execution.status, execution.ack_ns))
+
+
+@pytest.mark.skipif(not RUST_MODULE_AVAILABLE, reason="backtests need the compiled module")
+class TestBacktest:
+    @staticmethod
+    def _events():
+        events = []
+        for i in range(1, 200):
+            ts = i * 1_000_000
+            mid = 100.0 + (i % 17 - 8) * 0.25
+            events.append(BookDelta(ts, 1, 0, mid - 0.25, 5.0))
+            events.append(BookDelta(ts, 1, 1, mid + 0.25, 5.0))
+            events.append(BookDelta(ts, 1, 0, mid - 0.5, 0.0))
+            events.append(BookDelta(ts, 1, 1, mid + 0.5, 0.0))
+            if i % 3 == 0:
+                events.append(MarketTrade(ts, 1, mid + 0.25, 1.0, 0))
+        return events
+
+    def _run(self):
+        engine = RustExecutionEngine()
+        engine.set_latency_model("order_entry", "lognormal", mu=12.0, sigma=0.4)
+        engine.set_latency_model("ack", "constant", value=50_000)
+        engine.set_latency_seed(7)
+        strategy = _MeanReversion()
+        result = run_backtest(engine, self._events(), strategy, initial_equity=1000.0,
+                              equity_interval_ns=10_000_000)
+        fills = [(f.ts_ns, f.order_id, f.price, f.qty, f.is_maker) for f in result["fills"]]
+        return fills, result["equity_curve"], strategy.acks
+
+    def test_runs_are_deterministic(self):
+        """The same events and seed give the same fills, acks and equity curve"""
+        first, second = self._run(), self._run()
+        assert first[0] and first[2]
+        assert first == second
+
+    def test_failing_strategy_stops_fill_recording(self):
+        class Failing:
+            def on_book(self, engine, delta):
+                raise RuntimeError("strategy bug")
+
+        engine = RustExecutionEngine()
+        with pytest.raises(RuntimeError):
+            run_backtest(engine, self._events()[:2], Failing())
+        engine.on_book_level(1, 1, 100.0, 1.0)
+        engine.execute_order(1, 0, 0, 100.0, 1.0)
+        assert engine.drain_fills() == []
+
+    def test_clock_mode_restored(self):
+        """A run leaves a live engine on the wall clock and never rewinds a simulated one"""
+        class Failing:
+            on_start = None  # not a hook
+
+            def on_book(self, engine, delta):
+                raise RuntimeError("strategy bug")
+
+        engine = RustExecutionEngine()
+        with pytest.raises(RuntimeError):
+            run_backtest(engine, self._events()[:2], Failing())
+        engine.on_book_level(1, 1, 100.0, 1.0)
+        assert engine.execute_order(1, 0, 0, 100.0, 1.0).fills[0].ts_ns > 10**18
+
+        engine = RustExecutionEngine()
+        engine.enable_simulation(0)
+        engine.advance_time(5_000_000_000)
+        run_backtest(engine, self._events()[:2], object())
+        assert engine.get_time() == 5_000_000_000
+        engine.on_book_level(1, 1, 100.0, 1.0)
+        assert engine.execute_order(1, 0, 0, 100.0, 1.0).fills[0].ts_ns == 5_000_000_000
+
+
+class TestRateLimiters:
+    """The compiled and Python limiters make the same decisions"""
+
+    MS = 1_000_000
+
+    @pytest.mark.parametrize("limiter_cls", sorted({TokenBucketLimiter, _PyTokenBucketLimiter}, key=str))
+    def test_token_bucket(self, limiter_cls):
+        limiter = limiter_cls(rate=1000.0, burst=2)
+        acquire = lambda cost=1, t=0: limiter.try_acquire(venue="a", cost=cost, now_ns=t * self.MS)
+        assert [acquire(), acquire(), acquire()] == [True, True, False]
+        assert limiter.try_acquire(venue="b", now_ns=0)
+        assert acquire(t=1) and not acquire(t=1)
+        limiter.release(venue="a", cost=2, now_ns=self.MS)
+        assert acquire(cost=2, t=1) and not acquire(t=1)
+
+    @pytest.mark.parametrize("limiter_cls", sorted({SlidingWindowLimiter, _PySlidingWindowLimiter}, key=str))
+    def test_sliding_window(self, limiter_cls):
+        # 16 sub-windows of 1ms each
+        limiter = limiter_cls(4, window_ms=16)
+        acquire = lambda cost, t: limiter.try_acquire(symbol_id=1, cost=cost, now_ns=t * self.MS)
+        assert acquire(3, 0)
+        assert not acquire(2, 1)
+        assert acquire(1, 1)
+        limiter.release(symbol_id=1, cost=1, now_ns=self.MS)
+        assert acquire(1, 2) and not acquire(1, 2)
+        assert not acquire(1, 15)
+
+        # The first sub-window has rolled off; release honours `cost`.
+        assert acquire(3, 16) and not acquire(1, 16)
+        limiter.release(symbol_id=1, cost=2, now_ns=16 * self.MS)
+        assert acquire(2, 16) and not acquire(1, 16)
+        # Units from an earlier sub-window cannot be given back.
+        limiter.release(symbol_id=1, cost=1, now_ns=17 * self.MS)
+        assert not acquire(1, 17)
+
+        with pytest.raises(ValueError):
+            limiter_cls(0)
+
+    @pytest.mark.skipif(not RUST_MODULE_AVAILABLE, reason="engine rate limits need the compiled module")
+    def test_engine_amends(self):
+        """Amends pay the engine's limiters; refused ones give the unit back"""
+        engine = RustExecutionEngine()
+        engine.set_risk_limits(1, max_order_qty=5.0)
+        engine.add_rate_limiter(SlidingWindowLimiter(3, window_ms=60_000), scope=["account"])
+        bid = engine.execute_order(1, 0, 0, 90.0, 1.0, account="main")
+
+        assert engine.amend_order(bid.order_id, new_qty=6.0, account="main").reason_code == RejectReason.NOTIONAL_LIMIT
+        assert engine.amend_order(bid.order_id, new_qty=2.0, account="main").status == OrderStatus.SUBMITTED
+        assert engine.amend_order(bid.order_id, new_price=91.0, account="main").status == OrderStatus.SUBMITTED
+        reject = engine.amend_order(bid.order_id, new_qty=1.0, account="main")
+        assert reject.reason_code == RejectReason.RATE_LIMIT
+        assert engine.get_order(bid.order_id)["quantity"] == 2.0
+        assert engine.amend_order(bid.order_id, new_qty=1.0, account="other").status == OrderStatus.SUBMITTED
+
+    @pytest.mark.skipif(not RUST_MODULE_AVAILABLE, reason="engine rate limits need the compiled module")
+    def test_engine_orders(self):
+        """Refused orders are rejected with RATE_LIMIT, per scope key"""
+        engine = RustExecutionEngine()
+        engine.enable_simulation(0)
+        for symbol_id, venue_code in ((1, 1), (2, 1), (3, 2)):
+            engine.set_symbol_venue(symbol_id, venue_code=venue_code)
+        engine.add_rate_limiter(TokenBucketLimiter(rate=1000.0, burst=2), scope=["venue"])
+        submit = lambda symbol_id: engine.execute_order(symbol_id, 0, 0, 90.0, 1.0)
+
+        assert [submit(1).status, submit(2).status] == [OrderStatus.SUBMITTED] * 2
+        reject = submit(1)
+        assert (reject.reason_code, reject.source) == (RejectReason.RATE_LIMIT, "risk")
+        assert submit(3).status == OrderStatus.SUBMITTED
+        engine.advance_time(self.MS)
+        assert submit(2).status == OrderStatus.SUBMITTED
+
+    @pytest.mark.skipif(not RUST_MODULE_AVAILABLE, reason="engine rate limits need the compiled module")
+    def test_max_orders_per_sec(self):
+        engine = RustExecutionEngine()
+        engine.enable_simulation(0)
+        engine.set_risk_limits(1, max_orders_per_sec=2)
+        results = [engine.execute_order(1, 0, 0, 90.0, 1.0) for _ in range(3)]
+        assert results[2].reason_code == RejectReason.RATE_LIMIT
+        engine.advance_time(1000 * self.MS)
+        assert engine.execute_order(1, 0, 0, 90.0, 1.0).status == OrderStatus.SUBMITTED
+
+    @pytest.mark.skipif(not RUST_MODULE_AVAILABLE, reason="engine rate limits need the compiled module")
+    def test_refused_orders_do_not_count(self):
+        """Orders and amends refused after the risk checks leave max_orders_per_sec alone"""
+        engine = RustExecutionEngine()
+        engine.enable_simulation(0)
+        engine.set_instrument(1, "BTC", "USDT")
+        engine.deposit("USDT", 1000.0)
+        engine.set_risk_limits(1, max_orders_per_sec=3)
+        engine.add_rate_limiter(TokenBucketLimiter(rate=1.0, burst=1), scope=["account"])
+        submit = lambda account: engine.execute_order(1, 0, 0, 90.0, 1.0, account=account)
+
+        assert engine.execute_order(1, 0, 0, 90.0, 20.0).reason_code == RejectReason.INSUFFICIENT_BALANCE
+        bid = submit("a")
+        assert submit("a").reason_code == RejectReason.RATE_LIMIT
+        assert engine.amend_order(bid.order_id, new_qty=20.0, account="b").reason_code == RejectReason.INSUFFICIENT_BALANCE
+        assert [submit("b").status, submit("c").status] == [OrderStatus.SUBMITTED] * 2
+        reject = submit("d")
+        assert (reject.reason_code, reject.reason_msg) == (RejectReason.RATE_LIMIT, "Rate limit exceeded")
+
+
+class TestPositionLedger:
+    """The compiled and Python ledgers realize the same PnL"""
+
+    @pytest.mark.parametrize("ledger_cls", sorted({PositionLedger, _PyPositionLedger}, key=str))
+    @pytest.mark.parametrize("cost_method, realized, avg_entry", [
+        ("fifo", 20.0, 110.0),
+        ("lifo", 10.0, 100.0),
+        ("average", 15.0, 105.0),
+    ])
+    def test_cost_methods(self, ledger_cls, cost_method, realized, avg_entry):
+        ledger = ledger_cls(cost_method)
+        assert ledger.on_fill(1, 0, 100.0, 1.0, fee=0.1) is None
+        assert ledger.on_fill(1, 0, 110.0, 1.0) is None
+        assert ledger.on_fill(1, 1, 120.0, 1.0, fee=0.2) == pytest.approx(realized)
+        position = ledger.get_position(1)
+        assert (position["net_qty"], position["avg_entry_price"]) == (1.0, pytest.approx(avg_entry))
+        assert position["unrealized_pnl"] == pytest.approx(120.0 - avg_entry)
+        assert position["net_pnl"] == pytest.approx(29.7)
+
+        # Flipping closes the rest and opens a short at the fill price.
+        assert ledger.on_fill(1, 1, 90.0, 3.0) == pytest.approx(90.0 - avg_entry)
+        ledger.set_mark_price(1, 80.0)
+        position = ledger.get_position(1)
+        assert (position["net_qty"], position["avg_entry_price"], position["realized_pnl"]) == (-2.0, 90.0, 0.0)
+        assert position["unrealized_pnl"] == 20.0
+        portfolio = ledger.get_portfolio()
+        assert (portfolio["cost_method"], portfolio["gross_exposure"], portfolio["net_exposure"]) == (
+            cost_method, 160.0, -160.0)
+
+    @pytest.mark.parametrize("ledger_cls", sorted({PositionLedger, _PyPositionLedger}, key=str))
+    def test_invalid_input(self, ledger_cls):
+        with pytest.raises(ValueError):
+            ledger_cls("bogus")
+        with pytest.raises(ValueError):
+            ledger_cls().on_fill(1, 0, 0.0, 1.0)
+        assert ledger_cls().get_position(1) is None
+
+    @pytest.mark.skipif(not RUST_MODULE_AVAILABLE, reason="engine positions need the compiled module")
+    def test_engine_fills(self):
+        engine = RustExecutionEngine()
+        engine.set_cost_method("lifo")
+        for side, price in ((0, 100.0), (0, 110.0), (1, 120.0)):
+            engine.on_book_level(1, 1 - side, price, 1.0)
+            engine.execute_order(1, side, 1, 0.0, 1.0)
+        engine.set_mark_price(1, 130.0)
+        position = engine.get_position(1)
+        assert (position["net_qty"], position["realized_pnl"], position["unrealized_pnl"]) == (1.0, 10.0, 30.0)
+        assert engine.get_portfolio()["net_pnl"] == 40.0
+
+
+@pytest.mark.skipif(not RUST_MODULE_AVAILABLE, reason="balances need the compiled module")
+class TestBalances:
+    def test_spot_holds_and_settlement(self):
+        engine = RustExecutionEngine()
+        engine.deposit("USDT", 1000.0)
+        engine.set_instrument(1, "BTC", "USDT")
+        engine.on_book_level(1, 1, 100.0, 5.0)
+        engine.execute_order(1, 0, 0, 90.0, 5.0)
+        assert engine.get_balances()["USDT"] == {"total": 1000.0, "reserved": 450.0, "margin": 0.0, "available": 550.0}
+
+        reject = engine.execute_order(1, 0, 0, 100.0, 6.0)
+        assert (reject.reason_code, reject.source) == (RejectReason.INSUFFICIENT_BALANCE, "router")
+        assert engine.execute_order(1, 0, 0, 100.0, 5.0).status == OrderStatus.FILLED
+        balances = engine.get_balances()
+        assert (balances["USDT"]["total"], balances["USDT"]["available"]) == (500.0, 50.0)
+        assert balances["BTC"]["available"] == 5.0
+
+        # Sells hold base.
+        assert engine.execute_order(1, 1, 0, 200.0, 6.0).reason_code == RejectReason.INSUFFICIENT_BALANCE
+        assert engine.execute_order(1, 1, 0, 200.0, 5.0).status == OrderStatus.SUBMITTED
+        assert engine.get_balances()["BTC"]["reserved"] == 5.0
+        with pytest.raises(ValueError):
+            engine.withdraw("USDT", 200.0)
+        assert engine.execute_order(2, 0, 0, 100.0, 100.0).status == OrderStatus.SUBMITTED
+
+    @pytest.mark.parametrize("margin_mode, liquidation_price", [
+        ("isolated", (5000.0 - 500.0) / 49.75),
+        ("cross", (5000.0 - 1000.0) / 49.75),
+    ])
+    def test_perpetual_margin(self, margin_mode, liquidation_price):
+        engine = RustExecutionEngine()
+        engine.deposit("USDT", 1000.0)
+        engine.set_instrument(2, "BTC", "USDT", kind="perpetual", max_leverage=20.0,
+                              maintenance_rate=0.005, margin_mode=margin_mode)
+        engine.set_leverage(2, 10.0)
+        with pytest.raises(ValueError):
+            engine.set_leverage(2, 50.0)
+        engine.on_book_level(2, 1, 100.0, 200.0)
+        assert engine.execute_order(2, 0, 1, 0.0, 101.0).reason_code == RejectReason.INSUFFICIENT_BALANCE
+        assert engine.execute_order(2, 0, 1, 0.0, 50.0).status == OrderStatus.FILLED
+
+        assert engine.get_balances()["USDT"] == {"total": 1000.0, "reserved": 0.0, "margin": 500.0, "available": 500.0}
+        margin = engine.get_margin(2)
+        assert (margin["margin_mode"], margin["position_margin"], margin["maintenance_margin"]) == (
+            margin_mode, 500.0, 25.0)
+        assert margin["liquidation_price"] == pytest.approx(liquidation_price)
+
+
+class TestBookBuilder:
+    """The compiled and Python builders keep the same books"""
+
+    @staticmethod
+    def _builder(builder_cls):
+        books = builder_cls()
+        books.apply_snapshot(BookSnapshot(1, 1, 1000, [(99.0, 2.0), (98.0, 1.0)], [(101.0, 1.0), (102.0, 3.0)]))
+        return books
+
+    @pytest.mark.parametrize("builder_cls", sorted({BookBuilder, _PyBookBuilder}, key=str))
+    def test_deltas_and_metrics(self, builder_cls):
+        books = self._builder(builder_cls)
+        assert books.apply_delta(BookDelta(2, 1, 0, 99.5, 1.0, 1001)) is None
+        # Already covered by the book.
+        assert books.apply_delta(BookDelta(2, 1, 0, 97.0, 1.0, 1000)) is None
+        assert books.get_depth(1, 2)["bids"] == [(99.5, 1.0), (99.0, 2.0)]
+        assert books.top_of_book(1)["bid_px"] == 99.5
+        assert (books.mid_price(1), books.micro_price(1), books.spread(1)) == (100.25, 100.25, 1.5)
+
+        # seq=0 always applies; size 0 deletes.
+        assert books.apply_delta(BookDelta(3, 1, 0, 99.5, 0.0, 0)) is None
+        assert books.get_depth(1, 5)["bids"] == [(99.0, 2.0), (98.0, 1.0)]
+        assert books.imbalance(1, depth=5) == pytest.approx(-1 / 7)
+        assert books.top_of_book(2) is None and books.imbalance(2) == 0.0
+
+    @pytest.mark.parametrize("builder_cls", sorted({BookBuilder, _PyBookBuilder}, key=str))
+    def test_gap_until_snapshot(self, builder_cls):
+        books = self._builder(builder_cls)
+        gap = books.apply_delta(BookDelta(2, 1, 1, 101.0, 0.0, 1002))
+        assert (gap.expected_seq, gap.received_seq) == (1001, 1002)
+        assert not books.is_synced(1) and books.gap_count(1) == 1
+        assert books.apply_delta(BookDelta(2, 1, 1, 101.0, 0.0, 1003)) is None
+        assert (books.sequence(1), books.top_of_book(1)["ask_px"]) == (1000, 101.0)
+
+        books.apply_snapshot(BookSnapshot(4, 1, 1010, [(99.0, 2.0)], [(100.0, 1.0)]))
+        assert books.is_synced(1)
+        assert books.apply_delta(BookDelta(5, 1, 0, 99.0, 0.5, 1011)) is None
+        assert books.get_depth(1)["bids"] == [(99.0, 0.5)]
+
+    @pytest.mark.parametrize("builder_cls", sorted({BookBuilder, _PyBookBuilder}, key=str))
+    def test_stale_snapshot_ignored(self, builder_cls):
+        books = self._builder(builder_cls)
+        assert books.apply_delta(BookDelta(2, 1, 0, 99.5, 1.0, 1001)) is None
+        assert books.apply_snapshot(BookSnapshot(3, 1, 999, [(90.0, 1.0)], [(110.0, 1.0)])) is False
+        assert (books.sequence(1), books.top_of_book(1)["bid_px"]) == (1001, 99.5)
+
+        # Also while out of sync: a snapshot before the gap cannot resync.
+        books.apply_delta(BookDelta(4, 1, 0, 99.5, 2.0, 1005))
+        assert books.apply_snapshot(BookSnapshot(5, 1, 1000, [(90.0, 1.0)], [(110.0, 1.0)])) is False
+        assert not books.is_synced(1)
+        assert books.apply_snapshot(BookSnapshot(6, 1, 1001, [(90.0, 1.0)], [(110.0, 1.0)])) is True
+        assert books.is_synced(1) and books.top_of_book(1)["bid_px"] == 90.0
+
+    @pytest.mark.parametrize("builder_cls", sorted({BookBuilder, _PyBookBuilder}, key=str))
+    def test_invalid_delta(self, builder_cls):
+        books = self._builder(builder_cls)
+        with pytest.raises(ValueError):
+            books.apply_delta(BookDelta(2, 1, 2, 99.0, 1.0, 1001))
+        with pytest.raises(ValueError):
+            books.apply_delta(BookDelta(2, 1, 0, -1.0, 1.0, 1001))
+
+
+class TestFeatureCalculator:
+    """The compiled and Python calculators produce the same frames"""
+
+    MS = 1_000_000
+    QUOTES = [(0, 99.0, 2.0, 101.0, 1.0), (200, 99.5, 1.0, 100.5, 3.0),
+              (400, 100.0, 1.0, 101.0, 1.0), (800, 100.0, 2.0, 102.0, 1.0)]
+
+    def _frames(self, calculator_cls):
+        features = calculator_cls(window_ms=500, trade_window_ms=1_000)
+        features.on_trade(0, 1, 100.0, 1.0, 0)
+        frames = [features.on_quote(t * self.MS, 1, *quote) for t, *quote in self.QUOTES]
+        features.on_trade(900 * self.MS, 1, 101.0, 3.0, 1)
+        return frames + [features.get_frame(1)]
+
+    @pytest.mark.parametrize("calculator_cls", sorted({FeatureCalculator, _PyFeatureCalculator}, key=str))
+    def test_windows(self, calculator_cls):
+        frames = self._frames(calculator_cls)
+        assert (frames[1].ofi, frames[1].spread_mean, frames[1].imbalance) == (-2.0, 1.5, -0.5)
+        assert frames[2].window_return == pytest.approx(0.005)
+        assert frames[2].realized_vol == pytest.approx(0.0025)
+        # The quotes at 0 and 200ms have left the 500ms window.
+        assert (frames[3].samples, frames[3].spread_min, frames[3].price_range) == (2, 1.0, 0.5)
+        last = frames[4]
+        assert (last.trade_count, last.signed_volume, last.vwap, last.trade_intensity) == (2, -2.0, 100.75, 2.0)
+
+    def test_implementations_agree(self):
+        for compiled, fallback in zip(self._frames(FeatureCalculator), self._frames(_PyFeatureCalculator)):
+            assert compiled.to_dict() == pytest.approx(fallback.to_dict())
+
+    @pytest.mark.parametrize("calculator_cls", sorted({FeatureCalculator, _PyFeatureCalculator}, key=str))
+    def test_invalid_input(self, calculator_cls):
+        with pytest.raises(ValueError):
+            calculator_cls(window_ms=0)
+        features = calculator_cls()
+        with pytest.raises(ValueError):
+            features.on_trade(0, 1, 100.0, 1.0, 2)
+        with pytest.raises(ValueError):
+            features.on_quote(0, 1, -1.0, 1.0, 101.0, 1.0)
+        assert features.get_frame(1) is None
+
+
+class TestSelfTradePrevention:
+    @staticmethod
+    def _cross(engine):
+        """Own ask at 101 behind venue liquidity at 100.5, then an own bid through both"""
+        ask = engine.execute_order(1, 1, 0, 101.0, 1.0)
+        engine.on_book_level(1, 1, 100.5, 0.5)
+        return ask, engine.execute_order(1, 0, 0, 101.0, 1.0)
+
+    def test_cancel_resting(self):
+        engine = RustExecutionEngine()
+        assert engine.get_self_trade_prevention() == "cancel_resting"
+        ask, bid = self._cross(engine)
+        assert [(f.price, f.qty, f.matched_order_id) for f in bid.fills] == [(100.5, 0.5, 0)]
+        assert bid.status == OrderStatus.PARTIAL
+        assert engine.get_order(ask.order_id)["status"] == OrderStatus.CANCELLED
+        assert engine.get_order(ask.order_id)["filled_quantity"] == 0.0
+        assert engine.get_book(1, 5) == {"symbol_id": 1, "bids": [(101.0, 0.5, 1)], "asks": []}
+
+    def test_cancel_incoming(self):
+        engine = RustExecutionEngine()
+        engine.set_self_trade_prevention("cancel_incoming")
+        ask, bid = self._cross(engine)
+        assert bid.executed_quantity == 0.5
+        assert bid.status == OrderStatus.CANCELLED
+        assert engine.get_order(ask.order_id)["status"] == OrderStatus.SUBMITTED
+        assert engine.get_book(1, 5)["bids"] == []
+
+        # FOK counts only the venue quantity ahead of the engine's own order.
+        engine.on_book_level(1, 1, 100.5, 0.5)
+        assert engine.execute_order(1, 0, 0, 101.0, 1.0, 3).status == OrderStatus.REJECTED
+
+        with pytest.raises(ValueError):
+            engine.set_self_trade_prevention("decrement")
+
+    @pytest.mark.skipif(not RUST_MODULE_AVAILABLE, reason="positions need the compiled module")
+    def test_no_self_fill_booked(self, tmp_path):
+        """Only the venue fill reaches positions and fees, live and after replay"""
+        engine = RustExecutionEngine(journal_dir=str(tmp_path))
+        engine.set_fee_schedule([(0.0, 10.0, 10.0)], symbol_id=1)
+        self._cross(engine)
+        positions = engine.get_positions()
+        assert [(p["net_qty"], p["fill_count"]) for p in positions] == [(0.5, 1)]
+        assert positions[0]["fees"] == pytest.approx(100.5 * 0.5 * 0.001)
+        assert engine.drain_maker_fills() == []
+        del engine
+
+        engine = RustExecutionEngine()
+        engine.set_fee_schedule([(0.0, 10.0, 10.0)], symbol_id=1)
+        engine.open_journal(str(tmp_path))
+        assert [(p["net_qty"], p["fill_count"], p["fees"]) for p in engine.get_positions()] == \
+            [(p["net_qty"], p["fill_count"], p["fees"]) for p in positions]
+
+
+@pytest.mark.skipif(not RUST_MODULE_AVAILABLE, reason="pre-trade checks need the compiled module")
+class TestAmendChecks:
+    def test_balance(self):
+        """A re-priced or larger order needs the balance a new one would"""
+        engine = RustExecutionEngine()
+        engine.set_instrument(1, "BTC", "USDT")
+        engine.deposit("USDT", 1000.0)
+        bid = engine.execute_order(1, 0, 0, 90.0, 5.0)
+        stop = engine.execute_order(1, 0, 2, 0.0, 2.0, trigger_price=110.0)
+
+        reject = engine.amend_order(bid.order_id, new_qty=20.0)
+        assert reject.reason_code == 203 and reject.source == "router"
+        assert engine.amend_order(stop.order_id, new_qty=6.0).reason_code == 203
+        assert engine.get_balances()["USDT"]["reserved"] == 450.0 + 220.0
+
+        # The order's own hold counts towards the amended size.
+        assert engine.amend_order(bid.order_id, new_price=100.0, new_qty=7.0).status == OrderStatus.SUBMITTED
+        assert engine.get_balances()["USDT"]["reserved"] == 700.0 + 220.0
+        assert engine.amend_order(bid.order_id, new_qty=1.0).status == OrderStatus.SUBMITTED
+        assert engine.get_balances()["USDT"]["reserved"] == 100.0 + 220.0
+
+    def test_risk_limits(self):
+        """Amends are held to the symbol's limits and leave the order unchanged"""
+        engine = RustExecutionEngine()
+        engine.set_risk_limits(1, max_order_qty=5.0, max_order_notional=1000.0)
+        bid = engine.execute_order(1, 0, 0, 90.0, 5.0)
+        stop = engine.execute_order(1, 1, 3, 80.0, 2.0, trigger_price=85.0)
+
+        reject = engine.amend_order(bid.order_id, new_qty=6.0)
+        assert (reject.reason_code, reject.source) == (RejectReason.NOTIONAL_LIMIT, "risk")
+        assert engine.amend_order(bid.order_id, new_price=250.0).reason_code == RejectReason.NOTIONAL_LIMIT
+        assert engine.amend_order(stop.order_id, new_qty=6.0).reason_code == RejectReason.NOTIONAL_LIMIT
+        assert engine.get_order(bid.order_id)["price"] == 90.0
+        assert engine.get_book(1, 5)["bids"] == [(90.0, 5.0, 1)]
+        assert engine.amend_order(bid.order_id, new_price=95.0).status == OrderStatus.SUBMITTED
+
+
+class TestBookSync:
+    def test_recorded_resync(self):
+        """Replay a recorded depth feed with a gap and check every transition"""
+        sync = BookSync(max_buffer=100, stale_after_ms=5_000, resync_timeout_ms=10_000)
+        for line in (FIXTURES / "book_resync.ndjson").read_text().splitlines():
+            msg = json.loads(line)
+            if msg["type"] == "snapshot":
+                requests = sync.on_snapshot(BookSnapshot(
+                    msg["ts_ns"], msg["symbol_id"], msg["seq"],
+                    [tuple(level) for level in msg["bids"]],
+                    [tuple(level) for level in msg["asks"]]
+                ))
+            elif msg["type"] == "delta":
+                deltas = [
+                    BookDelta(msg["ts_ns"], msg["symbol_id"], side, price, qty, msg["seq"])
+                    for side, levels in ((0, msg["bids"]), (1, msg["asks"]))
+                    for price, qty in levels
+                ]
+                requests = sync.on_update(deltas, first_seq=msg["first_seq"])
+            else:
+                requests = sync.check(msg["ts_ns"])
+
+            expect = msg["expect"]
+            assert [r.reason for r in requests] == expect["requests"], line
+            assert sync.state(1) == expect["state"], line
+            if "seq" in expect:
+                assert sync.get_stats(1)["seq"] == expect["seq"], line
+
+        assert sync.get_depth(1, 5)["bids"] == [(100.5, 1.0), (100.0, 1.0)]
+        assert sync.top_of_book(1)["ask_px"] == 101.5
+        assert sync.get_stats(1)["gaps"] == 1
+
+    def test_subscribe_without_time(self):
+        """The snapshot timeout of an untimed subscribe starts at the first check"""
+        sync = BookSync(resync_timeout_ms=10_000)
+        sync.subscribe(1)
+        now_ns = 1_700_000_000_000_000_000
+        assert sync.check(now_ns) == []
+        assert sync.check(now_ns + 10_000_000_000) == []
+        assert [r.reason for r in sync.check(now_ns + 10_000_000_001)] == ["timeout"]
+
+    def test_reads_blocked_until_synced(self):
+        sync = BookSync()
+        sync.on_delta(BookDelta(1, 7, 0, 10.0, 1.0, 5))
+        assert sync.state(7) == "buffering"
+        assert sync.top_of_book(7) is None
+        assert sync.mid_price(7) is None
+
+
+class TestShmRing:
+    def test_publish_poll_across_handles(self, tmp_path):
+        """Frames published on one mapping are consumed in order on another"""
+        path = str(tmp_path / "ticks")
+        producer = ShmRing(path, create=True, capacity=3, slot_size=64)
+        consumer = ShmRing(path)
+        assert consumer.capacity == 4
+
+        updates = [MdUpdate(i, 1, 100.0, 1.0, 100.5, 2.0, i) for i in range(5)]
+        assert [producer.publish(codec.encode(u)) for u in updates] == [True] * 4 + [False]
+        assert len(consumer) == 4
+
+        assert codec.decode(consumer.poll()) == updates[0]
+        assert [codec.decode(f) for f in consumer.poll_batch()] == updates[1:4]
+        assert consumer.poll() is None
+        assert consumer.wait(timeout_ms=1) is None
+        stats = producer.get_stats()
+        assert (stats["published"], stats["consumed"], stats["dropped"]) == (4, 4, 1)
+
+        with pytest.raises(ValueError):
+            producer.publish(b"x" * 65)
+
+    def test_wait_timeouts(self, tmp_path):
+        """An unbounded timeout waits like None; NaN is rejected"""
+        ring = ShmRing(str(tmp_path / "ticks"), create=True, capacity=4, slot_size=16)
+        ring.publish(b"frame")
+        assert ring.wait(timeout_ms=float("inf")) == b"frame"
+        ring.publish(b"again")
+        assert ring.wait(timeout_ms=1e308) == b"again"
+        with pytest.raises(ValueError):
+            ring.wait(timeout_ms=float("nan"))
+
+    @staticmethod
+    def _produce(path, producer_id, count):
+        ring = ShmRing(path)
+        for i in range(count):
+            while not ring.publish(struct.pack("<II", producer_id, i)):
+                pass
+
+    @pytest.mark.skipif(not RUST_MODULE_AVAILABLE, reason="multi-producer rings need the compiled module")
+    def test_multi_producer_loses_nothing(self, tmp_path):
+        """Concurrent producers each get every frame through exactly once, in order"""
+        path = str(tmp_path / "orders")
+        consumer = ShmRing(path, create=True, capacity=256, slot_size=16, multi_producer=True)
+        producers, count = 4, 5000
+        context = multiprocessing.get_context("fork")
+        workers = [context.Process(target=self._produce, args=(path, p, count)) for p in range(producers)]
+        for worker in workers:
+            worker.start()
+
+        received = {p: [] for p in range(producers)}
+        for _ in range(producers * count):
+            frame = consumer.wait(timeout_ms=10_000)
+            assert frame is not None
+            producer_id, i = struct.unpack("<II", frame)
+            received[producer_id].append(i)
+        for worker in workers:
+            worker.join(timeout=10)
+            assert worker.exitcode == 0
+
+        assert consumer.poll() is None
+        assert all(seen == list(range(count)) for seen in received.values())
+        assert consumer.get_stats()["published"] == producers * count
+
+
+@pytest.mark.skipif(not RUST_MODULE_AVAILABLE, reason="journal needs the compiled module")
+class TestJournal:
+    def test_recovery_after_torn_write(self, tmp_path):
+        """A restarted engine gets its open orders, positions and ids back"""
+        engine = RustExecutionEngine(journal_dir=str(tmp_path))
+        engine.execute_order(3, 1, 0, 105.0, 2.0, client_id="ask")
+        engine.on_book_level(1, 1, 101.0, 0.5)
+        engine.execute_order(1, 0, 0, 101.0, 1.0, client_id="bid")
+        engine.execute_order(2, 0, 2, 0.0, 1.0, trigger_price=120.0)
+        positions = engine.get_positions()
+        book = engine.get_book(1, 5)
+        del engine
+
+        # Crash mid-write: half a record at the end of the newest segment.
+        segment = sorted(tmp_path.glob("*.journal"))[-1]
+        with open(segment, "ab") as f:
+            f.write(b"\x40\x00\x00\x00torn")
+        assert len(read_journal(str(tmp_path))) > 0
+
+        engine = RustExecutionEngine()
+        summary = engine.open_journal(str(tmp_path))
+        assert summary["torn_bytes"] == 8
+        assert summary["open_orders"] == 3
+        assert summary["next_order_id"] == 4
+        assert engine.get_positions() == positions
+        assert engine.get_book(1, 5) == book
+        assert engine.get_order(client_id="bid")["filled_quantity"] == 0.5
+        assert len(engine.get_conditional_orders(2)) == 1
+        assert engine.execute_order(3, 0, 0, 10.0, 1.0).order_id == 4
+
+    def test_snapshot_recovery_and_pruning(self, tmp_path):
+        """Recovery starts from the newest snapshot once old segments are gone"""
+        engine = RustExecutionEngine(journal_dir=str(tmp_path))
+        engine.set_instrument(1, "BTC", "USDT")
+        engine.deposit("USDT", 1000.0)
+        engine.set_snapshot_policy(every_records=4, keep=2)
+        for i in range(6):
+            engine.execute_order(1, i % 2, 0, 100.0 + i % 3, 1.0)
+        engine.execute_order(1, 0, 0, 90.0, 1.0, client_id="bid")
+        stats = engine.get_journal_stats()
+        assert stats["snapshots_written"] >= 2 and stats["snapshot_error"] is None
+        assert len(list(tmp_path.glob("*.snapshot"))) == 2
+        assert not (tmp_path / f"{0:020}.journal").exists()
+        positions, book, balances = engine.get_positions(), engine.get_book(1, 5), engine.get_balances()
+        del engine
+
+        engine = RustExecutionEngine()
+        engine.set_instrument(1, "BTC", "USDT")
+        summary = engine.open_journal(str(tmp_path))
+        assert summary["snapshot_seq"] == stats["last_snapshot_seq"]
+        assert summary["records"] == stats["next_seq"] - stats["last_snapshot_seq"]
+        assert engine.get_positions() == positions
+        assert engine.get_book(1, 5) == book
+        assert engine.get_balances() == balances
+        assert engine.get_order(client_id="bid")["status"] == 2
+
+
+@pytest.mark.skipif(not RUST_MODULE_AVAILABLE, reason="replay needs the compiled module")
+class TestMarketDataReplay:
+    def test_formats_replay_identically(self, tmp_path):
+        """CSV and gzipped binary recordings drive the engine to the same state"""
+        events = []
+        for i in range(1, 41):
+            events.append(BookDelta(i * 1000, 1, i % 2, 100.0 - i % 3 if i % 2 == 0 else 101.0 + i % 4, 1.0 + i, i))
+            events.append(BookDelta(i * 1000, 2, 0, 50.0, float(i)))
+            if i % 5 == 0:
+                events.append(MarketTrade(i * 1000, 1, 100.0, 2.5, 1))
+        write_market_data(str(tmp_path / "md.bin.gz"), events, compress=True)
+        with open(tmp_path / "md.csv", "w") as f:
+            f.write("type,ts_ns,symbol_id,side,price,quantity,seq\n")
+            for e in events:
+                if isinstance(e, BookDelta):
+                    f.write(f"delta,{e.ts_ns},{e.symbol_id},{e.side},{e.price!r},{e.quantity!r},{e.seq}\n")
+                else:
+                    f.write(f"trade,{e.ts_ns},{e.symbol_id},{e.aggressor_side},{e.price!r},{e.quantity!r},\n")
+
+        results = []
+        for name in ("md.bin.gz", "md.csv"):
+            engine = RustExecutionEngine()
+            engine.set_fill_recording(True)
+            replayer = MarketDataReplayer(str(tmp_path / name), symbols=[1], engine=engine)
+            replayer.seek(20_000)
+            assert replayer.position_ns == 19_000
+            engine.execute_order(1, 0, 0, 100.5, 1.0)
+            replayer.run()
+            stats = replayer.get_stats()
+            assert stats["finished"] and stats["events_filtered"] == 40
+            fills = [(f.ts_ns, f.price, f.qty) for f in engine.drain_fills()]
+            results.append((replayer.format, stats["events_replayed"], fills,
+                            engine.get_time(), replayer.book_builder.get_depth(1)))
+        assert results[0][0] == "binary" and results[1][0] == "csv"
+        assert results[0][1:] == results[1][1:]
+        assert results[0][2]
+
+        with pytest.raises(ValueError):
+            replayer.seek(0)
+
+    def test_oversized_frame_rejected(self, tmp_path):
+        """A corrupt frame length is a format error, not a huge allocation"""
+        path = tmp_path / "md.bin"
+        write_market_data(str(path), [BookDelta(1000, 1, 0, 100.0, 1.0, 1)])
+        with open(path, "ab") as f:
+            f.write(b"\xf0\xff\xff\xff")
+        replayer = MarketDataReplayer(str(path))
+        with pytest.raises(ValueError, match="frame 2: frame length 4294967280 exceeds"):
+            replayer.run()
+
+    def test_snapshots_not_written_as_binary(self, tmp_path):
+        """The binary format has no snapshot frame, so a snapshot is refused"""
+        snapshot = BookSnapshot(1000, 1, 1, [(100.0, 1.0)], [(101.0, 1.0)])
+        with pytest.raises(ValueError, match="NDJSON"):
+            write_market_data(str(tmp_path / "md.bin"), [BookDelta(900, 1, 0, 100.0, 1.0, 1), snapshot])
+
+    def test_tiny_speed_waits_until_paused(self, tmp_path):
+        """A speed whose pacing overflows waits instead of failing"""
+        path = tmp_path / "md.bin"
+        write_market_data(str(path), [BookDelta(0, 1, 0, 100.0, 1.0, 1),
+                                      BookDelta(1_000_000_000, 1, 0, 100.0, 2.0, 2)])
+        replayer = MarketDataReplayer(str(path), speed=1e-300)
+        timer = threading.Timer(0.2, replayer.pause)
+        timer.start()
+        assert replayer.run() == 1
+        timer.join()
+        assert replayer.paused
+
+
+@pytest.mark.skipif(not RUST_MODULE_AVAILABLE, reason="export needs the compiled module")
+class TestColumnarExport:
+    @staticmethod
+    def _trade(journal_dir):
+        engine = RustExecutionEngine(journal_dir=str(journal_dir))
+        engine.set_fill_recording(True)
+        engine.on_book_level(1, 1, 101.0, 2.0)
+        executions = [engine.execute_order(1, 1, 0, 105.0, 2.0, client_id="ask"),
+                      engine.execute_order(1, 0, 0, 101.0, 0.5),
+                      engine.execute_order(1, 0, 0, 101.0, 0.25)]
+        return engine, executions
+
+    def test_parquet_and_ipc_files(self, tmp_path):
+        """Engine, journal and execution sources write the same rows"""
+        engine, executions = self._trade(tmp_path / "journal")
+        assert export_table(executions, "executions", tmp_path / "executions.parquet") == 3
+        assert export_table(engine, "fills", tmp_path / "fills.parquet", compression="snappy") == 2
+        assert export_table(tmp_path / "journal", "fills", tmp_path / "fills.arrow", format="ipc") == 2
+        assert export_table(engine, "orders", tmp_path / "orders.parquet") == 6
+        assert export_table(tmp_path / "journal", "positions", tmp_path / "equity.parquet") == 2
+        assert (tmp_path / "fills.parquet").read_bytes()[:4] == b"PAR1"
+        assert (tmp_path / "fills.arrow").read_bytes()[:6] == b"ARROW1"
+
+        assert export_table([(1000, 10.0), (2000, 12.5)], "equity", tmp_path / "equity_curve.parquet") == 2
+
+        with pytest.raises(ValueError):
+            export_table(engine, "executions", tmp_path / "x.parquet")
+        with pytest.raises(ValueError):
+            export_table(engine, "equity", tmp_path / "x.parquet")
+        with pytest.raises(ValueError):
+            export_table(executions, "equity", tmp_path / "x.parquet")
+
+    def test_record_batch(self, tmp_path):
+        pa = pytest.importorskip("pyarrow")
+        engine, _ = self._trade(tmp_path)
+        fills = export_arrow(engine, "fills")
+        assert isinstance(fills, pa.RecordBatch)
+        assert fills.num_rows == 2
+        assert fills.column("qty").to_pylist() == [0.5, 0.25]
+        equity = export_arrow(str(tmp_path), "positions")
+        assert equity.column("fill_count").to_pylist() == [1, 2]
+
+        result = run_backtest(RustExecutionEngine(), [BookDelta(1000, 1, 0, 100.0, 1.0),
+                                                      BookDelta(2000, 1, 1, 101.0, 1.0)],
+                              object(), initial_equity=500.0)
+        curve = export_arrow(result["equity_curve"], "equity")
+        assert curve.column("ts_ns").to_pylist() == [1000, 2000]
+        assert curve.column("equity").to_pylist() == [500.0, 500.0]
+
+    def test_journal_positions_match_the_engine(self, tmp_path):
+        """Positions rebuilt from the journal agree with the engine's own"""
+        pa = pytest.importorskip("pyarrow")
+        engine = RustExecutionEngine(journal_dir=str(tmp_path))
+        engine.set_cost_method("lifo")
+        engine.set_fee_schedule([(0.0, 0.0, 10.0)], symbol_id=1, fee_currency="BTC", charge_in_base=True)
+        for side, price, qty in ((0, 100.0, 1.0), (0, 110.0, 1.0), (1, 120.0, 1.5)):
+            engine.on_book_level(1, 1 - side, price, qty)
+            engine.execute_order(1, side, 1, 0.0, qty)
+
+        expected = engine.get_positions()[0]
+        positions = export_arrow(tmp_path, "positions", cost_method="lifo").to_pylist()[-1]
+        for key in ("net_qty", "avg_entry_price", "realized_pnl", "fees", "fill_count"):
+            assert positions[key] == pytest.approx(expected[key]), key
+        fifo = export_arrow(tmp_path, "positions").to_pylist()[-1]
+        assert fifo["realized_pnl"] != pytest.approx(expected["realized_pnl"])
+
+        with pytest.raises(ValueError):
+            export_arrow(tmp_path, "positions", cost_method="bogus")
+        with pytest.raises(ValueError):
+            export_arrow(engine, "positions", cost_method="lifo")
+
+
 if __name__ == "__main__":
     pytest.main([__file__, "-v"])
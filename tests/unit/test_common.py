@@ -124,13 +124,16 @@ class TestRiskEngine:
     def test_risk_position_limit(self):
         """Test position limit check"""
         from apps.risk.risk_engine import RiskEngine
+        from core.modules.rust_execution import PositionLedger, SlidingWindowLimiter
         from pkg.schemas import OrderIntent, Side, OrderType
+        from pkg.schemas.orders import RejectReason
         
         config = Config()
         config.risk.max_position_usd = 1000.0
         config.risk.max_order_usd = 500.0
         
-        engine = RiskEngine(config, None)
+        engine = RiskEngine(config, None, PositionLedger(cost_method="fifo"),
+                            SlidingWindowLimiter(config.risk.rate_limit_per_sec, window_ms=1000))
         
         # Valid order
         order = OrderIntent.create(
@@ -148,6 +151,85 @@ class TestRiskEngine:
         order.qty = 100.0  # 100 * 50000 = 5M USD
         passed, code, msg = engine._perform_checks(order)
         assert passed == False
+        assert code == RejectReason.NOTIONAL_LIMIT  # same code as the Rust gate
+
+    def test_risk_market_order_valuation(self):
+        """Market orders are checked and booked at the last price, or rejected"""
+        from apps.risk.risk_engine import RiskEngine
+        from core.modules.rust_execution import PositionLedger, SlidingWindowLimiter
+        from pkg.schemas import OrderIntent, Side, OrderType
+        from pkg.schemas.orders import RejectReason
+        
+        config = Config()
+        config.risk.max_position_usd = 1000.0
+        config.risk.max_order_usd = 500.0
+        
+        engine = RiskEngine(config, None, PositionLedger(cost_method="fifo"),
+                            SlidingWindowLimiter(config.risk.rate_limit_per_sec, window_ms=1000))
+        order = OrderIntent.create(
+            symbol_id=1,
+            side=Side.BUY,
+            order_type=OrderType.MARKET,
+            qty=0.005
+        )
+        
+        passed, code, msg = engine._perform_checks(order)
+        assert passed == False
+        assert code == RejectReason.INVALID_PRICE
+        
+        engine.last_prices[1] = 50000.0
+        passed, code, msg = engine._perform_checks(order)
+        assert passed == True
+        engine._update_position_tracking(order)
+        assert engine.positions.get_position(1)["net_qty"] == 0.005
+        
+        order.qty = 0.02  # 0.02 * 50000 = 1000 USD
+        passed, code, msg = engine._perform_checks(order)
+        assert code == RejectReason.NOTIONAL_LIMIT
+
+
+class TestRoutingEngine:
+    """Test router rate limiting"""
+    
+    def test_rate_limit_charged_after_publish(self):
+        """A failed publish gives the venue's token back"""
+        import asyncio
+        from apps.router.routing_engine import RoutingEngine
+        from core.modules.rust_execution import TokenBucketLimiter
+        from pkg.schemas import OrderIntent, Side, OrderType
+        
+        class Publisher:
+            def __init__(self):
+                self.fail = True
+                self.sent = []
+            
+            async def publish(self, order, venue):
+                if self.fail:
+                    raise ConnectionError("gateway down")
+                self.sent.append((order.client_id, venue))
+        
+        publisher = Publisher()
+        config = Config()
+        router = RoutingEngine(config, publisher,
+                               TokenBucketLimiter(1000.0 / config.risk.cooldown_ms, burst=1))
+        order = OrderIntent.create(
+            symbol_id=1,
+            side=Side.BUY,
+            order_type=OrderType.LIMIT,
+            qty=0.01,
+            price=50000.0
+        )
+        
+        asyncio.run(router.route_order(order))
+        assert publisher.sent == []
+        
+        # The failed attempt did not use up the one-order-per-cooldown budget
+        publisher.fail = False
+        asyncio.run(router.route_order(order))
+        assert publisher.sent == [(order.client_id, "binance")]
+        asyncio.run(router.route_order(order))
+        assert len(publisher.sent) == 1
+
 
 
 if __name__ == "__main__":
@@ -6,8 +6,10 @@ import pytest
 import time
 from pkg.schemas import (
     MdUpdate, TopOfBook, OrderIntent, OrderAck, FeatureFrame, SignalEvent, L2Book, BookLevel,
-    Side, OrderType, TimeInForce, OrderStatus, SignalType
+    Side, OrderType, TimeInForce, OrderStatus, SignalType, Cancel, Amend, Fill, Reject, BookDelta,
+    MarketTrade
 )
+from pkg.schemas import codec
 
 
 class TestMarketDataSchemas:
@@ -169,5 +171,51 @@ class TestSignalSchemas:
         assert signal.metadata["source"] == "volatility_scanner"
 
 
+class TestBinaryCodec:
+    """Test binary frame encoding"""
+
+    def test_roundtrip(self):
+        """Test every message type decodes back to an equal dataclass"""
+        messages = [
+            OrderIntent.create(1, Side.SELL, OrderType.LIMIT, 0.5, price=50000.0,
+                               tif=TimeInForce.IOC, route="binance"),
+            OrderIntent.create(2, Side.BUY, OrderType.MARKET, 1.0),
+            Cancel(ts_ns=1, client_id="abc", symbol_id=1, reason="stale"),
+            Amend(ts_ns=1, client_id="abc", symbol_id=1, new_qty=0.25),
+            Amend(ts_ns=1, client_id="abc", symbol_id=1, new_price=49000.0),
+            OrderAck(ts_ns=2, client_id="abc", exchange_order_id="x1",
+                     status=OrderStatus.SUBMITTED, venue_code=1),
+            Fill(ts_ns=3, client_id="abc", exchange_order_id="x1", price=50000.0, qty=0.5,
+                 fee=0.01, fee_currency="USDT", venue_code=1, is_maker=True, trade_id="t1"),
+            Reject(ts_ns=3, client_id="abc", reason_code=203, reason_msg="Insufficient USDT",
+                   source="router", venue_code=1),
+            MdUpdate.now(symbol_id=1, bid_px=1.0, bid_sz=2.0, ask_px=1.1, ask_sz=3.0, seq=7),
+            BookDelta(ts_ns=4, symbol_id=1, is_bid=False, price=1.1, size=0.0, seq=8),
+            MarketTrade(ts_ns=5, symbol_id=1, price=1.1, quantity=0.25, aggressor_side=0),
+        ]
+        for message in messages:
+            frame = codec.encode(message)
+            decoded = codec.decode(frame)
+            assert decoded == message
+            assert type(decoded) is type(message)
+            if codec.RUST_CODEC:
+                assert codec.sbe_encode(message) == codec._py_encode(message)
+        assert isinstance(codec.decode(codec.encode(messages[0])).side, Side)
+        assert codec.decode(codec.encode(messages[1])).price is None
+        assert codec.decode(codec.encode(messages[3])).new_price is None
+
+    def test_header_and_errors(self):
+        """Test schema header and malformed frames"""
+        frame = codec.encode(Cancel(ts_ns=1, client_id="abc", symbol_id=1))
+        assert codec.header(frame) == (16, 2, codec.SCHEMA_ID, codec.SCHEMA_VERSION)
+
+        with pytest.raises(ValueError):
+            codec.decode(frame[:-1])
+        with pytest.raises(ValueError):
+            codec.decode(frame[:4] + b"\x09\x00" + frame[6:])
+        with pytest.raises(TypeError):
+            codec.encode(TopOfBook(1, 1, 1.0, 1.0, 1.0, 1.0))
+
+
 if __name__ == "__main__":
     pytest.main([__file__, "-v"])